@@ -1,75 +1,402 @@
-use proc_macro::{TokenStream, TokenTree};
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Block, Ident, LitStr, Signature, Token};
 
-#[proc_macro_attribute]
-pub fn profile(_: TokenStream, items: TokenStream) -> TokenStream {
-    let mut items = items.into_iter().collect::<Vec<_>>();
+/// Arguments of `#[profile(...)]`.
+#[derive(Default)]
+struct Args {
+    /// `name = "..."`
+    name: Option<LitStr>,
+    /// `skip`
+    skip: bool,
+    /// `args` or `args(a, b)`, empty if all arguments should be captured
+    args: Option<Vec<Ident>>,
+    /// `owner = "..."`
+    owner: Option<LitStr>,
+}
 
-    let Some(TokenTree::Group(body)) = items.pop() else {
-        return r#"compile_error!("Expected function body");"#.parse().unwrap();
-    };
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = Self::default();
+        while !input.is_empty() {
+            let key = input.parse::<Ident>()?;
+            match key.to_string().as_str() {
+                "name" => {
+                    input.parse::<Token![=]>()?;
+                    args.name = Some(input.parse()?);
+                }
+                "skip" => args.skip = true,
+                "owner" => {
+                    input.parse::<Token![=]>()?;
+                    args.owner = Some(input.parse()?);
+                }
+                "args" => {
+                    let mut selected = Vec::new();
+                    if input.peek(syn::token::Paren) {
+                        let content;
+                        syn::parenthesized!(content in input);
+                        selected
+                            .extend(Punctuated::<Ident, Token![,]>::parse_terminated(&content)?);
+                    }
+                    args.args = Some(selected);
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!(
+                            "Unknown argument `{key}`, expected `name`, `skip`, `args` or `owner`"
+                        ),
+                    ))
+                }
+            }
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+        Ok(args)
+    }
+}
 
-    let cr = match proc_macro_crate::crate_name("profi").unwrap() {
-        proc_macro_crate::FoundCrate::Itself => std::borrow::Cow::Borrowed("profi"),
-        proc_macro_crate::FoundCrate::Name(n) => std::borrow::Cow::Owned(n),
+/// Path of the `profi` crate, which may be renamed in the user's `Cargo.toml`.
+fn crate_path() -> TokenStream2 {
+    let name = match proc_macro_crate::crate_name("profi") {
+        Ok(proc_macro_crate::FoundCrate::Name(name)) => name,
+        _ => "profi".into(),
     };
-    let profile = {
-        use proc_macro::{Delimiter as D, Group, Ident, Punct, Spacing as S, Span};
-
-        [
-            TokenTree::Punct(Punct::new(':', S::Joint)),
-            TokenTree::Punct(Punct::new(':', S::Alone)),
-            TokenTree::Ident(Ident::new(&cr, Span::call_site())),
-            TokenTree::Punct(Punct::new(':', S::Joint)),
-            TokenTree::Punct(Punct::new(':', S::Alone)),
-            TokenTree::Ident(Ident::new("prof", Span::call_site())),
-            TokenTree::Punct(Punct::new('!', S::Alone)),
-            TokenTree::Group(Group::new(D::Parenthesis, TokenStream::new())),
-            TokenTree::Punct(Punct::new(';', S::Alone)),
-            TokenTree::Group(body),
-        ]
+    let name = Ident::new(&name, proc_macro2::Span::call_site());
+    quote!(::#name)
+}
+
+/// Names of the arguments of the function that are plain identifiers, excluding `self` and `_`.
+fn argument_names(sig: &Signature) -> Vec<Ident> {
+    sig.inputs
+        .iter()
+        .filter_map(|input| match input {
+            syn::FnArg::Typed(arg) => match &*arg.pat {
+                syn::Pat::Ident(pat) => Some(pat.ident.clone()),
+                _ => None,
+            },
+            syn::FnArg::Receiver(_) => None,
+        })
+        .collect()
+}
+
+/// Expression with the name of the scope without the arguments.
+fn base_name(cr: &TokenStream2, args: &Args) -> TokenStream2 {
+    match &args.name {
+        Some(name) => name.to_token_stream(),
+        // Same as `prof!()`, removing the `{{closure}}` suffixes of `async fn`s
+        None => quote!({
+            fn f() {}
+            let name = #cr::zz_private::type_name_of(f);
+            #cr::zz_private::strip_closures(&name[..name.len() - 3])
+        }),
+    }
+}
+
+/// Expression with the name of the scope, `None` if the default can be used.
+fn scope_name(cr: &TokenStream2, args: &Args, sig: &Signature) -> Option<TokenStream2> {
+    let base = base_name(cr, args);
+    match &args.args {
+        None if args.name.is_none() => None,
+        None => Some(base),
+        Some(selected) => {
+            let names = match selected.is_empty() {
+                true => argument_names(sig),
+                false => selected.clone(),
+            };
+            let format = names
+                .iter()
+                .map(|n| format!("{n} = {{:?}}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let format = format!("{{}}({format})");
+            Some(quote!(::std::format!(#format, #base, #(#names),*)))
+        }
+    }
+}
+
+/// Adds a guard at the start of the function's `block`.
+fn profile_fn(
+    cr: &TokenStream2,
+    args: &Args,
+    sig: &Signature,
+    block: &mut Block,
+) -> syn::Result<()> {
+    if let Some(constness) = &sig.constness {
+        return Err(syn::Error::new_spanned(
+            constness,
+            "`const fn`s can't be profiled",
+        ));
+    }
+    if let Some(owner) = &args.owner {
+        // Any arguments appended to the name are matched by `*`
+        let pattern = match (base_name(cr, args), &args.args) {
+            (base, None) => base,
+            (base, Some(_)) => quote!(::std::format!("{}(*", #base)),
+        };
+        block.stmts.insert(
+            0,
+            syn::parse_quote!({
+                static OWNER: ::std::sync::Once = ::std::sync::Once::new();
+                OWNER.call_once(|| #cr::set_owner(#pattern, #owner));
+            }),
+        );
+    }
+    let name = scope_name(cr, args, sig);
+    match (&sig.asyncness, name) {
+        (Some(_), name) => {
+            let name = name.map(|name| quote!(#name,));
+            *block = syn::parse_quote!({ #cr::prof_future!(#name async move #block).await });
+        }
+        (None, name) => block.stmts.insert(0, syn::parse_quote!(#cr::prof!(#name);)),
+    }
+    Ok(())
+}
+
+/// Returns `true` if the attribute is `#[profile]` or `#[profile(...)]`, with any path.
+fn is_profile_attribute(attribute: &Attribute) -> bool {
+    attribute
+        .path()
+        .segments
+        .last()
+        .is_some_and(|s| s.ident == "profile")
+}
+
+/// Returns `true` if the item has its own `#[profile]` attribute, which takes precedence.
+fn has_profile_attribute(attributes: &[Attribute]) -> bool {
+    attributes.iter().any(is_profile_attribute)
+}
+
+/// Adds `name = "..."` to the `#[profile]` attribute of the item, unless it already has a name or is skipped.
+fn name_profile_attribute(attributes: &mut [Attribute], name: &str) {
+    for attribute in attributes.iter_mut().filter(|a| is_profile_attribute(a)) {
+        let name = LitStr::new(name, attribute.path().segments.last().unwrap().ident.span());
+        let path = attribute.path().clone();
+        attribute.meta = match &attribute.meta {
+            syn::Meta::Path(_) => syn::parse_quote!(#path(name = #name)),
+            syn::Meta::List(list) => match list.parse_args::<Args>() {
+                // Invalid arguments are reported by the attribute itself
+                Ok(args) if args.name.is_none() && !args.skip => {
+                    let tokens = &list.tokens;
+                    let comma = (!tokens.is_empty()).then(|| quote!(,));
+                    syn::parse_quote!(#path(#tokens #comma name = #name))
+                }
+                _ => continue,
+            },
+            syn::Meta::NameValue(_) => continue,
+        };
+    }
+}
+
+/// Name of the type of an `impl` block without its generics, like `Vec` for `impl<T> Clone for Vec<T>`.
+fn impl_type(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+        syn::Type::Reference(reference) => impl_type(&reference.elem),
+        syn::Type::Group(group) => impl_type(&group.elem),
+        syn::Type::Paren(paren) => impl_type(&paren.elem),
+        _ => None,
+    }
+}
+
+/// Joins `name` to the `prefix` of the enclosing blocks.
+fn prefixed(prefix: &str, name: impl std::fmt::Display) -> String {
+    match prefix.is_empty() {
+        true => name.to_string(),
+        false => format!("{prefix}::{name}"),
+    }
+}
+
+/// Profiles a function found inside a block as `prefix::function`, unless it's `const` or has its own attribute.
+fn profile_method(
+    cr: &TokenStream2,
+    args: &Args,
+    prefix: &str,
+    attributes: &mut [Attribute],
+    sig: &Signature,
+    block: &mut Block,
+) -> syn::Result<()> {
+    if sig.constness.is_some() {
+        return Ok(());
+    }
+    let name = prefixed(prefix, &sig.ident);
+    if has_profile_attribute(attributes) {
+        name_profile_attribute(attributes, &name);
+        return Ok(());
+    }
+    let args = Args {
+        name: Some(LitStr::new(&name, sig.ident.span())),
+        skip: false,
+        args: args.args.clone(),
+        owner: args.owner.clone(),
     };
-    let tree = TokenTree::from(proc_macro::Group::new(
-        proc_macro::Delimiter::Brace,
-        TokenStream::from_iter(profile),
-    ));
-    items.push(tree);
+    profile_fn(cr, &args, sig, block)
+}
 
-    TokenStream::from_iter(items)
+/// Profiles every function of the `impl` block, named `prefix::Type::method`.
+fn profile_impl(
+    cr: &TokenStream2,
+    args: &Args,
+    prefix: &str,
+    item: &mut syn::ItemImpl,
+) -> syn::Result<()> {
+    let prefix = match impl_type(&item.self_ty) {
+        Some(ty) => prefixed(prefix, ty),
+        None => prefix.to_string(),
+    };
+    for item in &mut item.items {
+        if let syn::ImplItem::Fn(f) = item {
+            profile_method(cr, args, &prefix, &mut f.attrs, &f.sig, &mut f.block)?;
+        }
+    }
+    Ok(())
 }
 
-#[proc_macro_attribute]
-pub fn main(_: TokenStream, items: TokenStream) -> TokenStream {
-    let mut items = items.into_iter().collect::<Vec<_>>();
+/// Profiles every default method of the trait, named `prefix::Trait::method`.
+fn profile_trait(
+    cr: &TokenStream2,
+    args: &Args,
+    prefix: &str,
+    item: &mut syn::ItemTrait,
+) -> syn::Result<()> {
+    let prefix = prefixed(prefix, &item.ident);
+    for item in &mut item.items {
+        if let syn::TraitItem::Fn(f) = item {
+            if let Some(block) = &mut f.default {
+                profile_method(cr, args, &prefix, &mut f.attrs, &f.sig, block)?;
+            }
+        }
+    }
+    Ok(())
+}
 
-    let Some(TokenTree::Group(body)) = items.pop() else {
-        return r#"compile_error!("Expected function body");"#.parse().unwrap();
+/// Profiles every function inside the inline module, named `prefix::module::function`.
+fn profile_mod(
+    cr: &TokenStream2,
+    args: &Args,
+    prefix: &str,
+    item: &mut syn::ItemMod,
+) -> syn::Result<()> {
+    let prefix = prefixed(prefix, &item.ident);
+    let Some((_, items)) = &mut item.content else {
+        return Err(syn::Error::new_spanned(
+            &*item,
+            "Expected a block, only inline modules can be profiled",
+        ));
     };
+    for item in items {
+        match item {
+            syn::Item::Fn(f) => {
+                profile_method(cr, args, &prefix, &mut f.attrs, &f.sig, &mut f.block)?
+            }
+            // Nested blocks with their own attribute are profiled by it
+            syn::Item::Impl(i) if !has_profile_attribute(&i.attrs) => {
+                profile_impl(cr, args, &prefix, i)?
+            }
+            syn::Item::Trait(t) if !has_profile_attribute(&t.attrs) => {
+                profile_trait(cr, args, &prefix, t)?
+            }
+            syn::Item::Mod(m) if m.content.is_some() && !has_profile_attribute(&m.attrs) => {
+                profile_mod(cr, args, &prefix, m)?
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
 
-    let cr = match proc_macro_crate::crate_name("profi").unwrap() {
-        proc_macro_crate::FoundCrate::Itself => std::borrow::Cow::Borrowed("profi"),
-        proc_macro_crate::FoundCrate::Name(n) => std::borrow::Cow::Owned(n),
+/// Profiles a function that only parses as an associated one, like a trait method or a `default fn`.
+fn profile_associated_fn(
+    cr: &TokenStream2,
+    args: &Args,
+    items: TokenStream,
+) -> syn::Result<TokenStream2> {
+    if let Ok(mut f) = syn::parse::<syn::ImplItemFn>(items.clone()) {
+        profile_fn(cr, args, &f.sig, &mut f.block)?;
+        return Ok(f.into_token_stream());
+    }
+    let mut f = syn::parse::<syn::TraitItemFn>(items)?;
+    let Some(block) = &mut f.default else {
+        return Err(syn::Error::new_spanned(
+            &f.sig,
+            "Expected a function body, only default methods can be profiled",
+        ));
     };
-    let profile = {
-        use proc_macro::{Delimiter as D, Group, Ident, Punct, Spacing as S, Span};
-
-        [
-            TokenTree::Punct(Punct::new(':', S::Joint)),
-            TokenTree::Punct(Punct::new(':', S::Alone)),
-            TokenTree::Ident(Ident::new(&cr, Span::call_site())),
-            TokenTree::Punct(Punct::new(':', S::Joint)),
-            TokenTree::Punct(Punct::new(':', S::Alone)),
-            TokenTree::Ident(Ident::new("print_on_exit", Span::call_site())),
-            TokenTree::Punct(Punct::new('!', S::Alone)),
-            TokenTree::Group(Group::new(D::Parenthesis, TokenStream::new())),
-            TokenTree::Punct(Punct::new(';', S::Alone)),
-            TokenTree::Group(body),
-        ]
+    profile_fn(cr, args, &f.sig, block)?;
+    Ok(f.into_token_stream())
+}
+
+fn expand_profile(attr: TokenStream, items: TokenStream) -> syn::Result<TokenStream2> {
+    let args = syn::parse::<Args>(attr)?;
+    if args.skip {
+        return Ok(items.into());
+    }
+
+    let cr = crate_path();
+    let item = match syn::parse::<syn::Item>(items.clone()) {
+        Ok(syn::Item::Verbatim(_)) | Err(_) => {
+            return profile_associated_fn(&cr, &args, items.clone()).map_err(|e| {
+                // Report why it isn't a regular item if it's not a function either
+                syn::parse::<syn::Item>(items).err().unwrap_or(e)
+            });
+        }
+        Ok(item) => item,
     };
-    let tree = TokenTree::from(proc_macro::Group::new(
-        proc_macro::Delimiter::Brace,
-        TokenStream::from_iter(profile),
-    ));
-    items.push(tree);
+    if let (Some(name), syn::Item::Impl(_) | syn::Item::Mod(_) | syn::Item::Trait(_)) =
+        (&args.name, &item)
+    {
+        return Err(syn::Error::new_spanned(
+            name,
+            "`name` can only be used on functions",
+        ));
+    }
+    match item {
+        syn::Item::Fn(mut f) => {
+            profile_fn(&cr, &args, &f.sig, &mut f.block)?;
+            Ok(f.into_token_stream())
+        }
+        syn::Item::Impl(mut i) => {
+            profile_impl(&cr, &args, "", &mut i)?;
+            Ok(i.into_token_stream())
+        }
+        syn::Item::Trait(mut t) => {
+            profile_trait(&cr, &args, "", &mut t)?;
+            Ok(t.into_token_stream())
+        }
+        syn::Item::Mod(mut m) => {
+            profile_mod(&cr, &args, "", &mut m)?;
+            Ok(m.into_token_stream())
+        }
+        item => Err(syn::Error::new_spanned(
+            item,
+            "Expected a function, an impl block, a trait or an inline module",
+        )),
+    }
+}
+
+#[proc_macro_attribute]
+pub fn profile(attr: TokenStream, items: TokenStream) -> TokenStream {
+    expand_profile(attr, items)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
 
-    TokenStream::from_iter(items)
+#[proc_macro_attribute]
+pub fn main(_: TokenStream, items: TokenStream) -> TokenStream {
+    let mut f = match syn::parse::<syn::ItemFn>(items) {
+        Ok(f) => f,
+        Err(e) => return e.into_compile_error().into(),
+    };
+    let cr = crate_path();
+    let body = &f.block;
+    f.block = syn::parse_quote!({
+        #cr::print_on_exit!();
+        #body
+    });
+    f.into_token_stream().into()
 }
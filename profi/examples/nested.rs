@@ -2,6 +2,7 @@ use profi::{print_on_exit, prof};
 
 fn main() {
     // Try running with 'cargo run --release --example benchmark --features deep-hierarchy'
+    // or changing the line below to 'print_on_exit!(hierarchy = deep)'
     // for a look on how the feature works
     //
     // If 'deep-hierarchy' is enabled, each call will go deeper in the hierarchy
@@ -0,0 +1,177 @@
+//! User-defined statistics added as columns of the report.
+
+/// Custom statistic computed for each scope, registered with [`register_aggregator`].
+///
+/// Each aggregator adds a column to the table, and its values are stored in [`ScopeStats::aggregates`](crate::ScopeStats::aggregates),
+/// so they're also serialized with the `serde` feature.
+///
+/// # Example
+/// ```
+/// use profi::{prof, Aggregator};
+/// use std::time::Duration;
+///
+/// struct GeometricMean;
+///
+/// impl Aggregator for GeometricMean {
+///   fn name(&self) -> &str {
+///     "Geometric mean (µs)"
+///   }
+///
+///   fn aggregate(&self, calls: &[Duration], _counters: &[(String, u64)]) -> Option<f64> {
+///     if calls.is_empty() {
+///       return None;
+///     }
+///     let logs = calls.iter().map(|d| (d.as_secs_f64() * 1e6).max(f64::MIN_POSITIVE).ln());
+///     Some((logs.sum::<f64>() / calls.len() as f64).exp())
+///   }
+/// }
+///
+/// fn main() {
+///   profi::register_aggregator(GeometricMean);
+///   for _ in 0..10 {
+///     prof!(work);
+///   }
+///   let report = profi::report();
+///   # #[cfg(all(feature = "enable", not(feature = "bounded-memory")))]
+///   assert!(report.scope("work").unwrap().aggregate("Geometric mean (µs)").is_some());
+///   report.print(std::io::stdout(), &Default::default()).unwrap();
+/// }
+/// ```
+pub trait Aggregator: Send + Sync {
+    /// Name of the column, must be unique.
+    fn name(&self) -> &str;
+
+    /// Computes the value of a scope from the duration of each of its calls and the totals of its [counters](crate::prof_count).
+    ///
+    /// With the `bounded-memory` feature the durations are not stored, so `calls` is always empty.
+    /// Returns `None` if the value can't be computed, shown as `-` in the table.
+    fn aggregate(&self, calls: &[std::time::Duration], counters: &[(String, u64)]) -> Option<f64>;
+
+    /// Formats the value for the table, with two decimals by default.
+    fn format(&self, value: f64) -> String {
+        format!("{value:.2}")
+    }
+}
+
+/// Built-in [`Aggregator`] with the [Apdex](https://en.wikipedia.org/wiki/Apdex) score of each scope, between `0.0` and `1.0`.
+///
+/// Calls that take at most the target time are satisfied, up to four times the target are tolerating, and slower ones are frustrated.
+/// The score is `(satisfied + tolerating / 2) / calls`.
+///
+/// The column is named after the target, like `Apdex (T=50.00ms)`, so several targets can be registered at the same time.
+///
+/// # Example
+/// ```
+/// use profi::{prof, Apdex};
+/// use std::time::Duration;
+///
+/// fn main() {
+///   let apdex = Apdex::new(Duration::from_millis(50));
+///   let name = apdex.column().to_owned();
+///   profi::register_aggregator(apdex);
+///   for _ in 0..10 {
+///     prof!(request);
+///   }
+///   let report = profi::report();
+///   # #[cfg(all(feature = "enable", not(feature = "bounded-memory")))]
+///   assert_eq!(report.scope("request").unwrap().aggregate(&name), Some(1.0));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Apdex {
+    target: std::time::Duration,
+    name: String,
+}
+
+impl Apdex {
+    pub fn new(target: std::time::Duration) -> Self {
+        Self {
+            target,
+            name: format!("Apdex (T={target:.2?})"),
+        }
+    }
+
+    /// Name of the column, and key in [`ScopeStats::aggregates`](crate::ScopeStats::aggregates).
+    pub fn column(&self) -> &str {
+        &self.name
+    }
+
+    /// Number of satisfied, tolerating and frustrated calls.
+    pub fn buckets(&self, calls: &[std::time::Duration]) -> (usize, usize, usize) {
+        let satisfied = calls.iter().filter(|&&d| d <= self.target).count();
+        let tolerating = calls
+            .iter()
+            .filter(|&&d| d > self.target && d <= self.target * 4)
+            .count();
+        (satisfied, tolerating, calls.len() - satisfied - tolerating)
+    }
+}
+
+impl Aggregator for Apdex {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn aggregate(&self, calls: &[std::time::Duration], _: &[(String, u64)]) -> Option<f64> {
+        if calls.is_empty() {
+            return None;
+        }
+        let (satisfied, tolerating, _) = self.buckets(calls);
+        Some((satisfied as f64 + tolerating as f64 / 2.0) / calls.len() as f64)
+    }
+}
+
+#[cfg(feature = "enable")]
+static AGGREGATORS: std::sync::RwLock<Vec<Box<dyn Aggregator>>> =
+    std::sync::RwLock::new(Vec::new());
+
+/// Adds a custom statistic to all the following reports, see [`Aggregator`].
+///
+/// Registering an aggregator with the same name as another one replaces it.
+#[allow(unused_variables)]
+pub fn register_aggregator(aggregator: impl Aggregator + 'static) {
+    #[cfg(feature = "enable")]
+    {
+        let mut aggregators = AGGREGATORS.write().unwrap_or_else(|e| e.into_inner());
+        aggregators.retain(|a| a.name() != aggregator.name());
+        aggregators.push(Box::new(aggregator));
+    }
+}
+
+/// Returns `true` if any aggregator is registered, so the duration of each call must be kept.
+pub(crate) fn any() -> bool {
+    #[cfg(feature = "enable")]
+    {
+        !AGGREGATORS
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_empty()
+    }
+    #[cfg(not(feature = "enable"))]
+    false
+}
+
+/// Computes the registered aggregators for `stats`, and discards the durations they used.
+#[cfg(feature = "enable")]
+pub(crate) fn apply(stats: &mut crate::ScopeStats) {
+    let calls = std::mem::take(&mut stats.durations);
+    for aggregator in AGGREGATORS.read().unwrap_or_else(|e| e.into_inner()).iter() {
+        if let Some(value) = aggregator.aggregate(&calls, &stats.counters) {
+            stats.aggregates.push((aggregator.name().to_owned(), value));
+        }
+    }
+}
+
+/// Formats `value` with the aggregator `name`, or with two decimals if it's not registered.
+#[cfg(feature = "enable")]
+pub(crate) fn format(name: &str, value: f64) -> String {
+    match AGGREGATORS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|a| a.name() == name)
+    {
+        Some(aggregator) => aggregator.format(value),
+        None => format!("{value:.2}"),
+    }
+}
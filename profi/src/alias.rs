@@ -0,0 +1,51 @@
+//! Renaming of scopes when aggregating the results.
+
+#[cfg(feature = "enable")]
+type Aliases = std::collections::HashMap<Box<str>, Box<str>>;
+
+#[cfg(feature = "enable")]
+static ALIASES: std::sync::RwLock<Option<Aliases>> = std::sync::RwLock::new(None);
+
+/// Reports the scopes named `old` as `new`, so they're merged with it.
+///
+/// Useful when a scope was renamed, for example by a new version of a library, to keep merging it and comparing it with [`Report::diff`](crate::Report::diff) against older baselines,
+/// whose scopes are also renamed when compared.
+///
+/// Aliases are applied when aggregating the report, so they affect all the results, even the ones recorded before.
+/// They are not chained, `old` is always reported as `new`.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   profi::alias("db::query_v1", "db::query");
+///   {
+///     prof!("db::query_v1");
+///   }
+///   {
+///     prof!("db::query");
+///   }
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(profi::report().scope("db::query").unwrap().calls, 2);
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn alias(old: &str, new: &str) {
+    #[cfg(feature = "enable")]
+    ALIASES
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(Default::default)
+        .insert(old.into(), new.into());
+}
+
+/// Name under which the scope `name` is reported.
+#[cfg(feature = "enable")]
+pub(crate) fn resolve(name: &str) -> std::borrow::Cow<'_, str> {
+    let aliases = ALIASES.read().unwrap_or_else(|e| e.into_inner());
+    match aliases.as_ref().and_then(|a| a.get(name)) {
+        Some(new) => std::borrow::Cow::Owned(new.to_string()),
+        None => std::borrow::Cow::Borrowed(name),
+    }
+}
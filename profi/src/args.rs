@@ -0,0 +1,216 @@
+//! Profiling controls exposed as command line flags.
+
+/// Flags understood by [`init_from_args`], printed with `--profi-help`.
+const HELP: &str = "\
+Profiling flags:
+  --profi                       Print a report to stderr on exit
+  --profi-output <path>         Write the report to <path> instead
+  --profi-format <format>       table, folded, markdown, html or binary
+  --profi-filter <patterns>     Only profile the scopes matching a comma separated list of names, with * wildcards
+  --profi-interval <duration>   Also write a report every <duration>, like 500ms, 30s or 5m
+  --profi-help                  Print this help and exit
+";
+
+/// Reads the `--profi-*` flags from `args`, and returns the rest of them with a handle that writes the report when dropped.
+///
+/// Lets the end users of a binary choose how it's profiled, without touching the code:
+///
+/// | Flag                          | Effect                                                                             |
+/// |-------------------------------|------------------------------------------------------------------------------------|
+/// | `--profi`                     | Prints a report to stderr on exit                                                  |
+/// | `--profi-output <path>`       | Writes the report to `path` instead                                                |
+/// | `--profi-format <format>`     | `table`, `folded`, `markdown`, `html` or `binary`                                  |
+/// | `--profi-filter <patterns>`   | Only profiles the matching scopes, like `PROFI_FILTER`, see [`set_filter`](crate::set_filter) |
+/// | `--profi-interval <duration>` | Also writes a report every `duration`, like `500ms`, `30s` or `5m`                 |
+/// | `--profi-help`                | Prints the flags and exits                                                         |
+///
+/// Values can also be passed as `--profi-format=folded`, and the arguments after `--` are left untouched.
+/// Invalid flags print a warning and are ignored.
+///
+/// Without any of them profiling is turned off with [`set_enabled(false)`](crate::set_enabled),
+/// so users only pay for it when they ask for it.
+///
+/// # Example
+/// ```
+/// fn main() {
+///   let (args, _profi) = profi::init_from_args(std::env::args());
+///   // Parse the rest as usual, e.g. with `clap::Parser::parse_from(args)`
+///   let args = args.collect::<Vec<_>>();
+///   profi::prof!(work);
+/// }
+/// ```
+///
+/// ```
+/// let args = ["app", "--profi-format=folded", "input.txt", "--profi-filter", "parse::*"].map(String::from);
+/// let (args, _profi) = profi::init_from_args(args);
+/// assert_eq!(args.collect::<Vec<_>>(), ["app", "input.txt"]);
+/// ```
+pub fn init_from_args(
+    args: impl IntoIterator<Item = String>,
+) -> (std::vec::IntoIter<String>, ArgsHandle) {
+    let mut rest = Vec::new();
+    let mut flags = Flags::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            rest.push(arg);
+            rest.extend(args.by_ref());
+            break;
+        }
+        // Not `--profiler` or other flags of the program
+        let Some(flag) = arg
+            .strip_prefix("--profi")
+            .filter(|f| f.is_empty() || f.starts_with(['-', '=']))
+        else {
+            rest.push(arg);
+            continue;
+        };
+        flags.any = true;
+        let (flag, inline) = match flag.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_owned())),
+            None => (flag, None),
+        };
+        let mut value = |name: &str| {
+            let value = inline.clone().or_else(|| args.next());
+            if value.is_none() {
+                eprintln!("[profi] Missing value for --profi{name}");
+            }
+            value
+        };
+        match flag {
+            "" => {}
+            "-help" => {
+                eprint!("{HELP}");
+                std::process::exit(0)
+            }
+            "-output" => flags.output = value(flag).map(Into::into),
+            "-format" => {
+                flags.format = value(flag).and_then(|f| match f.as_str() {
+                    "table" => Some(crate::Format::Table),
+                    "folded" => Some(crate::Format::Folded),
+                    "markdown" => Some(crate::Format::Markdown),
+                    "html" => Some(crate::Format::Html),
+                    "binary" => Some(crate::Format::Binary),
+                    _ => {
+                        eprintln!(
+                        "[profi] Invalid format `{f}`, expected table, folded, markdown, html or binary"
+                    );
+                        None
+                    }
+                })
+            }
+            "-filter" => flags.filter = value(flag),
+            "-interval" => {
+                flags.interval = value(flag).and_then(|d| {
+                    let interval = parse_duration(&d);
+                    if interval.is_none() {
+                        eprintln!(
+                        "[profi] Invalid interval `{d}`, expected a duration like 500ms, 30s or 5m"
+                    );
+                    }
+                    interval
+                })
+            }
+            _ => eprintln!("[profi] Unknown flag --profi{flag}, see --profi-help"),
+        }
+    }
+    (rest.into_iter(), flags.start())
+}
+
+/// Flags read by [`init_from_args`].
+#[derive(Default)]
+struct Flags {
+    /// If any `--profi` flag was found.
+    any: bool,
+    output: Option<std::path::PathBuf>,
+    format: Option<crate::Format>,
+    filter: Option<String>,
+    interval: Option<std::time::Duration>,
+}
+
+impl Flags {
+    fn start(self) -> ArgsHandle {
+        if !self.any {
+            crate::set_enabled(false);
+            return ArgsHandle::default();
+        }
+        crate::set_enabled(true);
+        #[cfg(feature = "enable")]
+        if let Some(patterns) = &self.filter {
+            crate::set_filter(crate::filter::patterns_filter(patterns));
+        }
+        let mut options = crate::PrintOptions::new();
+        if let Some(format) = self.format {
+            options = options.format(format);
+        }
+
+        let file = self.output.as_ref().and_then(|path| {
+            std::fs::File::create(path)
+                .inspect_err(|e| {
+                    eprintln!(
+                        "[profi] Could not create {}, printing to stderr: {e}",
+                        path.display()
+                    )
+                })
+                .ok()
+        });
+        let writer = |file: Option<&std::fs::File>| -> Box<dyn std::io::Write + Send> {
+            // Clones share the cursor, so the reports are written one after the other
+            match file.and_then(|f| f.try_clone().ok()) {
+                Some(file) => Box::new(file),
+                None => Box::new(std::io::stderr()),
+            }
+        };
+        let periodic = self.interval.map(|every| {
+            crate::PeriodicReport::new(every)
+                .options(options.clone())
+                .start(writer(file.as_ref()))
+        });
+        ArgsHandle {
+            periodic,
+            printer: Some(crate::ExitPrinter::to(writer(file.as_ref()), options)),
+        }
+    }
+}
+
+/// Parses a duration like `250us`, `500ms`, `30s`, `5m` or `1h`, in seconds without a unit.
+pub(crate) fn parse_duration(duration: &str) -> Option<std::time::Duration> {
+    let duration = duration.trim();
+    let split = duration
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(duration.len());
+    let (value, unit) = duration.split_at(split);
+    let value = value.parse::<f64>().ok()?;
+    let secs = match unit.trim() {
+        "ns" => value / 1e9,
+        "us" | "µs" => value / 1e6,
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => return None,
+    };
+    std::time::Duration::try_from_secs_f64(secs)
+        .ok()
+        .filter(|d| !d.is_zero())
+}
+
+/// Handle returned by [`init_from_args`], writes the report when it's dropped, like [`ExitPrinter`](crate::ExitPrinter).
+///
+/// Keep it alive until the end of `main`.
+#[derive(Default)]
+#[must_use = "the report is written when the handle is dropped"]
+pub struct ArgsHandle {
+    /// Declared before `printer` so the periodic reports stop first
+    periodic: Option<crate::PeriodicHandle>,
+    printer: Option<crate::ExitPrinter<Box<dyn std::io::Write + Send>>>,
+}
+
+impl std::fmt::Debug for ArgsHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArgsHandle")
+            .field("periodic", &self.periodic.is_some())
+            .field("printer", &self.printer.is_some())
+            .finish()
+    }
+}
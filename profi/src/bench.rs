@@ -0,0 +1,84 @@
+//! Scopes of each [`criterion`](https://docs.rs/criterion) benchmark, written next to its results.
+//!
+//! [`Profiler`] is called by Criterion when benchmarks are run with `--profile-time`,
+//! it discards the measures taken before each benchmark and writes the scopes measured while it ran
+//! to `profi.txt` in its directory, like `target/criterion/<benchmark>/profile/profi.txt`.
+//!
+//! # Example
+//! ```no_run
+//! use criterion::{criterion_group, criterion_main, Criterion};
+//!
+//! fn parse(input: &str) -> usize {
+//!   profi::prof!();
+//!   input.split(',').count()
+//! }
+//!
+//! fn bench(c: &mut Criterion) {
+//!   c.bench_function("parse", |b| b.iter(|| parse("a,b,c")));
+//! }
+//!
+//! criterion_group! {
+//!   name = benches;
+//!   config = Criterion::default().with_profiler(profi::bench::Profiler::new());
+//!   targets = bench
+//! }
+//! criterion_main!(benches);
+//! ```
+//!
+//! And run them with `cargo bench -- --profile-time 5`.
+
+/// Writes the scopes of each benchmark to its directory, see the [module docs](self).
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    options: crate::PrintOptions,
+}
+
+impl Profiler {
+    /// Writes a table with the default [`PrintOptions`](crate::PrintOptions).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes the scopes with `options`, the extension of the file follows its [`Format`](crate::Format).
+    ///
+    /// # Example
+    /// ```
+    /// use profi::{Format, PrintOptions};
+    ///
+    /// let profiler = profi::bench::Profiler::with_options(PrintOptions::new().format(Format::Folded));
+    /// ```
+    pub fn with_options(options: crate::PrintOptions) -> Self {
+        Self { options }
+    }
+
+    /// File written in the directory of each benchmark.
+    fn file_name(&self) -> &'static str {
+        match self.options.format {
+            crate::Format::Table => "profi.txt",
+            crate::Format::Folded => "profi.folded",
+            crate::Format::Markdown => "profi.md",
+            crate::Format::Html => "profi.html",
+            crate::Format::Binary => "profi.trace",
+        }
+    }
+}
+
+impl ::criterion::profiler::Profiler for Profiler {
+    fn start_profiling(&mut self, _benchmark_id: &str, _benchmark_dir: &std::path::Path) {
+        crate::reset();
+    }
+
+    fn stop_profiling(&mut self, benchmark_id: &str, benchmark_dir: &std::path::Path) {
+        let path = benchmark_dir.join(self.file_name());
+        let report = crate::report();
+        let written = std::fs::create_dir_all(benchmark_dir)
+            .and_then(|()| std::fs::File::create(&path))
+            .and_then(|file| report.print(std::io::BufWriter::new(file), &self.options));
+        if let Err(e) = written {
+            eprintln!(
+                "[profi] Could not write the scopes of {benchmark_id} to {}: {e}",
+                path.display()
+            );
+        }
+    }
+}
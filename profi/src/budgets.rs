@@ -0,0 +1,246 @@
+//! Time budgets of the scopes, checked every time a report is generated.
+
+/// Time a scope is allowed to take, see [`set_budget`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Budget {
+    /// Limit of the average time per call, robust against the occasional slow call.
+    Average(std::time::Duration),
+    /// Limit of every call, exceeded if the slowest one takes longer.
+    Call(std::time::Duration),
+    /// Limit of the time of all calls combined.
+    Total(std::time::Duration),
+}
+
+impl Budget {
+    /// Time the scope is allowed to take.
+    pub fn limit(&self) -> std::time::Duration {
+        match *self {
+            Budget::Average(limit) | Budget::Call(limit) | Budget::Total(limit) => limit,
+        }
+    }
+
+    /// Time the scope took, compared against the limit.
+    pub fn actual(&self, scope: &crate::ScopeStats) -> std::time::Duration {
+        match self {
+            Budget::Average(_) => scope.average,
+            Budget::Call(_) => scope.max,
+            Budget::Total(_) => scope.total_real,
+        }
+    }
+}
+
+/// Scope that took longer than its [`Budget`], see [`Report::budget_violations`](crate::Report::budget_violations).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BudgetViolation {
+    pub scope: String,
+    pub budget: Budget,
+    /// Time the scope took, its average, slowest call or total depending on the budget.
+    pub actual: std::time::Duration,
+}
+
+impl BudgetViolation {
+    /// How much longer than its budget the scope took.
+    pub fn over(&self) -> std::time::Duration {
+        self.actual.saturating_sub(self.budget.limit())
+    }
+}
+
+impl std::fmt::Display for BudgetViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let what = match self.budget {
+            Budget::Average(_) => "on average per call",
+            Budget::Call(_) => "on its slowest call",
+            Budget::Total(_) => "in total",
+        };
+        write!(
+            f,
+            "{} took {:.2?} {what}, over its budget of {:.2?}",
+            self.scope,
+            self.actual,
+            self.budget.limit()
+        )
+    }
+}
+
+/// Patterns and their budgets, in the order they were set.
+#[cfg(feature = "enable")]
+static BUDGETS: std::sync::RwLock<Vec<(Box<str>, Budget)>> = std::sync::RwLock::new(Vec::new());
+
+#[cfg(feature = "enable")]
+type Callback = dyn Fn(&BudgetViolation) + Send + Sync;
+
+#[cfg(feature = "enable")]
+static CALLBACK: std::sync::RwLock<Option<std::sync::Arc<Callback>>> = std::sync::RwLock::new(None);
+
+/// Gives the scopes whose name matches `pattern` a time budget, where `*` matches any number of characters.
+///
+/// Budgets are checked every time a report is generated, by [`report`](crate::report), [`print_on_exit!`](crate::print_on_exit) or a periodic report.
+/// Each scope over its budget is printed to stderr, or passed to the callback set with [`on_budget_violation`],
+/// highlighted in the table and listed in a "Budgets" section after it.
+/// With `print_on_exit!(fail_on_budget)` the process also exits with code 1 after printing, turning a run into a performance gate.
+///
+/// A scope can have a budget of each kind, setting one again for the same pattern replaces it.
+/// Also available as [`assert_budget!`](crate::assert_budget), or from a file with [`load_budgets`].
+///
+/// # Example
+/// ```
+/// use profi::{prof, Budget};
+/// use std::time::Duration;
+///
+/// fn main() {
+///   profi::set_budget("physics", Budget::Average(Duration::from_millis(4)));
+///   profi::set_budget("physics", Budget::Call(Duration::from_micros(1)));
+///   {
+///     prof!(physics);
+///     std::thread::sleep(Duration::from_millis(1));
+///   }
+///   let violations = profi::report().budget_violations();
+///   # #[cfg(feature = "enable")]
+///   # {
+///   assert_eq!(violations.len(), 1);
+///   assert_eq!(violations[0].budget, Budget::Call(Duration::from_micros(1)));
+///   # }
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn set_budget(pattern: impl Into<String>, budget: Budget) {
+    #[cfg(feature = "enable")]
+    {
+        let pattern = pattern.into().into_boxed_str();
+        let mut budgets = BUDGETS.write().unwrap_or_else(|e| e.into_inner());
+        let kind = std::mem::discriminant(&budget);
+        budgets.retain(|(p, b)| *p != pattern || std::mem::discriminant(b) != kind);
+        budgets.push((pattern, budget));
+    }
+}
+
+/// Reads the budgets of the scopes from the file at `path`, see [`set_budget`].
+///
+/// Each line is a pattern followed by its budget, like `4ms`, `250us` or `2s`, and optionally its kind:
+/// `average`, the default, `call` or `total`.
+/// Empty lines and the ones starting with `#` are skipped.
+/// ```text
+/// # Scope   Budget   Kind
+/// physics   4ms
+/// render    16ms     call
+/// startup   2s       total
+/// ```
+///
+/// # Example
+/// ```
+/// fn main() {
+///   let path = std::env::temp_dir().join("profi-budgets.txt");
+///   std::fs::write(&path, "# Scope  Budget\nphysics  4ms\nstartup  2s  total\n").unwrap();
+///   profi::load_budgets(&path).unwrap();
+/// }
+/// ```
+pub fn load_budgets(path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    let text = std::fs::read_to_string(path)?;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let (pattern, limit, kind) = (words.next(), words.next(), words.next());
+        let budget = limit
+            .and_then(crate::args::parse_duration)
+            .and_then(|limit| budget(limit, kind.unwrap_or("average")));
+        match (pattern, budget) {
+            (Some(pattern), Some(budget)) => set_budget(pattern, budget),
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("line {} is not a valid budget", i + 1),
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Calls `f` with each scope over its budget every time a report is generated, instead of printing it to stderr.
+///
+/// # Example
+/// ```
+/// fn main() {
+///   profi::on_budget_violation(|violation| panic!("{violation}"));
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn on_budget_violation(f: impl Fn(&BudgetViolation) + Send + Sync + 'static) {
+    #[cfg(feature = "enable")]
+    {
+        *CALLBACK.write().unwrap_or_else(|e| e.into_inner()) = Some(std::sync::Arc::new(f));
+    }
+}
+
+/// Budget of the given kind, `None` if it's not one.
+fn budget(limit: std::time::Duration, kind: &str) -> Option<Budget> {
+    match kind {
+        "average" => Some(Budget::Average(limit)),
+        "call" => Some(Budget::Call(limit)),
+        "total" => Some(Budget::Total(limit)),
+        _ => None,
+    }
+}
+
+/// Scopes of `report` over their budget, in the order of the table.
+#[cfg(feature = "enable")]
+pub(crate) fn violations(report: &crate::Report) -> Vec<BudgetViolation> {
+    let budgets = BUDGETS.read().unwrap_or_else(|e| e.into_inner());
+    report
+        .scopes
+        .iter()
+        .flat_map(|scope| scope_violations(&budgets, scope))
+        .collect()
+}
+
+/// If `scope` is over any of its budgets.
+#[cfg(feature = "enable")]
+pub(crate) fn is_over(scope: &crate::ScopeStats) -> bool {
+    let budgets = BUDGETS.read().unwrap_or_else(|e| e.into_inner());
+    let over = scope_violations(&budgets, scope).next().is_some();
+    over
+}
+
+/// Budgets `scope` is over, like in a `CODEOWNERS` file the last pattern that matches wins for each kind.
+#[cfg(feature = "enable")]
+fn scope_violations<'a>(
+    budgets: &'a [(Box<str>, Budget)],
+    scope: &'a crate::ScopeStats,
+) -> impl Iterator<Item = BudgetViolation> + 'a {
+    let mut seen = Vec::new();
+    budgets
+        .iter()
+        .rev()
+        .filter(|(pattern, _)| crate::filter::glob_match(pattern, &scope.name))
+        .filter(move |(_, budget)| {
+            let kind = std::mem::discriminant(budget);
+            let first = !seen.contains(&kind);
+            seen.push(kind);
+            first
+        })
+        .filter_map(|(_, budget)| {
+            let actual = budget.actual(scope);
+            (actual > budget.limit()).then(|| BudgetViolation {
+                scope: scope.name.clone(),
+                budget: *budget,
+                actual,
+            })
+        })
+}
+
+/// Reports the violations of a newly generated report, to the callback or to stderr.
+#[cfg(feature = "enable")]
+pub(crate) fn check(report: &crate::Report) {
+    let callback = CALLBACK.read().unwrap_or_else(|e| e.into_inner()).clone();
+    for violation in violations(report) {
+        match &callback {
+            Some(f) => f(&violation),
+            None => eprintln!("[profi] Budget exceeded: {violation}"),
+        }
+    }
+}
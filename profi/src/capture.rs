@@ -0,0 +1,260 @@
+//! Reports built from the captures written by [`export::stream_jsonl`](crate::export::stream_jsonl).
+
+/// Combines the captures of several processes into a single report, like the threads of a single process are combined.
+///
+/// Each file must be a capture written by [`export::stream_jsonl`](crate::export::stream_jsonl),
+/// usually by [`export::stream_process`](crate::export::stream_process) in each of the processes.
+/// The threads of every process are kept apart in [`Report::threads`](crate::Report::threads), numbered in the order of `paths`,
+/// and a "Process" column shows where each scope ran.
+///
+/// Processes are named after their file, `profi-<pid>.jsonl` becomes `<pid>`.
+/// Like with [`load_mmap`](crate::load_mmap), the duration of each call is not kept, and a truncated last line is ignored.
+///
+/// Returns an empty report if the `enable` feature is disabled.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   let dir = std::env::temp_dir().join("profi-merge-files");
+///   let _ = std::fs::remove_dir_all(&dir);
+///   std::fs::create_dir_all(&dir).unwrap();
+///   // Each worker process would call this at its start
+///   let stream = profi::export::stream_process(&dir, std::time::Duration::from_secs(1)).unwrap();
+///   for _ in 0..10 {
+///     prof!(work);
+///   }
+///   stream.stop();
+///
+///   // The coordinator merges the files of all the workers
+///   let paths = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path());
+///   let report = profi::merge_files(paths).unwrap();
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(report.scope("work").unwrap().processes, [std::process::id().to_string()]);
+///   report.print(std::io::stdout(), &Default::default()).unwrap();
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn merge_files(
+    paths: impl IntoIterator<Item = impl AsRef<std::path::Path>>,
+) -> std::io::Result<crate::Report> {
+    #[cfg(feature = "enable")]
+    {
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let path = path.as_ref();
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                let process = stem.strip_prefix("profi-").unwrap_or(&stem).to_owned();
+                Ok((process, std::fs::read(path)?))
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let mut progress =
+            crate::progress::Progress::new(files.iter().map(|(_, bytes)| bytes.len()).sum());
+        let mut all = Vec::new();
+        let mut wall_time = std::time::Duration::ZERO;
+        for (process, bytes) in files {
+            let (threads, time) = threads(&bytes, all.len(), &mut progress)?;
+            all.extend(threads.into_iter().map(|thread| crate::ThreadStats {
+                process: Some(process.clone()),
+                ..thread
+            }));
+            wall_time = wall_time.max(time);
+        }
+        let report = crate::process::report_from_threads(all);
+        progress.finish();
+        Ok(crate::Report {
+            wall_time,
+            ..report
+        })
+    }
+    #[cfg(not(feature = "enable"))]
+    Ok(crate::Report::default())
+}
+
+/// Threads of a capture, numbered from `first_index`, and the time when its last scope ended.
+#[cfg(feature = "enable")]
+pub(crate) fn threads(
+    bytes: &[u8],
+    first_index: usize,
+    progress: &mut crate::progress::Progress,
+) -> std::io::Result<(Vec<crate::ThreadStats>, std::time::Duration)> {
+    let mut threads = indexmap::IndexMap::<u64, (Option<String>, crate::process::Closed)>::new();
+    let mut wall_time = std::time::Duration::ZERO;
+    let mut version = crate::export::STREAM_FORMAT_VERSION;
+    let mut lines = bytes.split(|&b| b == b'\n').enumerate().peekable();
+    while let Some((i, line)) = lines.next() {
+        progress.advance(line.len() + 1);
+        if line.is_empty() {
+            continue;
+        }
+        let record = match std::str::from_utf8(line).ok().and_then(Record::parse) {
+            Some(Line::Scope(record)) => record,
+            Some(Line::Header { format, .. }) if format <= crate::export::STREAM_FORMAT_VERSION => {
+                version = format;
+                continue;
+            }
+            Some(Line::Header { format, profi }) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "capture written by profi {profi} with format {format}, this version only reads up to {}, update profi to read it",
+                        crate::export::STREAM_FORMAT_VERSION
+                    ),
+                ));
+            }
+            None if lines.peek().is_none() => break,
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("line {} is not a scope streamed by profi", i + 1),
+                ))
+            }
+        };
+        let (_, thread) = threads
+            .entry(record.thread)
+            .or_insert_with(|| (record.thread_name.map(String::from), Default::default()));
+        // Before version 3 the later polls of a future continued its last call, they're kept as calls of their own
+        let resumed = record.resumed && version >= 3;
+        thread.add(
+            &record.name,
+            record.depth,
+            record.duration + record.polled,
+            resumed,
+        );
+        wall_time = wall_time.max(record.start + record.duration);
+    }
+    let threads = threads
+        .into_values()
+        .enumerate()
+        .map(|(i, (name, thread))| thread.into_thread(first_index + i, name))
+        .collect();
+    Ok((threads, wall_time))
+}
+
+/// Line of a capture, see [`export::stream_jsonl`](crate::export::stream_jsonl).
+#[cfg(feature = "enable")]
+enum Line<'a> {
+    Scope(Record<'a>),
+    /// Start of a stream.
+    Header {
+        format: u8,
+        profi: std::borrow::Cow<'a, str>,
+    },
+}
+
+/// Completed scope of a capture.
+#[cfg(feature = "enable")]
+struct Record<'a> {
+    thread: u64,
+    thread_name: Option<std::borrow::Cow<'a, str>>,
+    name: std::borrow::Cow<'a, str>,
+    depth: usize,
+    resumed: bool,
+    start: std::time::Duration,
+    duration: std::time::Duration,
+    /// Time of the earlier polls of a future, `0` before version 3.
+    polled: std::time::Duration,
+}
+
+#[cfg(feature = "enable")]
+impl<'a> Record<'a> {
+    /// Parses a line, `None` if it's not a JSON object with all the fields of a scope or a header.
+    fn parse(line: &'a str) -> Option<Line<'a>> {
+        let mut rest = line.trim().strip_prefix('{')?.strip_suffix('}')?;
+        let (mut thread, mut thread_name, mut name, mut depth) = (None, None, None, None);
+        let (mut resumed, mut start, mut duration, mut polled) = (None, None, None, None);
+        let (mut format, mut profi) = (None, None);
+        while !rest.is_empty() {
+            let (key, after) = string(rest)?;
+            let after = after.strip_prefix(':')?;
+            let (value, after) = if after.starts_with('"') {
+                let (value, after) = string(after)?;
+                (Value::Str(value), after)
+            } else {
+                let end = after.find(',').unwrap_or(after.len());
+                (Value::Raw(after[..end].trim()), &after[end..])
+            };
+            match (key.as_ref(), value) {
+                ("thread", Value::Raw(v)) => thread = v.parse().ok(),
+                ("thread_name", Value::Str(v)) => thread_name = Some(Some(v)),
+                ("thread_name", Value::Raw("null")) => thread_name = Some(None),
+                ("name", Value::Str(v)) => name = Some(v),
+                ("depth", Value::Raw(v)) => depth = v.parse().ok(),
+                ("resumed", Value::Raw(v)) => resumed = v.parse().ok(),
+                ("start_ns", Value::Raw(v)) => start = v.parse().ok().map(nanos),
+                ("duration_ns", Value::Raw(v)) => duration = v.parse().ok().map(nanos),
+                ("polled_ns", Value::Raw(v)) => polled = v.parse().ok().map(nanos),
+                ("format", Value::Raw(v)) => format = v.parse().ok(),
+                ("profi", Value::Str(v)) => profi = Some(v),
+                // Unknown fields, written by newer versions
+                _ => {}
+            }
+            rest = match after.strip_prefix(',') {
+                Some(after) => after,
+                None if after.is_empty() => after,
+                None => return None,
+            };
+        }
+        if let (Some(format), Some(profi)) = (format, profi) {
+            return Some(Line::Header { format, profi });
+        }
+        Some(Line::Scope(Self {
+            thread: thread?,
+            thread_name: thread_name?,
+            name: name?,
+            depth: depth?,
+            resumed: resumed?,
+            start: start?,
+            duration: duration?,
+            polled: polled.unwrap_or_default(),
+        }))
+    }
+}
+
+#[cfg(feature = "enable")]
+enum Value<'a> {
+    Str(std::borrow::Cow<'a, str>),
+    /// Number, boolean or `null`.
+    Raw(&'a str),
+}
+
+#[cfg(feature = "enable")]
+fn nanos(ns: u128) -> std::time::Duration {
+    std::time::Duration::new((ns / 1_000_000_000) as u64, (ns % 1_000_000_000) as u32)
+}
+
+/// JSON string at the start of `s` and the text after it, only copied if it has escapes.
+#[cfg(feature = "enable")]
+fn string(s: &str) -> Option<(std::borrow::Cow<'_, str>, &str)> {
+    let s = s.strip_prefix('"')?;
+    let end = s.find(['"', '\\'])?;
+    if s.as_bytes()[end] == b'"' {
+        return Some((s[..end].into(), &s[end + 1..]));
+    }
+    let mut out = String::from(&s[..end]);
+    let mut chars = s[end..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((out.into(), &s[end + i + 1..])),
+            '\\' => out.push(match chars.next()?.1 {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'u' => {
+                    let hex = (0..4)
+                        .map(|_| chars.next().map(|(_, c)| c))
+                        .collect::<Option<String>>()?;
+                    char::from_u32(u32::from_str_radix(&hex, 16).ok()?)
+                        .unwrap_or(char::REPLACEMENT_CHARACTER)
+                }
+                c => c,
+            }),
+            c => out.push(c),
+        }
+    }
+    None
+}
@@ -0,0 +1,175 @@
+//! Time source of all the measures.
+
+/// Source of the time of every measure, see [`set_clock`].
+#[derive(Debug, Clone, Copy)]
+pub enum Clock {
+    /// The CPU timestamp counter through [`minstant`](https://crates.io/crates/minstant), the fastest option.
+    ///
+    /// Falls back to [`std::time::Instant`] if the counter is not available or not reliable.
+    /// The default, unless the `std-clock` feature is enabled.
+    Tsc,
+    /// [`std::time::Instant`], slower but reliable on any platform, including VMs whose timestamp counter is not synchronized.
+    ///
+    /// With the `wasm` feature in the browser, both `Tsc` and `Std` use `performance.now()`.
+    Std,
+    /// Monotonic time since an arbitrary, fixed origin returned by a custom function.
+    Custom(fn() -> std::time::Duration),
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        match cfg!(feature = "std-clock") {
+            true => Clock::Std,
+            false => Clock::Tsc,
+        }
+    }
+}
+
+/// Chooses the source of the time of every measure.
+///
+/// The clock can't change once something is measured, because times from different clocks can't be compared,
+/// so it must be called at the start of the program, before any scope. Calls after the first measure, or a second call, print a warning and are ignored.
+///
+/// Without calling it, the `PROFI_CLOCK` environment variable can be `tsc` or `std` to choose the clock without recompiling,
+/// useful to force `std` in a VM.
+///
+/// # Example
+/// ```
+/// use profi::{prof, Clock};
+///
+/// fn main() {
+///   profi::set_clock(Clock::Std);
+///   prof!(work);
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn set_clock(clock: Clock) {
+    #[cfg(feature = "enable")]
+    if SOURCE.set(Source::new(clock)).is_err() {
+        eprintln!("[profi] The clock can't be changed after measuring, call set_clock at the start of the program");
+    }
+}
+
+/// Clock and its origin, chosen on first use.
+#[cfg(feature = "enable")]
+static SOURCE: std::sync::OnceLock<Source> = std::sync::OnceLock::new();
+
+/// Chosen [`Clock`], resolved once by [`Source::get`] and kept by the thread profilers and the guards,
+/// so taking the time is a direct call to the clock.
+#[cfg(feature = "enable")]
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Source {
+    Tsc(minstant::Instant),
+    Std(std::time::Instant),
+    Custom(fn() -> std::time::Duration),
+}
+
+#[cfg(feature = "enable")]
+impl Source {
+    /// The clock chosen with [`set_clock`] or `PROFI_CLOCK`, which can't change after this call.
+    pub(crate) fn get() -> Source {
+        *SOURCE.get_or_init(Source::from_env)
+    }
+
+    /// Current time of the clock.
+    #[inline(always)]
+    pub(crate) fn now(&self) -> Instant {
+        let since_origin = match self {
+            Source::Tsc(origin) => minstant::Instant::now().duration_since(*origin),
+            Source::Std(origin) => origin.elapsed(),
+            Source::Custom(f) => f(),
+        };
+        Instant(since_origin.as_nanos() as u64)
+    }
+
+    fn new(clock: Clock) -> Self {
+        // `std::time::Instant` panics in the browser
+        #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+        let clock = match clock {
+            Clock::Tsc | Clock::Std => Clock::Custom(crate::wasm::now),
+            custom => custom,
+        };
+        match clock {
+            Clock::Tsc => Source::Tsc(minstant::Instant::now()),
+            Clock::Std => Source::Std(std::time::Instant::now()),
+            Clock::Custom(f) => Source::Custom(f),
+        }
+    }
+
+    fn from_env() -> Self {
+        let clock = match std::env::var("PROFI_CLOCK").as_deref() {
+            Ok("tsc") => Clock::Tsc,
+            Ok("std") => Clock::Std,
+            Ok(other) => {
+                eprintln!("[profi] Unknown PROFI_CLOCK '{other}', expected 'tsc' or 'std'");
+                Clock::default()
+            }
+            Err(_) => Clock::default(),
+        };
+        Source::new(clock)
+    }
+}
+
+/// Current wall-clock time.
+#[cfg(feature = "enable")]
+pub(crate) fn system_time() -> std::time::SystemTime {
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+    return crate::wasm::system_time();
+    #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+    std::time::SystemTime::now()
+}
+
+/// Point in time of the chosen [`Clock`], in nanoseconds since its origin.
+#[cfg(feature = "enable")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Instant(u64);
+
+#[cfg(feature = "enable")]
+impl Instant {
+    /// Placeholder for measures whose time is set right after.
+    pub(crate) const ZERO: Instant = Instant(0);
+
+    /// Current time, prefer [`Source::now`] where the source is at hand.
+    #[inline(always)]
+    pub(crate) fn now() -> Instant {
+        SOURCE.get_or_init(Source::from_env).now()
+    }
+
+    pub(crate) fn duration_since(&self, earlier: Instant) -> std::time::Duration {
+        self.saturating_duration_since(earlier)
+    }
+
+    pub(crate) fn saturating_duration_since(&self, earlier: Instant) -> std::time::Duration {
+        std::time::Duration::from_nanos(self.0.saturating_sub(earlier.0))
+    }
+
+    pub(crate) fn elapsed(&self) -> std::time::Duration {
+        Instant::now().duration_since(*self)
+    }
+
+    pub(crate) fn checked_add(&self, duration: std::time::Duration) -> Option<Instant> {
+        let nanos = u64::try_from(duration.as_nanos()).ok()?;
+        self.0.checked_add(nanos).map(Instant)
+    }
+
+    pub(crate) fn checked_sub(&self, duration: std::time::Duration) -> Option<Instant> {
+        let nanos = u64::try_from(duration.as_nanos()).ok()?;
+        self.0.checked_sub(nanos).map(Instant)
+    }
+}
+
+#[cfg(feature = "enable")]
+impl std::ops::Add<std::time::Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: std::time::Duration) -> Instant {
+        Instant(self.0.saturating_add(duration.as_nanos() as u64))
+    }
+}
+
+#[cfg(feature = "enable")]
+impl std::ops::AddAssign<std::time::Duration> for Instant {
+    fn add_assign(&mut self, duration: std::time::Duration) {
+        *self = *self + duration;
+    }
+}
@@ -0,0 +1,103 @@
+//! Coalescing of bursts of short calls into a single measure.
+
+#[cfg(feature = "enable")]
+use crate::measure::{Measure, MeasureType};
+
+/// Threshold in nanoseconds, `0` if disabled.
+#[cfg(feature = "enable")]
+static THRESHOLD: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// Coalesces consecutive calls to the same scope that are shorter than `threshold` into a single measure with a count.
+///
+/// Tight loops of tiny scopes produce millions of measures, most of them shorter than the timer can resolve.
+/// Coalescing them keeps the memory and the time to build the report low, while the total time and the number of calls stay exact.
+/// The calls of a run are considered to take the same time, so the minimum, maximum and standard deviation are approximate,
+/// and [aggregators](crate::Aggregator) see each run as a single call.
+///
+/// Only calls without children, counters or other calls in between are coalesced, and the default is `None`, which disables it.
+/// The timer resolution measured by [`calibrate_noise`](crate::calibrate_noise) is a good threshold.
+/// Has no effect with the `bounded-memory` feature, which already folds every call as soon as it ends.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   let noise = profi::calibrate_noise(10);
+///   profi::set_coalescing(Some(noise.timer_resolution.max(std::time::Duration::from_micros(1))));
+///   for _ in 0..1000 {
+///     prof!(tiny);
+///   }
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(profi::report().scope("tiny").unwrap().calls, 1000);
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn set_coalescing(threshold: Option<std::time::Duration>) {
+    #[cfg(feature = "enable")]
+    {
+        let nanos = threshold.map_or(0, |t| t.as_nanos().min(u64::MAX as u128) as u64);
+        THRESHOLD.store(nanos, std::sync::atomic::Ordering::Relaxed);
+        crate::hooks::set(crate::hooks::COALESCE, nanos != 0);
+    }
+}
+
+/// If [`set_coalescing`] is enabled.
+#[cfg(feature = "enable")]
+#[inline(always)]
+pub(crate) fn is_enabled() -> bool {
+    THRESHOLD.load(std::sync::atomic::Ordering::Relaxed) != 0
+}
+
+/// Merges the call that ends with `end`, whose `Start` is the last of `measures`, into the previous call if both are short.
+///
+/// Returns `false` if it can't be merged, and `end` must be pushed as usual.
+#[cfg(feature = "enable")]
+#[inline(always)]
+pub(crate) fn coalesce(measures: &mut Vec<Measure>, end: &Measure) -> bool {
+    let threshold = THRESHOLD.load(std::sync::atomic::Ordering::Relaxed);
+    if threshold == 0 {
+        return false;
+    }
+    let threshold = std::time::Duration::from_nanos(threshold);
+    let n = measures.len();
+    let [.., previous_end, start] = &measures[..] else {
+        return false;
+    };
+    let (MeasureType::Start { name }, MeasureType::End) = (&start.ty, &previous_end.ty) else {
+        return false;
+    };
+    if end.time.duration_since(start.time) >= threshold {
+        return false;
+    }
+    // The previous call is either short, or an already coalesced run
+    let (run_start, calls) = match &measures[..n - 2] {
+        [.., s, Measure {
+            ty: MeasureType::Coalesced { calls },
+            ..
+        }] => (s, Some(*calls)),
+        [.., s] if previous_end.time.duration_since(s.time) < threshold => (s, None),
+        _ => return false,
+    };
+    if !matches!(&run_start.ty, MeasureType::Start { name: previous } if previous == name) {
+        return false;
+    }
+    // Remove the gap between both calls, so the run takes the sum of their times
+    let gap = start.time.duration_since(previous_end.time);
+    let run_start = n - 3 - usize::from(calls.is_some());
+    measures[run_start].time += gap;
+    // Drop the end of the previous call and the start of this one
+    measures.truncate(n - 2);
+    match measures.last_mut() {
+        Some(Measure {
+            ty: MeasureType::Coalesced { calls },
+            ..
+        }) => *calls += 1,
+        _ => measures.push(Measure {
+            ty: MeasureType::Coalesced { calls: 1 },
+            ..end.clone()
+        }),
+    }
+    measures.push(end.clone());
+    true
+}
@@ -0,0 +1,94 @@
+//! Detection of scopes whose inferred and explicit names collide.
+
+/// Names inferred from the enclosing function, with `prof!()`, `prof_future!` or `#[profile]`.
+#[cfg(feature = "enable")]
+static INFERRED: std::sync::Mutex<std::collections::BTreeSet<&'static str>> =
+    std::sync::Mutex::new(std::collections::BTreeSet::new());
+
+/// Names given as a literal or an identifier, like `prof!("query")` or `prof!(query)`.
+#[cfg(feature = "enable")]
+static EXPLICIT: std::sync::Mutex<std::collections::BTreeSet<&'static str>> =
+    std::sync::Mutex::new(std::collections::BTreeSet::new());
+
+/// Records the name of a call site, only the first time it's reached.
+#[cfg(feature = "enable")]
+pub(crate) fn register(name: &'static str, inferred: bool) {
+    let names = if inferred { &INFERRED } else { &EXPLICIT };
+    names
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .insert(name);
+}
+
+/// A scope named after its function that may be mixed up with one named explicitly, see [`Report::name_collisions`](crate::Report::name_collisions).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameCollision {
+    /// Name inferred from the function, like `app::db::query`.
+    pub inferred: String,
+    /// Name given explicitly, the same as `inferred` or its last segments, like `query` or `db::query`.
+    pub explicit: String,
+}
+
+impl NameCollision {
+    /// If both are reported in the same row, otherwise they're in separate rows that only differ by the module path.
+    pub fn is_merged(&self) -> bool {
+        self.inferred == self.explicit
+    }
+}
+
+impl std::fmt::Display for NameCollision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (inferred, explicit) = (&self.inferred, &self.explicit);
+        if self.is_merged() {
+            write!(
+                f,
+                "`{explicit}` is both named explicitly and inferred from its function, their calls are merged, rename one of them"
+            )
+        } else {
+            write!(
+                f,
+                "`{explicit}` and `{inferred}` only differ by the module path, merge them with `profi::alias(\"{explicit}\", \"{inferred}\")` or rename one of them"
+            )
+        }
+    }
+}
+
+/// Collisions between the names registered so far that are in `scopes`.
+///
+/// Names aliased to each other are merged on purpose, and not reported.
+#[cfg(feature = "enable")]
+pub(crate) fn find(scopes: &[crate::ScopeStats]) -> Vec<NameCollision> {
+    use crate::alias::resolve;
+
+    let reported = scopes
+        .iter()
+        .map(|s| s.name.as_str())
+        .collect::<std::collections::HashSet<_>>();
+    let inferred = INFERRED
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone();
+    let explicit = EXPLICIT
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone();
+    let mut collisions = Vec::new();
+    for &i in &inferred {
+        if !reported.contains(&*resolve(i)) {
+            continue;
+        }
+        for &e in &explicit {
+            let suffix = i
+                .strip_suffix(e)
+                .is_some_and(|prefix| prefix.ends_with("::"));
+            if !(i == e || suffix && resolve(i) != resolve(e) && reported.contains(&*resolve(e))) {
+                continue;
+            }
+            collisions.push(NameCollision {
+                inferred: i.to_owned(),
+                explicit: e.to_owned(),
+            });
+        }
+    }
+    collisions
+}
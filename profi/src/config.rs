@@ -0,0 +1,96 @@
+//! Configuration file that can be changed while the application is running.
+
+/// Watches the configuration file at `path`, applying it every time it changes.
+///
+/// The file is checked every `every`, and applied once on start if it exists.
+/// Useful for tightening or relaxing the profiling of a live service without restarting it.
+///
+/// Each line is a `key = value` pair, empty lines and lines starting with `#` are ignored:
+///
+/// | Key      | Value                                                                                          |
+/// |----------|------------------------------------------------------------------------------------------------|
+/// | `filter` | Same syntax as `PROFI_FILTER`, see [`set_filter`](crate::set_filter). Empty clears the filter. |
+///
+/// Errors are printed to stderr and the previous configuration is kept.
+///
+/// # Example
+/// ```no_run
+/// fn main() {
+///   profi::print_on_exit!();
+///   let _config = profi::watch_config("profi.conf", std::time::Duration::from_secs(5));
+///   // ...
+/// }
+/// ```
+#[must_use = "the file stops being watched when the handle is dropped"]
+#[allow(unused_variables)]
+pub fn watch_config(
+    path: impl Into<std::path::PathBuf>,
+    every: std::time::Duration,
+) -> ConfigHandle {
+    #[cfg(feature = "enable")]
+    {
+        let path = path.into();
+        let (stop, stopped) = std::sync::mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || {
+            let mut last = None;
+            loop {
+                let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+                if modified.is_some() && modified != last {
+                    last = modified;
+                    match std::fs::read_to_string(&path) {
+                        Ok(config) => apply(&config),
+                        Err(e) => eprintln!("[profi] Could not read {}: {e}", path.display()),
+                    }
+                }
+                if let Err(std::sync::mpsc::RecvTimeoutError::Disconnected) | Ok(()) =
+                    stopped.recv_timeout(every)
+                {
+                    return;
+                }
+            }
+        });
+        ConfigHandle(Some((stop, thread)))
+    }
+    #[cfg(not(feature = "enable"))]
+    ConfigHandle(None)
+}
+
+/// Handle returned by [`watch_config`], the file stops being watched when it's dropped.
+#[derive(Debug)]
+pub struct ConfigHandle(Option<(std::sync::mpsc::Sender<()>, std::thread::JoinHandle<()>)>);
+
+impl ConfigHandle {
+    /// Stops watching the file and waits for the watcher thread to finish.
+    pub fn stop(self) {}
+}
+
+impl Drop for ConfigHandle {
+    fn drop(&mut self) {
+        if let Some((stop, thread)) = self.0.take() {
+            let _ = stop.send(());
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(feature = "enable")]
+fn apply(config: &str) {
+    for (i, line) in config.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            eprintln!(
+                "[profi] Invalid config line {}: expected `key = value`",
+                i + 1
+            );
+            continue;
+        };
+        match (key.trim(), value.trim()) {
+            ("filter", "") => crate::clear_filter(),
+            ("filter", patterns) => crate::set_filter(crate::filter::patterns_filter(patterns)),
+            (key, _) => eprintln!("[profi] Unknown config key `{key}` on line {}", i + 1),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! CPU core and NUMA node where each scope starts.
+
+/// Core the current thread is running on, `u32::MAX` if unknown.
+#[inline(always)]
+pub(crate) fn current() -> u32 {
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: `sched_getcpu` has no preconditions
+        let core = unsafe { libc::sched_getcpu() };
+        if core >= 0 {
+            return core as u32;
+        }
+    }
+    u32::MAX
+}
+
+/// NUMA node of `core`, read from `/sys/devices/system/cpu/cpuN/nodeM`.
+pub(crate) fn numa_node(core: usize) -> Option<usize> {
+    static NODES: std::sync::OnceLock<std::sync::Mutex<Vec<Option<usize>>>> =
+        std::sync::OnceLock::new();
+
+    let mut nodes = NODES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if nodes.len() <= core {
+        let from = nodes.len();
+        nodes.extend((from..=core).map(read_numa_node));
+    }
+    nodes[core]
+}
+
+fn read_numa_node(core: usize) -> Option<usize> {
+    std::fs::read_dir(format!("/sys/devices/system/cpu/cpu{core}"))
+        .ok()?
+        .filter_map(|e| e.ok())
+        .find_map(|e| e.file_name().to_str()?.strip_prefix("node")?.parse().ok())
+}
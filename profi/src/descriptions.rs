@@ -0,0 +1,52 @@
+//! Descriptions of the scopes, shown in the reports to explain what each one measures.
+
+#[cfg(feature = "enable")]
+static DESCRIPTIONS: std::sync::RwLock<Option<std::collections::HashMap<Box<str>, Box<str>>>> =
+    std::sync::RwLock::new(None);
+
+/// Describes what the scope `name` measures, for the people reading the report who didn't write the instrumentation.
+///
+/// Scopes named with a literal or an identifier can be described where they're profiled instead, with `prof!(name, desc = "...")`.
+/// Descriptions are kept in [`ScopeStats::description`](crate::ScopeStats::description), so they reach the JSON exports,
+/// and shown as a tooltip on the name of the scope in [`Format::Html`](crate::Format::Html).
+/// Setting it again replaces the previous one.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   profi::set_description("flush", "Writes the dirty pages to disk");
+///   {
+///     prof!(flush);
+///   }
+///   {
+///     prof!("compact_db", desc = "Compacts the LSM tree");
+///   }
+///   let report = profi::report();
+///   # #[cfg(feature = "enable")]
+///   # {
+///   assert_eq!(report.scope("flush").unwrap().description.as_deref(), Some("Writes the dirty pages to disk"));
+///   assert_eq!(report.scope("compact_db").unwrap().description.as_deref(), Some("Compacts the LSM tree"));
+///   # }
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn set_description(name: impl Into<String>, description: impl Into<String>) {
+    #[cfg(feature = "enable")]
+    DESCRIPTIONS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(Default::default)
+        .insert(name.into().into(), description.into().into());
+}
+
+/// Description of the scope `name`, if it has one.
+#[cfg(feature = "enable")]
+pub(crate) fn get(name: &str) -> Option<String> {
+    let descriptions = DESCRIPTIONS.read().unwrap_or_else(|e| e.into_inner());
+    descriptions
+        .as_ref()?
+        .get(name)
+        .map(|description| description.to_string())
+}
@@ -0,0 +1,579 @@
+//! Comparison of two profiling runs.
+
+use crate::Report;
+
+/// Per-scope comparison between a baseline and a current [`Report`], obtained with [`Report::diff`].
+///
+/// Scopes are compared by their real time. The baseline is usually a report saved by a previous run,
+/// for example with the `serde` feature.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+/// use std::time::Duration;
+///
+/// fn parse(ms: u64) {
+///   prof!(parse);
+///   std::thread::sleep(Duration::from_millis(ms));
+/// }
+///
+/// fn main() {
+///   parse(1);
+///   let baseline = profi::report();
+///   profi::reset();
+///
+///   parse(50);
+///   let diff = profi::report().diff(&baseline).threshold(10.0);
+///   # #[cfg(feature = "enable")]
+///   assert!(diff.regressions().any(|scope| scope.name == "parse"));
+///   diff.print(std::io::stdout(), &Default::default()).unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReportDiff {
+    /// Scopes of the current report followed by the ones that only appear in the baseline.
+    pub scopes: Vec<ScopeDiff>,
+    /// Percentage a scope must get slower to be considered a regression, `5.0` by default.
+    pub threshold: f64,
+    /// Maximum p-value for a change to be considered significant, `0.05` by default.
+    pub significance: f64,
+    /// Biggest noise floor of both reports, see [`calibrate_noise`](crate::calibrate_noise).
+    pub noise: Option<crate::NoiseFloor>,
+}
+
+/// Real time of a scope in both reports.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScopeDiff {
+    pub name: String,
+    /// `None` if the scope is new.
+    pub baseline: Option<std::time::Duration>,
+    /// `None` if the scope was removed.
+    pub current: Option<std::time::Duration>,
+    /// Probability of getting a difference in the duration of the calls at least this big by chance, with the Mann-Whitney U test.
+    ///
+    /// `None` if the duration of each call is not available in both reports, like with the `bounded-memory` feature,
+    /// or if the scope was called less than 8 times in either of them.
+    pub p_value: Option<f64>,
+}
+
+impl Report {
+    /// Compares this report against `baseline`, see [`ReportDiff`].
+    ///
+    /// The scopes of both reports are renamed with the [aliases](crate::alias) before comparing them.
+    pub fn diff(&self, baseline: &Report) -> ReportDiff {
+        let baseline_noise = baseline.noise;
+        let baseline = scope_times(baseline);
+        let mut scopes = scope_times(self)
+            .into_iter()
+            .map(|current| {
+                let base = baseline.iter().find(|b| b.name == current.name);
+                ScopeDiff {
+                    baseline: base.map(|b| b.total),
+                    current: Some(current.total),
+                    p_value: base.and_then(|b| mann_whitney(&b.calls, &current.calls)),
+                    name: current.name,
+                }
+            })
+            .collect::<Vec<_>>();
+        let removed = baseline
+            .into_iter()
+            .filter(|b| !scopes.iter().any(|s| s.name == b.name))
+            .map(|b| ScopeDiff {
+                name: b.name,
+                baseline: Some(b.total),
+                current: None,
+                p_value: None,
+            })
+            .collect::<Vec<_>>();
+        scopes.extend(removed);
+        ReportDiff {
+            scopes,
+            threshold: 5.0,
+            significance: 0.05,
+            noise: noise(self.noise, baseline_noise),
+        }
+    }
+}
+
+/// Biggest noise floor of both reports.
+fn noise(a: Option<crate::NoiseFloor>, b: Option<crate::NoiseFloor>) -> Option<crate::NoiseFloor> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(crate::NoiseFloor {
+            timer_resolution: a.timer_resolution.max(b.timer_resolution),
+            run_variance: a.run_variance.max(b.run_variance),
+            iterations: a.iterations.min(b.iterations),
+        }),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Comparison of the scope trees of a baseline and a current [`Report`], obtained with [`Report::diff_tree`].
+///
+/// Unlike [`ReportDiff`], which merges all the calls of a scope, each node is matched by its path from the root,
+/// so a scope that got slower only when called from one place, or that moved to another parent, shows up as such.
+/// The trees of all threads are merged by path, useful with [`Hierarchy::Deep`](crate::Hierarchy::Deep).
+///
+/// # Example
+/// ```
+/// use profi::prof;
+/// use std::time::Duration;
+///
+/// fn load(cached: bool) {
+///   prof!(load);
+///   if cached {
+///     prof!(cache);
+///   } else {
+///     prof!(disk);
+///     std::thread::sleep(Duration::from_millis(1));
+///   }
+/// }
+///
+/// fn main() {
+///   load(false);
+///   let baseline = profi::report();
+///   profi::reset();
+///
+///   load(true);
+///   let diff = profi::report().diff_tree(&baseline);
+///   # #[cfg(feature = "enable")]
+///   # {
+///   assert!(diff.added().any(|path| path == ["load", "cache"]));
+///   assert!(diff.removed().any(|path| path == ["load", "disk"]));
+///   # }
+///   diff.print(std::io::stdout(), &Default::default()).unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TreeDiff {
+    /// Root scopes of the current report followed by the ones that only appear in the baseline.
+    pub roots: Vec<NodeDiff>,
+    /// See [`ReportDiff::threshold`].
+    pub threshold: f64,
+    /// See [`ReportDiff::significance`].
+    pub significance: f64,
+    /// See [`ReportDiff::noise`].
+    pub noise: Option<crate::NoiseFloor>,
+}
+
+/// Real time of a node of the scope tree in both reports, see [`TreeDiff`].
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeDiff {
+    pub name: String,
+    /// `0` for the root scopes.
+    pub depth: usize,
+    /// `None` if the node is new.
+    pub baseline: Option<std::time::Duration>,
+    /// `None` if the node was removed.
+    pub current: Option<std::time::Duration>,
+    /// See [`ScopeDiff::p_value`].
+    pub p_value: Option<f64>,
+    /// Children of the current report followed by the ones that only appear in the baseline.
+    pub children: Vec<NodeDiff>,
+}
+
+impl Report {
+    /// Compares the scope trees of this report and `baseline`, see [`TreeDiff`].
+    ///
+    /// The scopes of both reports are renamed with the [aliases](crate::alias) before comparing them.
+    pub fn diff_tree(&self, baseline: &Report) -> TreeDiff {
+        TreeDiff {
+            roots: diff_nodes(tree_times(self), tree_times(baseline), 0),
+            threshold: 5.0,
+            significance: 0.05,
+            noise: noise(self.noise, baseline.noise),
+        }
+    }
+}
+
+/// Scope tree of all the threads of a report, merged by path.
+struct TreeTimes {
+    name: String,
+    total: std::time::Duration,
+    calls: Vec<std::time::Duration>,
+    children: Vec<TreeTimes>,
+}
+
+fn tree_times(report: &Report) -> Vec<TreeTimes> {
+    fn add(tree: &mut Vec<TreeTimes>, node: &crate::ScopeNode) {
+        let name = resolve(&node.name);
+        let i = match tree.iter().position(|t| t.name == name) {
+            Some(i) => i,
+            None => {
+                tree.push(TreeTimes {
+                    name,
+                    total: std::time::Duration::ZERO,
+                    calls: Vec::new(),
+                    children: Vec::new(),
+                });
+                tree.len() - 1
+            }
+        };
+        let times = &mut tree[i];
+        times.total += node.sum;
+        times.calls.extend(&node.durations);
+        for child in &node.children {
+            add(&mut times.children, child);
+        }
+    }
+
+    let mut tree = Vec::new();
+    for node in report.threads.iter().flat_map(|t| &t.scopes) {
+        add(&mut tree, node);
+    }
+    tree
+}
+
+fn diff_nodes(
+    current: Vec<TreeTimes>,
+    mut baseline: Vec<TreeTimes>,
+    depth: usize,
+) -> Vec<NodeDiff> {
+    fn removed(node: TreeTimes, depth: usize) -> NodeDiff {
+        NodeDiff {
+            name: node.name,
+            depth,
+            baseline: Some(node.total),
+            current: None,
+            p_value: None,
+            children: node
+                .children
+                .into_iter()
+                .map(|c| removed(c, depth + 1))
+                .collect(),
+        }
+    }
+
+    let mut nodes = current
+        .into_iter()
+        .map(|current| {
+            let base = baseline
+                .iter()
+                .position(|b| b.name == current.name)
+                .map(|i| baseline.remove(i));
+            let (base_total, base_calls, base_children) = match base {
+                Some(b) => (Some(b.total), Some(b.calls), b.children),
+                None => (None, None, Vec::new()),
+            };
+            NodeDiff {
+                depth,
+                baseline: base_total,
+                current: Some(current.total),
+                p_value: base_calls.and_then(|b| mann_whitney(&b, &current.calls)),
+                children: diff_nodes(current.children, base_children, depth + 1),
+                name: current.name,
+            }
+        })
+        .collect::<Vec<_>>();
+    nodes.extend(baseline.into_iter().map(|b| removed(b, depth)));
+    nodes
+}
+
+impl TreeDiff {
+    /// Sets the percentage a node must get slower to be considered a regression.
+    pub fn threshold(mut self, percent: f64) -> Self {
+        self.threshold = percent;
+        self
+    }
+
+    /// Sets the maximum p-value for a change to be considered significant, see [`ReportDiff::significance`].
+    pub fn significance(mut self, p_value: f64) -> Self {
+        self.significance = p_value;
+        self
+    }
+
+    /// All the nodes with their path from the root, in depth-first order.
+    pub fn iter(&self) -> impl Iterator<Item = (Vec<&str>, &NodeDiff)> {
+        let mut stack = self
+            .roots
+            .iter()
+            .rev()
+            .map(|n| (vec![n.name.as_str()], n))
+            .collect::<Vec<_>>();
+        std::iter::from_fn(move || {
+            let (path, node) = stack.pop()?;
+            for child in node.children.iter().rev() {
+                let mut child_path = path.clone();
+                child_path.push(child.name.as_str());
+                stack.push((child_path, child));
+            }
+            Some((path, node))
+        })
+    }
+
+    /// Returns the node at `path`, the names of the scopes from the root.
+    pub fn node(&self, path: &[&str]) -> Option<&NodeDiff> {
+        let (first, rest) = path.split_first()?;
+        let mut node = self.roots.iter().find(|n| n.name == *first)?;
+        for name in rest {
+            node = node.children.iter().find(|n| n.name == *name)?;
+        }
+        Some(node)
+    }
+
+    /// Paths of the nodes that only appear in the current report.
+    ///
+    /// The children of a new node are not included, since they're new too.
+    pub fn added(&self) -> impl Iterator<Item = Vec<&str>> {
+        self.structural(|n| n.baseline.is_none())
+    }
+
+    /// Paths of the nodes that only appear in the baseline.
+    ///
+    /// The children of a removed node are not included, since they're removed too.
+    pub fn removed(&self) -> impl Iterator<Item = Vec<&str>> {
+        self.structural(|n| n.current.is_none())
+    }
+
+    fn structural(&self, changed: fn(&NodeDiff) -> bool) -> impl Iterator<Item = Vec<&str>> {
+        self.iter()
+            .filter(move |(path, node)| {
+                let parent = &path[..path.len() - 1];
+                changed(node) && !self.node(parent).is_some_and(changed)
+            })
+            .map(|(path, _)| path)
+    }
+
+    /// Paths of the nodes that got slower than the [threshold](Self::threshold), see [`ReportDiff::is_regression`].
+    pub fn regressions(&self) -> impl Iterator<Item = (Vec<&str>, &NodeDiff)> {
+        self.iter().filter(|(_, node)| self.is_regression(node))
+    }
+
+    /// Returns `true` if the node got slower than the [threshold](Self::threshold).
+    pub fn is_regression(&self, node: &NodeDiff) -> bool {
+        is_regression(
+            self.threshold,
+            self.significance,
+            self.noise,
+            (node.baseline, node.current),
+            node.p_value,
+        )
+    }
+
+    /// Prints a table with the tree of the current report and the removed nodes,
+    /// with the time of each one in both reports and the change between them, marking the regressions.
+    #[allow(unused_variables, unused_mut)]
+    pub fn print(
+        &self,
+        mut to: impl std::io::Write,
+        options: &crate::PrintOptions,
+    ) -> std::io::Result<()> {
+        #[cfg(feature = "enable")]
+        {
+            let table = crate::process::create_tree_diff_table(self, options);
+            crate::process::write_rendered(&mut to, &table, options.format)?;
+        }
+        Ok(())
+    }
+}
+
+impl NodeDiff {
+    /// Change in percentage from the baseline, positive if it got slower, see [`ScopeDiff::change`].
+    pub fn change(&self) -> Option<f64> {
+        change(self.baseline, self.current)
+    }
+}
+
+/// Real time and duration of each call of a scope, merging the ones that have the same alias.
+struct ScopeTimes {
+    name: String,
+    total: std::time::Duration,
+    calls: Vec<std::time::Duration>,
+}
+
+/// Name of the scope after applying the [aliases](crate::alias).
+fn resolve(name: &str) -> String {
+    #[cfg(feature = "enable")]
+    {
+        crate::alias::resolve(name).into_owned()
+    }
+    #[cfg(not(feature = "enable"))]
+    name.to_owned()
+}
+
+fn scope_times(report: &Report) -> Vec<ScopeTimes> {
+    let mut times = Vec::<ScopeTimes>::new();
+    for scope in &report.scopes {
+        let name = resolve(&scope.name);
+        match times.iter_mut().find(|t| t.name == name) {
+            Some(times) => times.total += scope.total_real,
+            None => times.push(ScopeTimes {
+                name,
+                total: scope.total_real,
+                calls: Vec::new(),
+            }),
+        }
+    }
+    let nodes = report
+        .threads
+        .iter()
+        .flat_map(|t| &t.scopes)
+        .flat_map(|s| s.iter());
+    for node in nodes {
+        let name = resolve(&node.name);
+        if let Some(times) = times.iter_mut().find(|t| t.name == name) {
+            times.calls.extend(&node.durations);
+        }
+    }
+    times
+}
+
+/// Minimum number of calls on each side for [`mann_whitney`].
+const MIN_CALLS: usize = 8;
+
+/// Two-sided p-value of the Mann-Whitney U test, with the normal approximation.
+///
+/// Returns `None` if there are less than [`MIN_CALLS`] on either side.
+fn mann_whitney(a: &[std::time::Duration], b: &[std::time::Duration]) -> Option<f64> {
+    if a.len() < MIN_CALLS || b.len() < MIN_CALLS {
+        return None;
+    }
+    let mut all = a
+        .iter()
+        .map(|d| (*d, true))
+        .chain(b.iter().map(|d| (*d, false)))
+        .collect::<Vec<_>>();
+    all.sort_unstable_by_key(|(d, _)| *d);
+
+    // Sum of the ranks of `a`, ties get the average of their ranks
+    let (mut rank_sum, mut ties) = (0.0, 0.0);
+    let mut i = 0;
+    while i < all.len() {
+        let j = i + all[i..].iter().take_while(|(d, _)| *d == all[i].0).count();
+        let rank = (i + j + 1) as f64 / 2.0;
+        rank_sum += rank * all[i..j].iter().filter(|(_, in_a)| *in_a).count() as f64;
+        let t = (j - i) as f64;
+        ties += t * t * t - t;
+        i = j;
+    }
+
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let n = n1 + n2;
+    let u = rank_sum - n1 * (n1 + 1.0) / 2.0;
+    let mean = n1 * n2 / 2.0;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        // All calls took the same time
+        return Some(1.0);
+    }
+    // With continuity correction
+    let z = ((u - mean).abs() - 0.5).max(0.0) / variance.sqrt();
+    Some((1.0 - erf(z / std::f64::consts::SQRT_2)).clamp(0.0, 1.0))
+}
+
+/// Error function, with the approximation 7.1.26 of Abramowitz and Stegun (error below `1.5e-7`).
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    (1.0 - poly * (-x * x).exp()).copysign(x)
+}
+
+impl ReportDiff {
+    /// Sets the percentage a scope must get slower to be considered a regression.
+    pub fn threshold(mut self, percent: f64) -> Self {
+        self.threshold = percent;
+        self
+    }
+
+    /// Sets the maximum p-value for a change to be considered significant.
+    ///
+    /// Lower values reduce the false alarms on noisy machines, but need more calls to detect a regression.
+    pub fn significance(mut self, p_value: f64) -> Self {
+        self.significance = p_value;
+        self
+    }
+
+    /// Returns the comparison of the scope with the given name.
+    pub fn scope(&self, name: &str) -> Option<&ScopeDiff> {
+        self.scopes.iter().find(|s| s.name == name)
+    }
+
+    /// Scopes that got slower than the [threshold](Self::threshold), useful for failing CI builds.
+    ///
+    /// See [`is_regression`](Self::is_regression).
+    pub fn regressions(&self) -> impl Iterator<Item = &ScopeDiff> {
+        self.scopes.iter().filter(|s| self.is_regression(s))
+    }
+
+    /// Returns `true` if the scope got slower than the [threshold](Self::threshold).
+    ///
+    /// Changes that are not [significant](Self::significance) are ignored, unless the [`ScopeDiff::p_value`] is unknown.
+    /// With a [noise floor](Self::noise), changes smaller than the run variance or the timer resolution are ignored too.
+    pub fn is_regression(&self, scope: &ScopeDiff) -> bool {
+        is_regression(
+            self.threshold,
+            self.significance,
+            self.noise,
+            (scope.baseline, scope.current),
+            scope.p_value,
+        )
+    }
+
+    /// Returns `None` if the p-value of the scope is unknown.
+    pub fn is_significant(&self, scope: &ScopeDiff) -> Option<bool> {
+        scope.p_value.map(|p| p < self.significance)
+    }
+
+    /// Prints a table with the time of each scope in both reports and the change between them, marking the regressions.
+    #[allow(unused_variables, unused_mut)]
+    pub fn print(
+        &self,
+        mut to: impl std::io::Write,
+        options: &crate::PrintOptions,
+    ) -> std::io::Result<()> {
+        #[cfg(feature = "enable")]
+        {
+            let table = crate::process::create_diff_table(self, options);
+            crate::process::write_rendered(&mut to, &table, options.format)?;
+        }
+        Ok(())
+    }
+}
+
+impl ScopeDiff {
+    /// Change in percentage from the baseline, positive if it got slower.
+    ///
+    /// `None` if the scope is not in both reports, or its baseline time is zero.
+    pub fn change(&self) -> Option<f64> {
+        change(self.baseline, self.current)
+    }
+}
+
+fn change(
+    baseline: Option<std::time::Duration>,
+    current: Option<std::time::Duration>,
+) -> Option<f64> {
+    let (baseline, current) = (baseline?, current?);
+    if baseline.is_zero() {
+        return None;
+    }
+    Some((current.as_secs_f64() / baseline.as_secs_f64() - 1.0) * 100.0)
+}
+
+/// See [`ReportDiff::is_regression`].
+fn is_regression(
+    threshold: f64,
+    significance: f64,
+    noise: Option<crate::NoiseFloor>,
+    (baseline, current): (Option<std::time::Duration>, Option<std::time::Duration>),
+    p_value: Option<f64>,
+) -> bool {
+    let threshold = match noise {
+        Some(noise) => threshold.max(noise.run_variance),
+        None => threshold,
+    };
+    let above_resolution = match (noise, baseline, current) {
+        (Some(noise), Some(baseline), Some(current)) => {
+            current.saturating_sub(baseline) > noise.timer_resolution
+        }
+        _ => true,
+    };
+    change(baseline, current).is_some_and(|c| c > threshold)
+        && above_resolution
+        && p_value.map(|p| p < significance) != Some(false)
+}
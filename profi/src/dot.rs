@@ -0,0 +1,167 @@
+//! Rendering of reports as [Graphviz](https://graphviz.org) DOT graphs.
+
+use crate::report::ScopeNode;
+
+/// Writes the call graph of `report`, with a node per scope name and an edge per caller and callee, merging all threads.
+pub(crate) fn write_call_graph(
+    report: &crate::Report,
+    mut to: impl std::io::Write,
+) -> std::io::Result<()> {
+    #[derive(Default)]
+    struct Graph<'a> {
+        /// Name, time and calls of each node, in the order they were first seen.
+        nodes: Vec<(&'a str, std::time::Duration, usize)>,
+        ids: std::collections::HashMap<&'a str, usize>,
+        /// Caller, callee and time of the callee beneath the caller.
+        edges: Vec<(usize, usize, std::time::Duration)>,
+    }
+
+    impl<'a> Graph<'a> {
+        fn node(&mut self, name: &'a str) -> usize {
+            let next = self.nodes.len();
+            let id = *self.ids.entry(name).or_insert(next);
+            if id == next {
+                self.nodes.push((name, std::time::Duration::ZERO, 0));
+            }
+            id
+        }
+
+        fn add(&mut self, node: &'a ScopeNode, callers: &mut Vec<usize>) {
+            let id = self.node(&node.name);
+            self.nodes[id].2 += node.count;
+            // The time of recursive calls is already part of the outermost one
+            if !callers.contains(&id) {
+                self.nodes[id].1 += node.sum;
+            }
+            if let Some(&caller) = callers.last() {
+                match self
+                    .edges
+                    .iter_mut()
+                    .find(|(c, i, _)| (*c, *i) == (caller, id))
+                {
+                    Some((_, _, time)) => *time += node.sum,
+                    None => self.edges.push((caller, id, node.sum)),
+                }
+            }
+            callers.push(id);
+            for child in &node.children {
+                self.add(child, callers);
+            }
+            callers.pop();
+        }
+    }
+
+    let mut graph = Graph::default();
+    for thread in &report.threads {
+        for node in &thread.scopes {
+            // Called by the scopes of the thread it was opened on behalf of
+            let mut callers = node
+                .parent
+                .iter()
+                .map(|parent| graph.node(parent))
+                .collect();
+            graph.add(node, &mut callers);
+        }
+    }
+
+    writeln!(to, "digraph profi {{")?;
+    writeln!(to, "  node [shape=box, fontname=\"monospace\"];")?;
+    for (id, (name, time, calls)) in graph.nodes.iter().enumerate() {
+        writeln!(
+            to,
+            "  n{id} [label={}];",
+            label(report, name, *time, *calls)
+        )?;
+    }
+    for (caller, callee, time) in &graph.edges {
+        writeln!(to, "  n{caller} -> n{callee} [{}];", edge(report, *time))?;
+    }
+    writeln!(to, "}}")
+}
+
+/// Writes the scope tree of each thread of `report`, with a node per scope and a cluster per thread.
+pub(crate) fn write_tree(
+    report: &crate::Report,
+    mut to: impl std::io::Write,
+) -> std::io::Result<()> {
+    fn write_node(
+        to: &mut impl std::io::Write,
+        report: &crate::Report,
+        node: &ScopeNode,
+        parent: Option<usize>,
+        next: &mut usize,
+    ) -> std::io::Result<()> {
+        let id = *next;
+        *next += 1;
+        writeln!(
+            to,
+            "    n{id} [label={}];",
+            label(report, &node.name, node.sum, node.count)
+        )?;
+        if let Some(parent) = parent {
+            writeln!(to, "    n{parent} -> n{id} [{}];", edge(report, node.sum))?;
+        }
+        for child in &node.children {
+            write_node(to, report, child, Some(id), next)?;
+        }
+        Ok(())
+    }
+
+    writeln!(to, "digraph profi {{")?;
+    writeln!(to, "  node [shape=box, fontname=\"monospace\"];")?;
+    let mut next = 0;
+    for thread in &report.threads {
+        let name = match &thread.name {
+            Some(name) => format!("Thread {} ({name})", thread.index),
+            None => format!("Thread {}", thread.index),
+        };
+        writeln!(to, "  subgraph cluster_{} {{", thread.index)?;
+        writeln!(to, "    label={};", quote(&name))?;
+        for node in &thread.scopes {
+            write_node(&mut to, report, node, None, &mut next)?;
+        }
+        writeln!(to, "  }}")?;
+    }
+    writeln!(to, "}}")
+}
+
+/// Label of a node, with its name, time, share of the application time and calls.
+fn label(report: &crate::Report, name: &str, time: std::time::Duration, calls: usize) -> String {
+    quote(&format!(
+        "{name}\n{time:.2?} ({:.2}%)\n{calls} calls",
+        percent(report, time)
+    ))
+}
+
+/// Attributes of an edge, labeled with the time of the callee and thicker the more time it takes.
+fn edge(report: &crate::Report, time: std::time::Duration) -> String {
+    let width = 1.0 + percent(report, time) / 20.0;
+    format!(
+        "label={}, penwidth={width:.2}",
+        quote(&format!("{time:.2?}"))
+    )
+}
+
+fn percent(report: &crate::Report, time: std::time::Duration) -> f64 {
+    match report.total_app.is_zero() {
+        true => 0.0,
+        false => time.as_secs_f64() / report.total_app.as_secs_f64() * 100.0,
+    }
+}
+
+/// Quoted DOT string.
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,219 @@
+//! `no_std` profiler for embedded targets, which only needs `alloc` and a [`critical-section`](https://crates.io/crates/critical-section) implementation.
+//!
+//! Time is read from the [`Clock`] registered with [`set_clock`], usually a hardware timer.
+//! Each scope is folded into its totals when it ends, without hierarchy, so scopes of tasks and interrupts that preempt each other are measured correctly.
+//!
+//! In builds without the `std` feature, [`prof!`](crate::prof) and [`prof_guard!`](crate::prof_guard) measure with it.
+//! Names built with `fmt = ...` need `alloc::format` in scope.
+//!
+//! # Example
+//! ```
+//! use profi::embedded::{self, Clock, ScopeGuard};
+//!
+//! struct Timer;
+//!
+//! impl Clock for Timer {
+//!     fn now(&self) -> u64 {
+//!         // Read the hardware counter here
+//!         static TICKS: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(0);
+//!         TICKS.fetch_add(1_000, core::sync::atomic::Ordering::Relaxed)
+//!     }
+//!
+//!     fn ticks_per_second(&self) -> u64 {
+//!         1_000_000
+//!     }
+//! }
+//!
+//! fn main() {
+//!     embedded::set_clock(&Timer);
+//!     for _ in 0..3 {
+//!         let _guard = ScopeGuard::new("control loop");
+//!     }
+//!     assert_eq!(embedded::scopes()[0].calls, 3);
+//!
+//!     let mut report = String::new();
+//!     embedded::write_report(&mut report).unwrap();
+//! }
+//! ```
+
+extern crate alloc;
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::{cell::RefCell, time::Duration};
+
+/// Monotonic time source of the embedded profiler.
+pub trait Clock: Sync {
+    /// Current value of a monotonic counter, like a hardware timer or the RTOS tick count.
+    fn now(&self) -> u64;
+    /// Increments of [`now`](Clock::now) per second.
+    fn ticks_per_second(&self) -> u64;
+}
+
+static CLOCK: critical_section::Mutex<core::cell::Cell<Option<&'static dyn Clock>>> =
+    critical_section::Mutex::new(core::cell::Cell::new(None));
+static SCOPES: critical_section::Mutex<RefCell<Vec<Totals>>> =
+    critical_section::Mutex::new(RefCell::new(Vec::new()));
+
+/// Registers the time source, nothing is measured until it's called.
+///
+/// Scopes that are open while the clock changes are discarded.
+pub fn set_clock(clock: &'static dyn Clock) {
+    critical_section::with(|cs| CLOCK.borrow(cs).set(Some(clock)));
+}
+
+/// Measures until it's dropped, returned by [`prof_guard!`](crate::prof_guard) in `no_std` builds.
+pub struct ScopeGuard {
+    /// `None` if there's no clock
+    start: Option<(Cow<'static, str>, &'static dyn Clock, u64)>,
+}
+
+impl ScopeGuard {
+    #[inline(always)]
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
+        let clock = critical_section::with(|cs| CLOCK.borrow(cs).get());
+        Self {
+            start: clock.map(|clock| (name.into(), clock, clock.now())),
+        }
+    }
+
+    /// Same as [`ScopeGuard::new`], every call is recorded.
+    #[inline(always)]
+    pub fn sampled(
+        name: impl Into<Cow<'static, str>>,
+        _calls: &crate::zz_private::SampleCounter,
+        _every: usize,
+    ) -> Self {
+        Self::new(name)
+    }
+
+    /// Fields are not recorded in `no_std` builds, see [`prof!`](crate::prof).
+    #[inline(always)]
+    pub fn field<K, V>(&mut self, _key: K, _value: V) {}
+}
+
+impl Drop for ScopeGuard {
+    #[inline(always)]
+    fn drop(&mut self) {
+        let Some((name, clock, start)) = self.start.take() else {
+            return;
+        };
+        let end = clock.now();
+        critical_section::with(|cs| {
+            // The clock changed while the scope was open
+            let current = CLOCK.borrow(cs).get();
+            if !current.is_some_and(|c| core::ptr::addr_eq(c, clock)) {
+                return;
+            }
+            let ticks = end.wrapping_sub(start);
+            let mut scopes = SCOPES.borrow_ref_mut(cs);
+            match scopes.iter_mut().find(|s| s.name == name) {
+                Some(totals) => totals.add(ticks),
+                None => scopes.push(Totals {
+                    name,
+                    calls: 1,
+                    total: ticks,
+                    min: ticks,
+                    max: ticks,
+                }),
+            }
+        });
+    }
+}
+
+/// Calls of a scope, in ticks.
+struct Totals {
+    name: Cow<'static, str>,
+    calls: u64,
+    total: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Totals {
+    fn add(&mut self, ticks: u64) {
+        self.calls += 1;
+        self.total += ticks;
+        self.min = self.min.min(ticks);
+        self.max = self.max.max(ticks);
+    }
+}
+
+/// Statistics of a scope, returned by [`scopes`].
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub name: Cow<'static, str>,
+    pub calls: u64,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl Scope {
+    /// Average time of each call.
+    pub fn average(&self) -> Duration {
+        match self.calls {
+            0 => Duration::ZERO,
+            calls => Duration::from_nanos((self.total.as_nanos() / u128::from(calls)) as u64),
+        }
+    }
+}
+
+/// Returns the scopes measured until now, sorted by total time.
+pub fn scopes() -> Vec<Scope> {
+    critical_section::with(|cs| {
+        let Some(clock) = CLOCK.borrow(cs).get() else {
+            return Vec::new();
+        };
+        let per_second = u128::from(clock.ticks_per_second().max(1));
+        let duration = |ticks: u64| {
+            Duration::from_nanos((u128::from(ticks) * 1_000_000_000 / per_second) as u64)
+        };
+        let mut scopes = SCOPES
+            .borrow_ref(cs)
+            .iter()
+            .map(|totals| Scope {
+                name: totals.name.clone(),
+                calls: totals.calls,
+                total: duration(totals.total),
+                min: duration(totals.min),
+                max: duration(totals.max),
+            })
+            .collect::<Vec<_>>();
+        scopes.sort_by_key(|scope| core::cmp::Reverse(scope.total));
+        scopes
+    })
+}
+
+/// Writes a table with the scopes measured until now to `to`, like a serial port or a log buffer.
+pub fn write_report(to: &mut impl core::fmt::Write) -> core::fmt::Result {
+    let scopes = scopes();
+    let width = scopes
+        .iter()
+        .map(|s| s.name.chars().count())
+        .chain([4])
+        .max()
+        .unwrap_or_default();
+    writeln!(
+        to,
+        "{:width$}  {:>8}  {:>12}  {:>12}  {:>12}  {:>12}",
+        "Name", "Calls", "Total", "Average", "Min", "Max"
+    )?;
+    for scope in &scopes {
+        writeln!(
+            to,
+            "{:width$}  {:>8}  {:>12}  {:>12}  {:>12}  {:>12}",
+            scope.name,
+            scope.calls,
+            alloc::format!("{:.2?}", scope.total),
+            alloc::format!("{:.2?}", scope.average()),
+            alloc::format!("{:.2?}", scope.min),
+            alloc::format!("{:.2?}", scope.max),
+        )?;
+    }
+    Ok(())
+}
+
+/// Discards all the scopes measured until now.
+pub fn reset() {
+    critical_section::with(|cs| SCOPES.borrow_ref_mut(cs).clear());
+}
@@ -0,0 +1,61 @@
+//! Package energy readings from Intel RAPL, exposed by Linux in `/sys/class/powercap`.
+
+/// Energy counter of a RAPL package zone, in microjoules.
+#[derive(Debug, Clone)]
+struct Zone {
+    path: std::path::PathBuf,
+    start: u64,
+    /// Value at which the counter wraps around
+    range: u64,
+}
+
+static START: std::sync::OnceLock<Option<Vec<Zone>>> = std::sync::OnceLock::new();
+
+/// Takes the initial reading, only the first call has any effect.
+pub(crate) fn start() {
+    START.get_or_init(read_zones);
+}
+
+/// Energy consumed by all packages since [`start`], in joules.
+///
+/// Returns `None` if RAPL is not available or not readable (usually requires root).
+pub(crate) fn consumed() -> Option<f64> {
+    let zones = START.get()?.as_ref()?;
+    let mut total = 0u64;
+    for zone in zones {
+        let now = read_u64(&zone.path.join("energy_uj"))?;
+        total += if now >= zone.start {
+            now - zone.start
+        } else {
+            // The counter wrapped around
+            zone.range - zone.start + now
+        };
+    }
+    Some(total as f64 / 1_000_000.0)
+}
+
+fn read_zones() -> Option<Vec<Zone>> {
+    let zones = std::fs::read_dir("/sys/class/powercap")
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            // Only top level package zones (`intel-rapl:0`), subzones (`intel-rapl:0:0`) are already included in them
+            let name = e.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with("intel-rapl:") && name.matches(':').count() == 1
+        })
+        .map(|e| {
+            let path = e.path();
+            Some(Zone {
+                start: read_u64(&path.join("energy_uj"))?,
+                range: read_u64(&path.join("max_energy_range_uj")).unwrap_or(u64::MAX),
+                path,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (!zones.is_empty()).then_some(zones)
+}
+
+fn read_u64(path: &std::path::Path) -> Option<u64> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
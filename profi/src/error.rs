@@ -0,0 +1,133 @@
+//! Errors of the report, and where they go when it's printed on exit.
+
+/// Handler of the errors of the reports printed on exit, `None` prints them to stderr.
+#[cfg(feature = "enable")]
+#[allow(clippy::type_complexity)]
+static CALLBACK: std::sync::RwLock<Option<std::sync::Arc<dyn Fn(&Error) + Send + Sync>>> =
+    std::sync::RwLock::new(None);
+
+/// Error while generating or printing a report.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The report could not be written to its destination.
+    Io(std::io::Error),
+    /// A thread ended more scopes than it started, like when a guard is forgotten and dropped elsewhere.
+    ///
+    /// The extra ends are ignored, so the rest of the report is still valid.
+    Unbalanced {
+        /// Index of the thread, see [`ThreadStats::index`](crate::ThreadStats::index).
+        thread: usize,
+        /// Name of the thread, if it had one.
+        name: Option<String>,
+        /// Number of ends without a start.
+        ends: usize,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "Could not print the results: {e}"),
+            Error::Unbalanced { thread, name, ends } => {
+                write!(f, "Thread {thread}")?;
+                if let Some(name) = name {
+                    write!(f, " ({name})")?;
+                }
+                write!(
+                    f,
+                    " ended {ends} scopes that were never started, they were ignored"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Unbalanced { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl Error {
+    /// Errors of the threads of `report` whose scopes were unbalanced, in the order of the threads.
+    #[allow(unused)]
+    pub(crate) fn unbalanced(report: &crate::Report) -> impl Iterator<Item = Error> + '_ {
+        report
+            .threads
+            .iter()
+            .filter(|thread| thread.unmatched > 0)
+            .map(|thread| Error::Unbalanced {
+                thread: thread.index,
+                name: thread.name.clone(),
+                ends: thread.unmatched,
+            })
+    }
+}
+
+/// Calls `f` with the errors of the reports printed by [`print_on_exit!`](crate::print_on_exit) and [`ExitPrinter`](crate::ExitPrinter),
+/// instead of printing them to stderr.
+///
+/// The report is printed while the program exits, where returning an error isn't possible and panicking could abort it,
+/// so a sink that fails or unbalanced scopes never stop the program.
+/// Use it to log them somewhere else, count them, or fail a test.
+///
+/// # Example
+/// ```
+/// use profi::{print_on_exit, prof};
+/// use std::io::{Error, ErrorKind, Write};
+///
+/// /// A closed pipe
+/// struct Closed;
+///
+/// impl Write for Closed {
+///   fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+///     Err(Error::new(ErrorKind::BrokenPipe, "broken pipe"))
+///   }
+///   fn flush(&mut self) -> std::io::Result<()> {
+///     Ok(())
+///   }
+/// }
+///
+/// static ERRORS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+///
+/// fn main() {
+///   profi::on_error(|e| {
+///     ERRORS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+///     log_somewhere(e);
+///   });
+///   {
+///     print_on_exit!(to = Closed);
+///     prof!(work);
+///   }
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(ERRORS.load(std::sync::atomic::Ordering::Relaxed), 1);
+/// }
+/// # fn log_somewhere(_: &profi::Error) {}
+/// ```
+#[allow(unused_variables)]
+pub fn on_error(f: impl Fn(&Error) + Send + Sync + 'static) {
+    #[cfg(feature = "enable")]
+    {
+        *CALLBACK.write().unwrap_or_else(|e| e.into_inner()) = Some(std::sync::Arc::new(f));
+    }
+}
+
+/// Sends `error` to the callback of [`on_error`], or prints it to stderr.
+#[cfg(feature = "enable")]
+pub(crate) fn handle(error: Error) {
+    let callback = CALLBACK.read().unwrap_or_else(|e| e.into_inner()).clone();
+    match callback {
+        Some(f) => f(&error),
+        None => eprintln!("[profi] {error}"),
+    }
+}
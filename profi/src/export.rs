@@ -0,0 +1,278 @@
+//! Exporters to formats understood by external tools.
+//!
+//! All functions follow the same rules as [`print_timings`](crate::zz_private::print_timings):
+//! it's recommended to only use them when all threads have exited and have been joined correctly.
+//! The exception is [`stream_jsonl`], which writes each scope as soon as it completes, optionally compressed with `stream_jsonl_zstd`.
+
+#[cfg(feature = "zstd")]
+pub use crate::stream::stream_jsonl_zstd;
+pub use crate::stream::{stream_jsonl, stream_process, StreamHandle, STREAM_FORMAT_VERSION};
+
+/// Writes the profiled timings as collapsed stacks (`parent;child 123456`).
+///
+/// The output can be fed directly to [`inferno`](https://github.com/jonhoo/inferno) or `flamegraph.pl`.
+/// Each line contains the *self time* of the stack in nanoseconds, stacks from different threads are merged.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   {
+///     prof!(parent);
+///     prof!(child);
+///   }
+///   let mut out = Vec::new();
+///   profi::export::folded(&mut out).unwrap();
+/// }
+/// ```
+#[inline(always)]
+#[allow(unused)]
+pub fn folded(to: impl std::io::Write) -> std::io::Result<()> {
+    #[cfg(feature = "enable")]
+    crate::measure::GLOBAL_PROFILER.print_folded(to)?;
+    Ok(())
+}
+
+/// Writes the measures of every thread in the [speedscope](https://www.speedscope.app) file format.
+///
+/// Each thread becomes an "evented" profile with its scopes in the order they happened, all of them share the same time origin.
+/// The profile spans the lifetime of the thread, from when it started profiling until it exited,
+/// which is also shown as a frame named after the thread around all of its scopes.
+/// Scopes that are still open are closed at the time of the export.
+///
+/// With the `bounded-memory` feature closed scopes are not kept, so only the open ones are exported.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   {
+///     prof!(parent);
+///     prof!(child);
+///   }
+///   let mut file = Vec::new();
+///   profi::export::speedscope(&mut file).unwrap();
+/// }
+/// ```
+#[inline(always)]
+#[allow(unused)]
+pub fn speedscope(to: impl std::io::Write) -> std::io::Result<()> {
+    #[cfg(feature = "enable")]
+    crate::measure::GLOBAL_PROFILER.print_speedscope(to)?;
+    Ok(())
+}
+
+/// Writes the profiled timings as a gzip-compressed [pprof](https://github.com/google/pprof) profile, see [`Report::write_pprof`](crate::Report::write_pprof).
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   {
+///     prof!(parent);
+///     prof!(child);
+///   }
+///   let file = std::fs::File::create(std::env::temp_dir().join("profi.pb.gz")).unwrap();
+///   profi::export::pprof(file).unwrap();
+/// }
+/// ```
+#[cfg(feature = "pprof")]
+pub fn pprof(to: impl std::io::Write) -> std::io::Result<()> {
+    crate::report().write_pprof(to)
+}
+
+/// Writes the call graph of all threads as a [Graphviz](https://graphviz.org) DOT file, see [`Report::write_dot`](crate::Report::write_dot).
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   {
+///     prof!(parent);
+///     prof!(child);
+///   }
+///   let file = std::fs::File::create(std::env::temp_dir().join("profi.dot")).unwrap();
+///   profi::export::dot(file).unwrap();
+/// }
+/// ```
+pub fn dot(to: impl std::io::Write) -> std::io::Result<()> {
+    crate::report().write_dot(to)
+}
+
+/// Quoted JSON string.
+#[cfg(feature = "enable")]
+pub(crate) fn json_string(s: &str) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(feature = "enable")]
+pub(crate) fn write_speedscope<'a>(
+    threads: impl IntoIterator<
+        Item = (
+            Option<&'a str>,
+            (crate::clock::Instant, Option<crate::clock::Instant>),
+            &'a [crate::measure::Measure],
+        ),
+    >,
+    mut to: impl std::io::Write,
+) -> std::io::Result<()> {
+    use crate::measure::MeasureType;
+
+    let threads = threads.into_iter().collect::<Vec<_>>();
+    let now = crate::clock::Instant::now();
+    let origin = threads
+        .iter()
+        .map(|(_, (started, _), _)| *started)
+        .min()
+        .unwrap_or(now);
+    let at = |time: crate::clock::Instant| time.saturating_duration_since(origin).as_nanos();
+
+    let names = threads
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _, _))| match name {
+            Some(name) => format!("Thread {i} ({name})"),
+            None => format!("Thread {i}"),
+        })
+        .collect::<Vec<_>>();
+    let mut frames = indexmap::IndexSet::<&str>::new();
+    let mut profiles = Vec::new();
+    for ((_, (started, exited), measures), name) in threads.iter().zip(&names) {
+        let end = exited.unwrap_or(now);
+        let mut events = String::new();
+        let mut open = Vec::new();
+        let mut event = |kind: char, frame: usize, time: crate::clock::Instant| {
+            if !events.is_empty() {
+                events.push(',');
+            }
+            events.push_str(&format!(
+                "{{\"type\":\"{kind}\",\"frame\":{frame},\"at\":{}}}",
+                at(time)
+            ));
+        };
+        // The lifetime of the thread, as a bar around its scopes
+        let lifetime = frames.insert_full(name.as_str()).0;
+        event('O', lifetime, *started);
+        for m in measures.iter() {
+            match &m.ty {
+                MeasureType::Start { name } | MeasureType::Resume { name } => {
+                    let frame = frames.insert_full(name.as_ref()).0;
+                    open.push(frame);
+                    event('O', frame, m.time);
+                }
+                MeasureType::End => {
+                    if let Some(frame) = open.pop() {
+                        event('C', frame, m.time);
+                    }
+                }
+                MeasureType::Count { .. }
+                | MeasureType::Field { .. }
+                | MeasureType::Parent { .. }
+                | MeasureType::Slow { .. }
+                | MeasureType::Wait { .. }
+                | MeasureType::Pause { .. }
+                | MeasureType::Sampled { .. }
+                | MeasureType::Coalesced { .. }
+                | MeasureType::Polled { .. }
+                | MeasureType::Spilled { .. } => {}
+            }
+        }
+        while let Some(frame) = open.pop() {
+            event('C', frame, end);
+        }
+        event('C', lifetime, end);
+        profiles.push(format!(
+            "{{\"type\":\"evented\",\"name\":{},\"unit\":\"nanoseconds\",\"startValue\":{},\"endValue\":{},\"events\":[{events}]}}",
+            json_string(name),
+            at(*started),
+            at(end),
+        ));
+    }
+
+    let frames = frames
+        .iter()
+        .map(|name| format!("{{\"name\":{}}}", json_string(name)))
+        .collect::<Vec<_>>()
+        .join(",");
+    write!(
+        to,
+        "{{\"$schema\":\"https://www.speedscope.app/file-format-schema.json\",\"exporter\":\"profi\",\"shared\":{{\"frames\":[{frames}]}},\"profiles\":[{}]}}",
+        profiles.join(",")
+    )
+}
+
+#[cfg(feature = "enable")]
+pub(crate) fn write_folded(
+    report: &crate::Report,
+    mut to: impl std::io::Write,
+) -> std::io::Result<()> {
+    fn push(stack: &mut String, name: &str) {
+        if !stack.is_empty() {
+            stack.push(';');
+        }
+        // ';' separates frames and the last ' ' separates the value, newlines would break the line format
+        stack.extend(name.chars().map(|c| match c {
+            ';' => ':',
+            '\n' | '\r' => ' ',
+            c => c,
+        }));
+    }
+
+    fn collect(
+        stack: &mut String,
+        node: &crate::report::ScopeNode,
+        stacks: &mut indexmap::IndexMap<String, u128>,
+    ) {
+        let len = stack.len();
+        push(stack, &node.name);
+
+        let self_time = node.self_time().as_nanos();
+        if self_time > 0 {
+            *stacks.entry(stack.clone()).or_default() += self_time;
+        }
+        for child in &node.children {
+            collect(stack, child, stacks);
+        }
+
+        stack.truncate(len);
+    }
+
+    let mut stacks = indexmap::IndexMap::new();
+    for thread in &report.threads {
+        for node in &thread.scopes {
+            // Beneath the scopes of the thread it was opened on behalf of
+            let mut stack = String::new();
+            for parent in &node.parent {
+                push(&mut stack, parent);
+            }
+            collect(&mut stack, node, &mut stacks);
+        }
+    }
+
+    for (stack, time) in stacks {
+        writeln!(to, "{stack} {time}")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,388 @@
+//! Runtime filtering of scopes by name.
+
+#[cfg(feature = "enable")]
+type Filter = dyn Fn(&str) -> bool + Send + Sync;
+
+#[cfg(feature = "enable")]
+static FILTER: std::sync::RwLock<Option<Box<Filter>>> = std::sync::RwLock::new(None);
+/// Avoids locking `FILTER` when there's no filter set.
+#[cfg(feature = "enable")]
+static ACTIVE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+#[cfg(feature = "enable")]
+static FROM_ENV: std::sync::Once = std::sync::Once::new();
+
+/// `0` if disabled, `1` if enabled, [`UNKNOWN`] until `PROFI` is read.
+#[cfg(feature = "enable")]
+static ENABLED: std::sync::atomic::AtomicU8 = std::sync::atomic::AtomicU8::new(UNKNOWN);
+#[cfg(feature = "enable")]
+const UNKNOWN: u8 = 2;
+
+/// Once a name is added it's never removed, so flipping its switch only needs a read lock.
+#[cfg(feature = "enable")]
+type Switches = std::sync::OnceLock<
+    std::sync::RwLock<std::collections::HashMap<Box<str>, std::sync::atomic::AtomicBool>>,
+>;
+
+/// Incremented whenever the filter or a switch changes, invalidating the decisions cached by [`Cached`].
+#[cfg(feature = "enable")]
+static EPOCH: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+
+/// Switches set by [`set_scope_enabled`].
+#[cfg(feature = "enable")]
+static SWITCHES: Switches = std::sync::OnceLock::new();
+/// Avoids looking up `SWITCHES` when no switch was ever set.
+#[cfg(feature = "enable")]
+static ANY_SWITCH: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Switches set by [`set_namespace_enabled`].
+#[cfg(feature = "enable")]
+static NAMESPACES: Switches = std::sync::OnceLock::new();
+/// Avoids looking up `NAMESPACES` when no switch was ever set.
+#[cfg(feature = "enable")]
+static ANY_NAMESPACE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Enables or disables all profiling at runtime, takes effect immediately on all threads.
+///
+/// Overrides the `PROFI` environment variable, which can be set to `0` to ship a binary built with profiling support that only measures when started with `PROFI=1`.
+/// Profiling is enabled by default.
+///
+/// While disabled, scopes cost two atomic loads and [`print_on_exit!`](crate::print_on_exit) doesn't print anything.
+/// Explicit [`Guard`](crate::Guard)s are always profiled.
+///
+/// # Example
+/// ```
+/// use profi::{prof, print_on_exit};
+///
+/// fn main() {
+///   print_on_exit!();
+///   profi::set_enabled(false);
+///   prof!(ignored);
+///
+///   profi::set_enabled(true);
+///   prof!(profiled);
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn set_enabled(enabled: bool) {
+    #[cfg(feature = "enable")]
+    {
+        ENABLED.store(u8::from(enabled), std::sync::atomic::Ordering::Relaxed);
+        refresh();
+    }
+}
+
+/// Returns `true` if profiling is enabled, see [`set_enabled`].
+///
+/// Always `false` without the `enable` feature.
+pub fn is_profiling_enabled() -> bool {
+    #[cfg(feature = "enable")]
+    {
+        match ENABLED.load(std::sync::atomic::Ordering::Relaxed) {
+            UNKNOWN => {
+                let enabled = std::env::var("PROFI").map_or(true, |v| v.trim() != "0");
+                // Don't override a concurrent `set_enabled`
+                let _ = ENABLED.compare_exchange(
+                    UNKNOWN,
+                    u8::from(enabled),
+                    std::sync::atomic::Ordering::Relaxed,
+                    std::sync::atomic::Ordering::Relaxed,
+                );
+                refresh();
+                ENABLED.load(std::sync::atomic::Ordering::Relaxed) == 1
+            }
+            enabled => enabled == 1,
+        }
+    }
+    #[cfg(not(feature = "enable"))]
+    false
+}
+
+/// Only profiles the scopes whose name is accepted by `filter`, the rest become no-ops.
+///
+/// Replaces the previous filter, including the one set by the `PROFI_FILTER` environment variable.
+///
+/// `PROFI_FILTER` accepts a comma separated list of names, where `*` matches any number of characters (e.g. `PROFI_FILTER=render::*,db::query`).
+///
+/// # Example
+/// ```
+/// use profi::{prof, print_on_exit};
+///
+/// fn main() {
+///   print_on_exit!();
+///   profi::set_filter(|name| name.starts_with("render"));
+///
+///   prof!("render::shadows"); // Profiled
+///   prof!("physics"); // Ignored
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn set_filter(filter: impl Fn(&str) -> bool + Send + Sync + 'static) {
+    #[cfg(feature = "enable")]
+    {
+        // Ensure the environment variable doesn't override it later
+        FROM_ENV.call_once(|| {});
+        *FILTER.write().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(filter));
+        ACTIVE.store(true, std::sync::atomic::Ordering::Release);
+        changed();
+    }
+}
+
+/// Removes the filter set by [`set_filter`] or `PROFI_FILTER`, profiling all scopes again.
+pub fn clear_filter() {
+    #[cfg(feature = "enable")]
+    {
+        FROM_ENV.call_once(|| {});
+        ACTIVE.store(false, std::sync::atomic::Ordering::Release);
+        *FILTER.write().unwrap_or_else(|e| e.into_inner()) = None;
+        changed();
+    }
+}
+
+/// Enables or disables profiling the scope with exactly this name, independently of the [filter](set_filter).
+///
+/// Useful for muting noisy scopes on a live application, changing a switch is cheap and takes effect immediately on all threads.
+///
+/// # Example
+/// ```
+/// use profi::{prof, print_on_exit};
+///
+/// fn main() {
+///   print_on_exit!();
+///   profi::set_scope_enabled("render::shadows", false);
+///   prof!("render::shadows"); // Ignored
+///
+///   profi::set_scope_enabled("render::shadows", true);
+///   prof!("render::shadows"); // Profiled
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn set_scope_enabled(name: &str, enabled: bool) {
+    #[cfg(feature = "enable")]
+    set_switch(&SWITCHES, &ANY_SWITCH, name, enabled)
+}
+
+/// Returns `false` if the scope was disabled with [`set_scope_enabled`].
+///
+/// The [filter](set_filter) is not taken into account.
+#[allow(unused_variables)]
+pub fn is_scope_enabled(name: &str) -> bool {
+    #[cfg(feature = "enable")]
+    if let Some(enabled) = get_switch(&SWITCHES, &ANY_SWITCH, name) {
+        return enabled;
+    }
+    true
+}
+
+/// Enables or disables profiling all the scopes of a namespace, the part of their name before the first `::`.
+///
+/// Meant for applications that depend on libraries instrumented with [`prof_lib!`](crate::prof_lib),
+/// but it also applies to the names inferred from the path of a function, like the ones of [`prof!()`](crate::prof).
+///
+/// # Example
+/// ```
+/// use profi::{prof_lib, print_on_exit};
+///
+/// fn main() {
+///   print_on_exit!();
+///   profi::set_namespace_enabled("mylib", false);
+///   prof_lib!(mylib, "decode"); // Ignored
+///   prof_lib!(otherlib, "decode"); // Profiled
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn set_namespace_enabled(namespace: &str, enabled: bool) {
+    #[cfg(feature = "enable")]
+    set_switch(&NAMESPACES, &ANY_NAMESPACE, namespace, enabled)
+}
+
+/// Returns `false` if the namespace was disabled with [`set_namespace_enabled`].
+#[allow(unused_variables)]
+pub fn is_namespace_enabled(namespace: &str) -> bool {
+    #[cfg(feature = "enable")]
+    if let Some(enabled) = get_switch(&NAMESPACES, &ANY_NAMESPACE, namespace) {
+        return enabled;
+    }
+    true
+}
+
+#[cfg(feature = "enable")]
+fn set_switch(switches: &Switches, any: &std::sync::atomic::AtomicBool, name: &str, enabled: bool) {
+    use std::sync::atomic::Ordering;
+
+    let switches = switches.get_or_init(Default::default);
+    if let Some(switch) = switches.read().unwrap_or_else(|e| e.into_inner()).get(name) {
+        switch.store(enabled, Ordering::Release);
+        changed();
+        return;
+    }
+    switches
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(name.into())
+        .or_default()
+        .store(enabled, Ordering::Release);
+    any.store(true, Ordering::Release);
+    changed();
+}
+
+/// Returns `None` if no switch was set for `name`.
+#[cfg(feature = "enable")]
+#[inline(always)]
+fn get_switch(
+    switches: &Switches,
+    any: &std::sync::atomic::AtomicBool,
+    name: &str,
+) -> Option<bool> {
+    if !any.load(std::sync::atomic::Ordering::Acquire) {
+        return None;
+    }
+    let switches = switches
+        .get_or_init(Default::default)
+        .read()
+        .unwrap_or_else(|e| e.into_inner());
+    switches
+        .get(name)
+        .map(|switch| switch.load(std::sync::atomic::Ordering::Acquire))
+}
+
+/// Returns `true` if the scope should be profiled.
+#[cfg(feature = "enable")]
+#[inline(always)]
+pub(crate) fn is_enabled(name: &str) -> bool {
+    if !is_profiling_enabled() {
+        return false;
+    }
+    if !FROM_ENV.is_completed() {
+        FROM_ENV.call_once(filter_from_env);
+        refresh();
+    }
+    if !is_scope_enabled(name) {
+        return false;
+    }
+    if let Some((namespace, _)) = name.split_once("::") {
+        if !is_namespace_enabled(namespace) {
+            return false;
+        }
+    }
+    if !ACTIVE.load(std::sync::atomic::Ordering::Acquire) {
+        return true;
+    }
+    match FILTER.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(filter) => filter(name),
+        None => true,
+    }
+}
+
+#[cfg(feature = "enable")]
+fn filter_from_env() {
+    let Ok(patterns) = std::env::var("PROFI_FILTER") else {
+        return;
+    };
+    *FILTER.write().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(patterns_filter(&patterns)));
+    ACTIVE.store(true, std::sync::atomic::Ordering::Release);
+    changed();
+}
+
+/// Invalidates the decisions cached by [`Cached`], must be called after the filter or a switch changes.
+#[cfg(feature = "enable")]
+fn changed() {
+    EPOCH.fetch_add(1, std::sync::atomic::Ordering::Release);
+    refresh();
+}
+
+/// Sets the [`FILTER`](crate::hooks::FILTER) hook if a scope could be rejected, so scopes skip [`is_enabled`] otherwise.
+#[cfg(feature = "enable")]
+fn refresh() {
+    use std::sync::atomic::Ordering;
+
+    // Serialized, so a stale decision never overwrites a newer one
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let active = !FROM_ENV.is_completed()
+        || ENABLED.load(Ordering::Relaxed) != 1
+        || ACTIVE.load(Ordering::Acquire)
+        || ANY_SWITCH.load(Ordering::Acquire)
+        || ANY_NAMESPACE.load(Ordering::Acquire);
+    crate::hooks::set(crate::hooks::FILTER, active);
+}
+
+/// Decision of [`is_enabled`] for the name of a call site, so the filter and the switches are only checked again after they change.
+#[cfg(feature = "enable")]
+pub(crate) struct Cached(
+    /// [`EPOCH`] when it was decided shifted left by one, plus `1` if enabled, `0` if never decided.
+    std::sync::atomic::AtomicU64,
+);
+
+#[cfg(feature = "enable")]
+impl Cached {
+    pub(crate) const fn new() -> Self {
+        Self(std::sync::atomic::AtomicU64::new(0))
+    }
+
+    /// Same as [`is_enabled`], `name` must always be the same.
+    #[inline(always)]
+    pub(crate) fn is_enabled(&self, name: &str) -> bool {
+        if !is_profiling_enabled() {
+            return false;
+        }
+        let cached = self.0.load(std::sync::atomic::Ordering::Relaxed);
+        if cached >> 1 == EPOCH.load(std::sync::atomic::Ordering::Acquire) {
+            return cached & 1 == 1;
+        }
+        self.decide(name)
+    }
+
+    #[cold]
+    fn decide(&self, name: &str) -> bool {
+        // Read before deciding, so a change in between invalidates it
+        let epoch = EPOCH.load(std::sync::atomic::Ordering::Acquire);
+        let enabled = is_enabled(name);
+        self.0.store(
+            epoch << 1 | u64::from(enabled),
+            std::sync::atomic::Ordering::Relaxed,
+        );
+        enabled
+    }
+}
+
+/// Filter accepting a comma separated list of [`glob_match`] patterns.
+#[cfg(feature = "enable")]
+pub(crate) fn patterns_filter(patterns: &str) -> impl Fn(&str) -> bool + Send + Sync + 'static {
+    let patterns = patterns
+        .split(',')
+        .map(|p| p.trim().to_owned())
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>();
+    move |name| patterns.iter().any(|p| glob_match(p, name))
+}
+
+/// Matches `name` against `pattern`, where `*` matches any number of characters.
+#[cfg(feature = "enable")]
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern, and of the character of `name` it matches up to
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            // Let the last `*` match one more character
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
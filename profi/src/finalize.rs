@@ -0,0 +1,83 @@
+//! Reports deferred until after the teardown code, see `print_on_exit!(at_exit)`.
+
+/// Reports of the `print_on_exit!(at_exit)` guards that were dropped, in the order they were dropped.
+#[cfg(feature = "enable")]
+static PENDING: std::sync::Mutex<Vec<Box<dyn FnOnce() + Send>>> = std::sync::Mutex::new(Vec::new());
+
+/// Prints the reports deferred by `print_on_exit!(at_exit)` now, with the scopes recorded after its guard was dropped.
+///
+/// By default `print_on_exit!` prints when its guard is dropped at the end of `main`, so the work done afterwards,
+/// like the destructors of thread locals, of the locals declared before it or of other threads, is never shown.
+/// With `at_exit`, the report is deferred until the process exits, after that teardown code.
+/// Call `finalize` to print it earlier, for example right after your own teardown, when exiting through a runtime that skips the exit handlers,
+/// or in the browser, which has none.
+///
+/// Unlike the default, the report doesn't wait for the other threads to exit, the still running ones are included as they are.
+/// Does nothing if there's no deferred report, so it can be called more than once.
+///
+/// # Example
+/// ```
+/// use profi::{prof, print_on_exit};
+///
+/// struct Cache;
+///
+/// impl Drop for Cache {
+///   fn drop(&mut self) {
+///     prof!("Cache::drop");
+///   }
+/// }
+///
+/// fn main() {
+///   let cache = Cache;
+///   {
+///     print_on_exit!(at_exit);
+///     prof!(work);
+///   }
+///   // Would be missing from the report without `at_exit`
+///   drop(cache);
+///   profi::finalize();
+/// }
+/// ```
+pub fn finalize() {
+    #[cfg(feature = "enable")]
+    {
+        let pending = std::mem::take(&mut *PENDING.lock().unwrap_or_else(|e| e.into_inner()));
+        for print in pending {
+            print();
+        }
+    }
+}
+
+/// Prints `print` on [`finalize`] or at the exit of the process.
+#[cfg(feature = "enable")]
+pub(crate) fn defer(print: Box<dyn FnOnce() + Send>) {
+    PENDING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(print);
+}
+
+/// Calls [`finalize`] when the process exits, registered as early as possible
+/// so it runs after the exit handlers registered later, which run first.
+#[cfg(feature = "enable")]
+pub(crate) fn register() {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        extern "C" {
+            fn atexit(callback: extern "C" fn()) -> std::ffi::c_int;
+        }
+
+        extern "C" fn on_exit() {
+            // Unwinding out of the handler would abort the process
+            let _ = std::panic::catch_unwind(finalize);
+        }
+
+        static REGISTERED: std::sync::Once = std::sync::Once::new();
+        REGISTERED.call_once(|| {
+            // SAFETY: `on_exit` is a valid handler for the whole life of the process
+            if unsafe { atexit(on_exit) } != 0 {
+                eprintln!("[profi] Could not register the exit handler, call profi::finalize to print the report");
+            }
+        });
+    }
+}
@@ -0,0 +1,181 @@
+//! Heuristics that point at the scopes worth looking at first, see [`Report::findings`](crate::Report::findings).
+
+/// Something in a report that may be worth optimizing or investigating, see [`Report::findings`](crate::Report::findings).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Finding {
+    /// How much time is at stake.
+    pub severity: Severity,
+    pub kind: FindingKind,
+    /// Scope or thread it's about.
+    pub subject: String,
+    /// What was found, with the data that triggered it.
+    pub message: String,
+}
+
+/// How much time is at stake in a [`Finding`], from its share of the application time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Severity {
+    /// Less than 5% of the application time.
+    Low,
+    /// Less than 20% of the application time.
+    Medium,
+    /// 20% of the application time or more.
+    High,
+}
+
+/// Heuristic that produced a [`Finding`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FindingKind {
+    /// The calls of a scope take very different times, its coefficient of variation is 100% or more.
+    HighVariance,
+    /// A scope takes 90% or more of the time of its parent, which has other children.
+    DominatesParent,
+    /// A scope is called 1000 times or more per call of its parent, or so often and so fast that measuring it may cost more than running it.
+    ManyCalls,
+    /// A thread spent 80% or more of its lifetime outside of its scopes, waiting or not instrumented.
+    IdleThread,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+        })
+    }
+}
+
+impl Severity {
+    /// Severity of a finding about `percent` of the application time.
+    #[cfg(feature = "enable")]
+    fn of(percent: f64) -> Self {
+        match percent {
+            p if p >= 20.0 => Severity::High,
+            p if p >= 5.0 => Severity::Medium,
+            _ => Severity::Low,
+        }
+    }
+}
+
+/// Findings of `report`, the most severe first.
+#[cfg(feature = "enable")]
+pub(crate) fn find(report: &crate::Report) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut push = |severity, kind, subject: &str, message: String| {
+        findings.push(Finding {
+            severity,
+            kind,
+            subject: subject.to_owned(),
+            message,
+        })
+    };
+
+    // Enclosing scopes of the current one, from the outermost
+    let mut ancestors = Vec::<&crate::ScopeStats>::new();
+    for (i, scope) in report.scopes.iter().enumerate() {
+        while ancestors.last().is_some_and(|a| a.depth >= scope.depth) {
+            ancestors.pop();
+        }
+        let severity = Severity::of(scope.percent_app);
+
+        if scope.cv >= 100.0 && scope.calls >= 10 {
+            push(
+                severity,
+                FindingKind::HighVariance,
+                &scope.name,
+                format!(
+                    "calls vary by {:.0}% of their average of {:.2?}, from {:.2?} to {:.2?} over {} calls",
+                    scope.cv, scope.average, scope.min, scope.max, scope.calls
+                ),
+            );
+        }
+
+        if let Some(parent) = ancestors.last() {
+            // Scopes after the current one, until the next sibling of the parent
+            let siblings = report.scopes[i + 1..]
+                .iter()
+                .take_while(|s| s.depth > parent.depth)
+                .chain(
+                    report.scopes[..i]
+                        .iter()
+                        .rev()
+                        .take_while(|s| s.depth > parent.depth),
+                )
+                .filter(|s| s.depth == scope.depth)
+                .count();
+            let share = share(scope.total_real, parent.total_real);
+            if siblings > 0 && share >= 90.0 && scope.name != parent.name {
+                push(
+                    severity,
+                    FindingKind::DominatesParent,
+                    &scope.name,
+                    format!(
+                        "takes {share:.0}% of the {:.2?} of `{}`, its {siblings} other children take the rest",
+                        parent.total_real, parent.name
+                    ),
+                );
+            }
+
+            let per_call = scope.calls / parent.calls.max(1);
+            if per_call >= 1000 && scope.name != parent.name {
+                push(
+                    severity,
+                    FindingKind::ManyCalls,
+                    &scope.name,
+                    format!(
+                        "called {per_call} times per call of `{}`, {} calls in total, batching them may help",
+                        parent.name, scope.calls
+                    ),
+                );
+            }
+        }
+
+        if scope.calls >= 10_000 && scope.average < std::time::Duration::from_micros(1) {
+            push(
+                severity,
+                FindingKind::ManyCalls,
+                &scope.name,
+                format!(
+                    "called {} times with an average of {:.2?}, measuring it may cost more than running it, profile its caller instead",
+                    scope.calls, scope.average
+                ),
+            );
+        }
+
+        ancestors.push(scope);
+    }
+
+    for thread in report.utilization() {
+        let (lifetime, idle) = (thread.lifetime, 100.0 - thread.percent);
+        if lifetime >= std::time::Duration::from_millis(10) && idle >= 80.0 {
+            let name = match &thread.name {
+                Some(name) => format!("Thread {} ({name})", thread.index),
+                None => format!("Thread {}", thread.index),
+            };
+            push(
+                Severity::Low,
+                FindingKind::IdleThread,
+                &name,
+                format!(
+                    "spent {idle:.0}% of its {lifetime:.2?} lifetime outside of its scopes, it may be waiting or not instrumented"
+                ),
+            );
+        }
+    }
+
+    findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    findings
+}
+
+/// `part` as a percentage of `total`.
+#[cfg(feature = "enable")]
+fn share(part: std::time::Duration, total: std::time::Duration) -> f64 {
+    match total.is_zero() {
+        true => 0.0,
+        false => part.as_secs_f64() / total.as_secs_f64() * 100.0,
+    }
+}
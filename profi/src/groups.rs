@@ -0,0 +1,94 @@
+//! Rules that merge the scopes whose name matches a pattern into a single row.
+
+/// Pattern of a group.
+#[cfg(feature = "enable")]
+enum Pattern {
+    Glob(Box<str>),
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+/// Patterns and the names of their groups, in the order they were set.
+#[cfg(feature = "enable")]
+static GROUPS: std::sync::RwLock<Vec<(Pattern, Box<str>)>> = std::sync::RwLock::new(Vec::new());
+
+/// Reports all the scopes whose name matches `pattern` as `name`, where `*` matches any number of characters.
+///
+/// Scopes with dynamic names, like the ones of `prof!(fmt = ..)` or [`tag`](crate::zz_private::ScopeGuard::tag),
+/// can fill the table with hundreds of near-identical rows, which a group merges into a single one.
+/// Add `expand_groups` to [`print_on_exit!`](crate::print_on_exit) to also show the scopes of each group as `name [scope]` rows beneath it,
+/// see [`PrintOptions::expand_groups`](crate::PrintOptions::expand_groups).
+///
+/// Like [aliases](crate::alias), groups are applied when aggregating the report, so they also affect the scopes recorded before.
+/// Aliases are resolved first, and if several patterns match a scope the last one set wins.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   profi::group("db::*", "database");
+///   for table in ["users", "orders", "items"] {
+///     prof!(fmt = "db::{table}");
+///   }
+///   # #[cfg(feature = "enable")]
+///   # {
+///   let report = profi::report();
+///   let database = report.scope("database").unwrap();
+///   assert_eq!(database.calls, 3);
+///   assert!(report.scope("db::users").is_none());
+///   # }
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn group(pattern: &str, name: &str) {
+    #[cfg(feature = "enable")]
+    GROUPS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((Pattern::Glob(pattern.into()), name.into()));
+}
+
+/// Reports all the scopes whose name matches the regex `pattern` as `name`, like [`group`].
+///
+/// Returns an error without adding the group if `pattern` is not a valid regex.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   profi::group_regex(r"^request #\d+$", "request").unwrap();
+///   for id in 0..3 {
+///     prof!(fmt = "request #{id}");
+///   }
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(profi::report().scope("request").unwrap().calls, 3);
+/// }
+/// ```
+#[cfg(feature = "regex")]
+#[allow(unused_variables)]
+pub fn group_regex(pattern: &str, name: &str) -> Result<(), regex::Error> {
+    let regex = regex::Regex::new(pattern)?;
+    #[cfg(feature = "enable")]
+    GROUPS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((Pattern::Regex(regex), name.into()));
+    Ok(())
+}
+
+/// Name of the group of the scope `name`, if it's in one.
+#[cfg(feature = "enable")]
+pub(crate) fn group_of(name: &str) -> Option<String> {
+    let groups = GROUPS.read().unwrap_or_else(|e| e.into_inner());
+    groups
+        .iter()
+        .rev()
+        .find(|(pattern, _)| match pattern {
+            Pattern::Glob(glob) => crate::filter::glob_match(glob, name),
+            #[cfg(feature = "regex")]
+            Pattern::Regex(regex) => regex.is_match(name),
+        })
+        .map(|(_, group)| group.to_string())
+}
@@ -0,0 +1,52 @@
+//! Single flag of the optional work done when a scope opens or closes, so the hot path only checks one atomic.
+//!
+//! Each setter that enables some work on every scope, like a filter, a budget or a stream, sets its bit.
+//! While no bit is set, a scope only takes its two measures.
+
+/// Filter, switches, or profiling disabled, see [`crate::set_filter`] and [`crate::set_enabled`].
+#[cfg(feature = "enable")]
+pub(crate) const FILTER: u32 = 1 << 0;
+/// Overhead budget, see [`crate::set_overhead_budget`].
+#[cfg(feature = "enable")]
+pub(crate) const BUDGET: u32 = 1 << 1;
+/// Stream of closed scopes, see [`crate::stream_jsonl`].
+#[cfg(feature = "enable")]
+pub(crate) const STREAM: u32 = 1 << 2;
+/// Tail latencies, see [`crate::watch_tail`].
+#[cfg(feature = "enable")]
+pub(crate) const TAIL: u32 = 1 << 3;
+/// Slow call warnings of the `log` feature.
+#[cfg(all(feature = "enable", feature = "log"))]
+pub(crate) const SLOW_LOG: u32 = 1 << 4;
+/// Merging of short calls, see [`crate::set_coalescing`].
+#[cfg(feature = "enable")]
+pub(crate) const COALESCE: u32 = 1 << 5;
+/// Limit of measures, see [`crate::set_retention`].
+#[cfg(feature = "enable")]
+pub(crate) const RETENTION: u32 = 1 << 6;
+/// Limit of memory, see [`crate::set_memory_limit`].
+#[cfg(feature = "enable")]
+pub(crate) const SPILL: u32 = 1 << 7;
+
+/// Bits of the active hooks, starts with [`FILTER`] until `PROFI` and `PROFI_FILTER` are read.
+#[cfg(feature = "enable")]
+static ACTIVE: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(FILTER);
+
+/// Returns `true` if any hook is active, and scopes must go through the slow path.
+///
+/// Relaxed, each hook still reads its own state once in the slow path.
+#[cfg(feature = "enable")]
+#[inline(always)]
+pub(crate) fn any() -> bool {
+    ACTIVE.load(std::sync::atomic::Ordering::Relaxed) != 0
+}
+
+/// Sets or clears the bit of `hook`.
+#[cfg(feature = "enable")]
+pub(crate) fn set(hook: u32, active: bool) {
+    if active {
+        ACTIVE.fetch_or(hook, std::sync::atomic::Ordering::Relaxed);
+    } else {
+        ACTIVE.fetch_and(!hook, std::sync::atomic::Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,159 @@
+//! Tiny HTTP server to fetch the profile of a running process, see [`serve`].
+
+/// Serves the live profile over HTTP at `addr`, so the profile of a long-running service can be fetched without stopping it.
+///
+/// A background thread answers `GET` requests to:
+/// - `/report`: the [`Report`](crate::Report) as JSON.
+/// - `/report.txt`: the report as a table, like [`print_on_exit!`](crate::print_on_exit) prints it.
+/// - `/flamegraph`: the collapsed stacks of [`Format::Folded`](crate::Format::Folded), ready for [`inferno`](https://github.com/jonhoo/inferno) or `flamegraph.pl`.
+///
+/// Each request builds a new report, including the threads that are still running, like [`report`](crate::report).
+/// Requests are answered one at a time, and there's no authentication, so bind it to a local address.
+///
+/// The server stops when the returned handle is dropped.
+/// Returns an error if `addr` can't be bound, and does nothing if the `enable` feature is disabled.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   // Port 0 picks any free port, use a fixed one like "127.0.0.1:6969" to know where to curl
+///   let server = profi::serve("127.0.0.1:0").unwrap();
+///   {
+///     prof!(work);
+///   }
+///   # #[cfg(feature = "enable")]
+///   # {
+///   use std::io::{Read, Write};
+///
+///   let mut stream = std::net::TcpStream::connect(server.local_addr().unwrap()).unwrap();
+///   stream.write_all(b"GET /report.txt HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+///   let mut response = String::new();
+///   stream.read_to_string(&mut response).unwrap();
+///   assert!(response.starts_with("HTTP/1.1 200 OK"));
+///   assert!(response.contains("work"));
+///   # }
+/// }
+/// ```
+#[must_use = "the server stops when the handle is dropped"]
+#[allow(unused_variables)]
+pub fn serve(addr: impl std::net::ToSocketAddrs) -> std::io::Result<ServerHandle> {
+    #[cfg(feature = "enable")]
+    {
+        let listener = std::net::TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        // Polled, so the thread can notice when the handle is dropped
+        listener.set_nonblocking(true)?;
+        let (stop, stopped) = std::sync::mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = respond(stream) {
+                        eprintln!("[profi] Could not answer the HTTP request: {e}");
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    match stopped.recv_timeout(std::time::Duration::from_millis(50)) {
+                        Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+                        _ => return,
+                    }
+                }
+                Err(e) => eprintln!("[profi] Could not accept the HTTP connection: {e}"),
+            }
+        });
+        Ok(ServerHandle(Some((local_addr, stop, thread))))
+    }
+    #[cfg(not(feature = "enable"))]
+    Ok(ServerHandle(None))
+}
+
+/// Handle of the server started by [`serve`], the server stops when it's dropped.
+#[derive(Debug)]
+#[allow(clippy::type_complexity)]
+pub struct ServerHandle(
+    Option<(
+        std::net::SocketAddr,
+        std::sync::mpsc::Sender<()>,
+        std::thread::JoinHandle<()>,
+    )>,
+);
+
+impl ServerHandle {
+    /// Address the server listens on, useful when binding to port 0.
+    ///
+    /// Returns [`None`] if the `enable` feature is disabled.
+    pub fn local_addr(&self) -> Option<std::net::SocketAddr> {
+        self.0.as_ref().map(|(addr, _, _)| *addr)
+    }
+
+    /// Stops the server and waits for it to finish.
+    pub fn stop(self) {}
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        if let Some((_, stop, thread)) = self.0.take() {
+            let _ = stop.send(());
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Reads the request from `stream` and writes the response, closing the connection.
+#[cfg(feature = "enable")]
+fn respond(mut stream: std::net::TcpStream) -> std::io::Result<()> {
+    use std::io::{BufRead, Write};
+
+    stream.set_nonblocking(false)?;
+    // A client that doesn't send the request would block the rest
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))?;
+    let mut reader = std::io::BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // The headers are not used, but are read so the client doesn't see the connection reset
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut words = request.split_whitespace();
+    let (method, path) = (words.next(), words.next().unwrap_or_default());
+    // The query is ignored
+    let path = path.split('?').next().unwrap_or_default();
+    let (status, content_type, body) = match (method, path) {
+        (Some("GET"), "/report") => {
+            let body = serde_json::to_vec(&crate::report()).map_err(std::io::Error::other)?;
+            ("200 OK", "application/json", body)
+        }
+        (Some("GET"), "/report.txt") => {
+            let mut body = Vec::new();
+            let options = crate::PrintOptions::new().color(false);
+            crate::report().print(&mut body, &options)?;
+            ("200 OK", "text/plain; charset=utf-8", body)
+        }
+        (Some("GET"), "/flamegraph") => {
+            let mut body = Vec::new();
+            let options = crate::PrintOptions::new().format(crate::Format::Folded);
+            crate::report().print(&mut body, &options)?;
+            ("200 OK", "text/plain; charset=utf-8", body)
+        }
+        (Some("GET"), _) => (
+            "404 Not Found",
+            "text/plain; charset=utf-8",
+            b"Not found, try /report, /report.txt or /flamegraph\n".to_vec(),
+        ),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain; charset=utf-8",
+            b"Only GET is supported\n".to_vec(),
+        ),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()
+}
@@ -1,15 +1,237 @@
 #![doc = include_str!("../README.md")]
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
 #![allow(clippy::needless_doctest_main)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(feature = "std")]
+mod aggregate;
+#[cfg(feature = "std")]
+mod alias;
+#[cfg(feature = "std")]
+mod args;
+#[cfg(all(feature = "std", feature = "criterion"))]
+pub mod bench;
+#[cfg(feature = "std")]
+mod budgets;
+#[cfg(feature = "std")]
+mod capture;
+#[cfg(feature = "std")]
+mod clock;
+#[cfg(feature = "std")]
+mod coalesce;
+#[cfg(feature = "std")]
+mod collisions;
+#[cfg(feature = "std")]
+mod config;
+#[cfg(all(feature = "std", feature = "enable", feature = "cores"))]
+mod cores;
+#[cfg(feature = "std")]
+mod descriptions;
+#[cfg(feature = "std")]
+mod diff;
+#[cfg(feature = "std")]
+mod dot;
+#[cfg(all(
+    feature = "std",
+    feature = "enable",
+    feature = "energy",
+    target_os = "linux"
+))]
+mod energy;
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "std")]
+mod filter;
+#[cfg(feature = "std")]
+mod finalize;
+#[cfg(feature = "std")]
+mod findings;
+#[cfg(feature = "std")]
+mod groups;
+#[cfg(feature = "std")]
+mod hooks;
+#[cfg(all(feature = "std", feature = "http"))]
+mod http;
+#[cfg(feature = "std")]
+mod lifetimes;
+#[cfg(feature = "std")]
 mod measure;
+#[cfg(all(feature = "std", feature = "mmap"))]
+mod mmap;
+#[cfg(feature = "std")]
+mod names;
+#[cfg(feature = "std")]
+mod noise;
+#[cfg(feature = "std")]
+mod options;
+#[cfg(feature = "std")]
+pub mod overhead;
+#[cfg(feature = "std")]
+mod owners;
+#[cfg(all(
+    feature = "std",
+    feature = "enable",
+    feature = "perf",
+    target_os = "linux"
+))]
+mod perf;
+#[cfg(feature = "std")]
+mod periodic;
+#[cfg(all(feature = "std", feature = "pprof"))]
+mod pprof;
+#[cfg(feature = "std")]
+pub mod prelude;
+#[cfg(feature = "std")]
 mod process;
+#[cfg(feature = "std")]
+mod progress;
+#[cfg(all(feature = "std", feature = "rayon"))]
+pub mod rayon;
+#[cfg(feature = "std")]
+mod registry;
+#[cfg(feature = "std")]
+mod report;
+#[cfg(feature = "std")]
+mod retention;
+#[cfg(feature = "std")]
+mod runs;
+#[cfg(all(feature = "std", feature = "enable"))]
+mod rusage;
+#[cfg(feature = "std")]
+mod sampling;
+#[cfg(all(feature = "std", feature = "signals"))]
+mod signals;
+#[cfg(feature = "std")]
+mod sink;
+#[cfg(all(feature = "std", feature = "log"))]
+mod slow_log;
+#[cfg(feature = "std")]
+mod spill;
+#[cfg(feature = "std")]
+mod startup;
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(feature = "std")]
+mod sync;
+#[cfg(feature = "std")]
+mod tail;
+#[cfg(feature = "std")]
+pub mod thread;
+#[cfg(all(feature = "std", feature = "tokio"))]
+pub mod tokio;
+#[cfg(feature = "std")]
+pub mod trace;
+#[cfg(all(feature = "std", feature = "tracing"))]
+pub mod tracing;
+#[cfg(all(
+    feature = "std",
+    feature = "enable",
+    feature = "wasm",
+    target_arch = "wasm32"
+))]
+mod wasm;
+#[cfg(all(feature = "std", feature = "wgpu"))]
+pub mod wgpu;
+#[cfg(feature = "std")]
 pub mod zz_private;
 
+#[cfg(feature = "embedded")]
+pub mod embedded;
+
+/// Items used by the macros in `no_std` builds, measured by [`embedded`] if enabled.
+#[cfg(not(feature = "std"))]
+#[doc(hidden)]
+pub mod zz_private {
+    #[cfg(feature = "embedded")]
+    pub use crate::embedded::ScopeGuard;
+
+    /// Measures nothing without the `embedded` feature.
+    #[cfg(not(feature = "embedded"))]
+    pub struct ScopeGuard;
+
+    #[cfg(not(feature = "embedded"))]
+    impl ScopeGuard {
+        #[inline(always)]
+        pub fn new<N>(_name: N) -> Self {
+            ScopeGuard
+        }
+
+        #[inline(always)]
+        pub fn sampled<N>(_name: N, _calls: &SampleCounter, _every: usize) -> Self {
+            ScopeGuard
+        }
+
+        #[inline(always)]
+        pub fn field<K, V>(&mut self, _key: K, _value: V) {}
+    }
+
+    /// Calls of a call site with `sample = 1 / N`, the `embedded` profiler records all of them.
+    pub struct SampleCounter;
+
+    impl SampleCounter {
+        #[allow(clippy::new_without_default)]
+        pub const fn new() -> Self {
+            SampleCounter
+        }
+    }
+
+    /// Name of a call site, collisions are only found with `std`.
+    pub struct ScopeName;
+
+    impl ScopeName {
+        #[allow(clippy::new_without_default)]
+        pub const fn new() -> Self {
+            ScopeName
+        }
+
+        #[inline(always)]
+        pub fn inferred(&self, name: &'static str) -> &'static str {
+            name
+        }
+
+        #[inline(always)]
+        pub fn explicit(&self, name: &'static str) -> &'static str {
+            name
+        }
+
+        #[inline(always)]
+        pub fn described(&self, name: &'static str, _desc: &'static str) -> &'static str {
+            name
+        }
+    }
+
+    #[inline(always)]
+    pub fn type_name_of<T>(_: T) -> &'static str {
+        core::any::type_name::<T>()
+    }
+
+    pub fn strip_closures(mut name: &str) -> &str {
+        while let Some(stripped) = name.strip_suffix("::{{closure}}") {
+            name = stripped;
+        }
+        name
+    }
+}
+
 /// Enables profiling for the annotated function.
 ///
 /// Equivalent to putting [`prof!()`] at the start.
 ///
+/// On `async fn`s the body is wrapped with [`prof_future!`] instead, so only the time spent being polled is measured.
+///
+/// Accepts the following comma separated arguments:
+/// - `name = "..."`: Uses the given name instead of inferring it, useful on trait impls and generic functions.
+/// - `args` or `args(a, b)`: Appends the `Debug` representation of all or the selected arguments to the name, like `query(id = 4)`.
+///   Only arguments with a plain name are captured by `args`, `self` never is.
+/// - `skip`: Does not profile the function, useful with `cfg_attr`.
+/// - `owner = "..."`: Sets the owner of the scope with [`set_owner`] the first time it's called, like `owner = "@storage"`.
+///
+/// It can also be applied to `impl` blocks, traits and inline modules, which profiles every function inside named as `Type::method`, `Trait::method` or `module::function`.
+/// Only the default methods of a trait are profiled, and `const fn`s inside a block are left as they are.
+/// `args` is applied to all of them, and functions with their own `#[profile(...)]` keep their arguments, so `#[profile(skip)]` opts one out.
+///
 /// # Examples
 /// ```rust
 /// use profi::profile;
@@ -18,6 +240,64 @@ pub mod zz_private;
 /// fn anotated() {
 ///     // ...
 /// }
+///
+/// #[profile(name = "db::query", args(id), owner = "@storage")]
+/// fn query(id: u32, cache: &mut Vec<u32>) {
+///     // ...
+/// }
+///
+/// #[cfg_attr(not(debug_assertions), profile(skip))]
+/// #[cfg_attr(debug_assertions, profile)]
+/// fn only_in_debug() {
+///     // ...
+/// }
+///
+/// struct Cache(Vec<u32>);
+///
+/// // Profiled as "Cache::get", but not "Cache::len"
+/// #[profile]
+/// impl Cache {
+///     fn get(&self, i: usize) -> u32 {
+///         self.0[i]
+///     }
+///
+///     #[profile(skip)]
+///     fn len(&self) -> usize {
+///         self.0.len()
+///     }
+/// }
+///
+/// // Profiled as "Store::load", implementors only profile it if they keep the default
+/// #[profile]
+/// trait Store {
+///     fn load<T>(&self, key: T) -> Option<T>
+///     where
+///         T: Clone,
+///     {
+///         Some(key)
+///     }
+/// }
+/// ```
+///
+/// The owner is matched against the name with any arguments:
+/// ```rust
+/// #[profi::profile(name = "query", args(id), owner = "@storage")]
+/// fn query(id: u32) {
+///     // ...
+/// }
+///
+/// fn main() {
+///     query(4);
+///     let report = profi::report();
+///     # #[cfg(feature = "enable")]
+///     assert_eq!(report.scope("query(id = 4)").unwrap().owner.as_deref(), Some("@storage"));
+/// }
+/// ```
+///
+/// Unknown arguments and unsupported items are reported where they are written:
+/// ```compile_fail
+/// #[profi::profile(nmae = "typo")]
+/// fn typo() {}
 /// ```
 #[cfg(feature = "attributes")]
 pub use profi_attributes::profile;
@@ -36,7 +316,91 @@ pub use profi_attributes::profile;
 #[cfg(feature = "attributes")]
 pub use profi_attributes::main;
 
-pub use zz_private::Guard;
+#[cfg(feature = "std")]
+pub use aggregate::{register_aggregator, Aggregator, Apdex};
+#[cfg(feature = "std")]
+pub use alias::alias;
+#[cfg(feature = "std")]
+pub use args::{init_from_args, ArgsHandle};
+#[cfg(feature = "std")]
+pub use budgets::{load_budgets, on_budget_violation, set_budget, Budget, BudgetViolation};
+#[cfg(feature = "std")]
+pub use capture::merge_files;
+#[cfg(feature = "std")]
+pub use clock::{set_clock, Clock};
+#[cfg(feature = "std")]
+pub use coalesce::set_coalescing;
+#[cfg(feature = "std")]
+pub use collisions::NameCollision;
+#[cfg(feature = "std")]
+pub use config::{watch_config, ConfigHandle};
+#[cfg(feature = "std")]
+pub use descriptions::set_description;
+#[cfg(feature = "std")]
+pub use diff::{NodeDiff, ReportDiff, ScopeDiff, TreeDiff};
+#[cfg(feature = "std")]
+pub use error::{on_error, Error};
+#[cfg(feature = "std")]
+pub use filter::{
+    clear_filter, is_namespace_enabled, is_profiling_enabled, is_scope_enabled, set_enabled,
+    set_filter, set_namespace_enabled, set_scope_enabled,
+};
+#[cfg(feature = "std")]
+pub use finalize::finalize;
+#[cfg(feature = "std")]
+pub use findings::{Finding, FindingKind, Severity};
+#[cfg(feature = "std")]
+pub use groups::group;
+#[cfg(all(feature = "std", feature = "regex"))]
+pub use groups::group_regex;
+#[cfg(all(feature = "std", feature = "http"))]
+pub use http::{serve, ServerHandle};
+#[cfg(all(feature = "std", feature = "mmap"))]
+pub use mmap::load_mmap;
+#[cfg(feature = "std")]
+pub use noise::{calibrate_noise, NoiseFloor};
+#[cfg(feature = "std")]
+pub use options::{Column, Format, Hierarchy, PrintOptions, Sort, TableFormat, TimeUnit};
+#[cfg(feature = "std")]
+pub use owners::{load_owners, set_owner};
+#[cfg(feature = "std")]
+pub use periodic::{start_periodic_report, PeriodicHandle, PeriodicReport};
+#[cfg(feature = "std")]
+pub use progress::on_report_progress;
+#[cfg(feature = "std")]
+pub use report::{
+    report, report_string, reset, snapshot, try_report, CoreUsage, FieldStats, FrameStats,
+    LifetimeStats, Milestone, OwnerStats, Report, ScopeNode, ScopeStats, SlowCall, TailEvent,
+    ThreadStats, ThreadUtilization,
+};
+#[cfg(feature = "std")]
+pub use retention::{set_retention, Retention};
+#[cfg(feature = "std")]
+pub use runs::{aggregate_runs, MultiRunReport, RunStats};
+#[cfg(feature = "std")]
+pub use sampling::set_overhead_budget;
+#[cfg(all(feature = "std", feature = "signals"))]
+pub use signals::{dump_on_signal, Signal};
+#[cfg(feature = "std")]
+pub use sink::{Fallback, ReportSink};
+#[cfg(all(feature = "std", feature = "log"))]
+pub use slow_log::set_slow_warning;
+#[cfg(feature = "std")]
+pub use spill::set_memory_limit;
+#[cfg(feature = "std")]
+pub use startup::{begin_shutdown, ready, startup_mode, StartupMode};
+#[cfg(feature = "std")]
+pub use sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(feature = "std")]
+pub use tail::{watch_tail, watch_tail_with};
+#[cfg(feature = "std")]
+pub use thread::spawn_profiled;
+#[cfg(feature = "std")]
+pub use zz_private::{profile_fn, record, record_at, record_many, ExitPrinter, Guard, PauseGuard};
+
+/// Version of `profi`, embedded in the [binary traces](trace) and the [streamed captures](export::stream_jsonl) it writes.
+#[cfg(feature = "std")]
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// Allows profiling the profiling methods
 #[allow(unused)]
@@ -113,6 +477,96 @@ pub(crate) type Str = beef::lean::Cow<'static, str>;
 /// }
 /// ```
 ///
+/// ## Sample a hot scope
+/// With `sample = 1 / N` only one every N calls of the scope is measured, and each recorded call counts as N calls of the same duration,
+/// along with the scopes opened inside it.
+/// ```
+/// use profi::{prof, print_on_exit};
+///
+/// fn hot(i: u64) -> u64 {
+///   prof!("hot", sample = 1 / 1024);
+///   i * 2
+/// }
+///
+/// fn main() {
+///   print_on_exit!();
+///   for i in 0..10_240 {
+///     std::hint::black_box(hot(i));
+///   }
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(profi::report().scope("hot").unwrap().calls, 10_240);
+/// }
+/// ```
+///
+/// The times of the skipped calls are taken from the recorded ones:
+/// ```
+/// use profi::{prof, print_on_exit};
+/// use std::time::Duration;
+///
+/// fn step() {
+///   prof!("step", sample = 1 / 4);
+///   std::thread::sleep(Duration::from_millis(2));
+/// }
+///
+/// fn main() {
+///   print_on_exit!();
+///   for _ in 0..8 {
+///     step();
+///   }
+///   # #[cfg(feature = "enable")]
+///   {
+///     let report = profi::report();
+///     let step = report.scope("step").unwrap();
+///     assert_eq!(step.calls, 8);
+///     // Two calls of at least 2ms were recorded, standing for all eight
+///     assert!(step.total_real >= Duration::from_millis(16));
+///     assert_eq!(step.sample_rate, Some(0.25));
+///   }
+/// }
+/// ```
+///
+/// ## Label calls with fields
+/// With `fields = { key: value }` each call records the values, which are strings, without changing the name of the scope.
+/// Group the report by one of them to split the scope into a row per value,
+/// with [`Report::group_by`] or `print_on_exit!(group_by = "key")`:
+/// ```
+/// use profi::{prof, print_on_exit};
+///
+/// fn query(table: &'static str) {
+///   prof!("query", fields = { table: table, op: "select" });
+/// }
+///
+/// fn main() {
+///   print_on_exit!(group_by = "table");
+///   query("users");
+///   query("orders");
+/// }
+/// ```
+///
+/// ## Describe a scope
+/// With `desc = "..."` right after a literal or identifier name, the description is shown in the HTML reports and kept in the JSON ones,
+/// see [`set_description`] for the other names.
+/// ```
+/// use profi::{prof, print_on_exit};
+///
+/// fn main() {
+///   print_on_exit!(format = html);
+///   prof!("compact_db", desc = "Compacts the LSM tree");
+/// }
+/// ```
+///
+/// ## Errors
+/// Anything else after the name fails to compile with an explanation, like a format string that is not a literal:
+/// ```compile_fail
+/// let format = "iteration {}";
+/// profi::prof!(fmt = format, 1);
+/// ```
+///
+/// Or a rate that is not written as `1 / N`:
+/// ```compile_fail
+/// profi::prof!("hot", sample = 0.01);
+/// ```
+///
 #[macro_export]
 macro_rules! prof {
     ($($tt:tt)*) => {
@@ -153,20 +607,513 @@ macro_rules! prof_guard {
         $crate::prof_guard!({
             // https://docs.rs/stdext/latest/src/stdext/macros.rs.html#63-74
             fn f() {}
+            static NAME: $crate::zz_private::ScopeName = $crate::zz_private::ScopeName::new();
             let name = $crate::zz_private::type_name_of(f);
             // `3` is the length of the `::f`.
-            &name[..name.len() - 3]
+            NAME.inferred(&name[..name.len() - 3])
         })
     };
-    ($name:ident) => {
-        $crate::prof_guard!(stringify!($name))
+    (@explicit $name:expr) => {{
+        static NAME: $crate::zz_private::ScopeName = $crate::zz_private::ScopeName::new();
+        NAME.explicit($name)
+    }};
+    (@described $name:expr, $desc:literal $(, $($opts:tt)*)?) => {
+        $crate::prof_guard!({
+            static NAME: $crate::zz_private::ScopeName = $crate::zz_private::ScopeName::new();
+            NAME.described($name, $desc)
+        } $(, $($opts)*)?)
+    };
+    ($name:ident, desc = $desc:literal $(, $($opts:tt)*)?) => {
+        $crate::prof_guard!(@described stringify!($name), $desc $(, $($opts)*)?)
+    };
+    ($name:literal, desc = $desc:literal $(, $($opts:tt)*)?) => {
+        $crate::prof_guard!(@described $name, $desc $(, $($opts)*)?)
+    };
+    ($name:ident $(, $($opts:tt)*)?) => {
+        $crate::prof_guard!($crate::prof_guard!(@explicit stringify!($name)) $(, $($opts)*)?)
+    };
+    ($name:literal $(, $($opts:tt)*)?) => {
+        $crate::prof_guard!($crate::prof_guard!(@explicit $name) $(, $($opts)*)?)
+    };
+    (fmt = $format:literal $(, $arg:expr)* $(,)?) => {
+        $crate::prof_guard!(format!($format $(, $arg)*))
+    };
+    (fmt = $($other:tt)*) => {
+        compile_error!(concat!(
+            "`fmt` expects a format string followed by its arguments, like `fmt = \"iteration {}\", i`, found `",
+            stringify!($($other)*),
+            "`"
+        ))
     };
-    (fmt = $( $name:tt )+) => {
-        $crate::prof_guard!(format!($($name)+))
+    ($name:expr, sample = 1 / $every:expr) => {{
+        static CALLS: $crate::zz_private::SampleCounter = $crate::zz_private::SampleCounter::new();
+        $crate::zz_private::ScopeGuard::sampled($name, &CALLS, $every)
+    }};
+    ($name:expr, sample = $($other:tt)*) => {
+        compile_error!(concat!(
+            "`sample` expects a rate like `sample = 1 / 1024`, found `",
+            stringify!($($other)*),
+            "`"
+        ))
+    };
+    ($name:expr, fields = { $($key:ident : $value:expr),* $(,)? }) => {{
+        #[allow(unused_mut)]
+        let mut guard = $crate::zz_private::ScopeGuard::new($name);
+        $(guard.field(stringify!($key), $value);)*
+        guard
+    }};
+    ($name:expr, fields = $($other:tt)*) => {
+        compile_error!(concat!(
+            "`fields` expects `key: value` pairs in braces, like `fields = { table: \"users\" }`, found `",
+            stringify!($($other)*),
+            "`"
+        ))
     };
     ($name:expr) => {
         $crate::zz_private::ScopeGuard::new($name)
     };
+    ($name:expr, $($other:tt)*) => {
+        compile_error!(concat!(
+            "Unexpected `",
+            stringify!($($other)*),
+            "` after the name of the scope, the options are `desc = \"...\"` right after a literal or identifier name, `sample = 1 / N` and `fields = { key: value }`"
+        ))
+    };
+}
+
+/// Profiles an expression and returns its value.
+///
+/// Useful to instrument a value inline, without moving it into its own block.
+/// The whole expression is measured, including the evaluation of its arguments.
+///
+/// Accepts a name like [`prof!`] before the expression, if not provided the name of the enclosing function is used.
+/// See [`profile_fn`] for the equivalent with closures.
+///
+/// # Examples
+/// ```
+/// use profi::{prof_ret, print_on_exit};
+///
+/// fn parse(input: &str) -> u32 {
+///   input.parse().unwrap()
+/// }
+///
+/// fn main() {
+///   print_on_exit!();
+///
+///   let a = prof_ret!("parse", parse("42"));
+///   let b = prof_ret!(sum, a + parse("8"));
+///   // Inferred name, `main`
+///   let c = prof_ret!(b * 2);
+///   assert_eq!(c, 100);
+/// }
+/// ```
+#[macro_export]
+macro_rules! prof_ret {
+    ($name:ident, $value:expr) => {
+        $crate::prof_ret!(stringify!($name), $value)
+    };
+    ($name:expr, $value:expr) => {{
+        let _guard = $crate::prof_guard!($name);
+        $value
+    }};
+    ($value:expr) => {{
+        let _guard = $crate::prof_guard!();
+        $value
+    }};
+}
+
+/// Profiles a scope of a library, prefixing its name with the library's namespace, like `mylib::decode`.
+///
+/// The namespace is either given explicitly as the first argument, or declared once at the crate root with [`library_namespace!`].
+/// Applications can then mute all the scopes of the library with [`set_namespace_enabled`].
+///
+/// Names must be identifiers or string literals, the prefixed name is built only once, anything else fails to compile.
+///
+/// # Examples
+/// ```
+/// use profi::{prof_lib, print_on_exit};
+///
+/// profi::library_namespace!("mylib");
+///
+/// fn decode() {
+///   // Profiled as "mylib::decode"
+///   prof_lib!(decode);
+///   // ...
+/// }
+///
+/// fn main() {
+///   print_on_exit!();
+///   decode();
+///   // Explicit namespace, profiled as "otherlib::parse"
+///   prof_lib!(otherlib, "parse");
+/// }
+/// ```
+#[allow(clippy::crate_in_macro_def)]
+#[macro_export]
+macro_rules! prof_lib {
+    ($namespace:ident, $name:ident) => {
+        $crate::prof!($crate::prof_guard!(@explicit ::std::concat!(
+            ::std::stringify!($namespace),
+            "::",
+            ::std::stringify!($name)
+        )))
+    };
+    ($namespace:ident, $name:literal) => {
+        $crate::prof!($crate::prof_guard!(@explicit ::std::concat!(::std::stringify!($namespace), "::", $name)))
+    };
+    (@namespaced $name:expr) => {
+        $crate::prof!($crate::prof_guard!(@explicit {
+            static NAME: ::std::sync::OnceLock<::std::string::String> =
+                ::std::sync::OnceLock::new();
+            // `crate` is the library calling the macro, where `library_namespace!` was declared
+            NAME.get_or_init(|| ::std::format!("{}::{}", crate::__PROFI_NAMESPACE, $name))
+                .as_str()
+        }))
+    };
+    ($name:ident) => {
+        $crate::prof_lib!(@namespaced ::std::stringify!($name))
+    };
+    ($name:literal) => {
+        $crate::prof_lib!(@namespaced $name)
+    };
+    ($($other:tt)*) => {
+        compile_error!(concat!(
+            "`prof_lib!` expects an identifier or a string literal as the name, optionally after a namespace, found `",
+            stringify!($($other)*),
+            "`"
+        ))
+    };
+}
+
+/// Declares the namespace used by [`prof_lib!`] for the scopes of this crate.
+///
+/// Must be called once at the crate root, usually with the name of the crate.
+///
+/// # Example
+/// ```
+/// profi::library_namespace!("mylib");
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! library_namespace {
+    ($namespace:literal) => {
+        #[doc(hidden)]
+        #[allow(dead_code)]
+        pub(crate) const __PROFI_NAMESPACE: &str = $namespace;
+    };
+}
+
+/// Adds to numeric counters of the innermost open scope, like processed bytes or items.
+///
+/// The totals and their rate per second are shown as extra columns, one per counter name.
+/// If a name is given before the counters, a new scope is opened like with [`prof!`].
+///
+/// Values are converted to `u64` with `as`.
+///
+/// # Examples
+/// ```
+/// use profi::{prof, prof_count, print_on_exit};
+///
+/// fn decode(data: &[u8]) {
+///   prof!();
+///   prof_count!(bytes = data.len(), items = 1);
+///   // ...
+/// }
+///
+/// fn main() {
+///   print_on_exit!();
+///   decode(&[0; 1024]);
+///
+///   // Opens the scope `parse` and adds to its `bytes` counter
+///   prof_count!("parse", bytes = 512);
+/// }
+/// ```
+#[macro_export]
+macro_rules! prof_count {
+    ($($counter:ident = $value:expr),+ $(,)?) => {
+        $( $crate::zz_private::count(stringify!($counter), $value as u64); )+
+    };
+    ($name:tt, $($counter:ident = $value:expr),+ $(,)?) => {
+        $crate::prof!($name);
+        $crate::prof_count!($($counter = $value),+)
+    };
+}
+
+/// Records a duration measured elsewhere, like a GPU query or an external library, as a call of a scope.
+///
+/// The call is placed inside the innermost open scope and ends now, so it appears in the same hierarchy and table as the scopes around it.
+/// Accepts a name like [`prof!`], see [`record_at`] for calls with a known start and end, and [`record_many`] for several at once.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use profi::{prof, record, print_on_exit};
+///
+/// fn main() {
+///   print_on_exit!();
+///   prof!(frame);
+///   // Read back from a timestamp query
+///   let gpu_time = Duration::from_micros(1200);
+///   record!("gpu::pass", gpu_time);
+///   record!(upload, Duration::from_micros(300));
+///
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(profi::report().scope("gpu::pass").unwrap().total_real, gpu_time);
+/// }
+/// ```
+#[macro_export]
+macro_rules! record {
+    ($name:ident, $duration:expr $(,)?) => {
+        $crate::record!(stringify!($name), $duration)
+    };
+    ($name:expr, $duration:expr $(,)?) => {
+        $crate::zz_private::record($name, $duration)
+    };
+}
+
+/// Records the time elapsed since profiling started, useful to optimize the startup of an application.
+///
+/// Milestones are listed in their own section after the table, with the time since the start and since the previous milestone.
+/// Profiling starts with the first scope or milestone, usually the one created by [`print_on_exit!`] at the top of `main`.
+///
+/// # Examples
+/// ```
+/// use profi::{milestone, print_on_exit};
+///
+/// fn main() {
+///   print_on_exit!();
+///   // Load the configuration...
+///   milestone!("config loaded");
+///   // Open the window...
+///   milestone!(ready);
+/// }
+/// ```
+#[macro_export]
+macro_rules! milestone {
+    ($name:ident) => {
+        $crate::milestone!(stringify!($name))
+    };
+    ($name:expr) => {
+        $crate::zz_private::milestone($name)
+    };
+}
+
+/// Marks the start of a new frame, for applications with a main loop like games.
+///
+/// Once two frames are marked, the report includes the [frame statistics](crate::FrameStats):
+/// the table gets a "Per frame" column with the average time of each scope per frame,
+/// followed by a "Frames" section with the frames per second, the mean and worst frame time, and the scopes of the slowest frame.
+///
+/// # Examples
+/// ```
+/// use profi::{new_frame, prof, print_on_exit};
+///
+/// fn main() {
+///   print_on_exit!();
+///   for frame in 0..10 {
+///     new_frame!();
+///     prof!(update);
+///     if frame == 5 {
+///       std::thread::sleep(std::time::Duration::from_millis(5));
+///     }
+///   }
+///   new_frame!();
+///   # #[cfg(all(feature = "enable", not(feature = "bounded-memory")))]
+///   # {
+///   let frames = profi::report().frames.unwrap();
+///   assert_eq!((frames.count, frames.worst_index), (10, 5));
+///   assert_eq!(frames.slowest[0].name, "update");
+///   # }
+/// }
+/// ```
+#[macro_export]
+macro_rules! new_frame {
+    () => {
+        $crate::zz_private::new_frame()
+    };
+}
+
+/// Pauses measuring all the scopes that are open on this thread, until [`resume!`] is called.
+///
+/// The paused time is subtracted from them, useful to exclude known noisy sections, like waiting for user input.
+/// Scopes opened or closed while paused are measured normally, and nested pauses are only resumed by the outermost `resume!`.
+///
+/// See [`PauseGuard`] to resume when a scope ends.
+///
+/// # Examples
+/// ```
+/// use profi::{prof, pause, resume};
+///
+/// fn main() {
+///   {
+///     prof!(setup);
+///     pause!();
+///     // Waiting for the user, not counted in `setup`
+///     std::thread::sleep(std::time::Duration::from_millis(50));
+///     resume!();
+///   }
+///   # #[cfg(feature = "enable")]
+///   assert!(profi::report().scope("setup").unwrap().total_real < std::time::Duration::from_millis(50));
+/// }
+/// ```
+#[macro_export]
+macro_rules! pause {
+    () => {
+        $crate::zz_private::pause()
+    };
+}
+
+/// Resumes measuring after [`pause!`].
+#[macro_export]
+macro_rules! resume {
+    () => {
+        $crate::zz_private::resume()
+    };
+}
+
+/// Profiles a future only while it's being polled.
+///
+/// Time spent suspended is not counted, and the measures are always recorded on the thread that polls the future, even if it moves between threads.
+/// All polls are merged into a single call, added when the future completes or is dropped, on the thread that does it.
+///
+/// Accepts a name like [`prof!`] before the future, if not provided the name of the enclosing function is used.
+///
+/// # Examples
+/// ```
+/// use profi::{prof_future, print_on_exit};
+///
+/// async fn fetch() -> u32 {
+///   42
+/// }
+///
+/// async fn run() {
+///   // Inferred name, `run`
+///   let a = prof_future!(fetch()).await;
+///   // Given name
+///   let b = prof_future!("fetch", fetch()).await;
+///   let c = prof_future!(fetch, fetch()).await;
+/// }
+/// ```
+///
+/// Polled on two threads:
+/// ```
+/// use std::{future::Future, pin::Pin, task::{Context, Poll, Waker}, time::Duration};
+///
+/// /// Works for 5ms in each of its three polls
+/// struct Steps(u32);
+///
+/// impl Future for Steps {
+///   type Output = ();
+///   fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+///     std::thread::sleep(Duration::from_millis(5));
+///     self.0 += 1;
+///     if self.0 == 3 { Poll::Ready(()) } else { Poll::Pending }
+///   }
+/// }
+///
+/// fn main() {
+///   let mut future = Box::pin(profi::prof_future!("steps", Steps(0)));
+///   let mut poll = || future.as_mut().poll(&mut Context::from_waker(Waker::noop()));
+///   assert!(poll().is_pending());
+///   std::thread::scope(|s| s.spawn(|| assert!(poll().is_pending())).join().unwrap());
+///   assert!(poll().is_ready());
+///   # #[cfg(feature = "enable")]
+///   # {
+///   let report = profi::report();
+///   let steps = report.scope("steps").unwrap();
+///   assert_eq!(steps.calls, 1);
+///   assert!(steps.total_real >= Duration::from_millis(15));
+///   assert_eq!(steps.total_cpu, steps.total_real);
+///   # }
+/// }
+/// ```
+#[macro_export]
+macro_rules! prof_future {
+    ($name:ident, $future:expr) => {
+        $crate::prof_future!($crate::prof_guard!(@explicit stringify!($name)), $future)
+    };
+    ($name:literal, $future:expr) => {
+        $crate::prof_future!($crate::prof_guard!(@explicit $name), $future)
+    };
+    ($name:expr, $future:expr) => {
+        $crate::zz_private::ScopeGuard::instrument($future, $name)
+    };
+    ($future:expr) => {
+        $crate::prof_future!(
+            {
+                fn f() {}
+                static NAME: $crate::zz_private::ScopeName = $crate::zz_private::ScopeName::new();
+                let name = $crate::zz_private::type_name_of(f);
+                // `3` is the length of the `::f`, async functions add a `::{{closure}}` suffix
+                NAME.inferred($crate::zz_private::strip_closures(&name[..name.len() - 3]))
+            },
+            $future
+        )
+    };
+}
+
+/// Gives the scope `name` a time budget, checked every time a report is generated, see [`set_budget`].
+///
+/// The budget is a duration like `4ms`, `250us` or `2s`, checked at compile time, or an expression returning a [`Duration`](std::time::Duration),
+/// optionally followed by what it limits: `average`, the time per call on average and the default, `call`, every call, or `total`.
+///
+/// Scopes over their budget are printed to stderr, highlighted in the table, and make `print_on_exit!(fail_on_budget)` exit with code 1.
+///
+/// # Examples
+/// ```
+/// use profi::{assert_budget, prof};
+///
+/// fn main() {
+///   assert_budget!("physics", 4ms);
+///   assert_budget!("render", 16ms, call);
+///   assert_budget!("startup", std::time::Duration::from_secs(2), total);
+///   {
+///     prof!(physics);
+///   }
+///   # #[cfg(feature = "enable")]
+///   assert!(profi::report().budget_violations().is_empty());
+/// }
+/// ```
+///
+/// Only `average`, `call` and `total` are accepted:
+/// ```compile_fail
+/// profi::assert_budget!("physics", 4ms, median);
+/// ```
+///
+/// And a misspelled duration is a compile error rather than a panic:
+/// ```compile_fail
+/// profi::assert_budget!("physics", 4msec);
+/// ```
+#[macro_export]
+macro_rules! assert_budget {
+    ($name:expr, $limit:literal $(, $kind:ident)?) => {
+        $crate::assert_budget!(@set $name, {
+            const LIMIT: ::std::time::Duration = $crate::zz_private::budgets::limit(stringify!($limit));
+            LIMIT
+        } $(, $kind)?)
+    };
+    ($name:expr, $limit:expr $(, $kind:ident)?) => {
+        $crate::assert_budget!(@set $name, $limit $(, $kind)?)
+    };
+    (@set $name:expr, $limit:expr) => {
+        $crate::assert_budget!(@set $name, $limit, average)
+    };
+    (@set $name:expr, $limit:expr, average) => {
+        $crate::set_budget($name, $crate::zz_private::budgets::average($limit))
+    };
+    (@set $name:expr, $limit:expr, call) => {
+        $crate::set_budget($name, $crate::zz_private::budgets::call($limit))
+    };
+    (@set $name:expr, $limit:expr, total) => {
+        $crate::set_budget($name, $crate::zz_private::budgets::total($limit))
+    };
+    (@set $name:expr, $limit:expr, $kind:ident) => {
+        compile_error!(concat!(
+            "The kind of budget must be `average`, `call` or `total`, found `",
+            stringify!($kind),
+            "`"
+        ))
+    };
 }
 
 /// Prints the profiled timings to stdout when `main` exits.
@@ -177,7 +1124,12 @@ macro_rules! prof_guard {
 ///
 /// Print to stderr instead with `print_on_exit!(stderr)`.
 ///
-/// Or print to a `std::io::Write` with `print_on_exit!(to = std::io::stdout())`
+/// Or print to a `std::io::Write` with `print_on_exit!(to = std::io::stdout())`,
+/// or send the report anywhere else with a [`ReportSink`].
+/// Add `fallback = stderr`, `fallback = stdout` or `fallback = <sink>` to send it there if the target fails, see [`Fallback`].
+/// Add `at_exit` to print it when the process exits instead, so the destructors that run after the end of `main` are profiled too, see [`finalize`].
+///
+/// Any number of `key = value` options can be added at the end, see [`PrintOptions`] for all of them.
 ///
 /// # Examples
 /// ```
@@ -209,25 +1161,105 @@ macro_rules! prof_guard {
 ///   // ...
 /// }
 /// ```
+///
+/// Print to stderr if the file can't be written, like when the disk is full:
+/// ```no_run
+/// use profi::{prof, print_on_exit};
+///
+/// fn main() {
+///   let file = std::fs::File::create("profi.txt").unwrap();
+///   print_on_exit!(to = file, fallback = stderr);
+///   // ...
+/// }
+/// ```
+///
+/// Print as collapsed stacks, ready to be converted into a flamegraph:
+/// ```
+/// use profi::{prof, print_on_exit};
+///
+/// fn main() {
+///   print_on_exit!(stderr, format = folded);
+///   // ...
+/// }
+/// ```
+///
+/// Only print the 20 scopes with the most CPU time, hiding the ones below 0.5% of the application time:
+/// ```
+/// use profi::{prof, print_on_exit};
+///
+/// fn main() {
+///   print_on_exit!(sort = cpu_time, limit = 20, min_percent = 0.5);
+///   // ...
+/// }
+/// ```
+///
+/// Only one can be used in each scope, a second one would print the results twice,
+/// so it fails with "the name `print_on_exit_can_only_be_used_once_per_scope` is defined multiple times":
+/// ```compile_fail,E0428
+/// use profi::print_on_exit;
+///
+/// fn main() {
+///   print_on_exit!();
+///   print_on_exit!(stderr);
+/// }
+/// ```
 #[allow(clippy::needless_doctest_main)]
 #[macro_export]
 macro_rules! print_on_exit {
     () => {
         $crate::print_on_exit!(stdout)
     };
-    (stdout) => {
-        $crate::print_on_exit!(to = std::io::stdout())
-    };
-    (stderr) => {
-        $crate::print_on_exit!(to = std::io::stderr())
+    (stdout $(, $($opts:tt)*)?) => {
+        $crate::print_on_exit!(to = std::io::stdout(), color = $crate::zz_private::is_color_terminal(&std::io::stdout()) $(, $($opts)*)?)
     };
-    (to = $to:expr) => {
-        $crate::print_on_exit!(to = $to, ondrop = |_| {})
+    (stderr $(, $($opts:tt)*)?) => {
+        $crate::print_on_exit!(to = std::io::stderr(), color = $crate::zz_private::is_color_terminal(&std::io::stderr()) $(, $($opts)*)?)
     };
-    (to = $to:expr, ondrop = $ondrop:expr) => {
-        let mut _to = $to;
-        let _guard = $crate::zz_private::ProfiDrop::new(&mut _to, $ondrop);
+    (to = $to:expr $(, $($opts:tt)*)?) => {
+        // Two in the same scope would print twice, this makes it a "defined multiple times" error that names the problem
+        #[allow(dead_code, non_camel_case_types)]
+        struct print_on_exit_can_only_be_used_once_per_scope;
+        let _guard = $crate::print_on_exit!(@build new, $to, |_| {}, $crate::PrintOptions::new(); $($($opts)*)?);
         // Implicit guard for profiling the whole application
         $crate::prof!()
     };
+    (@build $mode:ident, $to:expr, $ondrop:expr, $opts:expr; $(,)?) => {
+        $crate::zz_private::ProfiDrop::$mode($to, $ondrop, $opts)
+    };
+    (@build $mode:ident, $to:expr, $ondrop:expr, $opts:expr; at_exit $(, $($rest:tt)*)?) => {
+        $crate::print_on_exit!(@build at_exit, $to, $ondrop, $opts; $($($rest)*)?)
+    };
+    (@build $mode:ident, $to:expr, $ondrop:expr, $opts:expr; fallback = stdout $(, $($rest:tt)*)?) => {
+        $crate::print_on_exit!(@build $mode, $to, $ondrop, $opts; fallback = std::io::stdout() $(, $($rest)*)?)
+    };
+    (@build $mode:ident, $to:expr, $ondrop:expr, $opts:expr; fallback = stderr $(, $($rest:tt)*)?) => {
+        $crate::print_on_exit!(@build $mode, $to, $ondrop, $opts; fallback = std::io::stderr() $(, $($rest)*)?)
+    };
+    (@build $mode:ident, $to:expr, $ondrop:expr, $opts:expr; fallback = $fallback:expr $(, $($rest:tt)*)?) => {
+        $crate::print_on_exit!(@build $mode, $crate::Fallback::new($to, $fallback), $ondrop, $opts; $($($rest)*)?)
+    };
+    (@build $mode:ident, $to:expr, $_ondrop:expr, $opts:expr; ondrop = $ondrop:expr $(, $($rest:tt)*)?) => {
+        $crate::print_on_exit!(@build $mode, $to, $ondrop, $opts; $($($rest)*)?)
+    };
+    (@build $mode:ident, $to:expr, $ondrop:expr, $opts:expr; format = $format:ident $(, $($rest:tt)*)?) => {
+        $crate::print_on_exit!(@build $mode, $to, $ondrop, $opts.format($crate::zz_private::formats::$format); $($($rest)*)?)
+    };
+    (@build $mode:ident, $to:expr, $ondrop:expr, $opts:expr; sort = $sort:ident $(, $($rest:tt)*)?) => {
+        $crate::print_on_exit!(@build $mode, $to, $ondrop, $opts.sort($crate::zz_private::sorts::$sort); $($($rest)*)?)
+    };
+    (@build $mode:ident, $to:expr, $ondrop:expr, $opts:expr; hierarchy = $hierarchy:ident $(, $($rest:tt)*)?) => {
+        $crate::print_on_exit!(@build $mode, $to, $ondrop, $opts.hierarchy($crate::zz_private::hierarchies::$hierarchy); $($($rest)*)?)
+    };
+    (@build $mode:ident, $to:expr, $ondrop:expr, $opts:expr; time_unit = $unit:ident $(, $($rest:tt)*)?) => {
+        $crate::print_on_exit!(@build $mode, $to, $ondrop, $opts.time_unit($crate::zz_private::time_units::$unit); $($($rest)*)?)
+    };
+    (@build $mode:ident, $to:expr, $ondrop:expr, $opts:expr; $key:ident = $value:expr $(, $($rest:tt)*)?) => {
+        $crate::print_on_exit!(@build $mode, $to, $ondrop, $opts.$key($value); $($($rest)*)?)
+    };
+    (@build $mode:ident, $to:expr, $ondrop:expr, $opts:expr; $flag:ident $(, $($rest:tt)*)?) => {
+        $crate::print_on_exit!(@build $mode, $to, $ondrop, $opts.$flag(true); $($($rest)*)?)
+    };
+    ($($opts:tt)+) => {
+        $crate::print_on_exit!(stdout, $($opts)+)
+    };
 }
@@ -0,0 +1,158 @@
+//! Lifetimes of the objects wrapped in a [`Guard`](crate::Guard), reported apart from the scopes.
+
+#[cfg(feature = "enable")]
+use crate::Str;
+#[cfg(feature = "enable")]
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering::Relaxed};
+
+/// Totals of each name, never removed so the ones of the alive objects stay valid.
+#[cfg(feature = "enable")]
+static LIFETIMES: std::sync::Mutex<Vec<&'static Totals>> = std::sync::Mutex::new(Vec::new());
+
+#[cfg(feature = "enable")]
+thread_local! {
+    /// Totals of the names used by this thread, so creating an object doesn't lock `LIFETIMES`.
+    static NAMES: std::cell::RefCell<std::collections::HashMap<Str, &'static Totals>> = Default::default();
+}
+
+/// Objects created and dropped with the same name, updated from any thread.
+#[cfg(feature = "enable")]
+struct Totals {
+    name: Str,
+    /// If the name is accepted by the filter
+    filter: crate::filter::Cached,
+    created: AtomicUsize,
+    dropped: AtomicUsize,
+    alive: AtomicUsize,
+    max_alive: AtomicUsize,
+    /// When `max_alive` was first reached, in nanoseconds since the profiler started
+    max_alive_at: AtomicU64,
+    /// Sum of the lifetimes in nanoseconds
+    total: AtomicU64,
+    /// Longest lifetime in nanoseconds
+    max: AtomicU64,
+}
+
+#[cfg(feature = "enable")]
+impl Totals {
+    /// Totals of `name`, created the first time it's used.
+    fn get(name: Str) -> &'static Totals {
+        if let Ok(Some(totals)) = NAMES.try_with(|names| names.borrow().get(&name).copied()) {
+            return totals;
+        }
+        let totals = Totals::intern(name);
+        // Not cached if the thread is exiting
+        let _ = NAMES.try_with(|names| names.borrow_mut().insert(totals.name.clone(), totals));
+        totals
+    }
+
+    #[cold]
+    fn intern(name: Str) -> &'static Totals {
+        let mut lifetimes = LIFETIMES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(totals) = lifetimes.iter().find(|l| l.name == name) {
+            return totals;
+        }
+        let totals = Box::leak(Box::new(Totals {
+            name,
+            filter: crate::filter::Cached::new(),
+            created: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+            alive: AtomicUsize::new(0),
+            max_alive: AtomicUsize::new(0),
+            max_alive_at: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+        }));
+        lifetimes.push(totals);
+        totals
+    }
+}
+
+/// Nanoseconds from the start of the profiler until `time`.
+#[cfg(feature = "enable")]
+fn since_start(time: crate::clock::Instant) -> u64 {
+    let start = crate::measure::GLOBAL_PROFILER.start();
+    time.saturating_duration_since(start).as_nanos() as u64
+}
+
+/// Lifetime of an object, recorded when it's dropped.
+pub(crate) struct Lifetime(
+    /// Totals of its name and creation time, `None` if rejected by the filter
+    #[cfg(feature = "enable")]
+    Option<(&'static Totals, crate::clock::Instant)>,
+);
+
+impl Lifetime {
+    #[cfg(feature = "enable")]
+    pub(crate) fn start(name: Str) -> Self {
+        let totals = Totals::get(name);
+        if !totals.filter.is_enabled(&totals.name) {
+            return Self(None);
+        }
+        let now = crate::clock::Instant::now();
+        totals.created.fetch_add(1, Relaxed);
+        let alive = totals.alive.fetch_add(1, Relaxed) + 1;
+        if totals.max_alive.fetch_max(alive, Relaxed) < alive {
+            totals.max_alive_at.store(since_start(now), Relaxed);
+        }
+        Self(Some((totals, now)))
+    }
+
+    #[cfg(not(feature = "enable"))]
+    pub(crate) fn start() -> Self {
+        Self()
+    }
+}
+
+#[cfg(feature = "enable")]
+impl Drop for Lifetime {
+    fn drop(&mut self) {
+        let Some((totals, created)) = self.0 else {
+            return;
+        };
+        let lifetime = created.elapsed().as_nanos() as u64;
+        totals.dropped.fetch_add(1, Relaxed);
+        totals.alive.fetch_sub(1, Relaxed);
+        totals.total.fetch_add(lifetime, Relaxed);
+        totals.max.fetch_max(lifetime, Relaxed);
+    }
+}
+
+/// Lifetimes of the objects created since the start or the last reset, and the ones still alive.
+#[cfg(feature = "enable")]
+pub(crate) fn stats() -> Vec<crate::LifetimeStats> {
+    use std::time::Duration;
+
+    LIFETIMES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|l| crate::LifetimeStats {
+            name: l.name.to_string(),
+            created: l.created.load(Relaxed),
+            alive: l.alive.load(Relaxed),
+            average: match l.dropped.load(Relaxed) as u64 {
+                0 => Default::default(),
+                dropped => Duration::from_nanos(l.total.load(Relaxed) / dropped),
+            },
+            max: Duration::from_nanos(l.max.load(Relaxed)),
+            max_alive: l.max_alive.load(Relaxed),
+            max_alive_at: Duration::from_nanos(l.max_alive_at.load(Relaxed)),
+        })
+        .filter(|l| l.created > 0 || l.alive > 0)
+        .collect()
+}
+
+/// Discards the objects dropped until now, the alive ones are still counted.
+#[cfg(feature = "enable")]
+pub(crate) fn reset() {
+    let now = since_start(crate::clock::Instant::now());
+    for l in LIFETIMES.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        l.created.store(0, Relaxed);
+        l.dropped.store(0, Relaxed);
+        l.max_alive.store(l.alive.load(Relaxed), Relaxed);
+        l.max_alive_at.store(now, Relaxed);
+        l.total.store(0, Relaxed);
+        l.max.store(0, Relaxed);
+    }
+}
@@ -1,21 +1,122 @@
 #[allow(unused)]
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
 
 #[cfg(feature = "enable")]
-use crate::Str;
+use crate::{names::Name, Str};
 
 #[cfg(feature = "enable")]
 #[derive(Debug, Clone)]
 pub(crate) struct Measure {
     pub(crate) ty: MeasureType,
-    pub(crate) time: minstant::Instant,
+    pub(crate) time: crate::clock::Instant,
+    /// Core where the measure was taken, only recorded for `Start` and `Resume`.
+    #[cfg(feature = "cores")]
+    pub(crate) core: u32,
+    #[cfg(any(
+        feature = "context-switches",
+        feature = "page-faults",
+        feature = "perf"
+    ))]
+    pub(crate) usage: crate::rusage::Usage,
+}
+
+#[cfg(feature = "enable")]
+impl Measure {
+    /// End of the innermost open scope.
+    #[inline(always)]
+    pub(crate) fn end(time: crate::clock::Instant) -> Self {
+        Self {
+            time,
+            ty: MeasureType::End,
+            #[cfg(feature = "cores")]
+            core: u32::MAX,
+            #[cfg(any(
+                feature = "context-switches",
+                feature = "page-faults",
+                feature = "perf"
+            ))]
+            usage: crate::rusage::Usage::current(),
+        }
+    }
+
+    /// Resource usage of the thread when the measure was taken, zero if not recorded.
+    pub(crate) fn usage(&self) -> crate::rusage::Usage {
+        #[cfg(any(
+            feature = "context-switches",
+            feature = "page-faults",
+            feature = "perf"
+        ))]
+        {
+            self.usage
+        }
+        #[cfg(not(any(
+            feature = "context-switches",
+            feature = "page-faults",
+            feature = "perf"
+        )))]
+        Default::default()
+    }
 }
 
 #[cfg(feature = "enable")]
 #[derive(Debug, Clone)]
 pub(crate) enum MeasureType {
-    Start { name: Str },
+    Start {
+        name: Name,
+    },
+    /// Poll of a [`prof_future!`](crate::prof_future) that didn't complete it, only holds the scopes opened in it.
+    ///
+    /// Its time is added to the poll that completes the future, which may be in another thread.
+    Resume {
+        name: Name,
+    },
     End,
+    /// Adds `value` to the counter `name` of the innermost open scope.
+    Count {
+        name: crate::names::Id,
+        value: u64,
+    },
+    /// Sets the field `key` of the call of the innermost open scope to `value`, boxed to keep the measures small.
+    Field {
+        field: Box<(Name, Str)>,
+    },
+    /// Path of open scopes of another thread the outermost open scope was opened beneath, see `profi::rayon` and `profi::thread`.
+    Parent {
+        path: Box<[Name]>,
+    },
+    /// Slow call of the innermost open scope, boxed to keep the measures small.
+    Slow {
+        call: Box<crate::SlowCall>,
+    },
+    /// Time the open scopes spent in an intentional wait, like a `tokio` sleep.
+    Wait {
+        duration: std::time::Duration,
+    },
+    /// Measuring was paused for `duration` until this measure, which is subtracted from the open scopes.
+    Pause {
+        duration: std::time::Duration,
+    },
+    /// Extra calls merged into the call of the innermost open scope, see [`crate::set_coalescing`].
+    Coalesced {
+        calls: usize,
+    },
+    /// Time the call of the innermost open scope spent in earlier polls of its future, see [`MeasureType::Resume`].
+    Polled {
+        duration: std::time::Duration,
+    },
+    /// The call of the innermost open scope and its children stand for `every` calls, recorded with `sample = 1 / every`.
+    Sampled {
+        every: usize,
+    },
+    /// Closed scopes moved to the spill file at `offset`, see [`crate::set_memory_limit`].
+    Spilled {
+        offset: u64,
+        len: u64,
+    },
 }
 
 #[cfg(feature = "enable")]
@@ -23,15 +124,331 @@ pub(crate) enum MeasureType {
 pub(crate) struct GlobalProfiler {
     pub(crate) threads: std::sync::Mutex<usize>,
     pub(crate) cvar: std::sync::Condvar,
-    measures: std::sync::RwLock<Vec<(std::time::Duration, Vec<Measure>)>>,
+    measures: std::sync::RwLock<Vec<ExitedThread>>,
+    /// Measures of the threads that are still running, in creation order.
+    live: Mutex<Vec<Arc<SharedMeasures>>>,
+    /// When the first thread started profiling, along with the wall-clock time.
+    start: std::sync::OnceLock<(crate::clock::Instant, std::time::SystemTime)>,
+    /// When [`crate::begin_shutdown`] was called.
+    shutdown: std::sync::OnceLock<crate::clock::Instant>,
+    milestones: Mutex<Vec<crate::Milestone>>,
+    /// Start of each frame marked with [`crate::new_frame!`].
+    frames: Mutex<Vec<crate::clock::Instant>>,
 }
 
 #[cfg(feature = "enable")]
 #[derive(Debug)]
-pub(crate) struct ThreadProfiler {
+struct ExitedThread {
+    /// When the thread started profiling.
+    started: crate::clock::Instant,
+    /// `None` if the thread is still running.
+    exited: Option<crate::clock::Instant>,
+    name: Option<String>,
     measures: Vec<Measure>,
-    thread_start: minstant::Instant,
+    folded: crate::process::Folded,
+    samplers: Vec<crate::sampling::Sampler>,
+    spill: Option<Arc<crate::spill::Spill>>,
+}
+
+#[cfg(feature = "enable")]
+impl ExitedThread {
+    /// Measures of the thread with the spilled ones read back.
+    fn measures(&self) -> std::borrow::Cow<'_, [Measure]> {
+        crate::spill::expand(&self.measures, self.spill.as_deref())
+    }
+}
+
+/// Measures of a running thread, shared with [`GlobalProfiler`] so reports can be taken at any time.
+#[cfg(feature = "enable")]
+#[derive(Debug)]
+pub(crate) struct SharedMeasures {
+    /// Order in which the thread started profiling.
+    pub(crate) id: usize,
+    pub(crate) name: Option<String>,
+    /// When the thread started profiling.
+    started: crate::clock::Instant,
+    measures: MeasureLog,
+    /// Closed scopes with the `bounded-memory` feature or past [`crate::Retention::max_measures`],
+    /// always locked after `measures`.
+    pub(crate) folded: Mutex<crate::process::Folded>,
+    /// Only used if [`crate::set_overhead_budget`] is enabled.
+    samplers: Mutex<Vec<crate::sampling::Sampler>>,
+    /// Created when the measures first exceed [`crate::set_memory_limit`], `None` if it failed.
+    pub(crate) spill: std::sync::OnceLock<Option<Arc<crate::spill::Spill>>>,
+}
+
+/// Number of measures in each chunk of a [`MeasureLog`].
+#[cfg(feature = "enable")]
+const CHUNK: usize = 4096;
+
+/// Measures of a thread, which only its [`ThreadProfiler`] writes.
+///
+/// The owner appends to the end of the last chunk without locking, and publishes the new length once the measure is complete,
+/// so readers can copy the measures up to it at any time.
+/// When the last chunk is full a new one is started, so the measures are never moved while appending.
+/// Everything else that changes them, like renaming a scope or folding the closed ones,
+/// is done while holding `lock`, which readers hold while copying.
+#[cfg(feature = "enable")]
+pub(crate) struct MeasureLog {
+    /// Reset requested by another thread, as the published length and the time of the reset.
+    ///
+    /// Applied by the owner the next time it takes the lock, and by readers to their copy until then.
+    lock: Mutex<Option<(usize, crate::clock::Instant)>>,
+    /// If there's a reset in `lock`, checked by the owner before appending.
+    reset: std::sync::atomic::AtomicBool,
+    /// Full chunks before `measures`, only changed while holding `lock`.
+    chunks: std::cell::UnsafeCell<Vec<Vec<Measure>>>,
+    /// Number of measures in `chunks`.
+    chunked: std::sync::atomic::AtomicUsize,
+    /// Last chunk, where the measures are appended.
+    measures: std::cell::UnsafeCell<Vec<Measure>>,
+    /// Buffer of `measures`, only changed while holding `lock`.
+    ptr: std::sync::atomic::AtomicPtr<Measure>,
+    /// Number of measures of the last chunk readers can see.
+    len: std::sync::atomic::AtomicUsize,
+}
+
+// SAFETY: `chunks` and `measures` are only written by their owner, readers only access the published measures while holding `lock`,
+// and the owner never changes those without holding it.
+#[cfg(feature = "enable")]
+unsafe impl Sync for MeasureLog {}
+
+#[cfg(feature = "enable")]
+impl MeasureLog {
+    fn new() -> Self {
+        let mut measures = Vec::with_capacity(CHUNK);
+        Self {
+            lock: Mutex::new(None),
+            reset: std::sync::atomic::AtomicBool::new(false),
+            chunks: std::cell::UnsafeCell::new(Vec::new()),
+            chunked: std::sync::atomic::AtomicUsize::new(0),
+            ptr: std::sync::atomic::AtomicPtr::new(measures.as_mut_ptr()),
+            len: std::sync::atomic::AtomicUsize::new(0),
+            measures: std::cell::UnsafeCell::new(measures),
+        }
+    }
+
+    /// Copies the published measures, calling `then` before other threads can change them.
+    pub(crate) fn snapshot<R>(&self, then: impl FnOnce() -> R) -> (Vec<Measure>, R) {
+        let reset = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        // SAFETY: the chunks only change while holding the lock
+        let chunks = unsafe { &*self.chunks.get() };
+        let ptr = self.ptr.load(std::sync::atomic::Ordering::Relaxed);
+        let len = self.len.load(std::sync::atomic::Ordering::Acquire);
+        // SAFETY: the buffer only changes while holding the lock, and the owner never touches the first `len` measures without it
+        let last = unsafe { std::slice::from_raw_parts(ptr, len) };
+        let chunked = self.chunked.load(std::sync::atomic::Ordering::Relaxed);
+        let mut measures = Vec::with_capacity(chunked + len);
+        chunks
+            .iter()
+            .for_each(|chunk| measures.extend_from_slice(chunk));
+        measures.extend_from_slice(last);
+        if let Some(reset) = *reset {
+            apply_reset(&mut measures, reset);
+        }
+        (measures, then())
+    }
+
+    /// Discards the measures published until now, calling `then` before other threads can see them.
+    fn reset<R>(&self, now: crate::clock::Instant, then: impl FnOnce() -> R) -> R {
+        let mut reset = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let len = self.chunked.load(std::sync::atomic::Ordering::Relaxed)
+            + self.len.load(std::sync::atomic::Ordering::Acquire);
+        *reset = Some((len, now));
+        self.reset.store(true, std::sync::atomic::Ordering::Relaxed);
+        then()
+    }
+
+    /// Measures of the thread.
+    ///
+    /// # Safety
+    /// Only the owner can call it, and it can't change the measures while the view is alive.
+    unsafe fn owned(&self) -> Measures<'_> {
+        unsafe {
+            Measures {
+                chunks: &*self.chunks.get(),
+                chunked: self.chunked.load(std::sync::atomic::Ordering::Relaxed),
+                last: &*self.measures.get(),
+            }
+        }
+    }
+
+    /// Appends `measure`, which readers can only see once `finish` returns.
+    ///
+    /// # Safety
+    /// Only the owner can call it.
+    #[inline(always)]
+    unsafe fn push(&self, measure: Measure, finish: impl FnOnce(&mut Measure)) {
+        let full = {
+            let measures = unsafe { &*self.measures.get() };
+            measures.len() == measures.capacity()
+        };
+        if full || self.reset.load(std::sync::atomic::Ordering::Relaxed) {
+            unsafe { self.make_room() };
+        }
+        let measures = unsafe { &mut *self.measures.get() };
+        // Never reallocates, readers may be copying the buffer
+        measures.push(measure);
+        finish(measures.last_mut().unwrap());
+        self.len
+            .store(measures.len(), std::sync::atomic::Ordering::Release);
+    }
+
+    /// Applies a pending reset, and starts a new chunk if the last one is full.
+    ///
+    /// # Safety
+    /// Only the owner can call it.
+    #[cold]
+    unsafe fn make_room(&self) {
+        let mut reset = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let (chunks, measures) = unsafe { (&mut *self.chunks.get(), &mut *self.measures.get()) };
+        if let Some(pending) = reset.take() {
+            self.reset
+                .store(false, std::sync::atomic::Ordering::Relaxed);
+            self.merge(chunks, measures);
+            apply_reset(measures, pending);
+        }
+        if measures.len() == measures.capacity() {
+            self.chunked
+                .fetch_add(measures.len(), std::sync::atomic::Ordering::Relaxed);
+            chunks.push(std::mem::replace(measures, Vec::with_capacity(CHUNK)));
+        }
+        self.publish(measures);
+    }
+
+    /// Publishes the buffer and length of the last chunk, must hold the lock.
+    fn publish(&self, measures: &mut Vec<Measure>) {
+        self.ptr
+            .store(measures.as_mut_ptr(), std::sync::atomic::Ordering::Relaxed);
+        self.len
+            .store(measures.len(), std::sync::atomic::Ordering::Release);
+    }
+
+    /// Moves the measures of `chunks` in front of the ones of the last chunk, must hold the lock.
+    ///
+    /// Leaves room for as many measures as there are, so merging after every new chunk takes the same time as growing a `Vec`.
+    fn merge(&self, chunks: &mut Vec<Vec<Measure>>, measures: &mut Vec<Measure>) {
+        if chunks.is_empty() {
+            return;
+        }
+        let len = self.chunked.swap(0, std::sync::atomic::Ordering::Relaxed) + measures.len();
+        let mut merged = Vec::with_capacity((len * 2).max(CHUNK));
+        chunks.drain(..).for_each(|chunk| merged.extend(chunk));
+        merged.append(measures);
+        *measures = merged;
+    }
+
+    /// Locks out readers to change the measures, merging the chunks into one.
+    ///
+    /// # Safety
+    /// Only the owner can call it, and only once at a time.
+    unsafe fn lock(&self) -> MeasuresGuard<'_> {
+        let mut reset = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let (chunks, measures) = unsafe { (&mut *self.chunks.get(), &mut *self.measures.get()) };
+        self.merge(chunks, measures);
+        if let Some(pending) = reset.take() {
+            self.reset
+                .store(false, std::sync::atomic::Ordering::Relaxed);
+            apply_reset(measures, pending);
+        }
+        MeasuresGuard {
+            log: self,
+            _lock: reset,
+        }
+    }
+}
+
+#[cfg(feature = "enable")]
+impl std::fmt::Debug for MeasureLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.snapshot(|| ()).0).finish()
+    }
+}
+
+/// Measures of a [`MeasureLog`] seen by its owner, in order across the chunks.
+#[cfg(feature = "enable")]
+#[derive(Clone, Copy)]
+pub(crate) struct Measures<'a> {
+    chunks: &'a [Vec<Measure>],
+    /// Number of measures in `chunks`.
+    chunked: usize,
+    last: &'a [Measure],
+}
+
+#[cfg(feature = "enable")]
+impl<'a> Measures<'a> {
+    pub(crate) fn len(self) -> usize {
+        self.chunked + self.last.len()
+    }
+
+    pub(crate) fn is_empty(self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn iter(self) -> impl DoubleEndedIterator<Item = &'a Measure> {
+        self.chunks.iter().flatten().chain(self.last)
+    }
+}
+
+/// Exclusive access to the measures of a [`MeasureLog`], publishes them when dropped.
+#[cfg(feature = "enable")]
+struct MeasuresGuard<'a> {
+    log: &'a MeasureLog,
+    _lock: std::sync::MutexGuard<'a, Option<(usize, crate::clock::Instant)>>,
+}
+
+#[cfg(feature = "enable")]
+impl std::ops::Deref for MeasuresGuard<'_> {
+    type Target = Vec<Measure>;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the owner holds the lock
+        unsafe { &*self.log.measures.get() }
+    }
+}
+
+#[cfg(feature = "enable")]
+impl std::ops::DerefMut for MeasuresGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: the owner holds the lock
+        unsafe { &mut *self.log.measures.get() }
+    }
+}
+
+#[cfg(feature = "enable")]
+impl Drop for MeasuresGuard<'_> {
+    fn drop(&mut self) {
+        // SAFETY: the owner holds the lock
+        let measures = unsafe { &mut *self.log.measures.get() };
+        self.log.publish(measures);
+    }
+}
+
+/// Discards the measures before the `len` published when [`GlobalProfiler::reset`] was called, except the open scopes.
+#[cfg(feature = "enable")]
+fn apply_reset(measures: &mut Vec<Measure>, (len, now): (usize, crate::clock::Instant)) {
+    let after = measures.split_off(len.min(measures.len()));
+    retain_open(measures, now);
+    measures.extend(after);
+}
+
+#[cfg(feature = "enable")]
+#[derive(Debug)]
+pub(crate) struct ThreadProfiler {
+    shared: Arc<SharedMeasures>,
+    /// Index of each scope in `shared.samplers`.
+    sampler_ids: std::collections::HashMap<Name, usize>,
+    /// Number of open scopes.
+    depth: usize,
+    thread_start: crate::clock::Instant,
     thread_time: Option<std::time::Duration>,
+    /// When measuring was paused, and the number of nested pauses.
+    paused: Option<(crate::clock::Instant, usize)>,
+    /// If a scope was closed by a panic.
+    unwound: bool,
+    /// If the thread is no longer counted in [`GlobalProfiler::threads`].
+    detached: bool,
+    pub(crate) clock: crate::clock::Source,
 }
 
 #[cfg(feature = "enable")]
@@ -47,61 +464,812 @@ impl GlobalProfiler {
     const fn new() -> Self {
         Self {
             measures: std::sync::RwLock::new(Vec::new()),
+            live: Mutex::new(Vec::new()),
+            start: std::sync::OnceLock::new(),
+            shutdown: std::sync::OnceLock::new(),
+            milestones: Mutex::new(Vec::new()),
+            frames: Mutex::new(Vec::new()),
             threads: std::sync::Mutex::new(0),
             cvar: std::sync::Condvar::new(),
         }
     }
 
-    pub(crate) fn print_timings(&self, to: impl std::io::Write) -> std::io::Result<()> {
-        crate::process::print_timings(self.measures.read().unwrap().as_slice(), to)
+    /// Copies the measures of the threads that are still running, and locks the ones of the exited threads.
+    ///
+    /// Both are taken while holding the `live` lock, so a thread exiting in between isn't counted twice.
+    fn threads(
+        &self,
+    ) -> (
+        Vec<ExitedThread>,
+        std::sync::RwLockReadGuard<'_, Vec<ExitedThread>>,
+    ) {
+        let live = self.live.lock().unwrap_or_else(|e| e.into_inner());
+        let copies = live
+            .iter()
+            .map(|t| {
+                let (measures, folded) = t
+                    .measures
+                    .snapshot(|| t.folded.lock().unwrap_or_else(|e| e.into_inner()).clone());
+                ExitedThread {
+                    started: t.started,
+                    exited: None,
+                    name: t.name.clone(),
+                    measures,
+                    folded,
+                    samplers: t.samplers.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+                    spill: t.spill.get().cloned().flatten(),
+                }
+            })
+            .filter(|t| !t.measures.is_empty() || !t.folded.is_empty())
+            .collect();
+        (
+            copies,
+            self.measures.read().unwrap_or_else(|e| e.into_inner()),
+        )
+    }
+
+    /// Collects the measures of all running and exited threads.
+    pub(crate) fn report(&self) -> crate::Report {
+        let (live, threads) = self.threads();
+        // Scopes that are still open are measured until the copies were taken
+        let now = crate::clock::Instant::now();
+        let threads = live.iter().chain(threads.iter());
+        let measures = threads
+            .clone()
+            .map(ExitedThread::measures)
+            .collect::<Vec<_>>();
+        let mut report = crate::process::into_report(
+            threads
+                .clone()
+                .zip(&measures)
+                .map(|(t, measures)| (t.name.as_deref(), &t.folded, &measures[..])),
+            now,
+        );
+        report.frames = crate::process::frame_stats(
+            &self.frames.lock().unwrap_or_else(|e| e.into_inner()),
+            threads
+                .clone()
+                .zip(&measures)
+                .map(|(t, measures)| (t.name.as_deref(), &measures[..])),
+        );
+        let start = self.start();
+        for ((stats, thread), measures) in report
+            .threads
+            .iter_mut()
+            .zip(threads.clone())
+            .zip(&measures)
+        {
+            stats.started = thread.started.saturating_duration_since(start);
+            stats.exited = thread.exited.map(|t| t.saturating_duration_since(start));
+            // The folded scopes have no times
+            stats.longest_idle = thread.folded.is_empty().then(|| {
+                crate::process::longest_idle(measures, thread.started, thread.exited.unwrap_or(now))
+            });
+        }
+        report.set_sampling(threads.flat_map(|t| &t.samplers));
+        report.milestones = self
+            .milestones
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        report.tail_events = crate::tail::events();
+        report.lifetimes = crate::lifetimes::stats();
+        report.shutdown = self
+            .shutdown()
+            .map(|time| time.saturating_duration_since(self.start()));
+        if let Some(&(start, started)) = self.start.get() {
+            report.started = Some(started);
+            report.wall_time = start.elapsed();
+        }
+        report.noise = crate::noise::get();
+        #[cfg(all(feature = "energy", target_os = "linux"))]
+        if let Some(energy) = crate::energy::consumed() {
+            report.set_energy(energy);
+        }
+        crate::budgets::check(&report);
+        report
+    }
+
+    pub(crate) fn print_timings(
+        &self,
+        to: impl std::io::Write,
+        options: &crate::PrintOptions,
+    ) -> std::io::Result<()> {
+        let report = self.report();
+        crate::Error::unbalanced(&report).for_each(crate::error::handle);
+        crate::process::print_timings(&report, to, options)
+    }
+
+    pub(crate) fn print_folded(&self, to: impl std::io::Write) -> std::io::Result<()> {
+        crate::export::write_folded(&self.report(), to)
+    }
+
+    pub(crate) fn print_speedscope(&self, to: impl std::io::Write) -> std::io::Result<()> {
+        let (live, threads) = self.threads();
+        let threads = live.iter().chain(threads.iter()).collect::<Vec<_>>();
+        let measures = threads.iter().map(|t| t.measures()).collect::<Vec<_>>();
+        let threads = threads
+            .iter()
+            .zip(&measures)
+            .map(|(t, measures)| (t.name.as_deref(), (t.started, t.exited), &measures[..]));
+        crate::export::write_speedscope(threads, to)
+    }
+
+    /// Measures of every thread as a [binary trace](crate::trace).
+    pub(crate) fn trace(&self) -> Vec<u8> {
+        let (live, threads) = self.threads();
+        let threads = live.iter().chain(threads.iter()).collect::<Vec<_>>();
+        let measures = threads.iter().map(|t| t.measures()).collect::<Vec<_>>();
+        crate::trace::encode(
+            threads
+                .iter()
+                .zip(&measures)
+                .map(|(t, measures)| (t.name.as_deref(), &measures[..])),
+        )
+    }
+
+    /// Discards all the measures taken until now.
+    ///
+    /// Scopes that are still open are kept, and only the time after the reset is measured for them.
+    pub(crate) fn reset(&self) {
+        let now = crate::clock::Instant::now();
+        for thread in self.live.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+            // The thread applies it to its measures the next time it changes them
+            thread.measures.reset(now, || {
+                if let Some(Some(spill)) = thread.spill.get() {
+                    spill.clear();
+                }
+                *thread.folded.lock().unwrap_or_else(|e| e.into_inner()) = Default::default();
+            });
+            thread
+                .samplers
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter_mut()
+                .for_each(crate::sampling::Sampler::reset);
+        }
+        self.measures
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        self.milestones
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        crate::tail::reset();
+        crate::lifetimes::reset();
+        // The current frame continues after the reset
+        let mut frames = self.frames.lock().unwrap_or_else(|e| e.into_inner());
+        if !frames.is_empty() {
+            *frames = vec![now];
+        }
+    }
+
+    pub(crate) fn start(&self) -> crate::clock::Instant {
+        self.start
+            .get_or_init(|| {
+                // There are no environment variables nor process ids in the browser
+                if cfg!(not(all(feature = "wasm", target_arch = "wasm32"))) {
+                    crate::registry::register();
+                }
+                (crate::clock::Instant::now(), crate::clock::system_time())
+            })
+            .0
+    }
+
+    pub(crate) fn milestone(&self, name: Str, time: crate::clock::Instant) {
+        let milestone = crate::Milestone {
+            name: name.into_owned(),
+            elapsed: time.saturating_duration_since(self.start()),
+            thread: std::thread::current().name().map(String::from),
+        };
+        self.milestones
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(milestone);
+    }
+
+    pub(crate) fn new_frame(&self, time: crate::clock::Instant) {
+        self.start();
+        self.frames
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(time);
+    }
+
+    /// Marks the beginning of the shutdown phase, only the first call has effect.
+    pub(crate) fn begin_shutdown(&self, time: crate::clock::Instant) {
+        if self.shutdown.set(time).is_ok() {
+            self.milestone("shutdown".into(), time);
+        }
+    }
+
+    pub(crate) fn shutdown(&self) -> Option<crate::clock::Instant> {
+        self.shutdown.get().copied()
+    }
+}
+
+/// Removes all the measures of closed scopes, and restarts the open ones at `now`.
+#[cfg(feature = "enable")]
+fn retain_open(measures: &mut Vec<Measure>, now: crate::clock::Instant) {
+    let mut open = Vec::new();
+    for m in measures.drain(..) {
+        match m.ty {
+            MeasureType::Start { .. }
+            | MeasureType::Resume { .. }
+            | MeasureType::Field { .. }
+            | MeasureType::Parent { .. }
+            | MeasureType::Sampled { .. } => open.push(m),
+            // Along with the fields and parent of the scope
+            MeasureType::End => {
+                while let Some(m) = open.pop() {
+                    if matches!(m.ty, MeasureType::Start { .. } | MeasureType::Resume { .. }) {
+                        break;
+                    }
+                }
+            }
+            MeasureType::Count { .. }
+            | MeasureType::Slow { .. }
+            | MeasureType::Wait { .. }
+            | MeasureType::Pause { .. }
+            | MeasureType::Coalesced { .. }
+            | MeasureType::Polled { .. }
+            | MeasureType::Spilled { .. } => {}
+        }
+    }
+    for m in &mut open {
+        m.time = now;
+    }
+    *measures = open;
+}
+
+/// Index in `measures` of the `Start` or `Resume` of the open scope at `depth`, where `current` is the depth of the innermost one.
+#[cfg(feature = "enable")]
+fn open_scope(measures: &[Measure], mut current: usize, depth: usize) -> Option<usize> {
+    // Skip the scopes that are already closed
+    let mut closed = 0usize;
+    for (idx, m) in measures.iter().enumerate().rev() {
+        match m.ty {
+            MeasureType::End => closed += 1,
+            MeasureType::Start { .. } | MeasureType::Resume { .. } if closed > 0 => closed -= 1,
+            MeasureType::Start { .. } | MeasureType::Resume { .. } => {
+                if current == depth {
+                    return Some(idx);
+                }
+                current -= 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Renames the innermost open scope to `name (unwound)`, since it's being closed by a panic.
+///
+/// Keeps the partial calls apart from the ones that finished.
+#[cold]
+#[cfg(feature = "enable")]
+fn mark_unwound(measures: &mut [Measure]) {
+    let Some(idx) = open_scope(measures, 0, 0) else {
+        return;
+    };
+    if let MeasureType::Start { name } | MeasureType::Resume { name } = &mut measures[idx].ty {
+        *name = Name::new(format!("{name} (unwound)"));
     }
 }
 
 #[cfg(feature = "enable")]
 impl ThreadProfiler {
     pub(crate) fn new() -> Self {
-        *GLOBAL_PROFILER.threads.lock().unwrap() += 1;
+        #[cfg(all(feature = "energy", target_os = "linux"))]
+        crate::energy::start();
+        GLOBAL_PROFILER.start();
+        *GLOBAL_PROFILER
+            .threads
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) += 1;
+        static NEXT_ID: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+        let clock = crate::clock::Source::get();
+        let thread_start = clock.now();
+        let shared = Arc::new(SharedMeasures {
+            id: NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+            name: std::thread::current().name().map(String::from),
+            started: thread_start,
+            measures: MeasureLog::new(),
+            folded: Default::default(),
+            samplers: Mutex::new(Vec::new()),
+            spill: std::sync::OnceLock::new(),
+        });
+        GLOBAL_PROFILER
+            .live
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(shared.clone());
         Self {
-            measures: Vec::with_capacity(4096),
-            thread_start: minstant::Instant::now(),
+            shared,
+            sampler_ids: Default::default(),
+            depth: 0,
+            thread_start,
             thread_time: None,
+            paused: None,
+            unwound: false,
+            detached: false,
+            clock,
         }
     }
 
-    pub(crate) fn push(&mut self, name: Str) {
-        self.measures.push(Measure {
-            time: minstant::Instant::ZERO,
+    /// Measures of the thread, see [`MeasureLog::owned`].
+    #[inline(always)]
+    fn measures(&self) -> Measures<'_> {
+        // SAFETY: the thread profiler is the owner of its measures
+        unsafe { self.shared.measures.owned() }
+    }
+
+    /// Appends `measure` to the measures of the thread, see [`MeasureLog::push`].
+    #[inline(always)]
+    fn append(&mut self, measure: Measure, finish: impl FnOnce(&mut Measure)) {
+        // SAFETY: the thread profiler is the owner of its measures
+        unsafe { self.shared.measures.push(measure, finish) }
+    }
+
+    /// Locks out the reports to change the measures of the thread, see [`MeasureLog::lock`].
+    fn lock(&self) -> MeasuresGuard<'_> {
+        // SAFETY: the thread profiler is the owner of its measures, and `&self` can't be used to append while it's alive
+        unsafe { self.shared.measures.lock() }
+    }
+
+    /// Opens the scope `name`, returns its depth.
+    pub(crate) fn push(&mut self, name: Name) -> usize {
+        self.depth += 1;
+        let measure = Measure {
+            time: crate::clock::Instant::ZERO,
             ty: MeasureType::Start { name },
-        });
+            #[cfg(feature = "cores")]
+            core: crate::cores::current(),
+            #[cfg(any(
+                feature = "context-switches",
+                feature = "page-faults",
+                feature = "perf"
+            ))]
+            usage: crate::rusage::Usage::current(),
+        };
         // Do the measure as late as possible
-        let measure = self.measures.last_mut().unwrap();
-        measure.time = minstant::Instant::now();
+        let clock = self.clock;
+        self.append(measure, |m| m.time = clock.now());
+        self.depth
     }
 
-    pub(crate) fn pop(&mut self, time: minstant::Instant) {
-        self.measures.push(Measure {
+    /// Opens the scope `name` at `time` instead of now, returns its depth.
+    pub(crate) fn push_at(&mut self, name: Name, time: crate::clock::Instant) -> usize {
+        self.depth += 1;
+        let measure = Measure {
             time,
-            ty: MeasureType::End,
-        })
+            ty: MeasureType::Start { name },
+            #[cfg(feature = "cores")]
+            core: crate::cores::current(),
+            #[cfg(any(
+                feature = "context-switches",
+                feature = "page-faults",
+                feature = "perf"
+            ))]
+            usage: crate::rusage::Usage::current(),
+        };
+        self.append(measure, |_| {});
+        self.depth
+    }
+
+    /// Renames the open scope at `depth` with the result of `rename`.
+    pub(crate) fn rename(&mut self, depth: usize, rename: impl FnOnce(&str) -> Str) {
+        let mut measures = self.lock();
+        let Some(idx) = open_scope(&measures, self.depth, depth) else {
+            return;
+        };
+        if let MeasureType::Start { name } | MeasureType::Resume { name } = &mut measures[idx].ty {
+            *name = Name::new(rename(name));
+        }
+    }
+
+    /// Sets the field `key` of the current call of the open scope at `depth` to `value`.
+    pub(crate) fn field(&mut self, depth: usize, key: Name, value: Str) {
+        self.annotate_open(
+            depth,
+            MeasureType::Field {
+                field: Box::new((key, value)),
+            },
+        );
+    }
+
+    /// Attaches the open scope at `depth` beneath `path`, the open scopes of another thread.
+    ///
+    /// Only applies to the outermost scope, the others are already beneath it.
+    pub(crate) fn parent(&mut self, depth: usize, path: Box<[Name]>) {
+        if depth == 1 && !path.is_empty() {
+            self.annotate_open(depth, MeasureType::Parent { path });
+        }
+    }
+
+    /// Names of the open scopes from the outermost one, preceded by the path it was opened beneath.
+    pub(crate) fn path(&self) -> Vec<Name> {
+        let measures = self.measures();
+        let mut path = Vec::with_capacity(self.depth);
+        let mut parent = None;
+        let mut closed = 0usize;
+        for m in measures.iter().rev() {
+            if path.len() == self.depth {
+                break;
+            }
+            match &m.ty {
+                MeasureType::End => closed += 1,
+                MeasureType::Start { .. } | MeasureType::Resume { .. } if closed > 0 => closed -= 1,
+                MeasureType::Start { name } | MeasureType::Resume { name } => {
+                    path.push(name.clone())
+                }
+                MeasureType::Parent { path } if closed == 0 => parent = Some(path),
+                _ => {}
+            }
+        }
+        path.extend(parent.into_iter().flat_map(|p| p.iter().rev().cloned()));
+        path.reverse();
+        path
+    }
+
+    /// Marks the call of the open scope at `depth` as one of every `every` calls, see [`MeasureType::Sampled`].
+    pub(crate) fn sampled(&mut self, depth: usize, every: usize) {
+        self.annotate_open(depth, MeasureType::Sampled { every });
+    }
+
+    /// Turns the open poll at `depth` into the call of its future, which spent `polled` in the earlier polls.
+    pub(crate) fn complete(&mut self, depth: usize, polled: std::time::Duration) {
+        {
+            let mut measures = self.lock();
+            let Some(idx) = open_scope(&measures, self.depth, depth) else {
+                return;
+            };
+            if let MeasureType::Resume { name } = &measures[idx].ty {
+                measures[idx].ty = MeasureType::Start { name: name.clone() };
+            }
+        }
+        if !polled.is_zero() {
+            self.annotate_open(depth, MeasureType::Polled { duration: polled });
+        }
+    }
+
+    /// Inserts `ty` right after the start of the open scope at `depth`, so it isn't applied to one of its open children.
+    fn annotate_open(&mut self, depth: usize, ty: MeasureType) {
+        let mut measures = self.lock();
+        let Some(idx) = open_scope(&measures, self.depth, depth) else {
+            return;
+        };
+        measures.insert(
+            idx + 1,
+            Measure {
+                time: crate::clock::Instant::ZERO,
+                ty,
+                #[cfg(feature = "cores")]
+                core: u32::MAX,
+                #[cfg(any(
+                    feature = "context-switches",
+                    feature = "page-faults",
+                    feature = "perf"
+                ))]
+                usage: Default::default(),
+            },
+        );
+    }
+
+    /// Opens a poll of the future `name`, returns its depth.
+    pub(crate) fn resume(&mut self, name: Name) -> usize {
+        self.depth += 1;
+        let measure = Measure {
+            time: crate::clock::Instant::ZERO,
+            ty: MeasureType::Resume { name },
+            #[cfg(feature = "cores")]
+            core: crate::cores::current(),
+            #[cfg(any(
+                feature = "context-switches",
+                feature = "page-faults",
+                feature = "perf"
+            ))]
+            usage: crate::rusage::Usage::current(),
+        };
+        // Do the measure as late as possible
+        let clock = self.clock;
+        self.append(measure, |m| m.time = clock.now());
+        self.depth
+    }
+
+    pub(crate) fn pop(&mut self, time: crate::clock::Instant) {
+        let end = Measure::end(time);
+        self.depth = self.depth.saturating_sub(1);
+        if std::thread::panicking() {
+            mark_unwound(&mut self.lock());
+            self.unwound = true;
+        }
+        if !cfg!(feature = "bounded-memory") && !crate::hooks::any() {
+            return self.append(end, |_| {});
+        }
+        crate::stream::closed(&self.shared, self.measures(), self.depth, time);
+        crate::tail::closed(&self.shared, self.measures(), time);
+        #[cfg(feature = "log")]
+        crate::slow_log::closed(self.measures(), time);
+        if cfg!(feature = "bounded-memory") {
+            // Fold the scope right away, so only the open ones are stored
+            let mut measures = self.lock();
+            let mut folded = self.shared.folded.lock().unwrap_or_else(|e| e.into_inner());
+            folded.fold_last(&mut measures, &end);
+            crate::retention::evict_folded(&mut folded);
+        } else {
+            if crate::coalesce::is_enabled() {
+                let mut measures = self.lock();
+                if !crate::coalesce::coalesce(&mut measures, &end) {
+                    measures.push(end)
+                }
+            } else {
+                self.append(end, |_| {})
+            }
+            self.check_limits();
+        }
+    }
+
+    /// Folds or spills the closed scopes if the measures exceed the limits.
+    #[inline(always)]
+    fn check_limits(&self) {
+        if crate::retention::exceeded(self.measures().len()) {
+            crate::retention::fold(&self.shared, &mut self.lock());
+        }
+        if crate::spill::exceeded(self.measures().len()) {
+            crate::spill::spill(&self.shared, &mut self.lock());
+        }
+    }
+
+    /// Records each of `durations` as a call of the child scope `name`, one after the other and ending now.
+    pub(crate) fn record_many(&mut self, name: Name, durations: &[std::time::Duration]) {
+        let now = crate::clock::Instant::now();
+        let total = durations.iter().sum();
+        let mut time = now.checked_sub(total).unwrap_or(now);
+        self.record_calls(
+            name,
+            durations.iter().map(|&duration| {
+                let start = time;
+                time = time.checked_add(duration).unwrap_or(time);
+                (start, time)
+            }),
+        );
+    }
+
+    /// Records each `(start, end)` of `calls` as a call of the child scope `name`.
+    pub(crate) fn record_calls(
+        &mut self,
+        name: Name,
+        calls: impl IntoIterator<Item = (crate::clock::Instant, crate::clock::Instant)>,
+    ) {
+        #[cfg(feature = "cores")]
+        let core = crate::cores::current();
+        for (start, time) in calls {
+            let start = Measure {
+                time: start,
+                ty: MeasureType::Start { name: name.clone() },
+                #[cfg(feature = "cores")]
+                core,
+                #[cfg(any(
+                    feature = "context-switches",
+                    feature = "page-faults",
+                    feature = "perf"
+                ))]
+                usage: Default::default(),
+            };
+            self.append(start, |_| {});
+            let end = Measure {
+                time,
+                ty: MeasureType::End,
+                #[cfg(feature = "cores")]
+                core: u32::MAX,
+                #[cfg(any(
+                    feature = "context-switches",
+                    feature = "page-faults",
+                    feature = "perf"
+                ))]
+                usage: Default::default(),
+            };
+            crate::stream::closed(&self.shared, self.measures(), self.depth, time);
+            if cfg!(feature = "bounded-memory") {
+                let mut measures = self.lock();
+                let mut folded = self.shared.folded.lock().unwrap_or_else(|e| e.into_inner());
+                folded.fold_last(&mut measures, &end);
+                crate::retention::evict_folded(&mut folded);
+            } else {
+                self.append(end, |_| {})
+            }
+        }
+        self.check_limits();
+    }
+
+    /// Returns the id of the scope's sampler if this call should be recorded, see [`crate::set_overhead_budget`].
+    pub(crate) fn sample(&mut self, name: &Name) -> Option<usize> {
+        let mut samplers = self
+            .shared
+            .samplers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let id = *self.sampler_ids.entry(name.clone()).or_insert_with(|| {
+            samplers.push(crate::sampling::Sampler::new(name.clone().into()));
+            samplers.len() - 1
+        });
+        samplers[id].sample().then_some(id)
+    }
+
+    pub(crate) fn observe(&mut self, id: usize, duration: std::time::Duration, budget: f64) {
+        self.shared
+            .samplers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())[id]
+            .observe(duration, budget)
+    }
+
+    pub(crate) fn count(&mut self, name: crate::names::Id, value: u64) {
+        if cfg!(feature = "bounded-memory") && self.measures().is_empty() {
+            // Without open scopes it would never be folded
+            return;
+        }
+        let measure = Measure {
+            time: crate::clock::Instant::ZERO,
+            ty: MeasureType::Count { name, value },
+            #[cfg(feature = "cores")]
+            core: u32::MAX,
+            #[cfg(any(
+                feature = "context-switches",
+                feature = "page-faults",
+                feature = "perf"
+            ))]
+            usage: Default::default(),
+        };
+        self.append(measure, |_| {})
+    }
+
+    /// Only used with the `tokio` feature.
+    #[allow(unused)]
+    pub(crate) fn wait(&mut self, duration: std::time::Duration) {
+        if self.measures().is_empty() {
+            // Not inside any scope
+            return;
+        }
+        let measure = Measure {
+            time: crate::clock::Instant::ZERO,
+            ty: MeasureType::Wait { duration },
+            #[cfg(feature = "cores")]
+            core: u32::MAX,
+            #[cfg(any(
+                feature = "context-switches",
+                feature = "page-faults",
+                feature = "perf"
+            ))]
+            usage: Default::default(),
+        };
+        self.append(measure, |_| {})
+    }
+
+    /// Pauses measuring the open scopes, only the outermost of nested pauses has effect.
+    pub(crate) fn pause(&mut self, time: crate::clock::Instant) {
+        match &mut self.paused {
+            Some((_, nested)) => *nested += 1,
+            None => self.paused = Some((time, 1)),
+        }
+    }
+
+    /// Resumes measuring, the time since the outermost [`pause`](Self::pause) is subtracted from the open scopes.
+    pub(crate) fn unpause(&mut self, time: crate::clock::Instant) {
+        let Some((paused, nested)) = &mut self.paused else {
+            return;
+        };
+        *nested -= 1;
+        if *nested > 0 {
+            return;
+        }
+        let duration = time.saturating_duration_since(*paused);
+        self.paused = None;
+        if self.measures().is_empty() {
+            // Not inside any scope
+            return;
+        }
+        let measure = Measure {
+            time,
+            ty: MeasureType::Pause { duration },
+            #[cfg(feature = "cores")]
+            core: u32::MAX,
+            #[cfg(any(
+                feature = "context-switches",
+                feature = "page-faults",
+                feature = "perf"
+            ))]
+            usage: Default::default(),
+        };
+        self.append(measure, |_| {})
+    }
+
+    pub(crate) fn slow(&mut self, call: crate::SlowCall) {
+        let measure = Measure {
+            time: crate::clock::Instant::ZERO,
+            ty: MeasureType::Slow {
+                call: Box::new(call),
+            },
+            #[cfg(feature = "cores")]
+            core: u32::MAX,
+            #[cfg(any(
+                feature = "context-switches",
+                feature = "page-faults",
+                feature = "perf"
+            ))]
+            usage: Default::default(),
+        };
+        self.append(measure, |_| {})
     }
 
     pub(crate) fn manual_drop(&mut self, main_thread: bool) {
         self.set_thread_time();
         let thread_time = self.get_thread_time();
-        let measures = std::mem::take(&mut self.measures);
-        if !measures.is_empty() {
-            let mut lock = GLOBAL_PROFILER.measures.write().unwrap();
+        if self.unwound {
+            // Guards leaked while unwinding are never dropped, close their scopes when the thread exited
+            let exited = self.thread_start + thread_time;
+            while self.depth > 0 {
+                if !std::thread::panicking() {
+                    mark_unwound(&mut self.lock());
+                }
+                self.pop(exited);
+            }
+        }
+        // Move the measures from the live threads to the exited ones while holding the lock,
+        // so a concurrent report never misses or duplicates them
+        let mut live = GLOBAL_PROFILER
+            .live
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        live.retain(|t| !Arc::ptr_eq(t, &self.shared));
+        let measures = std::mem::take(&mut *self.lock());
+        let folded =
+            std::mem::take(&mut *self.shared.folded.lock().unwrap_or_else(|e| e.into_inner()));
+        if !measures.is_empty() || !folded.is_empty() {
+            let thread = ExitedThread {
+                started: self.thread_start,
+                exited: Some(self.thread_start + thread_time),
+                name: self.shared.name.clone(),
+                measures,
+                folded,
+                samplers: std::mem::take(
+                    &mut *self
+                        .shared
+                        .samplers
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner()),
+                ),
+                spill: self.shared.spill.get().cloned().flatten(),
+            };
+            let mut lock = GLOBAL_PROFILER
+                .measures
+                .write()
+                .unwrap_or_else(|e| e.into_inner());
             if main_thread {
                 // Ensure the main thread is always first
-                lock.insert(0, (thread_time, measures));
+                lock.insert(0, thread);
             } else {
-                lock.push((thread_time, measures));
+                lock.push(thread);
             }
         }
+        drop(live);
         if !main_thread {
-            let mut lock = GLOBAL_PROFILER.threads.lock().unwrap();
+            self.detach();
+        }
+    }
+
+    /// Stops counting the thread as running, so the printers waiting for the threads to exit don't wait for it.
+    pub(crate) fn detach(&mut self) {
+        if !std::mem::replace(&mut self.detached, true) {
+            let mut lock = GLOBAL_PROFILER
+                .threads
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
             *lock -= 1;
-            GLOBAL_PROFILER.cvar.notify_one()
+            GLOBAL_PROFILER.cvar.notify_all()
         }
     }
 
@@ -124,7 +1292,6 @@ impl ThreadProfiler {
 #[cfg(feature = "enable")]
 impl Drop for ThreadProfiler {
     fn drop(&mut self) {
-        #[cfg(not(feature = "rayon"))]
         self.manual_drop(false)
     }
-}
\ No newline at end of file
+}
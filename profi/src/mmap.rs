@@ -0,0 +1,52 @@
+//! Post-mortem analysis of streamed captures.
+
+/// Builds a report from a capture written by [`export::stream_jsonl`](crate::export::stream_jsonl), memory-mapping the file.
+///
+/// The scopes are folded into the report while the file is read, and names without escapes are never copied,
+/// so captures of several gigabytes can be analysed with little more memory than the report itself.
+/// Like with the `bounded-memory` feature, the duration of each call is not kept.
+///
+/// Each thread of the capture is a thread of the report, and the scopes whose parent was opened before the stream started are roots.
+/// A truncated last line, left by a program that crashed while writing, is ignored.
+/// Captures compressed with [`export::stream_jsonl_zstd`](crate::export::stream_jsonl_zstd) must be decompressed first.
+///
+/// Returns an empty report if the `enable` feature is disabled.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   let path = std::env::temp_dir().join("profi-load-mmap.jsonl");
+///   let _ = std::fs::remove_file(&path);
+///   let stream = profi::export::stream_jsonl(&path, std::time::Duration::from_secs(1)).unwrap();
+///   for _ in 0..10 {
+///     prof!(work);
+///   }
+///   stream.stop();
+///
+///   let report = profi::load_mmap(&path).unwrap();
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(report.scope("work").unwrap().calls, 10);
+///   report.print(std::io::stdout(), &Default::default()).unwrap();
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn load_mmap(path: impl AsRef<std::path::Path>) -> std::io::Result<crate::Report> {
+    #[cfg(feature = "enable")]
+    {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: the capture is only read, a stream still writing to it can only append new lines
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        let mut progress = crate::progress::Progress::new(map.len());
+        let (threads, wall_time) = crate::capture::threads(&map, 0, &mut progress)?;
+        let report = crate::process::report_from_threads(threads);
+        progress.finish();
+        Ok(crate::Report {
+            wall_time,
+            ..report
+        })
+    }
+    #[cfg(not(feature = "enable"))]
+    Ok(crate::Report::default())
+}
@@ -0,0 +1,248 @@
+//! Names of scopes, counters and fields, with the `'static` ones interned into small ids so the measures don't carry strings.
+//!
+//! Each distinct `'static` name is stored once for the rest of the program, in a table that can be read without locking.
+//! The ids of the `prof!` call sites are cached next to them, so after the first call no lookup is needed at all.
+//! Owned names, like the ones built with `fmt = ...`, are kept in the measures instead and dropped along with them.
+
+#[cfg(feature = "enable")]
+use crate::Str;
+
+/// Interned `'static` name, resolved with [`Id::get`].
+#[cfg(feature = "enable")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Id(u32);
+
+/// Name of a scope or field, either interned or owned, resolved with [`Name::get`].
+///
+/// Public only because [`IntoScopeName`](crate::zz_private::IntoScopeName) returns it.
+#[cfg(feature = "enable")]
+#[derive(Clone)]
+pub struct Name(Repr);
+
+#[cfg(feature = "enable")]
+#[derive(Clone)]
+enum Repr {
+    Interned(Id),
+    Owned(Str),
+}
+
+/// Size of the first bucket of [`TABLE`], each next one doubles it.
+#[cfg(feature = "enable")]
+const FIRST_BUCKET: usize = 64;
+
+/// Enough buckets for every `u32` id.
+#[cfg(feature = "enable")]
+const BUCKETS: usize = 27;
+
+/// Interned names by id, in buckets that are allocated when the previous ones are full and never move.
+#[cfg(feature = "enable")]
+static TABLE: [std::sync::atomic::AtomicPtr<std::sync::OnceLock<&'static str>>; BUCKETS] =
+    [const { std::sync::atomic::AtomicPtr::new(std::ptr::null_mut()) }; BUCKETS];
+
+/// Ids of the names interned until now, only locked to intern a new name.
+#[cfg(feature = "enable")]
+static IDS: std::sync::Mutex<Option<std::collections::HashMap<&'static str, Id>>> =
+    std::sync::Mutex::new(None);
+
+#[cfg(feature = "enable")]
+thread_local! {
+    /// Names already interned by this thread, so the global lock is only taken once per name and thread.
+    static LOCAL: std::cell::RefCell<std::collections::HashMap<&'static str, Id>> = Default::default();
+}
+
+#[cfg(feature = "enable")]
+impl Id {
+    /// Id of `name`, interning it the first time it's used.
+    ///
+    /// Borrowed names are used as they are, owned ones that weren't interned yet are kept for the rest of the program,
+    /// so they must come from a bounded set, like the counters of a spill file, which are always `'static`.
+    #[inline]
+    pub(crate) fn new(name: impl Into<Str>) -> Self {
+        let name = name.into();
+        if let Ok(Some(id)) = LOCAL.try_with(|local| local.borrow().get(&*name).copied()) {
+            return id;
+        }
+        let id = Self::intern(name);
+        // Not cached if the thread is exiting
+        let _ = LOCAL.try_with(|local| local.borrow_mut().insert(id.get(), id));
+        id
+    }
+
+    #[cold]
+    fn intern(name: Str) -> Self {
+        let mut ids = IDS.lock().unwrap_or_else(|e| e.into_inner());
+        let ids = ids.get_or_insert_with(Default::default);
+        if let Some(&id) = ids.get(&*name) {
+            return id;
+        }
+        let name: &'static str = if name.is_borrowed() {
+            name.unwrap_borrowed()
+        } else {
+            Box::leak(name.into_owned().into_boxed_str())
+        };
+        let id = Id(u32::try_from(ids.len()).expect("too many distinct names"));
+        let (bucket, slot) = id.position();
+        let mut ptr = TABLE[bucket].load(std::sync::atomic::Ordering::Acquire);
+        if ptr.is_null() {
+            let len = FIRST_BUCKET << bucket;
+            let slots = (0..len)
+                .map(|_| std::sync::OnceLock::new())
+                .collect::<Box<[_]>>();
+            ptr = Box::leak(slots).as_mut_ptr();
+            TABLE[bucket].store(ptr, std::sync::atomic::Ordering::Release);
+        }
+        // SAFETY: the bucket has `FIRST_BUCKET << bucket` slots and is never freed
+        let _ = unsafe { &*ptr.add(slot) }.set(name);
+        ids.insert(name, id);
+        id
+    }
+
+    /// Bucket of [`TABLE`] and slot in it of the name.
+    #[inline(always)]
+    fn position(self) -> (usize, usize) {
+        let i = self.0 as usize + FIRST_BUCKET;
+        let bucket =
+            (usize::BITS - 1 - i.leading_zeros()) as usize - FIRST_BUCKET.trailing_zeros() as usize;
+        (bucket, i - (FIRST_BUCKET << bucket))
+    }
+
+    /// The interned name.
+    #[inline]
+    pub(crate) fn get(self) -> &'static str {
+        let (bucket, slot) = self.position();
+        let ptr = TABLE[bucket].load(std::sync::atomic::Ordering::Acquire);
+        if ptr.is_null() {
+            return "";
+        }
+        // SAFETY: the bucket has `FIRST_BUCKET << bucket` slots and is never freed
+        unsafe { &*ptr.add(slot) }
+            .get()
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "enable")]
+impl std::fmt::Debug for Id {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self.get(), f)
+    }
+}
+
+#[cfg(feature = "enable")]
+impl Name {
+    /// Name of `name`, interned if it's borrowed and kept as it is if it's owned.
+    #[inline]
+    pub(crate) fn new(name: impl Into<Str>) -> Self {
+        let name = name.into();
+        if name.is_borrowed() {
+            Name(Repr::Interned(Id::new(name)))
+        } else {
+            Name(Repr::Owned(name))
+        }
+    }
+
+    /// The name.
+    #[inline]
+    pub(crate) fn get(&self) -> &str {
+        match &self.0 {
+            Repr::Interned(id) => id.get(),
+            Repr::Owned(name) => name,
+        }
+    }
+}
+
+#[cfg(feature = "enable")]
+impl From<Id> for Name {
+    #[inline]
+    fn from(id: Id) -> Self {
+        Name(Repr::Interned(id))
+    }
+}
+
+#[cfg(feature = "enable")]
+impl PartialEq for Name {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Repr::Interned(a), Repr::Interned(b)) => a == b,
+            _ => self.get() == other.get(),
+        }
+    }
+}
+
+#[cfg(feature = "enable")]
+impl Eq for Name {}
+
+#[cfg(feature = "enable")]
+impl std::hash::Hash for Name {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.get().hash(state)
+    }
+}
+
+#[cfg(feature = "enable")]
+impl std::ops::Deref for Name {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.get()
+    }
+}
+
+#[cfg(feature = "enable")]
+impl From<Name> for Str {
+    #[inline]
+    fn from(name: Name) -> Self {
+        match name.0 {
+            Repr::Interned(id) => Str::borrowed(id.get()),
+            Repr::Owned(name) => name,
+        }
+    }
+}
+
+#[cfg(feature = "enable")]
+impl std::fmt::Debug for Name {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self.get(), f)
+    }
+}
+
+#[cfg(feature = "enable")]
+impl std::fmt::Display for Name {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.get())
+    }
+}
+
+/// [`Id`] of a call site, interned the first time it's reached.
+#[cfg(feature = "enable")]
+pub(crate) struct Cached(
+    /// Id of the name plus one, `0` if not interned yet.
+    std::sync::atomic::AtomicU32,
+);
+
+#[cfg(feature = "enable")]
+impl Cached {
+    pub(crate) const fn new() -> Self {
+        Self(std::sync::atomic::AtomicU32::new(0))
+    }
+
+    /// Id of `name`, which must always be the same.
+    #[inline(always)]
+    pub(crate) fn get(&self, name: &'static str) -> Id {
+        // Acquire, so the interned name is visible along with its id
+        match self.0.load(std::sync::atomic::Ordering::Acquire) {
+            0 => self.intern(name),
+            id => Id(id - 1),
+        }
+    }
+
+    #[cold]
+    fn intern(&self, name: &'static str) -> Id {
+        let id = Id::new(name);
+        self.0.store(id.0 + 1, std::sync::atomic::Ordering::Release);
+        id
+    }
+}
@@ -0,0 +1,103 @@
+//! Calibration of the measurement noise of the host.
+
+/// Measurement noise of the host, obtained with [`calibrate_noise`].
+///
+/// Included in the reports taken after calibrating, see [`Report::noise`](crate::Report::noise).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoiseFloor {
+    /// Smallest non-zero difference between two readings of the timer.
+    pub timer_resolution: std::time::Duration,
+    /// Coefficient of variation between runs of the same workload, in percentage.
+    pub run_variance: f64,
+    /// Number of runs used to measure the variance.
+    pub iterations: usize,
+}
+
+#[cfg(feature = "enable")]
+static NOISE: std::sync::Mutex<Option<NoiseFloor>> = std::sync::Mutex::new(None);
+
+/// Measures the timer resolution and the run-to-run variance of the host, running a fixed workload `iterations` times.
+///
+/// The result is embedded in the following reports, and [`Report::diff`](crate::Report::diff) does not consider a change a regression
+/// unless it's bigger than the noise of both reports.
+/// Useful on shared CI machines, where the same code can take a few percent more or less on each run.
+///
+/// Calibrating takes around `iterations` times 50µs, and is not recorded as a scope.
+/// Does nothing if the `enable` feature is disabled, returning a zero noise floor.
+///
+/// # Example
+/// ```
+/// fn main() {
+///   let noise = profi::calibrate_noise(100);
+///   assert!(noise.run_variance >= 0.0);
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(profi::report().noise, Some(noise));
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn calibrate_noise(iterations: usize) -> NoiseFloor {
+    #[cfg(feature = "enable")]
+    {
+        let noise = NoiseFloor {
+            timer_resolution: timer_resolution(),
+            run_variance: run_variance(iterations),
+            iterations,
+        };
+        *NOISE.lock().unwrap_or_else(|e| e.into_inner()) = Some(noise);
+        noise
+    }
+    #[cfg(not(feature = "enable"))]
+    NoiseFloor::default()
+}
+
+/// Noise floor of the last calibration, if any.
+#[cfg(feature = "enable")]
+pub(crate) fn get() -> Option<NoiseFloor> {
+    *NOISE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(feature = "enable")]
+fn timer_resolution() -> std::time::Duration {
+    let mut resolution = std::time::Duration::MAX;
+    for _ in 0..1000 {
+        let start = crate::clock::Instant::now();
+        let mut end = crate::clock::Instant::now();
+        while end == start {
+            end = crate::clock::Instant::now();
+        }
+        resolution = resolution.min(end.duration_since(start));
+    }
+    resolution
+}
+
+#[cfg(feature = "enable")]
+fn run_variance(iterations: usize) -> f64 {
+    let times = (0..iterations)
+        .map(|_| {
+            let start = crate::clock::Instant::now();
+            workload();
+            start.elapsed().as_secs_f64()
+        })
+        .collect::<Vec<_>>();
+    if times.len() < 2 {
+        return 0.0;
+    }
+    let n = times.len() as f64;
+    let mean = times.iter().sum::<f64>() / n;
+    let variance = times.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    if mean == 0.0 {
+        return 0.0;
+    }
+    variance.sqrt() / mean * 100.0
+}
+
+/// Fixed amount of work, around 50µs on a modern CPU.
+#[cfg(feature = "enable")]
+fn workload() {
+    let mut x = 0u64;
+    for i in 0..50_000u64 {
+        x = std::hint::black_box(x.wrapping_mul(31).wrapping_add(i));
+    }
+    std::hint::black_box(x);
+}
@@ -0,0 +1,840 @@
+use crate::ScopeStats;
+
+/// Output format used when printing the profiling results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Format {
+    /// Human readable table, the default.
+    #[default]
+    Table,
+    /// Collapsed stacks (`parent;child 123456`), compatible with [`inferno`](https://github.com/jonhoo/inferno) and `flamegraph.pl`.
+    ///
+    /// Each line contains the *self time* of the stack in nanoseconds.
+    Folded,
+    /// GitHub-flavoured Markdown tables, for pasting into issues and pull requests.
+    Markdown,
+    /// Standalone HTML page with the tables and a collapsible tree of the scopes of each thread.
+    Html,
+    /// Compact binary trace of every measure taken by this process, read back with [`trace::read`](crate::trace::read).
+    ///
+    /// Unlike the other formats, the measures themselves are written instead of the printed report,
+    /// so the report must be the one of the current process, like the one printed by [`print_on_exit!`](crate::print_on_exit).
+    Binary,
+}
+
+/// Order of the rows of the printed table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Sort {
+    /// Same order as the hierarchy of scopes, the default.
+    #[default]
+    Tree,
+    /// Highest real time first.
+    RealTime,
+    /// Highest CPU time first.
+    CpuTime,
+    /// Highest average time per call first.
+    Average,
+    /// Most calls first.
+    Calls,
+}
+
+/// How the calls of a scope made from different places are grouped in the table.
+///
+/// Defaults to [`Hierarchy::Merged`], or to [`Hierarchy::Deep`] with the `deep-hierarchy` feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Hierarchy {
+    /// All the calls with the same name are merged into a single row.
+    #[cfg_attr(not(feature = "deep-hierarchy"), default)]
+    Merged,
+    /// Calls with the same name are only merged if they're at the same depth, so a recursive function has a row for each level.
+    #[cfg_attr(feature = "deep-hierarchy", default)]
+    Deep,
+}
+
+/// Unit of the durations of the table, see [`PrintOptions::time_unit`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TimeUnit {
+    /// The unit that fits each duration, like `1.01s` and `10.10ms`, the default.
+    #[default]
+    Auto,
+    /// Seconds (`s`).
+    Seconds,
+    /// Milliseconds (`ms`).
+    Millis,
+    /// Microseconds (`µs`).
+    Micros,
+    /// Nanoseconds (`ns`).
+    Nanos,
+}
+
+impl TimeUnit {
+    /// Suffix and nanoseconds in one unit, `None` for [`TimeUnit::Auto`].
+    #[cfg(feature = "enable")]
+    fn scale(self) -> Option<(&'static str, f64)> {
+        match self {
+            TimeUnit::Auto => None,
+            TimeUnit::Seconds => Some(("s", 1e9)),
+            TimeUnit::Millis => Some(("ms", 1e6)),
+            TimeUnit::Micros => Some(("µs", 1e3)),
+            TimeUnit::Nanos => Some(("ns", 1.0)),
+        }
+    }
+}
+
+/// Options that control how the results are printed.
+///
+/// Usually created implicitly by [`print_on_exit!`](crate::print_on_exit), where each `key = value` pair is forwarded to the method with the same name.
+///
+/// # Example
+/// ```
+/// use profi::{print_on_exit, Format, PrintOptions};
+///
+/// fn main() {
+///   let _options = PrintOptions::new().format(Format::Folded);
+///   // Equivalent to the above
+///   print_on_exit!(format = folded);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PrintOptions {
+    pub(crate) format: Format,
+    pub(crate) diagnostics: bool,
+    pub(crate) findings: bool,
+    pub(crate) fail_on_budget: bool,
+    pub(crate) threads: bool,
+    pub(crate) utilization: bool,
+    pub(crate) per_thread: bool,
+    pub(crate) header: bool,
+    pub(crate) label: Option<String>,
+    pub(crate) sort: Sort,
+    pub(crate) limit: Option<usize>,
+    pub(crate) min_percent: f64,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) show_untracked: bool,
+    pub(crate) expand_groups: bool,
+    pub(crate) group_by: Option<String>,
+    pub(crate) hierarchy: Option<Hierarchy>,
+    pub(crate) stddev: bool,
+    pub(crate) cv_threshold: Option<f64>,
+    pub(crate) color: bool,
+    pub(crate) color_thresholds: Option<(f64, f64, f64)>,
+    pub(crate) format_name: Option<Callback<NameFormatter>>,
+    pub(crate) format_duration: Option<Callback<DurationFormatter>>,
+    pub(crate) format_percent: Option<Callback<PercentFormatter>>,
+    pub(crate) time_unit: TimeUnit,
+    pub(crate) precision: Option<usize>,
+    pub(crate) thousands_separator: Option<char>,
+    pub(crate) table_format: Option<TableFormat>,
+}
+
+type NameFormatter = dyn Fn(&ScopeStats) -> String + Send + Sync;
+type DurationFormatter = dyn Fn(std::time::Duration) -> String + Send + Sync;
+type PercentFormatter = dyn Fn(f64) -> String + Send + Sync;
+type ColumnFormatter = dyn Fn(&ScopeStats) -> Option<String> + Send + Sync;
+
+/// User provided function, stored in an `Arc` to keep [`PrintOptions`] cheap to clone.
+pub(crate) struct Callback<F: ?Sized>(pub(crate) std::sync::Arc<F>);
+
+impl<F: ?Sized> Clone for Callback<F> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<F: ?Sized> std::fmt::Debug for Callback<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Callback")
+    }
+}
+
+impl<F: ?Sized> std::ops::Deref for Callback<F> {
+    type Target = F;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Built-in column of the table, see [`TableFormat`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    /// Name of the scope, padded by its depth with [`Sort::Tree`].
+    Name,
+    /// % Application Time
+    AppPercent,
+    /// Real Time
+    RealTime,
+    /// % CPU Time
+    CpuPercent,
+    /// CPU Time
+    CpuTime,
+    /// Average time per call.
+    Average,
+    /// Shortest call.
+    Min,
+    /// Longest call.
+    Max,
+    /// Standard deviation of the calls.
+    StdDev,
+    /// Coefficient of variation of the calls.
+    Cv,
+    /// Number of calls.
+    Calls,
+    /// Owner of the scope, see [`set_owner`](crate::set_owner).
+    Owner,
+    /// Total and rate per second of a counter, see [`prof_count!`](crate::prof_count).
+    Counter(String),
+    /// Value of an [aggregator](crate::Aggregator).
+    Aggregate(String),
+}
+
+impl Column {
+    /// Header of the column.
+    #[cfg(feature = "enable")]
+    pub(crate) fn header(&self) -> &str {
+        match self {
+            Column::Name => "Name",
+            Column::AppPercent => "% Application Time",
+            Column::RealTime => "Real Time",
+            Column::CpuPercent => "% CPU Time",
+            Column::CpuTime => "CPU Time",
+            Column::Average => "Average time",
+            Column::Min => "Min",
+            Column::Max => "Max",
+            Column::StdDev => "Std. dev.",
+            Column::Cv => "CV",
+            Column::Calls => "Calls",
+            Column::Owner => "Owner",
+            Column::Counter(name) | Column::Aggregate(name) => name,
+        }
+    }
+}
+
+/// Columns of the table, which replace the default ones when set with [`PrintOptions::table_format`].
+///
+/// Each column is either a built-in [`Column`] or a custom one computed from the [`ScopeStats`] of each row,
+/// shown in the order they were added.
+///
+/// # Example
+/// ```
+/// use profi::{prof_count, Column, PrintOptions, TableFormat};
+///
+/// fn main() {
+///   let format = TableFormat::new()
+///     .column(Column::Name)
+///     .column(Column::RealTime)
+///     .custom("Time per item", |scope| {
+///       let items = scope.counter("items")?;
+///       Some(format!("{:.2?}", scope.total_real / items.max(1) as u32))
+///     })
+///     .column(Column::Calls);
+///   for _ in 0..10 {
+///     prof_count!("parse", items = 100);
+///   }
+///   let mut out = Vec::new();
+///   profi::report().print(&mut out, &PrintOptions::new().table_format(format)).unwrap();
+///   let table = String::from_utf8(out).unwrap();
+///   # #[cfg(feature = "enable")]
+///   # {
+///   assert!(table.contains("Time per item"));
+///   assert!(!table.contains("Average time"));
+///   # }
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TableFormat {
+    pub(crate) columns: Vec<TableColumn>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "enable"), allow(dead_code))]
+pub(crate) enum TableColumn {
+    Builtin(Column),
+    Custom {
+        header: String,
+        value: Callback<ColumnFormatter>,
+    },
+}
+
+impl TableFormat {
+    /// A table without columns.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the built-in `column`.
+    pub fn column(mut self, column: Column) -> Self {
+        self.columns.push(TableColumn::Builtin(column));
+        self
+    }
+
+    /// Adds a column titled `header` with the value `f` returns for each scope, or `-` if it returns `None`.
+    pub fn custom(
+        mut self,
+        header: impl Into<String>,
+        f: impl Fn(&ScopeStats) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.columns.push(TableColumn::Custom {
+            header: header.into(),
+            value: Callback(std::sync::Arc::new(f)),
+        });
+        self
+    }
+}
+
+impl PrintOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the output [`Format`].
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Prints a separate table for each thread instead of merging all of them, titled with the thread's index and name.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::print_on_exit;
+    ///
+    /// fn main() {
+    ///   print_on_exit!(per_thread);
+    ///   std::thread::Builder::new()
+    ///     .name("worker".into())
+    ///     .spawn(|| {
+    ///       profi::prof!(work);
+    ///     })
+    ///     .unwrap()
+    ///     .join()
+    ///     .unwrap();
+    /// }
+    /// ```
+    pub fn per_thread(mut self, per_thread: bool) -> Self {
+        self.per_thread = per_thread;
+        self
+    }
+
+    /// Prints a header before the table with the start time, the wall time, the number of threads and the build profile.
+    ///
+    /// Useful to tell apart the results of many runs saved to files.
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Names the run in the header, which is printed even if [`header`](Self::header) is not enabled.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::print_on_exit;
+    ///
+    /// fn main() {
+    ///   print_on_exit!(label = "baseline");
+    /// }
+    /// ```
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Sets the order of the rows of the table.
+    ///
+    /// When not sorted by [`Sort::Tree`] the names are not padded with their depth, as the hierarchy is lost.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::print_on_exit;
+    ///
+    /// fn main() {
+    ///   // Hottest 20 scopes, hiding the ones below 0.5% of the application time
+    ///   print_on_exit!(sort = cpu_time, limit = 20, min_percent = 0.5);
+    /// }
+    /// ```
+    pub fn sort(mut self, sort: Sort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Only prints the first `limit` rows of the table, after sorting.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Hides the scopes that take less than `percent` of the application time.
+    pub fn min_percent(mut self, percent: f64) -> Self {
+        self.min_percent = percent;
+        self
+    }
+
+    /// Only prints `max_depth` levels of scopes, the last one being a single `name (children)` row
+    /// with the calls and the time of all the descendants of `name`.
+    ///
+    /// Keeps the table readable for recursive algorithms, `1` only shows the root scopes.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::{prof, print_on_exit};
+    ///
+    /// fn fib(n: u64) -> u64 {
+    ///   prof!(fib);
+    ///   if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+    /// }
+    ///
+    /// fn main() {
+    ///   print_on_exit!(max_depth = 4);
+    ///   fib(10);
+    /// }
+    /// ```
+    ///
+    /// The folded calls are not lost:
+    /// ```
+    /// use profi::{prof, PrintOptions, TableFormat};
+    /// use std::time::Duration;
+    ///
+    /// fn descend(n: u32) {
+    ///   prof!(descend);
+    ///   std::thread::sleep(Duration::from_millis(2));
+    ///   if n > 0 { descend(n - 1) }
+    /// }
+    ///
+    /// fn main() {
+    ///   descend(4);
+    ///   let format = TableFormat::new().custom("Row", |s| {
+    ///     Some(format!("<{}|{}|{}>", s.name, s.calls, s.total_real.as_micros()))
+    ///   });
+    ///   let mut out = Vec::new();
+    ///   let options = PrintOptions::new().max_depth(2).table_format(format);
+    ///   profi::report().print(&mut out, &options).unwrap();
+    ///   let table = String::from_utf8(out).unwrap();
+    ///   let rows: Vec<Vec<&str>> = table
+    ///     .lines()
+    ///     .filter_map(|l| Some(l.split_once('<')?.1.split_once('>')?.0.split('|').collect()))
+    ///     .collect();
+    ///   # #[cfg(feature = "enable")]
+    ///   # {
+    ///   assert_eq!(rows.len(), 2);
+    ///   assert_eq!(rows[0][..2], ["descend", "1"]);
+    ///   assert_eq!(rows[1][..2], ["descend (children)", "4"]);
+    ///   let (total, children) = (rows[0][2].parse::<u128>().unwrap(), rows[1][2].parse::<u128>().unwrap());
+    ///   assert!(total >= 10_000 && children >= 8_000 && children < total);
+    ///   # }
+    /// }
+    /// ```
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Adds a `name <untracked>` row after the children of each scope, with the time it spent outside of them.
+    ///
+    /// Shows where the time of a scope went without adding more [`prof!`](crate::prof) calls.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::{prof, print_on_exit};
+    ///
+    /// fn main() {
+    ///   print_on_exit!(show_untracked);
+    ///   {
+    ///     prof!(tracked);
+    ///     std::thread::sleep(std::time::Duration::from_millis(10));
+    ///   }
+    ///   // Shown in the `<untracked>` row of `main`
+    ///   std::thread::sleep(std::time::Duration::from_millis(10));
+    /// }
+    /// ```
+    pub fn show_untracked(mut self, show_untracked: bool) -> Self {
+        self.show_untracked = show_untracked;
+        self
+    }
+
+    /// Adds a `group [scope]` row beneath each row of a [`group`](crate::group), with the time of each of its scopes.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::{prof, print_on_exit};
+    ///
+    /// fn main() {
+    ///   print_on_exit!(expand_groups);
+    ///   profi::group("db::*", "database");
+    ///   for table in ["users", "orders"] {
+    ///     prof!(fmt = "db::{table}");
+    ///   }
+    /// }
+    /// ```
+    pub fn expand_groups(mut self, expand_groups: bool) -> Self {
+        self.expand_groups = expand_groups;
+        self
+    }
+
+    /// Adds a `name [key=value]` row after the children of each scope, with its calls for every value of the field `key`.
+    ///
+    /// See [`Report::group_by`](crate::Report::group_by).
+    ///
+    /// # Example
+    /// ```
+    /// use profi::{prof, print_on_exit};
+    ///
+    /// fn main() {
+    ///   print_on_exit!(group_by = "route");
+    ///   for route in ["/users", "/users", "/orders"] {
+    ///     prof!("request", fields = { route: route });
+    ///   }
+    /// }
+    /// ```
+    pub fn group_by(mut self, key: impl Into<String>) -> Self {
+        self.group_by = Some(key.into());
+        self
+    }
+
+    /// Groups the scopes with the given [`Hierarchy`], instead of the one the report was taken with.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::{prof, print_on_exit};
+    ///
+    /// fn fib(n: u64) -> u64 {
+    ///   prof!(fib);
+    ///   if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+    /// }
+    ///
+    /// fn main() {
+    ///   // A row for each level of recursion
+    ///   print_on_exit!(hierarchy = deep);
+    ///   fib(10);
+    /// }
+    /// ```
+    pub fn hierarchy(mut self, hierarchy: Hierarchy) -> Self {
+        self.hierarchy = Some(hierarchy);
+        self
+    }
+
+    /// Adds the "Std. dev." and "CV" columns, with the standard deviation and the coefficient of variation of the calls of each scope.
+    ///
+    /// Scopes whose coefficient of variation is above [`cv_threshold`](Self::cv_threshold) are marked with `⚠`,
+    /// as their time changes a lot between calls.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::{prof, print_on_exit};
+    ///
+    /// fn main() {
+    ///   print_on_exit!(stddev, cv_threshold = 5.0);
+    ///   for i in 0..10 {
+    ///     prof!(jittery);
+    ///     std::thread::sleep(std::time::Duration::from_micros(100 * i));
+    ///   }
+    /// }
+    /// ```
+    pub fn stddev(mut self, stddev: bool) -> Self {
+        self.stddev = stddev;
+        self
+    }
+
+    /// Coefficient of variation, in percentage, above which a scope is marked as jittery in the "CV" column, `10.0` by default.
+    pub fn cv_threshold(mut self, percent: f64) -> Self {
+        self.cv_threshold = Some(percent);
+        self
+    }
+
+    /// Colors the rows of the table with ANSI escape codes by their percentage of the application time,
+    /// red and yellow for the hottest scopes and dimmed for the ones with little impact.
+    ///
+    /// [`print_on_exit!`](crate::print_on_exit) enables it when printing to `stdout` or `stderr` if they're a terminal
+    /// and the `NO_COLOR` environment variable is not set, `color = false` disables it.
+    /// Only applies to [`Format::Table`].
+    ///
+    /// # Example
+    /// ```
+    /// use profi::print_on_exit;
+    ///
+    /// fn main() {
+    ///   // Even when redirected to a file, for `less -R`
+    ///   print_on_exit!(color, color_thresholds = (0.5, 5.0, 20.0));
+    /// }
+    /// ```
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Percentages of the application time that choose the color of each row with [`color`](Self::color), as `(dim, yellow, red)`.
+    ///
+    /// Rows below `dim` are dimmed, from `yellow` are yellow and from `red` are red, `(1.0, 10.0, 25.0)` by default.
+    pub fn color_thresholds(mut self, (dim, yellow, red): (f64, f64, f64)) -> Self {
+        self.color_thresholds = Some((dim, yellow, red));
+        self
+    }
+
+    /// Prints a "Diagnostics" section after the table, with the maximum scope depth, the number of guards created and the guards left open for each thread.
+    ///
+    /// Useful to find runaway recursion or mis-nested guards.
+    /// Also lists the scopes whose names may be mixed up, see [`Report::name_collisions`](crate::Report::name_collisions).
+    pub fn diagnostics(mut self, diagnostics: bool) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    /// Prints a "Findings" section at the end, with the scopes and threads worth looking at first, see [`Report::findings`](crate::Report::findings).
+    ///
+    /// # Example
+    /// ```
+    /// use profi::print_on_exit;
+    ///
+    /// fn main() {
+    ///   print_on_exit!(findings);
+    ///   for _ in 0..10 {
+    ///     profi::prof!(work);
+    ///   }
+    /// }
+    /// ```
+    pub fn findings(mut self, findings: bool) -> Self {
+        self.findings = findings;
+        self
+    }
+
+    /// Exits the process with code 1 after printing if any scope is over its budget, see [`set_budget`](crate::set_budget).
+    ///
+    /// Only used by [`print_on_exit!`](crate::print_on_exit) and [`ExitPrinter`](crate::ExitPrinter),
+    /// which turns a run of the program into a performance gate for CI.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use profi::print_on_exit;
+    ///
+    /// fn main() {
+    ///   print_on_exit!(fail_on_budget);
+    ///   profi::assert_budget!("physics", 4ms);
+    ///   // ...
+    /// }
+    /// ```
+    pub fn fail_on_budget(mut self, fail_on_budget: bool) -> Self {
+        self.fail_on_budget = fail_on_budget;
+        self
+    }
+
+    /// Prints a "Threads" section after the table, with when the threads started and exited and how long they lived.
+    ///
+    /// Threads with the same name are grouped in a single row, so thread storms, where many short-lived threads are spawned,
+    /// show up as such instead of as a long list.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::print_on_exit;
+    ///
+    /// fn main() {
+    ///   print_on_exit!(threads);
+    ///   for _ in 0..4 {
+    ///     std::thread::spawn(|| {
+    ///       profi::prof!(work);
+    ///     })
+    ///     .join()
+    ///     .unwrap();
+    ///   }
+    /// }
+    /// ```
+    pub fn threads(mut self, threads: bool) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Prints a "Utilization" section after the table, with how much of its lifetime each thread spent inside its scopes,
+    /// its longest time without any open scope and how many scopes it opened, see [`Report::utilization`](crate::Report::utilization).
+    ///
+    /// # Example
+    /// ```
+    /// use profi::print_on_exit;
+    ///
+    /// fn main() {
+    ///   print_on_exit!(utilization);
+    ///   std::thread::spawn(|| {
+    ///     for _ in 0..2 {
+    ///       profi::prof!(work);
+    ///       std::thread::sleep(std::time::Duration::from_millis(1));
+    ///     }
+    ///   })
+    ///   .join()
+    ///   .unwrap();
+    /// }
+    /// ```
+    pub fn utilization(mut self, utilization: bool) -> Self {
+        self.utilization = utilization;
+        self
+    }
+
+    /// Sets how the name of each scope is displayed in the table.
+    ///
+    /// The hierarchy padding is still added before the returned name.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::print_on_exit;
+    ///
+    /// fn main() {
+    ///   // Only show the last segment of the path
+    ///   print_on_exit!(format_name = |scope| scope.name.rsplit("::").next().unwrap().to_string());
+    /// }
+    /// ```
+    pub fn format_name(
+        mut self,
+        f: impl Fn(&ScopeStats) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.format_name = Some(Callback(std::sync::Arc::new(f)));
+        self
+    }
+
+    /// Sets how all the durations (real time, CPU time and average) are displayed in the table.
+    ///
+    /// Takes precedence over [`time_unit`](Self::time_unit) and [`precision`](Self::precision).
+    ///
+    /// # Example
+    /// ```
+    /// use profi::print_on_exit;
+    ///
+    /// fn main() {
+    ///   print_on_exit!(format_duration = |d| format!("{:.3}ms", d.as_secs_f64() * 1000.0));
+    /// }
+    /// ```
+    pub fn format_duration(
+        mut self,
+        f: impl Fn(std::time::Duration) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.format_duration = Some(Callback(std::sync::Arc::new(f)));
+        self
+    }
+
+    /// Shows all the durations of the table in the same unit, so the rows can be compared at a glance
+    /// and sorted after copying them, see [`TimeUnit`].
+    ///
+    /// In `print_on_exit!` it can be `auto`, `s`, `ms`, `us` or `ns`.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::print_on_exit;
+    ///
+    /// fn main() {
+    ///   // 1010.000ms, 10.100ms, 0.987ms
+    ///   print_on_exit!(time_unit = ms, precision = 3);
+    /// }
+    /// ```
+    pub fn time_unit(mut self, unit: TimeUnit) -> Self {
+        self.time_unit = unit;
+        self
+    }
+
+    /// Sets the number of decimals of the durations, `2` by default.
+    pub fn precision(mut self, decimals: usize) -> Self {
+        self.precision = Some(decimals);
+        self
+    }
+
+    /// Groups the digits of the number of calls in thousands with `separator`, like `1,234,567` with `','`.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::print_on_exit;
+    ///
+    /// fn main() {
+    ///   print_on_exit!(thousands_separator = '_');
+    /// }
+    /// ```
+    pub fn thousands_separator(mut self, separator: char) -> Self {
+        self.thousands_separator = Some(separator);
+        self
+    }
+
+    /// Sets how the percentages (% application time and % CPU time) are displayed in the table.
+    pub fn format_percent(mut self, f: impl Fn(f64) -> String + Send + Sync + 'static) -> Self {
+        self.format_percent = Some(Callback(std::sync::Arc::new(f)));
+        self
+    }
+
+    /// Chooses the columns of the table and their order, see [`TableFormat`].
+    ///
+    /// Also used by [`Format::Markdown`] and [`Format::Html`].
+    pub fn table_format(mut self, format: TableFormat) -> Self {
+        self.table_format = Some(format);
+        self
+    }
+
+    /// Scopes shown in the table, sorted and truncated.
+    #[cfg(feature = "enable")]
+    pub(crate) fn rows<'a>(&self, scopes: &'a [ScopeStats]) -> Vec<&'a ScopeStats> {
+        let mut rows = scopes
+            .iter()
+            .filter(|s| s.percent_app >= self.min_percent)
+            .collect::<Vec<_>>();
+        match self.sort {
+            Sort::Tree => {}
+            Sort::RealTime => rows.sort_by_key(|s| std::cmp::Reverse(s.total_real)),
+            Sort::CpuTime => rows.sort_by_key(|s| std::cmp::Reverse(s.total_cpu)),
+            Sort::Average => rows.sort_by_key(|s| std::cmp::Reverse(s.average)),
+            Sort::Calls => rows.sort_by_key(|s| std::cmp::Reverse(s.calls)),
+        }
+        if let Some(limit) = self.limit {
+            rows.truncate(limit);
+        }
+        rows
+    }
+
+    /// Returns `true` if a coefficient of variation of `cv` is above the threshold.
+    #[cfg(feature = "enable")]
+    pub(crate) fn is_jittery(&self, cv: f64) -> bool {
+        cv > self.cv_threshold.unwrap_or(10.0)
+    }
+
+    /// ANSI escape code of a row with `percent` of the application time, `None` for the default color.
+    #[cfg(feature = "enable")]
+    pub(crate) fn row_style(&self, percent: f64) -> Option<&'static str> {
+        let (dim, yellow, red) = self.color_thresholds.unwrap_or((1.0, 10.0, 25.0));
+        match percent {
+            p if p >= red => Some("\x1b[31m"),
+            p if p >= yellow => Some("\x1b[33m"),
+            p if p < dim => Some("\x1b[2m"),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "enable")]
+    pub(crate) fn fmt_duration(&self, d: std::time::Duration) -> String {
+        let precision = self.precision.unwrap_or(2);
+        match (&self.format_duration, self.time_unit.scale()) {
+            (Some(f), _) => f(d),
+            (None, None) => format!("{d:.precision$?}"),
+            (None, Some((suffix, nanos))) => {
+                format!("{:.precision$}{suffix}", d.as_nanos() as f64 / nanos)
+            }
+        }
+    }
+
+    /// Number of calls, with the digits grouped by the [thousands separator](Self::thousands_separator).
+    #[cfg(feature = "enable")]
+    pub(crate) fn fmt_calls(&self, calls: usize) -> String {
+        let digits = calls.to_string();
+        let Some(separator) = self.thousands_separator else {
+            return digits;
+        };
+        let mut grouped = String::with_capacity(digits.len() * 2);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                grouped.push(separator);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+
+    #[cfg(feature = "enable")]
+    pub(crate) fn fmt_percent(&self, p: f64) -> String {
+        match &self.format_percent {
+            Some(f) => f(p),
+            None => format!("{p:.2}%"),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Cost of measuring with `profi` on the current machine.
+//!
+//! Meant for regression tests in CI, which can assert that the overhead of the scopes stays below a limit
+//! for workloads shaped like their own:
+//!
+//! ```
+//! use std::time::Duration;
+//! use profi::overhead::Workload;
+//!
+//! fn main() {
+//!   let report = profi::overhead::run_with(&Workload {
+//!     calls: 10_000,
+//!     depth: 8,
+//!     ..Default::default()
+//!   });
+//!   println!("{report:#?}");
+//!   assert!(report.scope < Duration::from_millis(1));
+//! }
+//! ```
+
+use std::time::Duration;
+
+/// Shape of the scopes measured by [`run_with`].
+#[derive(Debug, Clone)]
+pub struct Workload {
+    /// Scopes opened by each thread for each kind of scope.
+    pub calls: usize,
+    /// Depth of the chain of nested scopes measured for [`OverheadReport::nested_scope`].
+    pub depth: usize,
+    /// Threads measuring at the same time, each runs the whole workload.
+    pub threads: usize,
+    /// Different names used by the dynamic scopes, the rest repeat them.
+    pub distinct_names: usize,
+}
+
+impl Default for Workload {
+    fn default() -> Self {
+        Self {
+            calls: 100_000,
+            depth: 16,
+            threads: 1,
+            distinct_names: 100,
+        }
+    }
+}
+
+/// Average cost of each kind of scope, obtained with [`run`] or [`run_with`].
+///
+/// Costs are the time to open and close an empty scope, minus the time of an empty loop.
+/// All fields are zero if the `enable` feature is disabled.
+#[derive(Debug, Clone, Default)]
+pub struct OverheadReport {
+    /// Workload that was measured.
+    pub workload: Workload,
+    /// Cost of `prof!("name")`, a scope with a static name.
+    pub scope: Duration,
+    /// Cost of `prof!(fmt = "name {i}")`, a scope whose name is formatted on each call.
+    pub dynamic_scope: Duration,
+    /// Cost of each scope of a chain of nested scopes.
+    pub nested_scope: Duration,
+    /// Time to build the [`Report`](crate::Report) of all the scopes measured.
+    pub report: Duration,
+    /// Scopes measured, on all threads.
+    pub scopes: usize,
+}
+
+/// Measures the overhead of the scopes with the default [`Workload`].
+///
+/// Same as [`run_with`], which discards all the measures taken before.
+pub fn run() -> OverheadReport {
+    run_with(&Workload::default())
+}
+
+/// Measures the overhead of the scopes with `workload`.
+///
+/// The scopes are measured by the global profiler, so it calls [`reset`](crate::reset) before and after,
+/// discarding all the measures taken until then. Run it before profiling, or in its own test.
+///
+/// Build in release mode for costs that are close to the real ones.
+#[allow(unused_variables)]
+pub fn run_with(workload: &Workload) -> OverheadReport {
+    #[cfg(feature = "enable")]
+    {
+        crate::reset();
+        let threads = workload.threads.max(1);
+        let costs = std::thread::scope(|s| {
+            let handles = (0..threads)
+                .map(|_| s.spawn(|| measure(workload)))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        let average = |cost: fn(&[Duration; 3]) -> Duration| {
+            costs.iter().map(cost).sum::<Duration>() / threads as u32
+        };
+
+        let start = std::time::Instant::now();
+        let report = crate::report();
+        let report_time = start.elapsed();
+        let scopes = report.scopes.iter().map(|s| s.calls).sum();
+        crate::reset();
+
+        OverheadReport {
+            workload: workload.clone(),
+            scope: average(|c| c[0]),
+            dynamic_scope: average(|c| c[1]),
+            nested_scope: average(|c| c[2]),
+            report: report_time,
+            scopes,
+        }
+    }
+    #[cfg(not(feature = "enable"))]
+    OverheadReport {
+        workload: workload.clone(),
+        ..Default::default()
+    }
+}
+
+/// Cost of a static, dynamic and nested scope in the current thread.
+#[cfg(feature = "enable")]
+fn measure(workload: &Workload) -> [Duration; 3] {
+    let calls = workload.calls.max(1);
+    let per_call = |elapsed: Duration, baseline: Duration, calls: usize| {
+        elapsed.saturating_sub(baseline) / calls as u32
+    };
+
+    let start = std::time::Instant::now();
+    for i in 0..calls {
+        std::hint::black_box(i);
+    }
+    let baseline = start.elapsed();
+
+    let start = std::time::Instant::now();
+    for i in 0..calls {
+        crate::prof!("profi::overhead::scope");
+        std::hint::black_box(i);
+    }
+    let scope = per_call(start.elapsed(), baseline, calls);
+
+    let names = workload.distinct_names.max(1);
+    let start = std::time::Instant::now();
+    for i in 0..calls {
+        crate::prof!(fmt = "profi::overhead::dynamic {}", i % names);
+        std::hint::black_box(i);
+    }
+    let dynamic = per_call(start.elapsed(), baseline, calls);
+
+    fn nest(depth: usize) {
+        if depth == 0 {
+            return;
+        }
+        crate::prof!("profi::overhead::nested");
+        nest(std::hint::black_box(depth - 1));
+    }
+    let depth = workload.depth.max(1);
+    let chains = (calls / depth).max(1);
+    let start = std::time::Instant::now();
+    for _ in 0..chains {
+        nest(depth);
+    }
+    let nested = per_call(start.elapsed(), Duration::ZERO, chains * depth);
+
+    [scope, dynamic, nested]
+}
@@ -0,0 +1,132 @@
+//! Owners of the scopes, so the results of each one can be routed to the team responsible for it.
+
+/// Patterns and their owners, in the order they were set.
+#[cfg(feature = "enable")]
+static OWNERS: std::sync::RwLock<Vec<(Box<str>, Box<str>)>> = std::sync::RwLock::new(Vec::new());
+
+/// Makes `owner` the owner of the scopes whose name matches `pattern`, where `*` matches any number of characters.
+///
+/// The owner of each scope is shown in an "Owner" column, kept in [`ScopeStats::owner`](crate::ScopeStats::owner) so it reaches the JSON exports,
+/// and the time of each owner is summed in an "Owners" table and in [`Report::owners`](crate::Report::owners).
+/// Like in a `CODEOWNERS` file, if several patterns match a scope the last one set wins.
+///
+/// Owners are applied when building the report, so they also affect the scopes recorded before.
+/// Functions can set their own owner with `#[profile(owner = "...")]`, or load all of them from a file with [`load_owners`].
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   profi::set_owner("db::*", "@storage");
+///   profi::set_owner("render", "@graphics");
+///   {
+///     prof!("db::query");
+///   }
+///   {
+///     prof!(render);
+///   }
+///   let report = profi::report();
+///   # #[cfg(feature = "enable")]
+///   # {
+///   assert_eq!(report.scope("db::query").unwrap().owner.as_deref(), Some("@storage"));
+///   assert_eq!(report.owners()[0].scopes, 1);
+///   # }
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn set_owner(pattern: impl Into<String>, owner: impl Into<String>) {
+    #[cfg(feature = "enable")]
+    OWNERS.write().unwrap_or_else(|e| e.into_inner()).push((
+        pattern.into().into_boxed_str(),
+        owner.into().into_boxed_str(),
+    ));
+}
+
+/// Reads the owners of the scopes from the file at `path`, see [`set_owner`].
+///
+/// Each line is a pattern followed by its owner, separated by whitespace, like in a `CODEOWNERS` file.
+/// Empty lines and the ones starting with `#` are skipped, and so are the owners after the first one.
+/// ```text
+/// # Scope       Owner
+/// db::*         @storage
+/// render::*     @graphics
+/// ```
+///
+/// # Example
+/// ```
+/// fn main() {
+///   let path = std::env::temp_dir().join("profi-owners.txt");
+///   std::fs::write(&path, "# Scope  Owner\ndb::*  @storage\n").unwrap();
+///   profi::load_owners(&path).unwrap();
+///   {
+///     profi::prof!("db::query");
+///   }
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(profi::report().scope("db::query").unwrap().owner.as_deref(), Some("@storage"));
+/// }
+/// ```
+pub fn load_owners(path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    let text = std::fs::read_to_string(path)?;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some(pattern), Some(owner)) => set_owner(pattern, owner),
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("line {} has no owner", i + 1),
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Owner of the scope `name`, the last one whose pattern matches.
+#[cfg(feature = "enable")]
+pub(crate) fn owner(name: &str) -> Option<String> {
+    let owners = OWNERS.read().unwrap_or_else(|e| e.into_inner());
+    owners
+        .iter()
+        .rev()
+        .find(|(pattern, _)| crate::filter::glob_match(pattern, name))
+        .map(|(_, owner)| owner.to_string())
+}
+
+/// Time of each owner, see [`Report::owners`](crate::Report::owners).
+#[cfg(feature = "enable")]
+pub(crate) fn summary(report: &crate::Report) -> Vec<crate::OwnerStats> {
+    let mut owners = indexmap::IndexMap::<&str, crate::OwnerStats>::new();
+    // Owners of the enclosing scopes, whose time already includes the one of their children
+    let mut ancestors = Vec::<(usize, Option<&str>)>::new();
+    for scope in &report.scopes {
+        while ancestors
+            .last()
+            .is_some_and(|&(depth, _)| depth >= scope.depth)
+        {
+            ancestors.pop();
+        }
+        let owner = scope.owner.as_deref();
+        if let Some(owner) = owner {
+            let stats = owners.entry(owner).or_insert_with(|| crate::OwnerStats {
+                owner: owner.to_owned(),
+                ..Default::default()
+            });
+            stats.scopes += 1;
+            stats.calls += scope.calls;
+            if !ancestors.iter().any(|&(_, o)| o == Some(owner)) {
+                stats.total_real += scope.total_real;
+                stats.percent_app += scope.percent_app;
+            }
+        }
+        ancestors.push((scope.depth, owner));
+    }
+    let mut owners = owners.into_values().collect::<Vec<_>>();
+    owners.sort_by_key(|o| std::cmp::Reverse(o.total_real));
+    owners
+}
@@ -0,0 +1,116 @@
+//! Hardware performance counters of the current thread, read with `perf_event_open`.
+
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+const PERF_TYPE_HARDWARE: u32 = 0;
+const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+const PERF_COUNT_HW_CACHE_MISSES: u64 = 3;
+const PERF_FORMAT_GROUP: u64 = 1 << 3;
+/// `exclude_kernel` and `exclude_hv`, so it works with the default `perf_event_paranoid`.
+const FLAGS: u64 = (1 << 5) | (1 << 6);
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 1 << 3;
+
+/// First version of `perf_event_attr`, accepted by every kernel with perf events.
+#[repr(C)]
+#[derive(Default)]
+struct Attr {
+    ty: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+}
+
+/// Counters opened as a group, so all of them are read at once.
+struct Group {
+    leader: OwnedFd,
+    /// Only kept open, they're read through the leader
+    #[allow(dead_code)]
+    members: Vec<OwnedFd>,
+    /// Position of the cycles, instructions and cache misses in the group, `None` if not available.
+    positions: [Option<usize>; 3],
+}
+
+thread_local! {
+    static GROUP: Option<Group> = Group::open();
+}
+
+impl Group {
+    fn open() -> Option<Self> {
+        let leader = match open(PERF_COUNT_HW_CPU_CYCLES, -1) {
+            Ok(fd) => fd,
+            Err(e) => {
+                static WARNED: std::sync::Once = std::sync::Once::new();
+                WARNED.call_once(|| {
+                    eprintln!("[profi] Hardware performance counters are not available: {e}")
+                });
+                return None;
+            }
+        };
+        let mut group = Self {
+            leader,
+            members: Vec::new(),
+            positions: [Some(0), None, None],
+        };
+        for (i, config) in [PERF_COUNT_HW_INSTRUCTIONS, PERF_COUNT_HW_CACHE_MISSES]
+            .into_iter()
+            .enumerate()
+        {
+            if let Ok(fd) = open(config, group.leader.as_raw_fd()) {
+                group.members.push(fd);
+                group.positions[i + 1] = Some(group.members.len());
+            }
+        }
+        Some(group)
+    }
+
+    fn read(&self) -> Option<[u64; 3]> {
+        // Number of counters followed by their values
+        let mut values = [0u64; 4];
+        let size = std::mem::size_of_val(&values);
+        // SAFETY: `values` is valid for `size` bytes
+        let read = unsafe { libc::read(self.leader.as_raw_fd(), values.as_mut_ptr().cast(), size) };
+        if read <= 0 {
+            return None;
+        }
+        Some(self.positions.map(|p| p.map_or(0, |p| values[p + 1])))
+    }
+}
+
+fn open(config: u64, group: i32) -> std::io::Result<OwnedFd> {
+    let attr = Attr {
+        ty: PERF_TYPE_HARDWARE,
+        size: std::mem::size_of::<Attr>() as u32,
+        config,
+        read_format: PERF_FORMAT_GROUP,
+        flags: FLAGS,
+        ..Default::default()
+    };
+    // SAFETY: `attr` is a valid `perf_event_attr`, measuring the calling thread on any CPU
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_perf_event_open,
+            &attr as *const Attr,
+            0,
+            -1,
+            group,
+            PERF_FLAG_FD_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: the syscall returned a new file descriptor
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+}
+
+/// Cycles, instructions and cache misses of the current thread, `None` if not available.
+pub(crate) fn read() -> Option<[u64; 3]> {
+    GROUP.with(|group| group.as_ref()?.read())
+}
@@ -0,0 +1,214 @@
+//! Periodic reporting for long-running applications.
+
+/// Builder for a background thread that prints a report on a fixed interval.
+///
+/// Useful for servers or other applications that never exit, where [`print_on_exit!`](crate::print_on_exit) is not an option.
+///
+/// Each report contains the measures of all threads, including the running ones.
+/// Scopes that are still open are not measured until they're closed.
+///
+/// # Example
+/// ```
+/// use profi::{prof, PeriodicReport};
+///
+/// fn main() {
+///   // Prints every 30 seconds, only with the measures taken since the last report
+///   let _reporter = PeriodicReport::new(std::time::Duration::from_secs(30))
+///     .reset(true)
+///     .start(std::io::stderr());
+///
+///   prof!(work);
+///   // ...
+/// }
+/// ```
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct PeriodicReport {
+    every: std::time::Duration,
+    options: crate::PrintOptions,
+    reset: bool,
+    csv: Option<std::path::PathBuf>,
+}
+
+impl PeriodicReport {
+    /// Prints a report every `every`.
+    pub fn new(every: std::time::Duration) -> Self {
+        Self {
+            every,
+            options: Default::default(),
+            reset: false,
+            csv: None,
+        }
+    }
+
+    /// Options used for printing each report.
+    pub fn options(mut self, options: crate::PrintOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Discard the measures after each report, so every report only contains the last interval.
+    ///
+    /// Guards that are alive are not affected.
+    pub fn reset(mut self, reset: bool) -> Self {
+        self.reset = reset;
+        self
+    }
+
+    /// Also writes the real time of each scope during each interval to `path` as a wide CSV, rewritten after every report.
+    ///
+    /// The first column is the time since profiling started in seconds, followed by a column per scope with its time in milliseconds,
+    /// so the evolution of the hotspots can be plotted directly with gnuplot or a spreadsheet.
+    /// Scopes that appear later get a new column, with zeros in the previous intervals.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::{prof, PeriodicReport};
+    ///
+    /// fn main() {
+    ///   let path = std::env::temp_dir().join("profi-intervals.csv");
+    ///   let reporter = PeriodicReport::new(std::time::Duration::from_millis(10))
+    ///     .csv(&path)
+    ///     .start(std::io::sink());
+    ///   for _ in 0..5 {
+    ///     prof!(work);
+    ///     std::thread::sleep(std::time::Duration::from_millis(10));
+    ///   }
+    ///   reporter.stop();
+    ///   # #[cfg(feature = "enable")]
+    ///   assert!(std::fs::read_to_string(&path).unwrap().starts_with("elapsed_s,"));
+    /// }
+    /// ```
+    pub fn csv(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.csv = Some(path.into());
+        self
+    }
+
+    /// Spawns the reporting thread, which writes to `to` until the returned handle is dropped.
+    #[must_use = "the reporting thread stops when the handle is dropped"]
+    #[allow(unused_mut, unused_variables)]
+    pub fn start(self, mut to: impl std::io::Write + Send + 'static) -> PeriodicHandle {
+        #[cfg(feature = "enable")]
+        {
+            let (stop, stopped) = std::sync::mpsc::channel::<()>();
+            let thread = std::thread::spawn(move || {
+                let mut timeline = Timeline::default();
+                while let Err(std::sync::mpsc::RecvTimeoutError::Timeout) =
+                    stopped.recv_timeout(self.every)
+                {
+                    let report = crate::measure::GLOBAL_PROFILER.report();
+                    if self.reset {
+                        crate::measure::GLOBAL_PROFILER.reset();
+                    }
+                    if let Some(path) = &self.csv {
+                        timeline.add(&report, self.reset);
+                        if let Err(e) = std::fs::write(path, timeline.to_csv()) {
+                            eprintln!("[profi] Could not write {}: {e}", path.display());
+                        }
+                    }
+                    if let Err(e) = report.print(&mut to, &self.options) {
+                        eprintln!("[profi] Periodic report failed: {e}");
+                        return;
+                    }
+                }
+            });
+            PeriodicHandle(Some((stop, thread)))
+        }
+        #[cfg(not(feature = "enable"))]
+        PeriodicHandle(None)
+    }
+}
+
+/// Time of each scope during each interval, see [`PeriodicReport::csv`].
+#[cfg(feature = "enable")]
+#[derive(Default)]
+struct Timeline {
+    scopes: Vec<String>,
+    /// End of each interval and the time of each scope during it, in the order of `scopes`.
+    rows: Vec<(std::time::Duration, Vec<std::time::Duration>)>,
+    /// Totals of the last report, to get the time of each interval if the measures are not reset.
+    previous: std::collections::HashMap<String, std::time::Duration>,
+}
+
+#[cfg(feature = "enable")]
+impl Timeline {
+    fn add(&mut self, report: &crate::Report, reset: bool) {
+        let mut row = vec![std::time::Duration::ZERO; self.scopes.len()];
+        for scope in &report.scopes {
+            let total = scope.total_real;
+            let interval = match reset {
+                true => total,
+                false => {
+                    let previous = self.previous.insert(scope.name.clone(), total);
+                    total.saturating_sub(previous.unwrap_or_default())
+                }
+            };
+            match self.scopes.iter().position(|s| *s == scope.name) {
+                Some(i) => row[i] += interval,
+                None => {
+                    self.scopes.push(scope.name.clone());
+                    row.push(interval);
+                }
+            }
+        }
+        self.rows.push((report.wall_time, row));
+    }
+
+    fn to_csv(&self) -> String {
+        use std::fmt::Write;
+
+        let mut csv = String::from("elapsed_s");
+        for scope in &self.scopes {
+            csv.push(',');
+            csv.push_str(&csv_field(scope));
+        }
+        csv.push('\n');
+        for (elapsed, times) in &self.rows {
+            let _ = write!(csv, "{:.3}", elapsed.as_secs_f64());
+            for i in 0..self.scopes.len() {
+                let time = times.get(i).copied().unwrap_or_default();
+                let _ = write!(csv, ",{:.3}", time.as_secs_f64() * 1000.0);
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// Quotes `s` if it has commas, quotes or newlines.
+#[cfg(feature = "enable")]
+fn csv_field(s: &str) -> std::borrow::Cow<'_, str> {
+    match s.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", s.replace('"', "\"\"")).into(),
+        false => s.into(),
+    }
+}
+
+/// Handle of a running [`PeriodicReport`], the reporting thread stops when it's dropped.
+#[derive(Debug)]
+pub struct PeriodicHandle(Option<(std::sync::mpsc::Sender<()>, std::thread::JoinHandle<()>)>);
+
+impl PeriodicHandle {
+    /// Stops the reporting thread and waits for it to finish.
+    pub fn stop(self) {}
+}
+
+impl Drop for PeriodicHandle {
+    fn drop(&mut self) {
+        if let Some((stop, thread)) = self.0.take() {
+            let _ = stop.send(());
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Prints a report to `to` every `every`, until the returned handle is dropped.
+///
+/// Shorthand for `PeriodicReport::new(every).start(to)`, see [`PeriodicReport`] for more options.
+#[must_use = "the reporting thread stops when the handle is dropped"]
+pub fn start_periodic_report(
+    every: std::time::Duration,
+    to: impl std::io::Write + Send + 'static,
+) -> PeriodicHandle {
+    PeriodicReport::new(every).start(to)
+}
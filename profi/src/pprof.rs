@@ -0,0 +1,173 @@
+//! Encoding of reports in the pprof format, see [`profile.proto`](https://github.com/google/pprof/blob/main/proto/profile.proto).
+
+/// Writes `report` as a gzip-compressed `profile.proto`.
+pub(crate) fn write(report: &crate::Report, to: impl std::io::Write) -> std::io::Result<()> {
+    let mut profile = Profile::default();
+    let calls = profile.string("calls");
+    let count = profile.string("count");
+    let wall = profile.string("wall");
+    let nanoseconds = profile.string("nanoseconds");
+    let thread = profile.string("thread");
+
+    // Profile.sample_type
+    for (ty, unit) in [(calls, count), (wall, nanoseconds)] {
+        let mut value_type = Vec::new();
+        varint_field(&mut value_type, 1, ty);
+        varint_field(&mut value_type, 2, unit);
+        bytes_field(&mut profile.out, 1, &value_type);
+    }
+
+    for t in &report.threads {
+        let name = match &t.name {
+            Some(name) => format!("Thread {} ({name})", t.index),
+            None => format!("Thread {}", t.index),
+        };
+        let name = profile.string(&name);
+        for node in &t.scopes {
+            // Beneath the scopes of the thread it was opened on behalf of
+            let mut stack = node
+                .parent
+                .iter()
+                .map(|parent| profile.function(parent))
+                .collect();
+            profile.samples(node, &mut stack, thread, name);
+        }
+    }
+
+    // Profile.location and Profile.function, each scope name has one of each with the same id
+    let functions = std::mem::take(&mut profile.functions);
+    for (i, name) in functions.into_iter().enumerate() {
+        let id = i as u64 + 1;
+        let mut line = Vec::new();
+        varint_field(&mut line, 1, id);
+        let mut location = Vec::new();
+        varint_field(&mut location, 1, id);
+        bytes_field(&mut location, 4, &line);
+        bytes_field(&mut profile.out, 4, &location);
+
+        let mut function = Vec::new();
+        varint_field(&mut function, 1, id);
+        varint_field(&mut function, 2, name);
+        varint_field(&mut function, 3, name);
+        bytes_field(&mut profile.out, 5, &function);
+    }
+
+    let mut out = std::mem::take(&mut profile.out);
+    // Profile.string_table
+    for s in &profile.strings {
+        bytes_field(&mut out, 6, s.as_bytes());
+    }
+    if let Some(started) = report
+        .started
+        .and_then(|s| s.duration_since(std::time::UNIX_EPOCH).ok())
+    {
+        varint_field(&mut out, 9, started.as_nanos() as u64);
+    }
+    varint_field(&mut out, 10, report.wall_time.as_nanos() as u64);
+    // Profile.period_type and Profile.period, every nanosecond is recorded
+    let mut period_type = Vec::new();
+    varint_field(&mut period_type, 1, wall);
+    varint_field(&mut period_type, 2, nanoseconds);
+    bytes_field(&mut out, 11, &period_type);
+    varint_field(&mut out, 12, 1);
+    varint_field(&mut out, 14, wall);
+
+    let mut gzip = flate2::write::GzEncoder::new(to, flate2::Compression::default());
+    std::io::Write::write_all(&mut gzip, &out)?;
+    gzip.finish()?;
+    Ok(())
+}
+
+#[derive(Default)]
+struct Profile {
+    /// Encoded fields, the string table is written last because samples add new strings.
+    out: Vec<u8>,
+    strings: Vec<String>,
+    indices: std::collections::HashMap<String, u64>,
+    /// String index of the name of each function, its id is the position plus one.
+    functions: Vec<u64>,
+    function_ids: std::collections::HashMap<String, u64>,
+}
+
+impl Profile {
+    /// Index of `s` in the string table, which always starts with `""`.
+    fn string(&mut self, s: &str) -> u64 {
+        if self.strings.is_empty() {
+            self.strings.push(String::new());
+            self.indices.insert(String::new(), 0);
+        }
+        if let Some(&i) = self.indices.get(s) {
+            return i;
+        }
+        let i = self.strings.len() as u64;
+        self.strings.push(s.to_owned());
+        self.indices.insert(s.to_owned(), i);
+        i
+    }
+
+    fn function(&mut self, name: &str) -> u64 {
+        if let Some(&id) = self.function_ids.get(name) {
+            return id;
+        }
+        let name_index = self.string(name);
+        self.functions.push(name_index);
+        let id = self.functions.len() as u64;
+        self.function_ids.insert(name.to_owned(), id);
+        id
+    }
+
+    /// Adds a sample with the calls and self time of `node` and its descendants, `stack` holds the locations of its parents.
+    fn samples(
+        &mut self,
+        node: &crate::report::ScopeNode,
+        stack: &mut Vec<u64>,
+        thread_key: u64,
+        thread: u64,
+    ) {
+        stack.push(self.function(&node.name));
+        let self_time = node.self_time().as_nanos() as u64;
+        if node.count > 0 || self_time > 0 {
+            let mut sample = Vec::new();
+            // Locations go from the leaf to the root
+            let locations = stack.iter().rev().copied().collect::<Vec<_>>();
+            packed_field(&mut sample, 1, &locations);
+            packed_field(&mut sample, 2, &[node.count as u64, self_time]);
+            let mut label = Vec::new();
+            varint_field(&mut label, 1, thread_key);
+            varint_field(&mut label, 2, thread);
+            bytes_field(&mut sample, 3, &label);
+            bytes_field(&mut self.out, 2, &sample);
+        }
+        for child in &node.children {
+            self.samples(child, stack, thread_key, thread);
+        }
+        stack.pop();
+    }
+}
+
+fn varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn varint_field(out: &mut Vec<u8>, field: u64, v: u64) {
+    varint(out, field << 3);
+    varint(out, v);
+}
+
+fn bytes_field(out: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    varint(out, field << 3 | 2);
+    varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn packed_field(out: &mut Vec<u8>, field: u64, values: &[u64]) {
+    let mut packed = Vec::new();
+    for &v in values {
+        varint(&mut packed, v);
+    }
+    bytes_field(out, field, &packed);
+}
@@ -0,0 +1,23 @@
+//! Convenience re-exports of the most commonly used items.
+//!
+//! # Example
+//! ```
+//! use profi::prelude::*;
+//!
+//! fn main() {
+//!   print_on_exit!();
+//!   prof!(work);
+//! }
+//! ```
+
+pub use crate::{
+    milestone, new_frame, pause, print_on_exit, prof, prof_count, prof_future, prof_guard,
+    prof_ret, resume,
+};
+pub use crate::{
+    report, ExitPrinter, Format, Guard, PrintOptions, Report, ScopeNode, ScopeStats, Sort,
+    ThreadStats,
+};
+
+#[cfg(feature = "attributes")]
+pub use crate::profile;
@@ -1,217 +1,2436 @@
 #[cfg(feature = "enable")]
 use crate::Str;
 
+#[cfg(feature = "enable")]
+use crate::report::{Report, ScopeNode, ScopeStats, ThreadStats};
+
+/// Name with a padding equal to its hierarchy depth.
+#[cfg(feature = "enable")]
+fn formatted_name(name: &str, depth: usize) -> String {
+    // If it's >= 20, add a numeric indicator and limit the padding
+    let spaces = if depth >= 20 {
+        let new = format!("(+{depth}) ");
+        format!("{}{new}", " ".repeat(20usize.saturating_sub(new.len())))
+    } else {
+        " ".repeat(depth)
+    };
+    format!("{spaces}{name}")
+}
+
+/// Formats `value` with a metric suffix, like `1.50M`.
+#[cfg(feature = "enable")]
+fn si(mut value: f64) -> String {
+    const UNITS: [&str; 5] = ["", "k", "M", "G", "T"];
+
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 && value.fract() == 0.0 {
+        format!("{value}")
+    } else {
+        format!("{value:.2}{}", UNITS[unit])
+    }
+}
+
+/// Most used cores of the scope, as `core:calls`.
+#[cfg(feature = "enable")]
+fn formatted_cores(timing: &ScopeStats) -> String {
+    const SHOWN: usize = 4;
+
+    let mut cores = timing.cores.iter().collect::<Vec<_>>();
+    cores.sort_by_key(|c| std::cmp::Reverse(c.calls));
+    let mut s = cores
+        .iter()
+        .take(SHOWN)
+        .map(|c| format!("{}:{}", c.core, c.calls))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if cores.len() > SHOWN {
+        s += &format!(" (+{})", cores.len() - SHOWN);
+    }
+    s
+}
+
+#[cfg(feature = "enable")]
+fn create_table<'a>(
+    timings: impl IntoIterator<Item = &'a ScopeStats>,
+    report: &Report,
+    options: &crate::PrintOptions,
+) -> comfy_table::Table {
+    if let Some(format) = &options.table_format {
+        return create_custom_table(timings, format, options);
+    }
+    let threads = report.threads.len();
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::UTF8_FULL);
+    let mut header = vec!["Name", "% Application Time", "Real Time"];
+    let shutdown = report.shutdown.is_some();
+    if shutdown {
+        header.extend(["Steady state", "Shutdown"]);
+    }
+    let wait = report.scopes.iter().any(|s| !s.wait.is_zero());
+    if wait {
+        header.push("Intentional wait");
+    }
+    if threads > 1 {
+        header.extend(["% CPU Time", "CPU Time"]);
+    }
+    if report.energy.is_some() {
+        header.push("Energy");
+    }
+    let processes = report.scopes.iter().any(|s| !s.processes.is_empty());
+    if processes {
+        header.push("Process");
+    }
+    let owners = report.scopes.iter().any(|s| s.owner.is_some());
+    if owners {
+        header.push("Owner");
+    }
+    let cores = report.scopes.iter().any(|s| !s.cores.is_empty());
+    if cores {
+        header.push("Cores");
+    }
+    let switches = cfg!(feature = "context-switches");
+    if switches {
+        header.push("Context switches\n(voluntary / involuntary)");
+    }
+    let faults = cfg!(feature = "page-faults");
+    if faults {
+        header.push("Page faults\n(minor / major)");
+    }
+    let perf = cfg!(feature = "perf");
+    if perf {
+        header.extend(["Cycles", "Instructions\n(per cycle)", "Cache misses"]);
+    }
+    let mut counters = Vec::<&str>::new();
+    for (name, _) in report.scopes.iter().flat_map(|s| &s.counters) {
+        if !counters.contains(&name.as_str()) {
+            counters.push(name);
+        }
+    }
+    header.extend(&counters);
+    let mut aggregates = Vec::<&str>::new();
+    for (name, _) in report.scopes.iter().flat_map(|s| &s.aggregates) {
+        if !aggregates.contains(&name.as_str()) {
+            aggregates.push(name);
+        }
+    }
+    header.extend(&aggregates);
+    if report.frames.is_some() {
+        header.push("Per frame");
+    }
+    header.push("Average time");
+    if options.stddev {
+        header.extend(["Std. dev.", "CV"]);
+    }
+    header.push("Calls");
+    table.set_header(header);
+
+    let empty = || comfy_table::Cell::new("-").set_alignment(comfy_table::CellAlignment::Center);
+
+    for timing in timings {
+        fn cell(c: impl Into<comfy_table::Cell>) -> comfy_table::Cell {
+            c.into()
+        }
+
+        let depth = match options.sort {
+            crate::Sort::Tree => timing.depth,
+            _ => 0,
+        };
+        let name = match &options.format_name {
+            Some(f) => formatted_name(&f(timing), depth),
+            None => formatted_name(&timing.name, depth),
+        };
+        let name = cell(name);
+        let app_percent = cell(options.fmt_percent(timing.percent_app));
+        let real_time = cell(options.fmt_duration(timing.total_real));
+        let average = if timing.average.is_zero() || timing.calls <= 1 {
+            empty()
+        } else {
+            cell(format!("{}/call", options.fmt_duration(timing.average)))
+        };
+        let calls = match timing.sample_rate {
+            _ if timing.calls == 0 => empty(),
+            Some(rate) => cell(format!(
+                "{} ({:.1}% sampled)",
+                options.fmt_calls(timing.calls),
+                rate * 100.0
+            )),
+            None => cell(options.fmt_calls(timing.calls)),
+        }
+        .set_alignment(comfy_table::CellAlignment::Right);
+        let mut row = vec![name, app_percent, real_time];
+        if shutdown {
+            row.extend([
+                cell(options.fmt_duration(timing.steady_state())),
+                cell(options.fmt_duration(timing.shutdown)),
+            ])
+        }
+        if wait {
+            row.push(match timing.wait.is_zero() {
+                true => empty(),
+                false => cell(options.fmt_duration(timing.wait)),
+            });
+        }
+        if threads > 1 {
+            row.extend([
+                cell(options.fmt_percent(timing.percent_cpu)),
+                cell(options.fmt_duration(timing.total_cpu)),
+            ])
+        }
+        if report.energy.is_some() {
+            row.push(match timing.energy {
+                Some(joules) => cell(format!("{joules:.2} J")),
+                None => empty(),
+            });
+        }
+        if processes {
+            row.push(cell(timing.processes.join(", ")));
+        }
+        if owners {
+            row.push(match &timing.owner {
+                Some(owner) => cell(owner),
+                None => empty(),
+            });
+        }
+        if cores {
+            row.push(cell(formatted_cores(timing)));
+        }
+        if switches {
+            row.push(cell(format!(
+                "{} / {}",
+                timing.voluntary_switches, timing.involuntary_switches
+            )));
+        }
+        if faults {
+            row.push(cell(format!(
+                "{} / {}",
+                timing.minor_faults, timing.major_faults
+            )));
+        }
+        if perf {
+            let ipc = match timing.cycles {
+                0 => 0.0,
+                cycles => timing.instructions as f64 / cycles as f64,
+            };
+            row.extend([
+                cell(si(timing.cycles as f64)),
+                cell(format!("{} ({ipc:.2})", si(timing.instructions as f64))),
+                cell(si(timing.cache_misses as f64)),
+            ]);
+        }
+        for &counter in &counters {
+            row.push(match timing.counter(counter) {
+                Some(value) => {
+                    let rate = timing.rate(counter).unwrap_or_default();
+                    cell(format!("{} ({}/s)", si(value as f64), si(rate)))
+                }
+                None => empty(),
+            });
+        }
+        for &aggregate in &aggregates {
+            row.push(match timing.aggregate(aggregate) {
+                Some(value) => cell(crate::aggregate::format(aggregate, value)),
+                None => empty(),
+            });
+        }
+        if let Some(frames) = &report.frames {
+            row.push(cell(
+                options.fmt_duration(timing.total_real / frames.count.max(1) as u32),
+            ));
+        }
+        row.push(average);
+        if options.stddev {
+            if timing.calls <= 1 {
+                row.extend([empty(), empty()]);
+            } else {
+                let marker = match options.is_jittery(timing.cv) {
+                    true => " ⚠",
+                    false => "",
+                };
+                row.extend([
+                    cell(options.fmt_duration(timing.stddev)),
+                    cell(format!("{}{marker}", options.fmt_percent(timing.cv))),
+                ]);
+            }
+        }
+        row.push(calls);
+        table.add_row(row);
+    }
+
+    table
+}
+
+/// Table with the columns of `format`, see [`crate::TableFormat`].
+#[cfg(feature = "enable")]
+fn create_custom_table<'a>(
+    timings: impl IntoIterator<Item = &'a ScopeStats>,
+    format: &crate::TableFormat,
+    options: &crate::PrintOptions,
+) -> comfy_table::Table {
+    use crate::options::TableColumn;
+    use crate::Column;
+
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::UTF8_FULL);
+    table.set_header(format.columns.iter().map(|c| match c {
+        TableColumn::Builtin(column) => column.header(),
+        TableColumn::Custom { header, .. } => header,
+    }));
+
+    let several = |timing: &ScopeStats| timing.calls > 1;
+    for timing in timings {
+        let row = format.columns.iter().map(|c| {
+            let value = match c {
+                TableColumn::Custom { value, .. } => value(timing),
+                TableColumn::Builtin(column) => match column {
+                    Column::Name => {
+                        let depth = match options.sort {
+                            crate::Sort::Tree => timing.depth,
+                            _ => 0,
+                        };
+                        Some(match &options.format_name {
+                            Some(f) => formatted_name(&f(timing), depth),
+                            None => formatted_name(&timing.name, depth),
+                        })
+                    }
+                    Column::AppPercent => Some(options.fmt_percent(timing.percent_app)),
+                    Column::RealTime => Some(options.fmt_duration(timing.total_real)),
+                    Column::CpuPercent => Some(options.fmt_percent(timing.percent_cpu)),
+                    Column::CpuTime => Some(options.fmt_duration(timing.total_cpu)),
+                    Column::Average => (several(timing) && !timing.average.is_zero())
+                        .then(|| format!("{}/call", options.fmt_duration(timing.average))),
+                    Column::Min => (timing.calls > 0).then(|| options.fmt_duration(timing.min)),
+                    Column::Max => (timing.calls > 0).then(|| options.fmt_duration(timing.max)),
+                    Column::StdDev => several(timing).then(|| options.fmt_duration(timing.stddev)),
+                    Column::Cv => several(timing).then(|| options.fmt_percent(timing.cv)),
+                    Column::Calls => (timing.calls > 0).then(|| options.fmt_calls(timing.calls)),
+                    Column::Owner => timing.owner.clone(),
+                    Column::Counter(name) => timing.counter(name).map(|value| {
+                        let rate = timing.rate(name).unwrap_or_default();
+                        format!("{} ({}/s)", si(value as f64), si(rate))
+                    }),
+                    Column::Aggregate(name) => timing
+                        .aggregate(name)
+                        .map(|value| crate::aggregate::format(name, value)),
+                },
+            };
+            let cell = match value {
+                Some(value) => comfy_table::Cell::new(value),
+                None => {
+                    comfy_table::Cell::new("-").set_alignment(comfy_table::CellAlignment::Center)
+                }
+            };
+            match c {
+                TableColumn::Builtin(Column::Calls) => {
+                    cell.set_alignment(comfy_table::CellAlignment::Right)
+                }
+                _ => cell,
+            }
+        });
+        table.add_row(row.collect::<Vec<_>>());
+    }
+    table
+}
+
+/// Slowest calls of each scope, with their context.
+#[cfg(feature = "enable")]
+fn create_slow_table(scopes: &[&ScopeStats], options: &crate::PrintOptions) -> comfy_table::Table {
+    const SHOWN: usize = 5;
+
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::UTF8_FULL);
+    table.set_header(["Name", "Time", "Context"]);
+    for scope in scopes {
+        let mut calls = scope.slow_calls.iter().collect::<Vec<_>>();
+        calls.sort_by_key(|c| std::cmp::Reverse(c.duration));
+        for call in calls.iter().take(SHOWN) {
+            table.add_row([
+                scope.name.clone(),
+                options.fmt_duration(call.duration),
+                call.context.clone(),
+            ]);
+        }
+        if calls.len() > SHOWN {
+            table.add_row([
+                scope.name.clone(),
+                String::new(),
+                format!("(+{} more)", calls.len() - SHOWN),
+            ]);
+        }
+    }
+    table
+}
+
+#[cfg(feature = "enable")]
+pub(crate) fn create_diff_table(
+    diff: &crate::ReportDiff,
+    options: &crate::PrintOptions,
+) -> comfy_table::Table {
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::UTF8_FULL);
+    table.set_header(["Name", "Baseline", "Current", "Change", "p-value", ""]);
+    for scope in &diff.scopes {
+        let regression = diff.is_regression(scope);
+        table.add_row(diff_row(
+            scope.name.clone(),
+            (scope.baseline, scope.current),
+            scope.change(),
+            scope.p_value,
+            regression,
+            options,
+        ));
+    }
+    table
+}
+
+/// Like [`create_diff_table`], with the nodes indented by their depth.
+#[cfg(feature = "enable")]
+pub(crate) fn create_tree_diff_table(
+    diff: &crate::TreeDiff,
+    options: &crate::PrintOptions,
+) -> comfy_table::Table {
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::UTF8_FULL);
+    table.set_header(["Name", "Baseline", "Current", "Change", "p-value", ""]);
+    for (_, node) in diff.iter() {
+        table.add_row(diff_row(
+            formatted_name(&node.name, node.depth),
+            (node.baseline, node.current),
+            node.change(),
+            node.p_value,
+            diff.is_regression(node),
+            options,
+        ));
+    }
+    table
+}
+
+/// Row of a diff table: name, baseline, current, change, p-value and status.
+#[cfg(feature = "enable")]
+fn diff_row(
+    name: String,
+    (baseline, current): (Option<std::time::Duration>, Option<std::time::Duration>),
+    change: Option<f64>,
+    p_value: Option<f64>,
+    regression: bool,
+    options: &crate::PrintOptions,
+) -> [String; 6] {
+    let time = |d: Option<std::time::Duration>| match d {
+        Some(d) => options.fmt_duration(d),
+        None => "-".into(),
+    };
+    let delta = match (baseline, current) {
+        (Some(baseline), Some(current)) => {
+            let (sign, delta) = match current.checked_sub(baseline) {
+                Some(delta) => ('+', delta),
+                None => ('-', baseline - current),
+            };
+            match change {
+                Some(change) => format!("{sign}{} ({change:+.2}%)", options.fmt_duration(delta)),
+                None => format!("{sign}{}", options.fmt_duration(delta)),
+            }
+        }
+        _ => "-".into(),
+    };
+    let p_value = match p_value {
+        Some(p) if p < 0.001 => "< 0.001".into(),
+        Some(p) => format!("{p:.3}"),
+        None => "-".into(),
+    };
+    let status = match (baseline, current) {
+        (None, _) => "new",
+        (_, None) => "removed",
+        _ if regression => "REGRESSION",
+        _ => "",
+    };
+    [
+        name,
+        time(baseline),
+        time(current),
+        delta,
+        p_value,
+        status.into(),
+    ]
+}
+
+#[cfg(feature = "enable")]
+fn create_milestones_table(
+    milestones: &[crate::Milestone],
+    options: &crate::PrintOptions,
+) -> comfy_table::Table {
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::UTF8_FULL);
+    table.set_header(["Name", "Thread", "Since start", "Since previous"]);
+    let mut previous = std::time::Duration::ZERO;
+    for milestone in milestones {
+        table.add_row([
+            milestone.name.clone(),
+            milestone.thread.clone().unwrap_or_else(|| "-".into()),
+            options.fmt_duration(milestone.elapsed),
+            format!(
+                "+{}",
+                options.fmt_duration(milestone.elapsed.saturating_sub(previous))
+            ),
+        ]);
+        previous = milestone.elapsed;
+    }
+    table
+}
+
+#[cfg(feature = "enable")]
+pub(crate) fn create_runs_table(
+    runs: &crate::MultiRunReport,
+    options: &crate::PrintOptions,
+) -> comfy_table::Table {
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::UTF8_FULL);
+    table.set_header([
+        "Name",
+        "Mean ± Std. dev.",
+        "CV",
+        "Min",
+        "Max",
+        "Calls",
+        "Runs",
+    ]);
+    for scope in runs.scopes.iter().chain([&runs.wall_time]) {
+        let cv = scope.cv();
+        let marker = match options.is_jittery(cv) {
+            true => " ⚠",
+            false => "",
+        };
+        table.add_row([
+            scope.name.clone(),
+            format!(
+                "{} ± {}",
+                options.fmt_duration(scope.mean),
+                options.fmt_duration(scope.stddev)
+            ),
+            format!("{}{marker}", options.fmt_percent(cv)),
+            options.fmt_duration(scope.min),
+            options.fmt_duration(scope.max),
+            format!("{:.1}", scope.calls),
+            format!("{}/{}", scope.runs, runs.runs),
+        ]);
+    }
+    table
+}
+
+#[cfg(feature = "enable")]
+fn create_tail_table(
+    events: &[crate::TailEvent],
+    options: &crate::PrintOptions,
+) -> comfy_table::Table {
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::UTF8_FULL);
+    table.set_header(["Name", "Thread", "Since start", "Time", "p99", "Metadata"]);
+    for event in events {
+        table.add_row([
+            event.scope.clone(),
+            event.thread.clone().unwrap_or_else(|| "-".into()),
+            options.fmt_duration(event.elapsed),
+            options.fmt_duration(event.duration),
+            options.fmt_duration(event.p99),
+            event.metadata.clone().unwrap_or_default(),
+        ]);
+    }
+    table
+}
+
+/// Objects wrapped in a `Guard`, grouped by name.
+#[cfg(feature = "enable")]
+fn create_lifetimes_table(
+    lifetimes: &[crate::LifetimeStats],
+    options: &crate::PrintOptions,
+) -> comfy_table::Table {
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::UTF8_FULL);
+    table.set_header([
+        "Name",
+        "Created",
+        "Alive",
+        "Average lifetime",
+        "Longest lifetime",
+        "Max alive",
+        "Max alive at",
+    ]);
+    for lifetime in lifetimes {
+        // Nothing was dropped yet
+        let duration = |d| match lifetime.max.is_zero() {
+            true => "-".into(),
+            false => options.fmt_duration(d),
+        };
+        table.add_row([
+            lifetime.name.clone(),
+            lifetime.created.to_string(),
+            lifetime.alive.to_string(),
+            duration(lifetime.average),
+            duration(lifetime.max),
+            lifetime.max_alive.to_string(),
+            options.fmt_duration(lifetime.max_alive_at),
+        ]);
+    }
+    table
+}
+
+#[cfg(feature = "enable")]
+fn create_owners_table(
+    owners: &[crate::OwnerStats],
+    options: &crate::PrintOptions,
+) -> comfy_table::Table {
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::UTF8_FULL);
+    table.set_header([
+        "Owner",
+        "Scopes",
+        "% Application Time",
+        "Real Time",
+        "Calls",
+    ]);
+    for owner in owners {
+        table.add_row([
+            owner.owner.clone(),
+            owner.scopes.to_string(),
+            options.fmt_percent(owner.percent_app),
+            options.fmt_duration(owner.total_real),
+            owner.calls.to_string(),
+        ]);
+    }
+    table
+}
+
+#[cfg(feature = "enable")]
+fn create_budgets_table(
+    violations: &[crate::BudgetViolation],
+    options: &crate::PrintOptions,
+) -> comfy_table::Table {
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::UTF8_FULL);
+    table.set_header(["Name", "Limits", "Budget", "Actual", "Over by"]);
+    for violation in violations {
+        let limits = match violation.budget {
+            crate::Budget::Average(_) => "Average",
+            crate::Budget::Call(_) => "Every call",
+            crate::Budget::Total(_) => "Total",
+        };
+        table.add_row([
+            violation.scope.clone(),
+            limits.to_owned(),
+            options.fmt_duration(violation.budget.limit()),
+            options.fmt_duration(violation.actual),
+            options.fmt_duration(violation.over()),
+        ]);
+    }
+    table
+}
+
+#[cfg(feature = "enable")]
+fn create_findings_table(findings: &[crate::Finding]) -> comfy_table::Table {
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::UTF8_FULL);
+    table.set_header(["Severity", "Subject", "Finding"]);
+    for finding in findings {
+        table.add_row([
+            finding.severity.to_string(),
+            finding.subject.clone(),
+            finding.message.clone(),
+        ]);
+    }
+    table
+}
+
+/// Lifetime of the threads, grouped by name so short-lived threads spawned over and over take a single row.
+#[cfg(feature = "enable")]
+fn create_threads_table(report: &Report, options: &crate::PrintOptions) -> comfy_table::Table {
+    let mut groups =
+        indexmap::IndexMap::<&str, Vec<(std::time::Duration, std::time::Duration)>>::new();
+    for t in &report.threads {
+        // Still running threads live until the report
+        let exited = t.exited.unwrap_or(report.wall_time).max(t.started);
+        groups
+            .entry(t.name.as_deref().unwrap_or("-"))
+            .or_default()
+            .push((t.started, exited));
+    }
+
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::UTF8_FULL);
+    table.set_header([
+        "Name",
+        "Threads",
+        "Max alive",
+        "First started",
+        "Last exited",
+        "Average lifetime",
+        "Shortest",
+        "Longest",
+    ]);
+    for (name, lifetimes) in groups {
+        let mut events = lifetimes
+            .iter()
+            .flat_map(|&(started, exited)| [(started, 1), (exited, -1)])
+            .collect::<Vec<_>>();
+        // Exits first, so a thread that starts when another exits doesn't count as both alive
+        events.sort_by_key(|&(time, change)| (time, change));
+        let (mut alive, mut max_alive) = (0i64, 0);
+        for (_, change) in events {
+            alive += change;
+            max_alive = max_alive.max(alive);
+        }
+        let durations = lifetimes.iter().map(|(started, exited)| *exited - *started);
+        let total = durations.clone().sum::<std::time::Duration>();
+        let right = |c: comfy_table::Cell| c.set_alignment(comfy_table::CellAlignment::Right);
+        table.add_row([
+            comfy_table::Cell::new(name),
+            right(comfy_table::Cell::new(lifetimes.len())),
+            right(comfy_table::Cell::new(max_alive)),
+            comfy_table::Cell::new(
+                options.fmt_duration(lifetimes.iter().map(|l| l.0).min().unwrap_or_default()),
+            ),
+            comfy_table::Cell::new(
+                options.fmt_duration(lifetimes.iter().map(|l| l.1).max().unwrap_or_default()),
+            ),
+            comfy_table::Cell::new(options.fmt_duration(total / lifetimes.len() as u32)),
+            comfy_table::Cell::new(
+                options.fmt_duration(durations.clone().min().unwrap_or_default()),
+            ),
+            comfy_table::Cell::new(options.fmt_duration(durations.max().unwrap_or_default())),
+        ]);
+    }
+    table
+}
+
+#[cfg(feature = "enable")]
+fn create_utilization_table(
+    threads: &[crate::ThreadUtilization],
+    options: &crate::PrintOptions,
+) -> comfy_table::Table {
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::UTF8_FULL);
+    table.set_header([
+        "Thread",
+        "Name",
+        "Lifetime",
+        "Busy",
+        "Utilization",
+        "Longest idle",
+        "Scopes",
+    ]);
+    let right = |c: comfy_table::Cell| c.set_alignment(comfy_table::CellAlignment::Right);
+    for t in threads {
+        table.add_row([
+            right(comfy_table::Cell::new(t.index)),
+            comfy_table::Cell::new(t.name.as_deref().unwrap_or("-")),
+            comfy_table::Cell::new(options.fmt_duration(t.lifetime)),
+            comfy_table::Cell::new(options.fmt_duration(t.busy)),
+            comfy_table::Cell::new(options.fmt_percent(t.percent)),
+            match t.longest_idle {
+                Some(idle) => comfy_table::Cell::new(options.fmt_duration(idle)),
+                None => comfy_table::Cell::new("-"),
+            },
+            right(comfy_table::Cell::new(options.fmt_calls(t.scopes))),
+        ]);
+    }
+    table
+}
+
+#[cfg(feature = "enable")]
+fn create_diagnostics_table(threads: &[ThreadStats]) -> comfy_table::Table {
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::UTF8_FULL);
+    table.set_header([
+        "Thread",
+        "Name",
+        "Max depth",
+        "Guards created",
+        "Unclosed guards",
+        "Evicted scopes",
+    ]);
+    for t in threads {
+        let mut row =
+            vec![comfy_table::Cell::new(t.index).set_alignment(comfy_table::CellAlignment::Right)];
+        row.push(comfy_table::Cell::new(t.name.as_deref().unwrap_or("-")));
+        row.extend(
+            [t.max_depth, t.guards, t.unclosed, t.evicted].map(|c| {
+                comfy_table::Cell::new(c).set_alignment(comfy_table::CellAlignment::Right)
+            }),
+        );
+        table.add_row(row);
+    }
+    table
+}
+
+/// Slow calls kept per scope with the `bounded-memory` feature.
+#[cfg(feature = "enable")]
+const BOUNDED_SLOW_CALLS: usize = 16;
+
 #[cfg(feature = "enable")]
 #[derive(Debug, Clone)]
-struct Timing {
-    formatted_name: Str,
-    name: Str,
-    /// % Application Time
-    percent_app: f64,
-    /// Real Time
-    total_real: std::time::Duration,
-    /// % CPU Time
-    percent_cpu: f64,
-    /// CPU Time
-    total_cpu: std::time::Duration,
-    average: std::time::Duration,
-    calls: usize,
-    thread: usize,
-}
-
-#[cfg(feature = "enable")]
-impl Timing {
-    fn from_durations(
-        name: impl Into<Str>,
-        formatted_name: impl Into<Str>,
-        timings: &[std::time::Duration],
+pub(crate) struct Node {
+    /// Duration of each call, not recorded with the `bounded-memory` feature.
+    pub(crate) measures: Vec<std::time::Duration>,
+    pub(crate) count: usize,
+    pub(crate) sum: std::time::Duration,
+    /// Sum of the squared duration of each call, in seconds.
+    squares: f64,
+    /// Shortest call, excluding `last`.
+    min: std::time::Duration,
+    max: std::time::Duration,
+    last: std::time::Duration,
+    /// Time spent after the shutdown began.
+    shutdown: std::time::Duration,
+    /// Time spent in intentional waits.
+    wait: std::time::Duration,
+    /// Extra calls merged into the next call, see [`crate::set_coalescing`].
+    coalesced: usize,
+    /// Time of the earlier polls of the call in progress, see [`MeasureType::Polled`](crate::measure::MeasureType::Polled).
+    polled: std::time::Duration,
+    pub(crate) cores: Vec<crate::CoreUsage>,
+    pub(crate) usage: crate::rusage::Usage,
+    pub(crate) counters: indexmap::IndexMap<Str, u64>,
+    pub(crate) slow_calls: Vec<crate::SlowCall>,
+    /// Fields of the call in progress, added to `fields` when it ends.
+    call_fields: Vec<(Str, Str)>,
+    pub(crate) fields: Vec<crate::FieldStats>,
+    /// Open scopes of another thread it was opened beneath, only for roots.
+    parent: Vec<Str>,
+    pub(crate) children: indexmap::IndexMap<Str, Node>,
+    pub(crate) depth: usize,
+}
+
+#[cfg(feature = "enable")]
+impl Node {
+    fn new(depth: usize) -> Self {
+        Self {
+            measures: Vec::new(),
+            count: 0,
+            sum: std::time::Duration::ZERO,
+            squares: 0.0,
+            min: std::time::Duration::MAX,
+            max: std::time::Duration::ZERO,
+            last: std::time::Duration::ZERO,
+            shutdown: std::time::Duration::ZERO,
+            wait: std::time::Duration::ZERO,
+            coalesced: 0,
+            polled: std::time::Duration::ZERO,
+            cores: Vec::new(),
+            usage: Default::default(),
+            counters: indexmap::IndexMap::new(),
+            slow_calls: Vec::new(),
+            call_fields: Vec::new(),
+            fields: Vec::new(),
+            parent: Vec::new(),
+            children: indexmap::IndexMap::new(),
+            depth,
+        }
+    }
+
+    /// Adds the call from `start` to `end` along with its earlier polls, a poll that didn't complete its future adds nothing.
+    ///
+    /// The call stands for `scale` calls of the same duration, see [`MeasureType::Sampled`](crate::measure::MeasureType::Sampled).
+    fn add_call(
+        &mut self,
+        start: crate::clock::Instant,
+        end: crate::clock::Instant,
+        resumed: bool,
+        scale: usize,
+    ) {
+        if resumed {
+            return;
+        }
+        if let Some(shutdown) = crate::measure::GLOBAL_PROFILER.shutdown() {
+            self.shutdown += end.saturating_duration_since(start.max(shutdown)) * scale as u32;
+        }
+        let polled = std::mem::take(&mut self.polled);
+        self.add_elapsed(end.duration_since(start) + polled, scale);
+    }
+
+    /// Adds `scale` calls that took `elapsed` each.
+    fn add_elapsed(&mut self, elapsed: std::time::Duration, scale: usize) {
+        for (key, value) in std::mem::take(&mut self.call_fields) {
+            let field = crate::FieldStats {
+                key: key.into_owned(),
+                value: value.into_owned(),
+                count: scale,
+                sum: elapsed * scale as u32,
+                sum_squares: elapsed.as_secs_f64().powi(2) * scale as f64,
+                min: elapsed,
+                max: elapsed,
+            };
+            merge_field(&mut self.fields, field);
+        }
+        // A coalesced run counts as that many calls of the same duration
+        let calls = std::mem::take(&mut self.coalesced) + 1;
+        let per_call = elapsed.div_f64(calls as f64);
+        if self.count > 0 {
+            self.min = self.min.min(self.last);
+        }
+        if calls > 1 {
+            self.min = self.min.min(per_call);
+        }
+        self.count += calls * scale;
+        self.squares += per_call.as_secs_f64().powi(2) * (calls * scale) as f64;
+        self.last = per_call;
+        if !cfg!(feature = "bounded-memory") {
+            self.measures.push(per_call);
+        }
+        self.sum += elapsed * scale as u32;
+        self.max = self.max.max(self.last);
+    }
+
+    #[allow(unused)]
+    fn add_core(&mut self, core: usize) {
+        match self.cores.binary_search_by_key(&core, |c| c.core) {
+            Ok(i) => self.cores[i].calls += 1,
+            Err(i) => self.cores.insert(
+                i,
+                crate::CoreUsage {
+                    core,
+                    numa_node: None,
+                    calls: 1,
+                },
+            ),
+        }
+    }
+
+    /// Applies a `Count`, `Field`, `Parent`, `Slow`, `Wait`, `Coalesced` or `Polled` measure.
+    fn annotate(&mut self, ty: &crate::measure::MeasureType) {
+        match ty {
+            crate::measure::MeasureType::Count { name, value } => {
+                *self.counters.entry(name.get().into()).or_default() += value;
+            }
+            crate::measure::MeasureType::Field { field } => {
+                let (key, value) = &**field;
+                self.call_fields.retain(|(k, _)| *k != **key);
+                self.call_fields.push((key.clone().into(), value.clone()));
+            }
+            crate::measure::MeasureType::Parent { path } if self.parent.is_empty() => {
+                self.parent = path.iter().map(|name| name.clone().into()).collect();
+            }
+            crate::measure::MeasureType::Slow { call } => {
+                self.slow_calls.push((**call).clone());
+                if cfg!(feature = "bounded-memory") && self.slow_calls.len() > BOUNDED_SLOW_CALLS {
+                    if let Some((fastest, _)) = self
+                        .slow_calls
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, c)| c.duration)
+                    {
+                        self.slow_calls.swap_remove(fastest);
+                    }
+                }
+            }
+            crate::measure::MeasureType::Wait { duration } => self.wait += *duration,
+            crate::measure::MeasureType::Coalesced { calls } => self.coalesced = *calls,
+            crate::measure::MeasureType::Polled { duration } => self.polled += *duration,
+            _ => {}
+        }
+    }
+
+    /// Adds the calls, annotations and children of `other`, which ran after the calls of `self`.
+    fn merge(&mut self, other: Node) {
+        if other.count > 0 {
+            if self.count > 0 {
+                self.min = self.min.min(self.last);
+            }
+            self.min = self.min.min(other.min);
+            self.last = other.last;
+        }
+        self.measures.extend(other.measures);
+        self.count += other.count;
+        self.sum += other.sum;
+        self.squares += other.squares;
+        self.max = self.max.max(other.max);
+        self.shutdown += other.shutdown;
+        self.wait += other.wait;
+        for core in other.cores {
+            match self.cores.binary_search_by_key(&core.core, |c| c.core) {
+                Ok(i) => self.cores[i].calls += core.calls,
+                Err(i) => self.cores.insert(i, core),
+            }
+        }
+        self.usage += other.usage;
+        for (name, value) in other.counters {
+            *self.counters.entry(name).or_default() += value;
+        }
+        self.slow_calls.extend(other.slow_calls);
+        for field in other.fields {
+            merge_field(&mut self.fields, field);
+        }
+        if self.parent.is_empty() {
+            self.parent = other.parent;
+        }
+        for (name, child) in other.children {
+            match self.children.get_mut(&name) {
+                Some(node) => node.merge(child),
+                None => {
+                    self.children.insert(name, child);
+                }
+            }
+        }
+    }
+
+    /// Child `name`, created if it doesn't exist.
+    fn child(
+        children: &mut indexmap::IndexMap<Str, Node>,
+        name: &crate::names::Name,
+        depth: usize,
+    ) -> usize {
+        match children.get_index_of(name.get()) {
+            Some(idx) => idx,
+            None => {
+                children
+                    .insert_full(name.clone().into(), Node::new(depth))
+                    .0
+            }
+        }
+    }
+
+    fn into_scope(self, name: Str) -> ScopeNode {
+        ScopeNode {
+            name: name.into_owned(),
+            depth: self.depth,
+            durations: self.measures,
+            count: self.count,
+            sum: self.sum,
+            sum_squares: self.squares,
+            min: match self.count {
+                0 => std::time::Duration::ZERO,
+                _ => self.min.min(self.last),
+            },
+            max: self.max,
+            shutdown: self.shutdown,
+            wait: self.wait,
+            cores: self.cores,
+            voluntary_switches: self.usage.voluntary_switches,
+            involuntary_switches: self.usage.involuntary_switches,
+            minor_faults: self.usage.minor_faults,
+            major_faults: self.usage.major_faults,
+            cycles: self.usage.cycles,
+            instructions: self.usage.instructions,
+            cache_misses: self.usage.cache_misses,
+            counters: self
+                .counters
+                .into_iter()
+                .map(|(name, value)| (name.into_owned(), value))
+                .collect(),
+            slow_calls: self.slow_calls,
+            fields: self.fields,
+            parent: self.parent.into_iter().map(Str::into_owned).collect(),
+            children: self
+                .children
+                .into_iter()
+                .map(|(name, child)| child.into_scope(name))
+                .collect(),
+        }
+    }
+}
+
+/// Adds the calls of `field` to the ones with the same key and value in `fields`.
+#[cfg(feature = "enable")]
+fn merge_field(fields: &mut Vec<crate::FieldStats>, field: crate::FieldStats) {
+    match fields
+        .iter_mut()
+        .find(|f| f.key == field.key && f.value == field.value)
+    {
+        Some(f) => {
+            f.count += field.count;
+            f.sum += field.sum;
+            f.sum_squares += field.sum_squares;
+            f.min = f.min.min(field.min);
+            f.max = f.max.max(field.max);
+        }
+        None => fields.push(field),
+    }
+}
+
+#[cfg(feature = "enable")]
+impl ScopeNode {
+    /// Statistics of this scope and its descendants.
+    ///
+    /// With `max_depth`, only that many levels are shown, the descendants of the scopes above the last one are folded into a single `name (children)` row.
+    /// With `untracked`, the time of each scope not spent in its children is added as a `name <untracked>` row after them.
+    /// With `group_by`, the calls with each value of that field are added as a `name [key=value]` row after them.
+    fn to_timings(
+        &self,
         total: std::time::Duration,
         thread: usize,
+        max_depth: Option<usize>,
+        untracked: bool,
+        group_by: Option<&str>,
+    ) -> Vec<ScopeStats> {
+        if max_depth.is_none() && !untracked && group_by.is_none() {
+            return self
+                .iter()
+                .map(|s| ScopeStats::from_node(s, total, thread))
+                .collect();
+        }
+        let mut timings = vec![ScopeStats::from_node(self, total, thread)];
+        // Depths start at 0, so the rows of the children are at `depth + 1`
+        if max_depth.is_some_and(|max_depth| self.depth + 1 >= max_depth) {
+            return timings;
+        }
+        if !max_depth.is_some_and(|max_depth| self.depth + 2 >= max_depth) {
+            for child in &self.children {
+                timings.extend(child.to_timings(total, thread, max_depth, untracked, group_by));
+            }
+        } else if !self.children.is_empty() {
+            let descendants = self.fold_descendants();
+            timings.push(ScopeStats::from_node(&descendants, total, thread));
+        }
+        if let Some(untracked) = untracked.then(|| self.untracked()).flatten() {
+            timings.push(ScopeStats::from_node(&untracked, total, thread));
+        }
+        for field in self
+            .fields
+            .iter()
+            .filter(|f| Some(f.key.as_str()) == group_by)
+        {
+            timings.push(ScopeStats::from_node(&self.field(field), total, thread));
+        }
+        timings
+    }
+
+    /// The calls with the value of `field`, as a child.
+    fn field(&self, field: &crate::FieldStats) -> ScopeNode {
+        ScopeNode {
+            name: format!("{} [{}={}]", self.name, field.key, field.value),
+            depth: self.depth + 1,
+            count: field.count,
+            sum: field.sum,
+            sum_squares: field.sum_squares,
+            min: field.min,
+            max: field.max,
+            ..Default::default()
+        }
+    }
+
+    /// Time of the calls not spent in any child, `None` if it has no children or they cover all of it.
+    fn untracked(&self) -> Option<ScopeNode> {
+        if self.children.is_empty() {
+            return None;
+        }
+        let children = self.children.iter().map(|c| c.sum).sum();
+        let sum = self.sum.checked_sub(children).filter(|s| !s.is_zero())?;
+        Some(ScopeNode {
+            name: format!("{} <untracked>", self.name),
+            depth: self.depth + 1,
+            count: self.count,
+            sum,
+            sum_squares: sum.as_secs_f64().powi(2) / self.count.max(1) as f64,
+            ..Default::default()
+        })
+    }
+
+    /// Single node with the calls of all the descendants.
+    ///
+    /// The times of the children already include the ones of their own children, so only they are added to the total.
+    fn fold_descendants(&self) -> ScopeNode {
+        let mut folded = ScopeNode {
+            name: format!("{} (children)", self.name),
+            depth: self.depth + 1,
+            min: std::time::Duration::MAX,
+            ..Default::default()
+        };
+        for child in &self.children {
+            folded.sum += child.sum;
+            folded.shutdown += child.shutdown;
+            folded.wait += child.wait;
+            folded.voluntary_switches += child.voluntary_switches;
+            folded.involuntary_switches += child.involuntary_switches;
+            folded.minor_faults += child.minor_faults;
+            folded.major_faults += child.major_faults;
+            folded.cycles += child.cycles;
+            folded.instructions += child.instructions;
+            folded.cache_misses += child.cache_misses;
+            for node in child.iter() {
+                folded.durations.extend(&node.durations);
+                folded.count += node.count;
+                folded.sum_squares += node.sum_squares;
+                folded.min = folded.min.min(node.min);
+                folded.max = folded.max.max(node.max);
+                folded.slow_calls.extend(node.slow_calls.iter().cloned());
+            }
+        }
+        folded
+    }
+}
+
+#[cfg(feature = "enable")]
+impl ThreadStats {
+    fn from_measures(
+        index: usize,
+        name: Option<&str>,
+        folded: &Folded,
+        measures: &[crate::measure::Measure],
+        now: crate::clock::Instant,
+        progress: &mut crate::progress::Progress,
     ) -> Self {
-        let sum = timings.iter().sum::<std::time::Duration>();
-        let percent = if !total.is_zero() {
-            (sum.as_secs_f64() / total.as_secs_f64()) * 100.0
-        } else {
-            100.0
+        let (total, mut tree) = into_tree(folded, measures, now, progress);
+        let mut evicted = folded.evicted;
+        if let Some(max) = crate::retention::max_scopes() {
+            let scopes = count_scopes(&tree);
+            evicted += evict(&mut tree, scopes, max);
+        }
+        let mut thread = Self {
+            index,
+            name: name.map(String::from),
+            total,
+            scopes: tree
+                .into_iter()
+                .map(|(name, node)| node.into_scope(name))
+                .collect(),
+            guards: folded.guards,
+            max_depth: folded.max_depth,
+            evicted,
+            ..Default::default()
         };
-        let average = sum / timings.len().max(1) as u32;
-        Self {
-            name: name.into(),
-            formatted_name: formatted_name.into(),
-            percent_app: percent,
-            total_real: sum,
-            percent_cpu: percent,
-            total_cpu: sum,
-            average,
-            calls: timings.len(),
-            thread,
+
+        let mut depth = 0usize;
+        for m in measures {
+            match m.ty {
+                crate::measure::MeasureType::Start { .. } => {
+                    depth += 1;
+                    thread.guards += 1;
+                    thread.max_depth = thread.max_depth.max(depth);
+                }
+                crate::measure::MeasureType::Resume { .. } => {
+                    depth += 1;
+                    thread.max_depth = thread.max_depth.max(depth);
+                }
+                crate::measure::MeasureType::End => match depth.checked_sub(1) {
+                    Some(open) => depth = open,
+                    None => thread.unmatched += 1,
+                },
+                crate::measure::MeasureType::Coalesced { calls } => thread.guards += calls,
+                crate::measure::MeasureType::Count { .. }
+                | crate::measure::MeasureType::Field { .. }
+                | crate::measure::MeasureType::Parent { .. }
+                | crate::measure::MeasureType::Slow { .. }
+                | crate::measure::MeasureType::Wait { .. }
+                | crate::measure::MeasureType::Pause { .. }
+                | crate::measure::MeasureType::Polled { .. }
+                | crate::measure::MeasureType::Sampled { .. }
+                | crate::measure::MeasureType::Spilled { .. } => {}
+            }
+        }
+        thread.unclosed = depth;
+        thread
+    }
+}
+
+/// Longest time from `started` until `exited` without any open scope in `measures`.
+#[cfg(feature = "enable")]
+pub(crate) fn longest_idle(
+    measures: &[crate::measure::Measure],
+    started: crate::clock::Instant,
+    exited: crate::clock::Instant,
+) -> std::time::Duration {
+    let mut longest = std::time::Duration::ZERO;
+    let mut idle_since = Some(started);
+    let mut depth = 0usize;
+    for m in measures {
+        match m.ty {
+            crate::measure::MeasureType::Start { .. }
+            | crate::measure::MeasureType::Resume { .. } => {
+                if let Some(since) = idle_since.take() {
+                    longest = longest.max(m.time.saturating_duration_since(since));
+                }
+                depth += 1;
+            }
+            crate::measure::MeasureType::End => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    idle_since = Some(m.time);
+                }
+            }
+            _ => {}
+        }
+    }
+    match idle_since {
+        Some(since) => longest.max(exited.saturating_duration_since(since)),
+        None => longest,
+    }
+}
+
+/// Merges the scopes of `threads` into `timings`, returns the CPU time added.
+///
+/// The roots opened beneath a scope of another thread are merged last, after the descendants of that scope.
+#[cfg(feature = "enable")]
+fn merge_threads(
+    threads: &[ThreadStats],
+    timings: &mut indexmap::IndexMap<String, ScopeStats>,
+    hierarchy: crate::Hierarchy,
+    max_depth: Option<usize>,
+    untracked: bool,
+    group_by: Option<&str>,
+    expand_groups: bool,
+) -> std::time::Duration {
+    let roots = threads
+        .iter()
+        .flat_map(|thread| thread.scopes.iter().map(move |node| (thread, node)));
+    let (mut attached, roots) = roots.partition::<Vec<_>, _>(|(_, node)| !node.parent.is_empty());
+    // Their parents may be attached too
+    attached.sort_by_key(|(_, node)| node.parent.len());
+
+    let mut total_cpu = std::time::Duration::ZERO;
+    for (thread, node) in roots.into_iter().chain(attached) {
+        // Right after the last descendant of the parent, or as a root if it isn't in the table
+        let (mut at, offset) = match attachment(timings, &node.parent, hierarchy) {
+            Some((at, depth)) => (Some(at), depth),
+            None => (None, 0),
+        };
+        let node_timings =
+            node.to_timings(thread.total, thread.index, max_depth, untracked, group_by);
+        for mut timing in node_timings {
+            total_cpu += timing.total_cpu;
+            if let std::borrow::Cow::Owned(name) = crate::alias::resolve(&timing.name) {
+                timing.name = name;
+            }
+            let member = crate::groups::group_of(&timing.name)
+                .map(|group| std::mem::replace(&mut timing.name, group));
+            timing.processes.extend(thread.process.clone());
+            timing.depth += offset;
+            let grouped = member.is_some();
+            // The scope on its own beneath the row of its group
+            let breakdown = member.filter(|_| expand_groups).map(|member| ScopeStats {
+                name: format!("{} [{member}]", timing.name),
+                depth: timing.depth + 1,
+                ..timing.clone()
+            });
+
+            let rows = std::iter::once((timing, grouped)).chain(breakdown.map(|b| (b, false)));
+            for (timing, grouped) in rows {
+                let name = match hierarchy {
+                    crate::Hierarchy::Merged => timing.name.clone(),
+                    crate::Hierarchy::Deep => formatted_name(&timing.name, timing.depth),
+                };
+                if let Some(other) = timings.get_mut(&name) {
+                    if grouped {
+                        other.merge_group(timing);
+                    } else {
+                        other.merge(timing);
+                    }
+                } else if let Some(idx) = at.as_mut() {
+                    timings.shift_insert(*idx, name, timing);
+                    *idx += 1;
+                } else {
+                    timings.insert(name, timing);
+                }
+            }
+        }
+    }
+    total_cpu
+}
+
+/// Index after the last descendant of the row of the innermost scope of `parent`, and the depth of its children.
+#[cfg(feature = "enable")]
+fn attachment(
+    timings: &indexmap::IndexMap<String, ScopeStats>,
+    parent: &[String],
+    hierarchy: crate::Hierarchy,
+) -> Option<(usize, usize)> {
+    let name = crate::alias::resolve(parent.last()?);
+    let name = match crate::groups::group_of(&name) {
+        Some(group) => std::borrow::Cow::Owned(group),
+        None => name,
+    };
+    let idx = timings.get_index_of(&*match hierarchy {
+        crate::Hierarchy::Merged => name.into_owned(),
+        crate::Hierarchy::Deep => formatted_name(&name, parent.len() - 1),
+    })?;
+    let depth = timings[idx].depth;
+    let end = timings[idx + 1..]
+        .values()
+        .position(|t| t.depth <= depth)
+        .map_or(timings.len(), |descendants| idx + 1 + descendants);
+    Some((end, depth + 1))
+}
+
+/// Report with only the scopes of `thread`.
+#[cfg(feature = "enable")]
+pub(crate) fn thread_report(thread: &ThreadStats, hierarchy: crate::Hierarchy) -> Report {
+    let mut timings = indexmap::IndexMap::<String, ScopeStats>::new();
+    merge_threads(
+        std::slice::from_ref(thread),
+        &mut timings,
+        hierarchy,
+        None,
+        false,
+        None,
+        false,
+    );
+    timings.iter_mut().for_each(|(_, t)| {
+        t.update_percent(thread.total, thread.total);
+        crate::aggregate::apply(t);
+        t.owner = crate::owners::owner(&t.name);
+        t.description = crate::descriptions::get(&t.name);
+    });
+
+    Report {
+        scopes: timings.into_values().collect(),
+        threads: vec![thread.clone()],
+        total_app: thread.total,
+        total_cpu: thread.total,
+        energy: None,
+        milestones: Vec::new(),
+        shutdown: None,
+        started: None,
+        wall_time: thread.total,
+        noise: None,
+        hierarchy,
+        frames: None,
+        tail_events: Vec::new(),
+        lifetimes: Vec::new(),
+    }
+}
+
+#[cfg(feature = "enable")]
+pub(crate) fn into_report<'a>(
+    threads: impl IntoIterator<Item = (Option<&'a str>, &'a Folded, &'a [crate::measure::Measure])>,
+    now: crate::clock::Instant,
+) -> Report {
+    let threads = threads.into_iter().collect::<Vec<_>>();
+    let mut progress =
+        crate::progress::Progress::new(threads.iter().map(|(_, _, measures)| measures.len()).sum());
+    let threads = threads
+        .into_iter()
+        .enumerate()
+        .map(|(i, (name, folded, measures))| {
+            ThreadStats::from_measures(i, name, folded, measures, now, &mut progress)
+        });
+    let report = report_from_threads(threads);
+    progress.finish();
+    report
+}
+
+/// Report with the scopes of all `threads` merged by name.
+#[cfg(feature = "enable")]
+pub(crate) fn report_from_threads(threads: impl IntoIterator<Item = ThreadStats>) -> Report {
+    let thread_stats = threads.into_iter().collect::<Vec<_>>();
+    let total_app = thread_stats
+        .iter()
+        .map(|thread| thread.total)
+        .max()
+        .unwrap_or_default();
+
+    let mut timings = indexmap::IndexMap::<String, ScopeStats>::new();
+    let total_cpu = merge_threads(
+        &thread_stats,
+        &mut timings,
+        Default::default(),
+        None,
+        false,
+        None,
+        false,
+    );
+    timings.iter_mut().for_each(|(_, t)| {
+        t.update_percent(total_app, total_cpu);
+        crate::aggregate::apply(t);
+        t.owner = crate::owners::owner(&t.name);
+        t.description = crate::descriptions::get(&t.name);
+    });
+
+    Report {
+        scopes: timings.into_values().collect(),
+        threads: thread_stats,
+        total_app,
+        total_cpu,
+        energy: None,
+        milestones: Vec::new(),
+        shutdown: None,
+        started: None,
+        wall_time: total_app,
+        noise: None,
+        hierarchy: Default::default(),
+        frames: None,
+        tail_events: Vec::new(),
+        lifetimes: Vec::new(),
+    }
+}
+
+/// Statistics of the frames that start at each of `starts`, with the scopes that started during the slowest one.
+#[cfg(feature = "enable")]
+pub(crate) fn frame_stats<'a>(
+    starts: &[crate::clock::Instant],
+    threads: impl IntoIterator<Item = (Option<&'a str>, &'a [crate::measure::Measure])>,
+) -> Option<crate::FrameStats> {
+    let durations = starts
+        .windows(2)
+        .map(|w| w[1].saturating_duration_since(w[0]))
+        .collect::<Vec<_>>();
+    let (worst_index, &worst) = durations.iter().enumerate().max_by_key(|(_, d)| **d)?;
+    let total = durations.iter().sum::<std::time::Duration>();
+
+    let (start, end) = (starts[worst_index], starts[worst_index + 1]);
+    let threads = threads
+        .into_iter()
+        .map(|(name, measures)| (name, started_within(measures, start, end)))
+        .collect::<Vec<_>>();
+    let folded = Folded::default();
+    let mut progress = crate::progress::Progress::silent();
+    let slowest = report_from_threads(threads.iter().enumerate().map(|(i, (name, measures))| {
+        ThreadStats::from_measures(i, *name, &folded, measures, end, &mut progress)
+    }));
+    let mut scopes = slowest.scopes;
+    for scope in &mut scopes {
+        scope.update_percent(worst, slowest.total_cpu);
+    }
+
+    Some(crate::FrameStats {
+        count: durations.len(),
+        fps: match total.as_secs_f64() {
+            secs if secs > 0.0 => durations.len() as f64 / secs,
+            _ => 0.0,
+        },
+        mean: total / durations.len() as u32,
+        worst,
+        worst_index,
+        slowest: scopes,
+    })
+}
+
+/// Measures of the scopes that started between `start` and `end`, their parents are left out.
+#[cfg(feature = "enable")]
+fn started_within(
+    measures: &[crate::measure::Measure],
+    start: crate::clock::Instant,
+    end: crate::clock::Instant,
+) -> Vec<crate::measure::Measure> {
+    use crate::measure::MeasureType;
+
+    let mut kept = Vec::new();
+    // Whether each open scope is kept
+    let mut open = Vec::<bool>::new();
+    for m in measures {
+        match m.ty {
+            MeasureType::Start { .. } | MeasureType::Resume { .. } => {
+                let keep = m.time >= start && m.time < end;
+                open.push(keep);
+                if keep {
+                    kept.push(m.clone());
+                }
+            }
+            MeasureType::End => {
+                if open.pop() == Some(true) {
+                    kept.push(m.clone());
+                }
+            }
+            MeasureType::Count { .. }
+            | MeasureType::Field { .. }
+            | MeasureType::Parent { .. }
+            | MeasureType::Slow { .. }
+            | MeasureType::Wait { .. }
+            | MeasureType::Pause { .. }
+            | MeasureType::Coalesced { .. }
+            | MeasureType::Polled { .. }
+            | MeasureType::Sampled { .. }
+            | MeasureType::Spilled { .. } => {
+                if open.last() == Some(&true) {
+                    kept.push(m.clone());
+                }
+            }
+        }
+    }
+    kept
+}
+
+/// Copy of `report` with the scopes grouped by `hierarchy`,
+/// only `max_depth` levels deep, with the deeper scopes folded into a `name (children)` row of their ancestor,
+/// with `untracked`, the time of each scope not spent in its children as a `name <untracked>` row,
+/// and with `group_by`, the calls with each value of that field as a `name [key=value]` row.
+#[cfg(feature = "enable")]
+pub(crate) fn regroup(
+    report: &Report,
+    hierarchy: crate::Hierarchy,
+    max_depth: Option<usize>,
+    untracked: bool,
+    group_by: Option<&str>,
+    expand_groups: bool,
+) -> Report {
+    let mut timings = indexmap::IndexMap::<String, ScopeStats>::new();
+    merge_threads(
+        &report.threads,
+        &mut timings,
+        hierarchy,
+        max_depth,
+        untracked,
+        group_by,
+        expand_groups,
+    );
+    for (_, timing) in timings.iter_mut() {
+        timing.update_percent(report.total_app, report.total_cpu);
+        crate::aggregate::apply(timing);
+        timing.owner = crate::owners::owner(&timing.name);
+        timing.description = crate::descriptions::get(&timing.name);
+        // Only known after merging all the threads
+        if let Some(scope) = report.scope(&timing.name) {
+            timing.sample_rate = scope.sample_rate;
+            timing.energy = scope.energy;
         }
     }
-    fn merge(&mut self, other: Timing) {
-        if self.formatted_name.len() > other.formatted_name.len() {
-            self.formatted_name = other.formatted_name;
-        }
-        self.average = (self.average + other.average) / 2;
-        self.calls += other.calls;
-        if self.thread != other.thread {
-            self.total_cpu += other.total_cpu;
-            self.total_real = self.total_real.max(other.total_real);
-        }
+    Report {
+        scopes: timings.into_values().collect(),
+        hierarchy,
+        ..report.clone()
+    }
+}
+
+/// Writes the run label, start time, wall time, number of threads and build profile.
+#[cfg(feature = "enable")]
+fn write_header(
+    mut to: impl std::io::Write,
+    report: &Report,
+    options: &crate::PrintOptions,
+) -> std::io::Result<()> {
+    if let Some(label) = &options.label {
+        writeln!(to, "Run:       {label}")?;
     }
-    fn update_percent(&mut self, total_app: std::time::Duration, total_cpu: std::time::Duration) {
-        self.percent_app = (self.total_real.as_secs_f64() / total_app.as_secs_f64()) * 100.;
-        self.percent_cpu = (self.total_cpu.as_secs_f64() / total_cpu.as_secs_f64()) * 100.;
+    if let Some(started) = report.started {
+        writeln!(to, "Started:   {}", iso_timestamp(started))?;
     }
+    writeln!(
+        to,
+        "Wall time: {} ({})",
+        iso_duration(report.wall_time),
+        options.fmt_duration(report.wall_time)
+    )?;
+    writeln!(to, "Threads:   {}", report.threads.len())?;
+    if let Some(noise) = report.noise {
+        writeln!(
+            to,
+            "Noise:     ±{:.2}% (timer resolution {})",
+            noise.run_variance,
+            options.fmt_duration(noise.timer_resolution)
+        )?;
+    }
+    let build = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    writeln!(to, "Build:     {build}")
 }
 
+/// Formats `time` as an ISO 8601 UTC timestamp, like `2024-03-01T12:30:05.250Z`.
 #[cfg(feature = "enable")]
-fn create_table(timings: impl IntoIterator<Item = Timing>, threads: usize) -> comfy_table::Table {
-    let mut table = comfy_table::Table::new();
-    table.load_preset(comfy_table::presets::UTF8_FULL);
-    let mut header = vec!["Name", "% Application Time", "Real Time"];
-    if threads > 1 {
-        header.extend(["% CPU Time", "CPU Time"]);
+fn iso_timestamp(time: std::time::SystemTime) -> String {
+    let since_epoch = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Formats `duration` as an ISO 8601 duration, like `PT1M5.250S`.
+#[cfg(feature = "enable")]
+fn iso_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes) = (secs / 3600, secs % 3600 / 60);
+    let mut s = String::from("PT");
+    if hours > 0 {
+        s += &format!("{hours}H");
     }
-    header.extend(["Average time", "Calls"]);
-    table.set_header(header);
+    if hours > 0 || minutes > 0 {
+        s += &format!("{minutes}M");
+    }
+    s += &format!("{}.{:03}S", secs % 60, duration.subsec_millis());
+    s
+}
 
-    let empty = || comfy_table::Cell::new("-").set_alignment(comfy_table::CellAlignment::Center);
+/// Writes the table of `report` with the rows selected by `options`, noting how many were hidden.
+#[cfg(feature = "enable")]
+fn write_table(
+    mut to: impl std::io::Write,
+    report: &Report,
+    options: &crate::PrintOptions,
+) -> std::io::Result<()> {
+    let regrouped;
+    let hierarchy = options.hierarchy.unwrap_or(report.hierarchy);
+    let report = if options.max_depth.is_some()
+        || options.show_untracked
+        || options.group_by.is_some()
+        || options.expand_groups
+        || hierarchy != report.hierarchy
+    {
+        regrouped = regroup(
+            report,
+            hierarchy,
+            options.max_depth,
+            options.show_untracked,
+            options.group_by.as_deref(),
+            options.expand_groups,
+        );
+        &regrouped
+    } else {
+        report
+    };
+    let rows = options.rows(&report.scopes);
+    let table = create_table(rows.iter().copied(), report, options);
+    write_scopes(&mut to, &table, &rows, options)?;
+    let hidden = report.scopes.len() - rows.len();
+    if hidden > 0 {
+        write_text(
+            &mut to,
+            &format!("(+{hidden} scopes hidden)\n"),
+            options.format,
+        )?;
+    }
+    Ok(())
+}
 
-    for timing in timings {
-        fn cell(c: impl Into<comfy_table::Cell>) -> comfy_table::Cell {
-            c.into()
-        }
+/// Writes the `table` of `rows` like [`write_rendered`], coloring each row if enabled in `options`.
+#[cfg(feature = "enable")]
+fn write_scopes(
+    mut to: impl std::io::Write,
+    table: &comfy_table::Table,
+    rows: &[&ScopeStats],
+    options: &crate::PrintOptions,
+) -> std::io::Result<()> {
+    if options.format == crate::Format::Html {
+        let titles = rows
+            .iter()
+            .map(|s| s.description.as_deref())
+            .collect::<Vec<_>>();
+        return write!(to, "{}", html_table(table, &titles));
+    }
+    if !options.color || options.format != crate::Format::Table {
+        return write_rendered(to, table, options.format);
+    }
+    let styles = rows
+        .iter()
+        .map(|s| match crate::budgets::is_over(s) {
+            true => Some("\x1b[1;31m"),
+            false => options.row_style(s.percent_app),
+        })
+        .collect::<Vec<_>>();
+    write!(to, "{}", colorize(&table.to_string(), &styles))
+}
 
-        let name = cell(timing.formatted_name);
-        let app_percent = cell(format!("{:.2}%", timing.percent_app));
-        let real_time = cell(format!("{:.2?}", timing.total_real));
-        let average = if timing.average.is_zero() || timing.calls <= 1 {
-            empty()
-        } else {
-            cell(format!("{:.2?}/call", timing.average))
-        };
-        let calls = if timing.calls == 0 {
-            empty()
-        } else {
-            cell(timing.calls).set_alignment(comfy_table::CellAlignment::Right)
+/// Wraps the contents of each row of a rendered table in the ANSI escape code of `styles`, which has one per row.
+///
+/// Relies on the `UTF8_FULL` preset, where the header ends with a `╞` line and rows are separated by `├` lines.
+#[cfg(feature = "enable")]
+fn colorize(table: &str, styles: &[Option<&str>]) -> String {
+    const RESET: &str = "\x1b[0m";
+
+    let mut out = String::with_capacity(table.len());
+    let mut row = None;
+    for line in table.lines() {
+        let style = match line.chars().next() {
+            Some('╞') => {
+                row = Some(0);
+                None
+            }
+            Some('├') => {
+                row = row.map(|r| r + 1);
+                None
+            }
+            Some('│') => row.and_then(|r| styles.get(r).copied().flatten()),
+            _ => None,
         };
-        let mut row = vec![name, app_percent, real_time];
-        if threads > 1 {
-            row.extend([
-                cell(format!("{:.2}%", timing.percent_cpu)),
-                cell(format!("{:.2?}", timing.total_cpu)),
-            ])
+        match style {
+            // Keep the outer borders uncolored
+            Some(style) if line.chars().count() > 2 => {
+                let (border, rest) = line.split_at('│'.len_utf8());
+                let (contents, end) = rest.split_at(rest.len() - '│'.len_utf8());
+                out.push_str(border);
+                out.push_str(style);
+                out.push_str(contents);
+                out.push_str(RESET);
+                out.push_str(end);
+            }
+            _ => out.push_str(line),
         }
-        row.extend([average, calls]);
-        table.add_row(row);
+        out.push('\n');
     }
+    out
+}
 
-    table
+/// Writes `table` as Markdown or HTML if `format` is one of them, or as a text table otherwise.
+#[cfg(feature = "enable")]
+pub(crate) fn write_rendered(
+    mut to: impl std::io::Write,
+    table: &comfy_table::Table,
+    format: crate::Format,
+) -> std::io::Result<()> {
+    match format {
+        crate::Format::Markdown => writeln!(to, "{}", markdown_table(table)),
+        crate::Format::Html => write!(to, "{}", html_table(table, &[])),
+        _ => writeln!(to, "{table}"),
+    }
 }
 
+/// Writes the title of a section in `format`.
 #[cfg(feature = "enable")]
-#[derive(Debug, Clone)]
-struct Node {
-    measures: Vec<std::time::Duration>,
-    children: indexmap::IndexMap<Str, Node>,
-    depth: usize,
+fn write_title(
+    mut to: impl std::io::Write,
+    title: &str,
+    format: crate::Format,
+) -> std::io::Result<()> {
+    match format {
+        crate::Format::Markdown => writeln!(to, "### {title}\n"),
+        crate::Format::Html => writeln!(to, "<h3>{}</h3>", html_escape(title)),
+        _ => writeln!(to, "{title}"),
+    }
 }
 
+/// Writes plain `text` in `format`, keeping its line breaks.
 #[cfg(feature = "enable")]
-impl Node {
-    fn new(depth: usize) -> Self {
-        Self {
-            measures: Vec::new(),
-            children: indexmap::IndexMap::new(),
-            depth,
+fn write_text(
+    mut to: impl std::io::Write,
+    text: &str,
+    format: crate::Format,
+) -> std::io::Result<()> {
+    match format {
+        crate::Format::Markdown => {
+            for line in text.lines() {
+                // Two trailing spaces are a line break
+                writeln!(to, "{line}  ")?;
+            }
+            writeln!(to)
         }
+        crate::Format::Html => writeln!(to, "<pre>{}</pre>", html_escape(text.trim_end())),
+        _ => write!(to, "{text}"),
     }
+}
 
-    fn to_timings(&self, name: Str, total: std::time::Duration, thread: usize) -> Vec<Timing> {
-        let formatted_name = {
-            // Add a padding equal to hierarchy depth
-            // If it's >= 20, add a numeric indicator and limit the padding
-            let spaces = if self.depth >= 20 {
-                let new = format!("(+{}) ", self.depth);
-                format!("{}{new}", " ".repeat(20usize.saturating_sub(new.len())))
-            } else {
-                " ".repeat(self.depth)
-            };
-            format!("{spaces}{name}")
-        };
-        let timing = Timing::from_durations(name, formatted_name, &self.measures, total, thread);
-        std::iter::once(timing)
-            .chain(
-                self.children
-                    .iter()
-                    .flat_map(|(name, child)| child.to_timings(name.clone(), total, thread)),
-            )
-            .collect()
+/// Renders `table` as a GitHub-flavoured Markdown table.
+#[cfg(feature = "enable")]
+fn markdown_table(table: &comfy_table::Table) -> String {
+    fn cell(content: &str) -> String {
+        // Leading spaces are collapsed, so the hierarchy is indented with non-breaking ones
+        let name = content.trim_start_matches(' ');
+        let indent = "&nbsp;".repeat(content.len() - name.len());
+        format!("{indent}{}", name.replace('|', "\\|").replace('\n', " "))
     }
+    fn row<'a>(cells: impl Iterator<Item = &'a comfy_table::Cell>) -> String {
+        let cells = cells.map(|c| cell(&c.content())).collect::<Vec<_>>();
+        format!("| {} |\n", cells.join(" | "))
+    }
+
+    let mut s = String::new();
+    if let Some(header) = table.header() {
+        s += &row(header.cell_iter());
+        s += &format!("|{}\n", " --- |".repeat(header.cell_count()));
+    }
+    for r in table.row_iter() {
+        s += &row(r.cell_iter());
+    }
+    s
 }
 
+/// Renders `table` as an HTML `<table>`.
 #[cfg(feature = "enable")]
-pub fn print_timings(
-    threads: &[(std::time::Duration, Vec<crate::measure::Measure>)],
+fn html_table(table: &comfy_table::Table, titles: &[Option<&str>]) -> String {
+    fn cell(tag: &str, content: &str, title: Option<&str>) -> String {
+        let name = content.trim_start_matches(' ');
+        let indent = content.len() - name.len();
+        let content = html_escape(name).replace('\n', "<br>");
+        let mut attributes = String::new();
+        if indent > 0 {
+            attributes += &format!(" style=\"padding-left: {indent}em\"");
+        }
+        if let Some(title) = title {
+            attributes += &format!(" title=\"{}\"", html_escape(title));
+        }
+        format!("<{tag}{attributes}>{content}</{tag}>")
+    }
+
+    let mut s = String::from("<table>\n");
+    if let Some(header) = table.header() {
+        let cells = header.cell_iter().map(|c| cell("th", &c.content(), None));
+        s += &format!("<thead><tr>{}</tr></thead>\n", cells.collect::<String>());
+    }
+    s += "<tbody>\n";
+    for (i, row) in table.row_iter().enumerate() {
+        // The title of the row is a tooltip on its first cell, the name of the scope
+        let title = titles.get(i).copied().flatten();
+        let cells = row
+            .cell_iter()
+            .enumerate()
+            .map(|(j, c)| cell("td", &c.content(), title.filter(|_| j == 0)));
+        s += &format!("<tr>{}</tr>\n", cells.collect::<String>());
+    }
+    s += "</tbody>\n</table>\n";
+    s
+}
+
+#[cfg(feature = "enable")]
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes a standalone HTML page with the tables and a collapsible tree of the scopes of each thread.
+#[cfg(feature = "enable")]
+pub(crate) fn print_html(
+    report: &Report,
     mut to: impl std::io::Write,
+    options: &crate::PrintOptions,
 ) -> std::io::Result<()> {
-    let mut total_app = std::time::Duration::ZERO;
-    let mut total_cpu = std::time::Duration::ZERO;
+    const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.5em; text-align: left; }
+th { background: #f0f0f0; }
+details { margin-left: 1.5em; }
+summary { cursor: pointer; }
+.leaf { margin-left: 2.7em; }
+.time { color: #666; }";
 
-    let mut timings = indexmap::IndexMap::<crate::Str, Timing>::new();
-
-    for (i, (_, measures)) in threads.iter().enumerate() {
-        let (total_thread, thread) = into_tree(measures);
-        total_app = total_app.max(total_thread);
-        let thread = thread
-            .iter()
-            .flat_map(|(name, node)| node.to_timings(name.clone(), total_thread, i));
-        for timing in thread {
-            total_cpu += timing.total_cpu;
-            let name = {
-                #[cfg(feature = "deep-hierarchy")]
-                {
-                    timing.formatted_name.clone()
-                }
-                #[cfg(not(feature = "deep-hierarchy"))]
-                {
-                    timing.name.clone()
-                }
-            };
+    fn write_node(
+        to: &mut impl std::io::Write,
+        node: &ScopeNode,
+        report: &Report,
+        options: &crate::PrintOptions,
+    ) -> std::io::Result<()> {
+        let name = match report
+            .scope(&node.name)
+            .and_then(|s| s.description.as_deref())
+        {
+            Some(description) => format!(
+                "<span title=\"{}\">{}</span>",
+                html_escape(description),
+                html_escape(&node.name)
+            ),
+            None => html_escape(&node.name),
+        };
+        let summary = format!(
+            "{name} <span class=\"time\">{} ({} calls)</span>",
+            html_escape(&options.fmt_duration(node.sum)),
+            node.count
+        );
+        if node.children.is_empty() {
+            return writeln!(to, "<div class=\"leaf\">{summary}</div>");
+        }
+        writeln!(to, "<details open><summary>{summary}</summary>")?;
+        for child in &node.children {
+            write_node(to, child, report, options)?;
+        }
+        writeln!(to, "</details>")
+    }
 
-            if let Some(other) = timings.get_mut(name.as_ref()) {
-                other.merge(timing);
-            } else {
-                #[cfg(feature = "deep-hierarchy")]
-                timings.insert(name, timing);
-                #[cfg(not(feature = "deep-hierarchy"))]
-                timings.insert(name, timing);
-            }
+    let title = html_escape(options.label.as_deref().unwrap_or("profi report"));
+    writeln!(to, "<!DOCTYPE html>")?;
+    writeln!(to, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(to, "<title>{title}</title>\n<style>\n{STYLE}\n</style>")?;
+    writeln!(to, "</head>\n<body>\n<h1>{title}</h1>")?;
+    print_timings(report, &mut to, options)?;
+    writeln!(to, "<h2>Scopes</h2>")?;
+    for thread in &report.threads {
+        let name = match &thread.name {
+            Some(name) => format!("Thread {} ({name})", thread.index),
+            None => format!("Thread {}", thread.index),
+        };
+        writeln!(
+            to,
+            "<details open><summary>{}</summary>",
+            html_escape(&name)
+        )?;
+        for node in &thread.scopes {
+            write_node(&mut to, node, report, options)?;
         }
+        writeln!(to, "</details>")?;
     }
-    timings
-        .iter_mut()
-        .for_each(|(_, t)| t.update_percent(total_app, total_cpu));
+    writeln!(to, "</body>\n</html>")
+}
 
+#[cfg(feature = "enable")]
+pub fn print_timings(
+    report: &Report,
+    mut to: impl std::io::Write,
+    options: &crate::PrintOptions,
+) -> std::io::Result<()> {
     #[cfg(feature = "metaprof")]
     {
-        let total_average = timings
+        let total_average = report
+            .scopes
             .iter()
-            .map(|t| t.1.average)
+            .map(|t| t.average)
             .sum::<std::time::Duration>();
-        let calls = timings.iter().map(|t| t.1.calls).sum::<usize>() as u32;
+        let calls = report.scopes.iter().map(|t| t.calls).sum::<usize>() as u32;
         eprintln!(
             "[profi] The average time per measure in your machine is: {:#?}",
             total_average / calls
         );
         writeln!(to, "\n\t\tTime/Measure: {:#?}\n", total_average / calls)?;
     }
-    writeln!(to, "{}", create_table(timings.into_values(), threads.len()))
+    let format = options.format;
+    if options.header || options.label.is_some() {
+        let mut header = Vec::new();
+        write_header(&mut header, report, options)?;
+        write_text(&mut to, &String::from_utf8_lossy(&header), format)?;
+    }
+    if options.per_thread {
+        for thread in &report.threads {
+            let title = match &thread.name {
+                Some(name) => format!("Thread {} ({name})", thread.index),
+                None => format!("Thread {}", thread.index),
+            };
+            write_title(&mut to, &title, format)?;
+            let report = Report {
+                shutdown: report.shutdown,
+                started: report.started,
+                wall_time: report.wall_time,
+                ..thread_report(thread, report.hierarchy)
+            };
+            write_table(&mut to, &report, options)?;
+        }
+    } else {
+        write_table(&mut to, report, options)?;
+    }
+
+    let violations = report.budget_violations();
+    if !violations.is_empty() {
+        write_title(&mut to, "Budgets", format)?;
+        let table = create_budgets_table(&violations, options);
+        write_rendered(&mut to, &table, format)?;
+    }
+
+    if options.threads && !report.threads.is_empty() {
+        write_title(&mut to, "Threads", format)?;
+        let table = create_threads_table(report, options);
+        write_rendered(&mut to, &table, format)?;
+    }
+
+    if options.utilization && !report.threads.is_empty() {
+        write_title(&mut to, "Utilization", format)?;
+        let table = create_utilization_table(&report.utilization(), options);
+        write_rendered(&mut to, &table, format)?;
+    }
+
+    let evicted = report.threads.iter().map(|t| t.evicted).sum::<usize>();
+    if evicted > 0 {
+        let note = format!(
+            "{evicted} scopes were evicted to stay under the retention limit, their time is in the `<evicted>` rows\n"
+        );
+        write_text(&mut to, &note, format)?;
+    }
+
+    let owners = report.owners();
+    if !owners.is_empty() {
+        write_title(&mut to, "Owners", format)?;
+        let table = create_owners_table(&owners, options);
+        write_rendered(&mut to, &table, format)?;
+    }
+
+    if !report.lifetimes.is_empty() {
+        write_title(&mut to, "Lifetimes", format)?;
+        let table = create_lifetimes_table(&report.lifetimes, options);
+        write_rendered(&mut to, &table, format)?;
+    }
+
+    if !report.milestones.is_empty() {
+        write_title(&mut to, "Milestones", format)?;
+        let table = create_milestones_table(&report.milestones, options);
+        write_rendered(&mut to, &table, format)?;
+    }
+
+    if let Some(frames) = &report.frames {
+        write_title(&mut to, "Frames", format)?;
+        let summary = format!(
+            "{} frames, {:.1} FPS, mean {}, worst {} (frame {})\n",
+            frames.count,
+            frames.fps,
+            options.fmt_duration(frames.mean),
+            options.fmt_duration(frames.worst),
+            frames.worst_index
+        );
+        write_text(&mut to, &summary, format)?;
+        if !frames.slowest.is_empty() {
+            write_title(
+                &mut to,
+                &format!("Slowest frame ({})", frames.worst_index),
+                format,
+            )?;
+            let slowest = Report {
+                scopes: frames.slowest.clone(),
+                total_app: frames.worst,
+                total_cpu: frames.worst,
+                ..Default::default()
+            };
+            let rows = options.rows(&slowest.scopes);
+            let table = create_table(rows.iter().copied(), &slowest, options);
+            write_scopes(&mut to, &table, &rows, options)?;
+        }
+    }
+
+    let slow = report
+        .scopes
+        .iter()
+        .filter(|s| !s.slow_calls.is_empty())
+        .collect::<Vec<_>>();
+    if !slow.is_empty() {
+        write_title(&mut to, "Slow calls", format)?;
+        write_rendered(&mut to, &create_slow_table(&slow, options), format)?;
+    }
+
+    if !report.tail_events.is_empty() {
+        write_title(&mut to, "Tail events", format)?;
+        let table = create_tail_table(&report.tail_events, options);
+        write_rendered(&mut to, &table, format)?;
+    }
+
+    if options.diagnostics {
+        write_title(&mut to, "Diagnostics", format)?;
+        let table = create_diagnostics_table(&report.threads);
+        write_rendered(&mut to, &table, format)?;
+        let collisions = report.name_collisions();
+        if !collisions.is_empty() {
+            let mut text = String::from("Suspected name collisions:\n");
+            for collision in collisions {
+                text += &format!("- {collision}\n");
+            }
+            write_text(&mut to, &text, format)?;
+        }
+    }
+
+    if options.findings {
+        write_title(&mut to, "Findings", format)?;
+        let findings = report.findings();
+        match findings.is_empty() {
+            true => write_text(&mut to, "Nothing stood out\n", format)?,
+            false => write_rendered(&mut to, &create_findings_table(&findings), format)?,
+        }
+    }
+    Ok(())
+}
+
+/// Scopes of a thread that were folded as soon as they closed, with the `bounded-memory` feature.
+#[cfg(feature = "enable")]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Folded {
+    tree: indexmap::IndexMap<Str, Node>,
+    guards: usize,
+    max_depth: usize,
+    /// Number of nodes in `tree`
+    scopes: usize,
+    /// Scopes merged into an `<evicted>` row, see [`crate::Retention::max_scopes`]
+    evicted: usize,
+}
+
+#[cfg(feature = "enable")]
+impl Folded {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Closes the innermost open scope of `measures` with `end`, and folds it into the tree.
+    ///
+    /// Every `Start` and `Resume` in `measures` must still be open.
+    #[allow(unused)]
+    pub(crate) fn fold_last(
+        &mut self,
+        measures: &mut Vec<crate::measure::Measure>,
+        end: &crate::measure::Measure,
+    ) {
+        use crate::measure::MeasureType;
+
+        let Some(idx) = measures
+            .iter()
+            .rposition(|m| matches!(m.ty, MeasureType::Start { .. } | MeasureType::Resume { .. }))
+        else {
+            return;
+        };
+        let mut path = measures[..=idx].iter().filter_map(|m| match &m.ty {
+            MeasureType::Start { name } | MeasureType::Resume { name } => Some(name),
+            _ => None,
+        });
+        let Some(first) = path.next() else {
+            return;
+        };
+        let len = self.tree.len();
+        let root = Node::child(&mut self.tree, first, 0);
+        self.scopes += self.tree.len() - len;
+        let mut node = &mut self.tree[root];
+        for name in path {
+            let depth = node.depth + 1;
+            let len = node.children.len();
+            let child = Node::child(&mut node.children, name, depth);
+            self.scopes += node.children.len() - len;
+            node = &mut node.children[child];
+        }
+
+        let start = &measures[idx];
+        let resumed = matches!(start.ty, MeasureType::Resume { .. });
+        let mut start_time = start.time;
+        // The sampled parents are skipped along with all their children
+        let scale = measures
+            .iter()
+            .filter_map(|m| match m.ty {
+                MeasureType::Sampled { every } => Some(every),
+                _ => None,
+            })
+            .product::<usize>();
+        // Annotated before adding the call, a coalesced run applies to it
+        let mut wait = std::time::Duration::ZERO;
+        for m in &measures[idx + 1..] {
+            match m.ty {
+                MeasureType::Wait { duration } => node.annotate(&MeasureType::Wait {
+                    duration: duration * scale as u32,
+                }),
+                _ => node.annotate(&m.ty),
+            }
+            match m.ty {
+                MeasureType::Pause { duration } => {
+                    start_time += paused(start_time, m.time, duration)
+                }
+                MeasureType::Wait { duration } => wait += duration,
+                MeasureType::Coalesced { calls } => self.guards += calls,
+                _ => {}
+            }
+        }
+        node.add_call(start_time, end.time, resumed, scale);
+        node.usage += end.usage().since(start.usage()) * scale as u64;
+        #[cfg(feature = "cores")]
+        if !resumed {
+            node.add_core(start.core as usize);
+        }
+        self.guards += usize::from(!resumed);
+        self.max_depth = self.max_depth.max(node.depth + 1);
+        let pauses = measures
+            .drain(idx..)
+            .filter(|m| matches!(m.ty, MeasureType::Pause { .. }))
+            .collect::<Vec<_>>();
+        if measures.is_empty() {
+            return;
+        }
+        // The parents also spent that time waiting
+        if !wait.is_zero() {
+            measures.push(crate::measure::Measure {
+                ty: MeasureType::Wait { duration: wait },
+                ..end.clone()
+            });
+        }
+        // And were paused for the same time
+        measures.extend(pauses);
+    }
+
+    /// Folds the closed scopes of `measures` into the tree, returns the open ones with their annotations.
+    pub(crate) fn fold_closed(
+        &mut self,
+        measures: impl IntoIterator<Item = crate::measure::Measure>,
+    ) -> Vec<crate::measure::Measure> {
+        let mut open = Vec::new();
+        for m in measures {
+            match m.ty {
+                crate::measure::MeasureType::End => self.fold_last(&mut open, &m),
+                _ => open.push(m),
+            }
+        }
+        open
+    }
+
+    /// Evicts the scopes with the least time if there are more than `max`, down to three quarters of it.
+    pub(crate) fn evict(&mut self, max: usize) {
+        if self.scopes > max {
+            self.evicted += evict(&mut self.tree, self.scopes, max - max / 4);
+            self.scopes = count_scopes(&self.tree);
+        }
+    }
+}
+
+/// Name of the row with the evicted children of a scope.
+#[cfg(feature = "enable")]
+const EVICTED: &str = "<evicted>";
+
+/// Number of nodes in `tree`.
+#[cfg(feature = "enable")]
+fn count_scopes(tree: &indexmap::IndexMap<Str, Node>) -> usize {
+    tree.values().map(|n| 1 + count_scopes(&n.children)).sum()
+}
+
+/// Merges the scopes of `tree` with the least time into an `<evicted>` row of their parent,
+/// until there are at most `max` of the `scopes`, returns how many were merged.
+#[cfg(feature = "enable")]
+fn evict(tree: &mut indexmap::IndexMap<Str, Node>, scopes: usize, max: usize) -> usize {
+    fn candidates(
+        tree: &indexmap::IndexMap<Str, Node>,
+        path: &mut Vec<Str>,
+        out: &mut Vec<(std::time::Duration, Vec<Str>)>,
+    ) {
+        for (name, node) in tree {
+            if name == EVICTED {
+                continue;
+            }
+            path.push(name.clone());
+            // Without calls it's an ancestor that is still open
+            if node.count > 0 {
+                out.push((node.sum, path.clone()));
+            }
+            candidates(&node.children, path, out);
+            path.pop();
+        }
+    }
+
+    let mut candidates_by_time = Vec::new();
+    candidates(tree, &mut Vec::new(), &mut candidates_by_time);
+    // The deepest first on ties, so children go before their parents
+    candidates_by_time
+        .sort_by(|(a, a_path), (b, b_path)| a.cmp(b).then(b_path.len().cmp(&a_path.len())));
+
+    let mut remaining = scopes;
+    let mut evicted = 0;
+    for (_, path) in candidates_by_time {
+        if remaining <= max {
+            break;
+        }
+        // `None` if an ancestor was already evicted
+        if let Some((removed, inserted)) = evict_path(tree, &path) {
+            remaining = remaining - removed + usize::from(inserted);
+            evicted += removed;
+        }
+    }
+    evicted
+}
+
+/// Merges the scope at `path` into the `<evicted>` row of its parent,
+/// returns how many nodes were removed and if the row was created.
+#[cfg(feature = "enable")]
+fn evict_path(tree: &mut indexmap::IndexMap<Str, Node>, path: &[Str]) -> Option<(usize, bool)> {
+    let (name, rest) = path.split_first()?;
+    if !rest.is_empty() {
+        return evict_path(&mut tree.get_mut(name)?.children, rest);
+    }
+    let mut node = tree.shift_remove(name)?;
+    let removed = 1 + count_scopes(&node.children);
+    node.children.clear();
+    match tree.get_mut(EVICTED) {
+        Some(evicted) => {
+            evicted.merge(node);
+            Some((removed, false))
+        }
+        None => {
+            tree.insert(Str::borrowed(EVICTED), node);
+            Some((removed, true))
+        }
+    }
+}
+
+/// Scope tree of a thread rebuilt from completed scopes, received in the order they closed.
+#[cfg(feature = "enable")]
+#[derive(Debug, Default)]
+pub(crate) struct Closed {
+    /// Scopes at each depth whose parent hasn't closed yet.
+    pending: Vec<indexmap::IndexMap<Str, Node>>,
+    guards: usize,
+    max_depth: usize,
+}
+
+#[cfg(feature = "enable")]
+impl Closed {
+    /// Adds a call of `name` that took `elapsed`, adopting the pending scopes one level deeper as its children.
+    ///
+    /// A `resumed` poll that didn't complete its future only adopts them, its time is part of the poll that completes it.
+    pub(crate) fn add(
+        &mut self,
+        name: &str,
+        depth: usize,
+        elapsed: std::time::Duration,
+        resumed: bool,
+    ) {
+        if self.pending.len() < depth + 2 {
+            self.pending.resize_with(depth + 2, Default::default);
+        }
+        let children = std::mem::take(&mut self.pending[depth + 1]);
+        let siblings = &mut self.pending[depth];
+        let idx = match siblings.get_index_of(name) {
+            Some(idx) => idx,
+            None => {
+                siblings
+                    .insert_full(Str::owned(name.to_owned()), Node::new(depth))
+                    .0
+            }
+        };
+        let node = &mut siblings[idx];
+        if !resumed {
+            node.add_elapsed(elapsed, 1);
+        }
+        for (name, child) in children {
+            match node.children.get_mut(&name) {
+                Some(other) => other.merge(child),
+                None => {
+                    node.children.insert(name, child);
+                }
+            }
+        }
+        self.guards += usize::from(!resumed);
+        self.max_depth = self.max_depth.max(depth + 1);
+    }
+
+    /// Statistics of the thread, the scopes whose parent never closed are treated as roots.
+    pub(crate) fn into_thread(self, index: usize, name: Option<String>) -> ThreadStats {
+        let mut roots = indexmap::IndexMap::<Str, Node>::new();
+        for (name, node) in self.pending.into_iter().flatten() {
+            match roots.get_mut(&name) {
+                Some(other) => other.merge(node),
+                None => {
+                    roots.insert(name, node);
+                }
+            }
+        }
+        ThreadStats {
+            index,
+            name,
+            total: roots.values().map(|node| node.sum).sum(),
+            max_depth: self.max_depth,
+            guards: self.guards,
+            unclosed: 0,
+            unmatched: 0,
+            evicted: 0,
+            started: std::time::Duration::ZERO,
+            exited: None,
+            longest_idle: None,
+            process: None,
+            scopes: roots
+                .into_iter()
+                .map(|(name, node)| node.into_scope(name))
+                .collect(),
+        }
+    }
+}
+
+/// Part of a pause of `duration` that ended at `resumed` that overlaps a scope started at `start`.
+#[cfg(feature = "enable")]
+fn paused(
+    start: crate::clock::Instant,
+    resumed: crate::clock::Instant,
+    duration: std::time::Duration,
+) -> std::time::Duration {
+    duration.min(resumed.saturating_duration_since(start))
 }
 
+/// Builds the scope tree of a thread, starting from the scopes that were already folded.
+///
+/// Scopes that are still open are closed at `now`.
 #[cfg(feature = "enable")]
-fn into_tree(
+pub(crate) fn into_tree(
+    folded: &Folded,
     measures: &[crate::measure::Measure],
+    now: crate::clock::Instant,
+    progress: &mut crate::progress::Progress,
 ) -> (std::time::Duration, indexmap::IndexMap<Str, Node>) {
     fn get_current<'r>(
         current_path: &[usize],
@@ -224,57 +2443,124 @@ fn into_tree(
         Some(current)
     }
 
-    let mut tree = indexmap::IndexMap::new();
+    /// Start of an open scope.
+    struct Open {
+        time: crate::clock::Instant,
+        /// Continuation of a previous call
+        resumed: bool,
+        usage: crate::rusage::Usage,
+        /// Calls it stands for, see [`crate::measure::MeasureType::Sampled`]
+        scale: usize,
+    }
+
+    /// Closes the innermost open scope, `usage` is `None` if unknown.
+    ///
+    /// Does nothing if there's no open scope, the end is counted in [`ThreadStats::unmatched`].
+    fn close(
+        current_path: &mut Vec<usize>,
+        tree: &mut indexmap::IndexMap<Str, Node>,
+        start_times: &mut Vec<Open>,
+        time: crate::clock::Instant,
+        usage: Option<crate::rusage::Usage>,
+    ) {
+        let Some(current) = get_current(current_path, tree) else {
+            return;
+        };
+        let Some(start) = start_times.pop() else {
+            return;
+        };
+        current.add_call(start.time, time, start.resumed, start.scale);
+        if let Some(usage) = usage {
+            current.usage += usage.since(start.usage) * start.scale as u64;
+        }
+        current_path.pop();
+    }
+
+    let mut tree = folded.tree.clone();
     let mut current_path: Vec<usize> = Vec::new();
-    let mut start_times: Vec<minstant::Instant> = Vec::new();
+    let mut start_times: Vec<Open> = Vec::new();
 
     for m in measures {
-        match m.ty {
-            crate::measure::MeasureType::Start { ref name } => {
-                start_times.push(m.time);
+        progress.advance(1);
+        match &m.ty {
+            crate::measure::MeasureType::Start { name }
+            | crate::measure::MeasureType::Resume { name } => {
+                let resumed = matches!(m.ty, crate::measure::MeasureType::Resume { .. });
+                start_times.push(Open {
+                    time: m.time,
+                    resumed,
+                    usage: m.usage(),
+                    scale: start_times.last().map_or(1, |open| open.scale),
+                });
 
-                let Some(current) = get_current(&current_path, &mut tree) else {
-                    // No current subtree, so insert to root
-                    if let Some(idx) = tree.get_index_of(name.as_ref()) {
-                        // If exists in tree, just add to current path
-                        current_path.push(idx);
-                    } else {
-                        // If not, create it
-                        tree.insert(name.clone(), Node::new(0));
-                        current_path.push(tree.len() - 1);
+                let idx = match get_current(&current_path, &mut tree) {
+                    // Insert node as child of current
+                    Some(current) => {
+                        let depth = current.depth + 1;
+                        Node::child(&mut current.children, name, depth)
                     }
-                    continue;
+                    // No current subtree, so insert to root
+                    None => Node::child(&mut tree, name, 0),
                 };
-                // Insert node as child of current
-                if let Some(idx) = current.children.get_index_of(name) {
-                    // If exists in tree, just add to current path
-                    current_path.push(idx);
-                } else {
-                    // If not, create it
-                    current
-                        .children
-                        .insert(name.clone(), Node::new(current.depth + 1));
-                    current_path.push(current.children.len() - 1);
+                current_path.push(idx);
+                #[cfg(feature = "cores")]
+                if !resumed {
+                    if let Some(current) = get_current(&current_path, &mut tree) {
+                        current.add_core(m.core as usize);
+                    }
                 }
             }
-            crate::measure::MeasureType::End => {
-                let current = get_current(&current_path, &mut tree).expect(
-                    "[profi] 'pop' called and 'current' is 'None', this should never happen!",
-                );
-                let start = start_times.pop().expect(
-                    "[profi] 'pop' called and 'start_times' is empty, this should never happen!",
-                );
-                current.measures.push(m.time.duration_since(start));
-                current_path.pop();
+            crate::measure::MeasureType::End => close(
+                &mut current_path,
+                &mut tree,
+                &mut start_times,
+                m.time,
+                Some(m.usage()),
+            ),
+            crate::measure::MeasureType::Count { .. }
+            | crate::measure::MeasureType::Field { .. }
+            | crate::measure::MeasureType::Parent { .. }
+            | crate::measure::MeasureType::Slow { .. }
+            | crate::measure::MeasureType::Coalesced { .. }
+            | crate::measure::MeasureType::Polled { .. } => {
+                if let Some(current) = get_current(&current_path, &mut tree) {
+                    current.annotate(&m.ty);
+                }
+            }
+            // Also applies to the children opened after it
+            crate::measure::MeasureType::Sampled { every } => {
+                if let Some(open) = start_times.last_mut() {
+                    open.scale *= every;
+                }
+            }
+            // Waits are also part of all the parents, scaled like their calls
+            crate::measure::MeasureType::Wait { duration } => {
+                for depth in 1..=current_path.len() {
+                    let scale = start_times.get(depth - 1).map_or(1, |open| open.scale);
+                    if let Some(node) = get_current(&current_path[..depth], &mut tree) {
+                        node.annotate(&crate::measure::MeasureType::Wait {
+                            duration: *duration * scale as u32,
+                        });
+                    }
+                }
             }
+            // Pauses are removed from all the open scopes
+            crate::measure::MeasureType::Pause { duration } => {
+                for open in &mut start_times {
+                    open.time += paused(open.time, m.time, *duration);
+                }
+            }
+            // Already read back with `spill::expand`
+            crate::measure::MeasureType::Spilled { .. } => {}
         }
     }
+    // The usage of other threads can't be read
+    while !start_times.is_empty() {
+        close(&mut current_path, &mut tree, &mut start_times, now, None);
+    }
 
     // Get total app by adding all root nodes
-    let total_app = tree
-        .iter()
-        .map(|n| n.1.measures.iter().sum::<std::time::Duration>())
-        .sum();
+    let total_app = tree.values().map(|n| n.sum).sum();
 
     (total_app, tree)
 }
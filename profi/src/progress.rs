@@ -0,0 +1,106 @@
+//! Progress of the report generation.
+
+#[cfg(feature = "enable")]
+type Callback = dyn Fn(usize, usize) + Send + Sync;
+
+#[cfg(feature = "enable")]
+static CALLBACK: std::sync::RwLock<Option<std::sync::Arc<Callback>>> = std::sync::RwLock::new(None);
+
+/// Calls `f` with the progress of every report generation, as `(done, total)`.
+///
+/// The units are the events recorded by all threads, or the bytes of the capture for `load_mmap` with the `mmap` feature.
+/// It's called every 1% of the work and once more when the report is ready, with `done == total`.
+///
+/// Without a callback, reports that take longer than a second print their progress to stderr every second,
+/// so a program with a huge capture doesn't look hung at exit.
+/// Registering a callback replaces that output, `on_report_progress(|_, _| {})` silences it.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   profi::on_report_progress(|done, total| eprintln!("report: {done}/{total}"));
+///   for _ in 0..1000 {
+///     prof!(work);
+///   }
+///   profi::report();
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn on_report_progress(f: impl Fn(usize, usize) + Send + Sync + 'static) {
+    #[cfg(feature = "enable")]
+    {
+        *CALLBACK.write().unwrap_or_else(|e| e.into_inner()) = Some(std::sync::Arc::new(f));
+    }
+}
+
+/// Time before the progress is printed to stderr, and between prints.
+#[cfg(feature = "enable")]
+const PRINT_EVERY: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Progress of a single report generation.
+#[cfg(feature = "enable")]
+pub(crate) struct Progress {
+    callback: Option<std::sync::Arc<Callback>>,
+    done: usize,
+    total: usize,
+    /// Value of `done` when the progress is checked again.
+    next: usize,
+    /// Last time the progress was printed, or the start if it wasn't.
+    printed: crate::clock::Instant,
+    any_printed: bool,
+}
+
+#[cfg(feature = "enable")]
+impl Progress {
+    pub(crate) fn new(total: usize) -> Self {
+        Self {
+            callback: CALLBACK.read().unwrap_or_else(|e| e.into_inner()).clone(),
+            done: 0,
+            total,
+            next: (total / 100).max(1),
+            printed: crate::clock::Instant::now(),
+            any_printed: false,
+        }
+    }
+
+    /// Progress that is never reported, for reports built internally.
+    pub(crate) fn silent() -> Self {
+        Self {
+            callback: None,
+            ..Self::new(0)
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn advance(&mut self, n: usize) {
+        self.done += n;
+        // The last notification is left for `finish`
+        if self.done >= self.next && self.done < self.total {
+            self.next = self.done + (self.total / 100).max(1);
+            self.notify();
+        }
+    }
+
+    fn notify(&mut self) {
+        match &self.callback {
+            Some(f) => f(self.done, self.total),
+            None if self.printed.elapsed() >= PRINT_EVERY => {
+                let percent = self.done as f64 / self.total.max(1) as f64 * 100.0;
+                eprintln!("[profi] Generating report... {percent:.0}%");
+                self.printed = crate::clock::Instant::now();
+                self.any_printed = true;
+            }
+            None => {}
+        }
+    }
+
+    pub(crate) fn finish(self) {
+        match &self.callback {
+            Some(f) => f(self.total, self.total),
+            None if self.any_printed => eprintln!("[profi] Generating report... done"),
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Parallel iterators of [`rayon`](https://docs.rs/rayon) whose scopes are shown beneath the scope that created them.
+//!
+//! The closures of a parallel iterator run in the threads of the pool, where their scopes would be roots of their own,
+//! apart from the scope that started the work.
+//! [`profiled`](ParallelIteratorExt::profiled) opens a scope for each chunk of items a thread processes,
+//! and shows it beneath the scopes that were open when it was called, both in the merged table and in the flamegraphs.
+//!
+//! # Example
+//! ```
+//! use profi::rayon::ParallelIteratorExt;
+//! use rayon::prelude::*;
+//!
+//! fn process(items: &[u64]) -> u64 {
+//!   profi::prof!(process);
+//!   items.par_iter().profiled("chunk").map(|i| i * 2).sum()
+//! }
+//!
+//! fn main() {
+//!   process(&[1, 2, 3, 4]);
+//!   let report = profi::report();
+//!   # #[cfg(feature = "enable")]
+//!   # {
+//!   let rows = report.scopes.iter().map(|s| (s.name.as_str(), s.depth));
+//!   assert_eq!(rows.collect::<Vec<_>>(), [("process", 0), ("chunk", 1)]);
+//!   # }
+//! }
+//! ```
+
+use ::rayon::iter::{
+    plumbing::{Consumer, Folder, UnindexedConsumer},
+    ParallelIterator,
+};
+
+use crate::zz_private::ScopeGuard;
+
+/// Scopes that were open in the thread that created it, for the scopes opened in other threads.
+///
+/// Used by [`profiled`](ParallelIteratorExt::profiled), or on its own with [`rayon::join`](::rayon::join) and [`rayon::spawn`](::rayon::spawn).
+///
+/// # Example
+/// ```
+/// use profi::rayon::Parent;
+///
+/// fn main() {
+///   profi::prof!(sort);
+///   let parent = Parent::current();
+///   rayon::join(
+///     || {
+///       let _guard = parent.prof_guard("left");
+///     },
+///     || {
+///       let _guard = parent.prof_guard("right");
+///     },
+///   );
+///
+///   let report = profi::report();
+///   # #[cfg(feature = "enable")]
+///   # {
+///   assert_eq!(report.scopes[0].name, "sort");
+///   for side in ["left", "right"] {
+///     let scope = report.scope(side).unwrap();
+///     assert_eq!((scope.depth, scope.calls), (1, 1));
+///   }
+///   # }
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Parent {
+    #[cfg(feature = "enable")]
+    path: std::sync::Arc<[crate::names::Name]>,
+}
+
+impl Parent {
+    /// Captures the scopes that are open in the current thread.
+    pub fn current() -> Self {
+        Self {
+            #[cfg(feature = "enable")]
+            path: crate::measure::THREAD_PROFILER
+                .with_borrow(|thread| thread.path())
+                .into(),
+        }
+    }
+
+    /// Opens the scope `name`, shown beneath the captured scopes if no other scope is open in the current thread.
+    ///
+    /// Otherwise it's nested in the open scopes as usual, like when `rayon` runs the work in the thread that created it.
+    #[allow(unused_mut)]
+    pub fn prof_guard(&self, name: &'static str) -> ScopeGuard {
+        let mut guard = ScopeGuard::new(name);
+        #[cfg(feature = "enable")]
+        guard.attach(&self.path);
+        guard
+    }
+}
+
+/// Adds [`profiled`](Self::profiled) to every [`ParallelIterator`].
+pub trait ParallelIteratorExt: ParallelIterator {
+    /// Opens the scope `name` for each chunk of items processed by a thread of the pool,
+    /// shown beneath the scopes that are open when it's called.
+    ///
+    /// The closures of the adapters that come after it run inside the scope.
+    fn profiled(self, name: &'static str) -> Profiled<Self> {
+        Profiled {
+            base: self,
+            name,
+            parent: Parent::current(),
+        }
+    }
+}
+
+impl<I: ParallelIterator> ParallelIteratorExt for I {}
+
+/// Parallel iterator returned by [`profiled`](ParallelIteratorExt::profiled).
+#[derive(Debug, Clone)]
+pub struct Profiled<I> {
+    base: I,
+    name: &'static str,
+    parent: Parent,
+}
+
+impl<I: ParallelIterator> ParallelIterator for Profiled<I> {
+    type Item = I::Item;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.base.drive_unindexed(ProfiledConsumer {
+            base: consumer,
+            name: self.name,
+            parent: &self.parent,
+        })
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        self.base.opt_len()
+    }
+}
+
+/// Opens the scope in each folder, which processes a chunk of items in a single thread.
+struct ProfiledConsumer<'p, C> {
+    base: C,
+    name: &'static str,
+    parent: &'p Parent,
+}
+
+impl<T, C: Consumer<T>> Consumer<T> for ProfiledConsumer<'_, C> {
+    type Folder = ProfiledFolder<C::Folder>;
+    type Reducer = C::Reducer;
+    type Result = C::Result;
+
+    fn split_at(self, index: usize) -> (Self, Self, Self::Reducer) {
+        let Self { base, name, parent } = self;
+        let (left, right, reducer) = base.split_at(index);
+        let with = |base| Self { base, name, parent };
+        (with(left), with(right), reducer)
+    }
+
+    fn into_folder(self) -> Self::Folder {
+        ProfiledFolder {
+            guard: self.parent.prof_guard(self.name),
+            base: self.base.into_folder(),
+        }
+    }
+
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
+
+impl<T, C: UnindexedConsumer<T>> UnindexedConsumer<T> for ProfiledConsumer<'_, C> {
+    fn split_off_left(&self) -> Self {
+        Self {
+            base: self.base.split_off_left(),
+            name: self.name,
+            parent: self.parent,
+        }
+    }
+
+    fn to_reducer(&self) -> Self::Reducer {
+        self.base.to_reducer()
+    }
+}
+
+struct ProfiledFolder<F> {
+    guard: ScopeGuard,
+    base: F,
+}
+
+impl<T, F: Folder<T>> Folder<T> for ProfiledFolder<F> {
+    type Result = F::Result;
+
+    fn consume(self, item: T) -> Self {
+        Self {
+            base: self.base.consume(item),
+            ..self
+        }
+    }
+
+    fn consume_iter<I>(self, iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        Self {
+            base: self.base.consume_iter(iter),
+            ..self
+        }
+    }
+
+    fn complete(self) -> Self::Result {
+        let result = self.base.complete();
+        // Closed after the work of the adapters that come after it
+        drop(self.guard);
+        result
+    }
+
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
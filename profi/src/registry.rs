@@ -0,0 +1,47 @@
+//! Detection of other versions of `profi` linked into the same binary.
+//!
+//! Each version has its own globals, so their scopes end up in separate reports.
+//! Every instance registers itself in an environment variable, the only process-wide registry shared by all versions,
+//! and warns if another one is already there.
+
+/// Entries are `pid:version@address` separated by `,`, the pid avoids false positives from inherited variables.
+#[cfg(feature = "enable")]
+const VAR: &str = "__PROFI_INSTANCES";
+
+/// Registers this instance, and warns if other versions of `profi` registered before.
+///
+/// Called once, when profiling starts.
+#[cfg(feature = "enable")]
+pub(crate) fn register() {
+    let pid = std::process::id();
+    let this = format!(
+        "{pid}:{}@{:p}",
+        env!("CARGO_PKG_VERSION"),
+        &crate::measure::GLOBAL_PROFILER
+    );
+    let registered = std::env::var(VAR).unwrap_or_default();
+    let others = instances(&registered, pid)
+        .filter(|entry| *entry != this)
+        .filter_map(|entry| entry.split(':').nth(1)?.split('@').next())
+        .collect::<Vec<_>>();
+    if !others.is_empty() {
+        eprintln!(
+            "[profi] Found other versions of profi in this binary ({}), their scopes are reported separately from the ones of {}. Unify the versions in Cargo.lock to get a single report.",
+            others.join(", "),
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+    let mut entries = instances(&registered, pid).collect::<Vec<_>>();
+    entries.push(&this);
+    std::env::set_var(VAR, entries.join(","));
+}
+
+/// Entries of the current process.
+#[cfg(feature = "enable")]
+fn instances(registered: &str, pid: u32) -> impl Iterator<Item = &str> {
+    registered.split(',').filter(move |entry| {
+        entry
+            .split_once(':')
+            .is_some_and(|(p, _)| p.parse() == Ok(pid))
+    })
+}
@@ -0,0 +1,1283 @@
+//! Programmatic access to the profiling results.
+
+/// Aggregated profiling results, obtained with [`report()`].
+///
+/// Contains the same data as the printed table, plus the hierarchy of scopes of each thread.
+///
+/// With the `serde` feature all report types implement `Serialize` and `Deserialize`,
+/// and the `msgpack` and `cbor` features add methods to write and read them in those compact binary formats.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   for _ in 0..10 {
+///     prof!(iteration);
+///   }
+///
+///   let report = profi::report();
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(report.scope("iteration").unwrap().calls, 10);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Report {
+    /// Scopes of all threads merged by name, in the same order as the printed table.
+    pub scopes: Vec<ScopeStats>,
+    /// Statistics and scope hierarchy of each thread.
+    ///
+    /// The first one is always the main thread if the results were collected by [`print_on_exit!`](crate::print_on_exit).
+    pub threads: Vec<ThreadStats>,
+    /// Time of the longest running thread.
+    pub total_app: std::time::Duration,
+    /// Combined time of all threads.
+    pub total_cpu: std::time::Duration,
+    /// Energy consumed by the CPU packages while profiling, in joules.
+    ///
+    /// Only available with the `energy` feature on Linux machines with readable RAPL counters.
+    pub energy: Option<f64>,
+    /// Milestones recorded with [`milestone!`](crate::milestone), in the order they were reached.
+    pub milestones: Vec<Milestone>,
+    /// Time since the start when [`begin_shutdown`](crate::begin_shutdown) was called, `None` if it wasn't.
+    pub shutdown: Option<std::time::Duration>,
+    /// Wall-clock time when profiling started, `None` if nothing was profiled.
+    pub started: Option<std::time::SystemTime>,
+    /// Time since profiling started until the report was taken.
+    pub wall_time: std::time::Duration,
+    /// Measurement noise of the host, `None` if [`calibrate_noise`](crate::calibrate_noise) wasn't called.
+    pub noise: Option<crate::NoiseFloor>,
+    /// How [`scopes`](Self::scopes) were grouped, see [`Report::deep`] and [`Report::merged`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hierarchy: crate::Hierarchy,
+    /// Frames marked with [`new_frame!`](crate::new_frame), `None` if less than two were marked.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub frames: Option<FrameStats>,
+    /// Calls of the scopes watched with [`watch_tail`](crate::watch_tail) that were slower than their p99 so far, oldest first.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tail_events: Vec<TailEvent>,
+    /// Objects wrapped in a [`Guard`](crate::Guard), grouped by name in the order they were first created.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub lifetimes: Vec<LifetimeStats>,
+}
+
+/// Merged statistics of all the scopes with the same name.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScopeStats {
+    pub name: String,
+    /// Shallowest depth at which the scope was found.
+    pub depth: usize,
+    /// % Application Time
+    pub percent_app: f64,
+    /// Real Time
+    pub total_real: std::time::Duration,
+    /// % CPU Time
+    pub percent_cpu: f64,
+    /// CPU Time
+    pub total_cpu: std::time::Duration,
+    pub average: std::time::Duration,
+    /// Standard deviation of the duration of the calls.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stddev: std::time::Duration,
+    /// Coefficient of variation of the duration of the calls, the standard deviation as a percentage of the mean.
+    ///
+    /// Scopes with a high value take very different times on each call, so their average is less reliable.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cv: f64,
+    /// Shortest call.
+    pub min: std::time::Duration,
+    /// Longest call.
+    pub max: std::time::Duration,
+    /// Part of the real time spent after [`begin_shutdown`](crate::begin_shutdown) was called.
+    pub shutdown: std::time::Duration,
+    /// Part of the real time spent in intentional waits, like `profi::tokio::sleep` with the `tokio` feature.
+    ///
+    /// Includes the waits of its children.
+    pub wait: std::time::Duration,
+    pub calls: usize,
+    /// Fraction of the calls that were recorded, `None` if all of them were.
+    ///
+    /// Sampled if [`set_overhead_budget`](crate::set_overhead_budget) is enabled, where `calls` and the total times only include the recorded calls,
+    /// or with `sample = 1 / N` in [`prof!`](crate::prof), where each recorded call counts as N calls of the same duration.
+    pub sample_rate: Option<f64>,
+    /// Estimated energy in joules, attributed proportionally to the CPU time.
+    ///
+    /// See [`Report::energy`].
+    pub energy: Option<f64>,
+    /// Number of calls started on each core, sorted by core.
+    ///
+    /// Only recorded with the `cores` feature.
+    pub cores: Vec<CoreUsage>,
+    /// Times the thread gave up the CPU while in the scope, usually blocked on I/O or a lock.
+    ///
+    /// Only recorded with the `context-switches` feature.
+    pub voluntary_switches: u64,
+    /// Times the thread was preempted while in the scope.
+    ///
+    /// Only recorded with the `context-switches` feature.
+    pub involuntary_switches: u64,
+    /// Page faults resolved without I/O while in the scope, like the first touch of newly allocated memory.
+    ///
+    /// Only recorded with the `page-faults` feature.
+    pub minor_faults: u64,
+    /// Page faults that required I/O while in the scope, like reading a memory-mapped file.
+    ///
+    /// Only recorded with the `page-faults` feature.
+    pub major_faults: u64,
+    /// CPU cycles spent in the scope, excluding the kernel.
+    ///
+    /// Only recorded with the `perf` feature, if the hardware counters are available.
+    pub cycles: u64,
+    /// Instructions retired in the scope, excluding the kernel.
+    ///
+    /// Only recorded with the `perf` feature, if the hardware counters are available.
+    pub instructions: u64,
+    /// Cache misses in the scope, usually of the last level cache.
+    ///
+    /// Only recorded with the `perf` feature, if the hardware counters are available.
+    pub cache_misses: u64,
+    /// Totals of the counters added with [`prof_count!`](crate::prof_count), in the order they were first used.
+    pub counters: Vec<(String, u64)>,
+    /// Calls that exceeded the threshold given to [`ScopeGuard::on_slow`](crate::zz_private::ScopeGuard::on_slow).
+    pub slow_calls: Vec<SlowCall>,
+    /// Values of the [aggregators](crate::Aggregator) registered when the report was taken, by name.
+    pub aggregates: Vec<(String, f64)>,
+    /// Processes where the scope ran, in the order they were merged, only set by [`merge_files`](crate::merge_files).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub processes: Vec<String>,
+    /// Team or person responsible for the scope, set with [`set_owner`](crate::set_owner).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub owner: Option<String>,
+    /// What the scope measures, set with `prof!(name, desc = "...")` or [`set_description`](crate::set_description).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub description: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) thread: usize,
+    /// Duration of each call, only kept until the aggregators are applied.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) durations: Vec<std::time::Duration>,
+    /// Sum and sum of squares of the duration of each call in seconds, to merge the standard deviation.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) moments: (f64, f64),
+}
+
+/// Statistics of a single thread.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThreadStats {
+    /// Order in which the thread was collected, `0` is the main thread.
+    pub index: usize,
+    /// Name of the thread, if it had one.
+    pub name: Option<String>,
+    /// Sum of the time of all root scopes.
+    pub total: std::time::Duration,
+    /// Maximum number of simultaneously open scopes.
+    pub max_depth: usize,
+    /// Total number of guards created.
+    pub guards: usize,
+    /// Guards that were never closed.
+    pub unclosed: usize,
+    /// Ends of scopes that were never started, which are ignored, see [`Error::Unbalanced`](crate::Error::Unbalanced).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub unmatched: usize,
+    /// Scopes merged into an `<evicted>` row to stay under [`Retention::max_scopes`](crate::Retention::max_scopes).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub evicted: usize,
+    /// When the thread started profiling, since profiling started.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub started: std::time::Duration,
+    /// When the thread exited, since profiling started, `None` if it was still running.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub exited: Option<std::time::Duration>,
+    /// Longest time between the start and the exit of the thread without any open scope,
+    /// `None` if unknown because its scopes were folded by [`set_retention`](crate::set_retention) or the `bounded-memory` feature.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub longest_idle: Option<std::time::Duration>,
+    /// Process the thread belonged to, only set by [`merge_files`](crate::merge_files).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub process: Option<String>,
+    /// Root scopes of the thread.
+    pub scopes: Vec<ScopeNode>,
+}
+
+/// A call that took longer than expected, see [`ScopeGuard::on_slow`](crate::zz_private::ScopeGuard::on_slow).
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SlowCall {
+    pub duration: std::time::Duration,
+    /// String returned by the user provided function.
+    pub context: String,
+}
+
+/// Calls of a scope where the field `key` was `value`, see [`Report::group_by`].
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FieldStats {
+    pub key: String,
+    pub value: String,
+    /// Number of calls.
+    pub count: usize,
+    /// Combined time of the calls.
+    pub sum: std::time::Duration,
+    /// Sum of the squared duration of each call, in seconds, used for the standard deviation.
+    pub sum_squares: f64,
+    /// Shortest call.
+    pub min: std::time::Duration,
+    /// Longest call.
+    pub max: std::time::Duration,
+}
+
+/// Statistics of the frames marked with [`new_frame!`](crate::new_frame).
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameStats {
+    /// Number of completed frames.
+    pub count: usize,
+    /// Frames per second, over the time of all the completed frames.
+    pub fps: f64,
+    /// Average duration of a frame.
+    pub mean: std::time::Duration,
+    /// Duration of the slowest frame.
+    pub worst: std::time::Duration,
+    /// Index of the slowest frame, starting at 0.
+    pub worst_index: usize,
+    /// Scopes that started during the slowest frame, with their percentage of its duration.
+    ///
+    /// Empty with the `bounded-memory` feature, where the time of each call is not kept.
+    pub slowest: Vec<ScopeStats>,
+}
+
+/// Call of a watched scope slower than its previous calls, see [`watch_tail`](crate::watch_tail).
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TailEvent {
+    pub scope: String,
+    /// Name of the thread that made the call, if it had one.
+    pub thread: Option<String>,
+    /// Time since profiling started when the call ended.
+    pub elapsed: std::time::Duration,
+    pub duration: std::time::Duration,
+    /// Approximate 99th percentile of the previous calls of the scope.
+    pub p99: std::time::Duration,
+    /// Returned by the function given to [`watch_tail_with`](crate::watch_tail_with).
+    pub metadata: Option<String>,
+}
+
+/// Lifetimes of the objects wrapped in a [`Guard`](crate::Guard) with the same name.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LifetimeStats {
+    pub name: String,
+    /// Objects created since the start or the last [`reset`](crate::reset).
+    pub created: usize,
+    /// Objects still alive when the report was taken.
+    pub alive: usize,
+    /// Average lifetime of the objects that were dropped.
+    pub average: std::time::Duration,
+    /// Longest lifetime of the objects that were dropped.
+    pub max: std::time::Duration,
+    /// Maximum number of objects alive at the same time, the high-water mark of the resource they wrap.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::Guard;
+    ///
+    /// fn main() {
+    ///   let buffers = (0..3)
+    ///     .map(|_| Guard::new(vec![0u8; 1024], "buffer"))
+    ///     .collect::<Vec<_>>();
+    ///   drop(buffers);
+    ///   let _buffer = Guard::new(vec![0u8; 1024], "buffer");
+    ///
+    ///   let report = profi::report();
+    ///   # #[cfg(feature = "enable")]
+    ///   assert_eq!(report.lifetime("buffer").unwrap().max_alive, 3);
+    /// }
+    /// ```
+    pub max_alive: usize,
+    /// Time since profiling started when `max_alive` was first reached.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_alive_at: std::time::Duration,
+}
+
+/// Scopes of an owner set with [`set_owner`](crate::set_owner), see [`Report::owners`].
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnerStats {
+    pub owner: String,
+    /// Number of scopes it owns.
+    pub scopes: usize,
+    /// Calls of all of its scopes.
+    pub calls: usize,
+    /// Real time of its scopes, counting the ones nested in another of its scopes only once.
+    pub total_real: std::time::Duration,
+    /// `total_real` as a percentage of the application time.
+    pub percent_app: f64,
+}
+
+/// How much of its lifetime a thread spent inside its scopes, see [`Report::utilization`].
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThreadUtilization {
+    /// See [`ThreadStats::index`].
+    pub index: usize,
+    /// See [`ThreadStats::name`].
+    pub name: Option<String>,
+    /// Time from the first scope of the thread until it exited, or until the report if it's still running.
+    pub lifetime: std::time::Duration,
+    /// Time inside any of its scopes.
+    pub busy: std::time::Duration,
+    /// `busy` as a percentage of `lifetime`.
+    pub percent: f64,
+    /// See [`ThreadStats::longest_idle`].
+    pub longest_idle: Option<std::time::Duration>,
+    /// Number of scopes opened by the thread.
+    pub scopes: usize,
+}
+
+/// Point of the execution recorded with [`milestone!`](crate::milestone).
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Milestone {
+    pub name: String,
+    /// Time since profiling started, which is the start of `main` if [`print_on_exit!`](crate::print_on_exit) is at its top.
+    pub elapsed: std::time::Duration,
+    /// Name of the thread that reached it, if it had one.
+    pub thread: Option<String>,
+}
+
+/// Calls of a scope that started on a specific CPU core.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoreUsage {
+    pub core: usize,
+    /// NUMA node of the core, if it could be determined.
+    pub numa_node: Option<usize>,
+    pub calls: usize,
+}
+
+/// A scope in the hierarchy of a thread.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScopeNode {
+    pub name: String,
+    pub depth: usize,
+    /// Duration of each call.
+    ///
+    /// Empty with the `bounded-memory` feature, where only the aggregates below are kept.
+    pub durations: Vec<std::time::Duration>,
+    /// Number of calls.
+    pub count: usize,
+    /// Combined time of all calls.
+    pub sum: std::time::Duration,
+    /// Sum of the squared duration of each call, in seconds, used for the standard deviation.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sum_squares: f64,
+    /// Shortest call.
+    pub min: std::time::Duration,
+    /// Longest call.
+    pub max: std::time::Duration,
+    /// See [`ScopeStats::shutdown`].
+    pub shutdown: std::time::Duration,
+    /// See [`ScopeStats::wait`].
+    pub wait: std::time::Duration,
+    /// See [`ScopeStats::cores`].
+    pub cores: Vec<CoreUsage>,
+    /// See [`ScopeStats::voluntary_switches`].
+    pub voluntary_switches: u64,
+    /// See [`ScopeStats::involuntary_switches`].
+    pub involuntary_switches: u64,
+    /// See [`ScopeStats::minor_faults`].
+    pub minor_faults: u64,
+    /// See [`ScopeStats::major_faults`].
+    pub major_faults: u64,
+    /// See [`ScopeStats::cycles`].
+    pub cycles: u64,
+    /// See [`ScopeStats::instructions`].
+    pub instructions: u64,
+    /// See [`ScopeStats::cache_misses`].
+    pub cache_misses: u64,
+    /// See [`ScopeStats::counters`].
+    pub counters: Vec<(String, u64)>,
+    /// See [`ScopeStats::slow_calls`].
+    pub slow_calls: Vec<SlowCall>,
+    /// Calls with each value of the fields set with `fields = { .. }` in [`prof!`](crate::prof), in the order they were first seen.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fields: Vec<FieldStats>,
+    /// Open scopes of another thread this root was opened beneath, from the outermost one.
+    ///
+    /// Set by the parallel iterators of `profi::rayon` with the `rayon` feature and the threads of `profi::thread`,
+    /// the scope is shown beneath the last one in the merged table.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub parent: Vec<String>,
+    pub children: Vec<ScopeNode>,
+}
+
+impl Report {
+    /// Returns the merged statistics of the scope with the given name.
+    pub fn scope(&self, name: &str) -> Option<&ScopeStats> {
+        self.scopes.iter().find(|s| s.name == name)
+    }
+
+    /// Returns the lifetimes of the objects wrapped in a [`Guard`](crate::Guard) with the given name.
+    pub fn lifetime(&self, name: &str) -> Option<&LifetimeStats> {
+        self.lifetimes.iter().find(|l| l.name == name)
+    }
+
+    /// Returns the time of each owner set with [`set_owner`](crate::set_owner), the one with the most real time first.
+    ///
+    /// Empty if no scope has an owner, or if the `enable` feature is disabled.
+    pub fn owners(&self) -> Vec<OwnerStats> {
+        #[cfg(feature = "enable")]
+        return crate::owners::summary(self);
+        #[cfg(not(feature = "enable"))]
+        Vec::new()
+    }
+
+    /// Returns how much of its lifetime each thread spent inside its scopes, in the order of [`threads`](Self::threads).
+    ///
+    /// Tells the threads that are mostly idle, waiting for work or not instrumented, from the saturated ones,
+    /// which the merged CPU time can't show.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// fn main() {
+    ///   std::thread::Builder::new()
+    ///     .name("worker".into())
+    ///     .spawn(|| {
+    ///       {
+    ///         profi::prof!(work);
+    ///       }
+    ///       std::thread::sleep(Duration::from_millis(20));
+    ///       profi::prof!(work);
+    ///     })
+    ///     .unwrap()
+    ///     .join()
+    ///     .unwrap();
+    ///   let report = profi::report();
+    ///   # #[cfg(feature = "enable")]
+    ///   # {
+    ///   let utilization = report.utilization();
+    ///   let worker = utilization.iter().find(|t| t.name.as_deref() == Some("worker")).unwrap();
+    ///   assert!(worker.percent < 50.0);
+    ///   assert!(worker.longest_idle.unwrap() >= Duration::from_millis(20));
+    ///   # }
+    /// }
+    /// ```
+    pub fn utilization(&self) -> Vec<ThreadUtilization> {
+        self.threads
+            .iter()
+            .map(|thread| {
+                let lifetime = thread
+                    .exited
+                    .unwrap_or(self.wall_time)
+                    .saturating_sub(thread.started);
+                let percent = match lifetime.is_zero() {
+                    true => 0.0,
+                    false => {
+                        (thread.total.as_secs_f64() / lifetime.as_secs_f64() * 100.0).min(100.0)
+                    }
+                };
+                ThreadUtilization {
+                    index: thread.index,
+                    name: thread.name.clone(),
+                    lifetime,
+                    busy: thread.total,
+                    percent,
+                    longest_idle: thread.longest_idle,
+                    scopes: thread.guards,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns a report with only the thread at `index`, or `None` if it doesn't exist.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::prof;
+    ///
+    /// fn main() {
+    ///   prof!(work);
+    ///   let report = profi::report();
+    ///   # #[cfg(feature = "enable")]
+    ///   # {
+    ///   let main = report.thread(0).unwrap();
+    ///   assert_eq!(main.threads[0].name.as_deref(), Some("main"));
+    ///   # }
+    /// }
+    /// ```
+    pub fn thread(&self, index: usize) -> Option<Report> {
+        #[cfg(feature = "enable")]
+        {
+            let thread = self.threads.get(index)?;
+            Some(Report {
+                shutdown: self.shutdown,
+                noise: self.noise,
+                ..crate::process::thread_report(thread, self.hierarchy)
+            })
+        }
+        #[cfg(not(feature = "enable"))]
+        {
+            let _ = index;
+            None
+        }
+    }
+
+    /// Returns a copy of the report where scopes with the same name are only merged if they're at the same depth.
+    ///
+    /// Same as [`Hierarchy::Deep`](crate::Hierarchy::Deep), without rebuilding with the `deep-hierarchy` feature.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::prof;
+    ///
+    /// fn recurse(n: usize) {
+    ///   prof!(recurse);
+    ///   if n > 0 {
+    ///     recurse(n - 1);
+    ///   }
+    /// }
+    ///
+    /// fn main() {
+    ///   recurse(2);
+    ///   let report = profi::report();
+    ///   # #[cfg(feature = "enable")]
+    ///   # {
+    ///   assert_eq!(report.merged().scopes.len(), 1);
+    ///   assert_eq!(report.deep().scopes.len(), 3);
+    ///   # }
+    /// }
+    /// ```
+    pub fn deep(&self) -> Report {
+        self.with_hierarchy(crate::Hierarchy::Deep)
+    }
+
+    /// Returns a copy of the report where all the scopes with the same name are merged, the default.
+    ///
+    /// Undoes [`Report::deep`] or the `deep-hierarchy` feature.
+    pub fn merged(&self) -> Report {
+        self.with_hierarchy(crate::Hierarchy::Merged)
+    }
+
+    /// Returns a copy of the report with a `name [key=value]` row after the children of each scope,
+    /// for every value of its field `key`.
+    ///
+    /// Fields are set with `fields = { .. }` in [`prof!`](crate::prof), and only split the rows when grouped by,
+    /// so dimensions like the table of a query or the route of a request don't multiply the scopes of the report.
+    ///
+    /// # Example
+    /// ```
+    /// fn query(table: &'static str) {
+    ///   profi::prof!("query", fields = { table: table, op: "select" });
+    /// }
+    ///
+    /// fn main() {
+    ///   query("users");
+    ///   query("users");
+    ///   query("orders");
+    ///   let report = profi::report();
+    ///   # #[cfg(feature = "enable")]
+    ///   # {
+    ///   assert_eq!(report.scope("query").unwrap().calls, 3);
+    ///   let report = report.group_by("table");
+    ///   assert_eq!(report.scope("query [table=users]").unwrap().calls, 2);
+    ///   assert_eq!(report.scope("query [table=orders]").unwrap().calls, 1);
+    ///   # }
+    /// }
+    /// ```
+    #[allow(unused_variables)]
+    pub fn group_by(&self, key: &str) -> Report {
+        #[cfg(feature = "enable")]
+        {
+            crate::process::regroup(self, self.hierarchy, None, false, Some(key), false)
+        }
+        #[cfg(not(feature = "enable"))]
+        {
+            self.clone()
+        }
+    }
+
+    /// Scopes named after their function that may be mixed up with the ones named explicitly.
+    ///
+    /// Scopes named with `prof!()` or `#[profile]` get the path of their function, like `app::db::query`.
+    /// Returns the ones that are also named explicitly somewhere else, like `prof!("app::db::query")`, so their calls are merged,
+    /// or whose explicit name only lacks the module path, like `prof!(query)`, so they're reported in separate rows.
+    /// Merge the latter on purpose with [`alias`](crate::alias), which also removes them from the list.
+    ///
+    /// Only the names used since profiling started are known, they're also listed by [`PrintOptions::diagnostics`](crate::PrintOptions::diagnostics).
+    ///
+    /// # Example
+    /// ```
+    /// mod db {
+    ///   pub fn query() {
+    ///     profi::prof!();
+    ///   }
+    /// }
+    ///
+    /// fn main() {
+    ///   db::query();
+    ///   {
+    ///     profi::prof!(query);
+    ///   }
+    ///   # #[cfg(feature = "enable")]
+    ///   # {
+    ///   let collisions = profi::report().name_collisions();
+    ///   assert_eq!(collisions[0].explicit, "query");
+    ///   assert!(collisions[0].inferred.ends_with("::db::query"));
+    ///
+    ///   profi::alias("query", &collisions[0].inferred);
+    ///   assert!(profi::report().name_collisions().is_empty());
+    ///   # }
+    /// }
+    /// ```
+    pub fn name_collisions(&self) -> Vec<crate::NameCollision> {
+        #[cfg(feature = "enable")]
+        {
+            crate::collisions::find(&self.scopes)
+        }
+        #[cfg(not(feature = "enable"))]
+        {
+            Vec::new()
+        }
+    }
+
+    /// Scopes over the budgets set with [`set_budget`](crate::set_budget), in the order of the table.
+    ///
+    /// Empty if the `enable` feature is disabled.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::prof;
+    ///
+    /// fn main() {
+    ///   profi::assert_budget!("physics", 1us);
+    ///   {
+    ///     prof!(physics);
+    ///     std::thread::sleep(std::time::Duration::from_millis(1));
+    ///   }
+    ///   # #[cfg(feature = "enable")]
+    ///   assert_eq!(profi::report().budget_violations()[0].scope, "physics");
+    /// }
+    /// ```
+    pub fn budget_violations(&self) -> Vec<crate::BudgetViolation> {
+        #[cfg(feature = "enable")]
+        {
+            crate::budgets::violations(self)
+        }
+        #[cfg(not(feature = "enable"))]
+        {
+            Vec::new()
+        }
+    }
+
+    /// Scopes and threads worth looking at first, found with simple heuristics, the most severe first.
+    ///
+    /// Meant as a starting point for optimizing, each [`Finding`](crate::Finding) says what triggered it:
+    /// - Scopes whose calls take very different times, see [`ScopeStats::cv`].
+    /// - Scopes that take most of the time of their parent, so the rest of its children barely matter.
+    /// - Scopes called many times per call of their parent, or so often and so fast that measuring them costs more than running them.
+    /// - Threads that spent most of their lifetime outside of their scopes.
+    ///
+    /// The severity of the findings about a scope depends on its share of the application time.
+    /// Printed with [`PrintOptions::findings`](crate::PrintOptions::findings), empty if the `enable` feature is disabled.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::{prof, FindingKind};
+    ///
+    /// fn main() {
+    ///   {
+    ///     prof!(parent);
+    ///     for _ in 0..2000 {
+    ///       prof!(child);
+    ///     }
+    ///   }
+    ///   let findings = profi::report().findings();
+    ///   # #[cfg(feature = "enable")]
+    ///   assert!(findings.iter().any(|f| f.kind == FindingKind::ManyCalls && f.subject == "child"));
+    /// }
+    /// ```
+    pub fn findings(&self) -> Vec<crate::Finding> {
+        #[cfg(feature = "enable")]
+        {
+            crate::findings::find(self)
+        }
+        #[cfg(not(feature = "enable"))]
+        {
+            Vec::new()
+        }
+    }
+
+    fn with_hierarchy(&self, hierarchy: crate::Hierarchy) -> Report {
+        #[cfg(feature = "enable")]
+        {
+            crate::process::regroup(self, hierarchy, None, false, None, false)
+        }
+        #[cfg(not(feature = "enable"))]
+        {
+            let _ = hierarchy;
+            self.clone()
+        }
+    }
+
+    /// Sets the sample rate of the scopes that skipped calls, summing the samplers of all threads,
+    /// or from their fixed rate.
+    #[cfg(feature = "enable")]
+    pub(crate) fn set_sampling<'a>(
+        &mut self,
+        samplers: impl IntoIterator<Item = &'a crate::sampling::Sampler>,
+    ) {
+        let mut calls = std::collections::HashMap::<&str, (u64, u64)>::new();
+        for s in samplers {
+            let (seen, recorded) = calls.entry(&s.name).or_default();
+            *seen += s.seen;
+            *recorded += s.recorded;
+        }
+        for scope in &mut self.scopes {
+            if let Some(&(seen, recorded)) = calls.get(scope.name.as_str()) {
+                if recorded < seen {
+                    scope.sample_rate = Some(recorded as f64 / seen as f64);
+                }
+            }
+        }
+        for (name, every) in crate::sampling::fixed_rates() {
+            if let Some(scope) = self.scopes.iter_mut().find(|s| s.name == name) {
+                scope.sample_rate = Some(1.0 / every as f64);
+            }
+        }
+    }
+
+    /// Sets the total energy consumed and attributes it to each scope by its share of CPU time.
+    #[allow(unused)]
+    pub(crate) fn set_energy(&mut self, joules: f64) {
+        self.energy = Some(joules);
+        let total_cpu = self.total_cpu.as_secs_f64();
+        for scope in &mut self.scopes {
+            let share = if total_cpu > 0.0 {
+                scope.total_cpu.as_secs_f64() / total_cpu
+            } else {
+                0.0
+            };
+            scope.energy = Some(joules * share);
+        }
+    }
+
+    /// Writes the report as [MessagePack](https://msgpack.org), a compact binary alternative to JSON.
+    ///
+    /// Structs are encoded as arrays, so the files are smaller but only readable with [`read_msgpack`](Self::read_msgpack)
+    /// or a decoder that knows the field order.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::prof;
+    ///
+    /// fn main() {
+    ///   for _ in 0..10 {
+    ///     prof!(iteration);
+    ///   }
+    ///   let mut bytes = Vec::new();
+    ///   profi::report().write_msgpack(&mut bytes).unwrap();
+    ///
+    ///   let report = profi::Report::read_msgpack(bytes.as_slice()).unwrap();
+    ///   # #[cfg(feature = "enable")]
+    ///   assert_eq!(report.scope("iteration").unwrap().calls, 10);
+    /// }
+    /// ```
+    #[cfg(feature = "msgpack")]
+    pub fn write_msgpack(&self, mut to: impl std::io::Write) -> std::io::Result<()> {
+        rmp_serde::encode::write(&mut to, self).map_err(std::io::Error::other)
+    }
+
+    /// Reads a report written with [`write_msgpack`](Self::write_msgpack).
+    #[cfg(feature = "msgpack")]
+    pub fn read_msgpack(from: impl std::io::Read) -> std::io::Result<Report> {
+        rmp_serde::decode::from_read(from).map_err(std::io::Error::other)
+    }
+
+    /// Writes the report as [CBOR](https://cbor.io), a compact binary alternative to JSON.
+    ///
+    /// Unlike [`write_msgpack`](Self::write_msgpack) the field names are kept, so it can be read by any CBOR decoder.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::prof;
+    ///
+    /// fn main() {
+    ///   for _ in 0..10 {
+    ///     prof!(iteration);
+    ///   }
+    ///   let mut bytes = Vec::new();
+    ///   profi::report().write_cbor(&mut bytes).unwrap();
+    ///
+    ///   let report = profi::Report::read_cbor(bytes.as_slice()).unwrap();
+    ///   # #[cfg(feature = "enable")]
+    ///   assert_eq!(report.scope("iteration").unwrap().calls, 10);
+    /// }
+    /// ```
+    #[cfg(feature = "cbor")]
+    pub fn write_cbor(&self, to: impl std::io::Write) -> std::io::Result<()> {
+        ciborium::into_writer(self, to).map_err(std::io::Error::other)
+    }
+
+    /// Reads a report written with [`write_cbor`](Self::write_cbor).
+    #[cfg(feature = "cbor")]
+    pub fn read_cbor(from: impl std::io::Read) -> std::io::Result<Report> {
+        ciborium::from_reader(from).map_err(std::io::Error::other)
+    }
+
+    /// Writes the report as a gzip-compressed [pprof](https://github.com/google/pprof) profile.
+    ///
+    /// Each scope becomes a function, and each path of the scope tree a sample with its number of calls and *self time* in nanoseconds,
+    /// labeled with the thread it ran on.
+    /// The file can be opened with `go tool pprof` or uploaded to continuous profiling tools like Grafana Pyroscope.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::prof;
+    ///
+    /// fn main() {
+    ///   {
+    ///     prof!(parent);
+    ///     prof!(child);
+    ///   }
+    ///   let mut bytes = Vec::new();
+    ///   profi::report().write_pprof(&mut bytes).unwrap();
+    /// }
+    /// ```
+    #[cfg(feature = "pprof")]
+    pub fn write_pprof(&self, to: impl std::io::Write) -> std::io::Result<()> {
+        crate::pprof::write(self, to)
+    }
+
+    /// Writes the call graph of the report as a [Graphviz](https://graphviz.org) DOT file, render it with `dot -Tsvg profi.dot -o profi.svg`.
+    ///
+    /// Each scope name is a node, labeled with its time, percentage of the application time and calls, merging all threads.
+    /// Each edge goes from a caller to a callee, labeled with the time of the callee beneath that caller, and thicker the more time it takes.
+    /// Use [`write_dot_tree`](Self::write_dot_tree) to keep every path of the scope tree apart instead.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::prof;
+    ///
+    /// fn main() {
+    ///   {
+    ///     prof!(parent);
+    ///     prof!(child);
+    ///   }
+    ///   let mut dot = Vec::new();
+    ///   profi::report().write_dot(&mut dot).unwrap();
+    ///   # #[cfg(feature = "enable")]
+    ///   assert!(String::from_utf8(dot).unwrap().contains("n0 -> n1"));
+    /// }
+    /// ```
+    pub fn write_dot(&self, to: impl std::io::Write) -> std::io::Result<()> {
+        crate::dot::write_call_graph(self, to)
+    }
+
+    /// Writes the scope tree of each thread as a [Graphviz](https://graphviz.org) DOT file, see [`write_dot`](Self::write_dot).
+    ///
+    /// Each scope of the tree is a node, so the same name can appear several times, and each thread is drawn in its own cluster.
+    pub fn write_dot_tree(&self, to: impl std::io::Write) -> std::io::Result<()> {
+        crate::dot::write_tree(self, to)
+    }
+
+    /// Prints the report to `to`, in the same way as [`print_on_exit!`](crate::print_on_exit).
+    ///
+    /// # Example
+    /// ```
+    /// use profi::{prof, PrintOptions};
+    ///
+    /// fn main() {
+    ///   {
+    ///     prof!(work);
+    ///   }
+    ///   let options = PrintOptions::new().format_duration(|d| format!("{}ns", d.as_nanos()));
+    ///   profi::report().print(std::io::stdout(), &options).unwrap();
+    /// }
+    /// ```
+    #[allow(unused_variables)]
+    pub fn print(
+        &self,
+        to: impl std::io::Write,
+        options: &crate::PrintOptions,
+    ) -> std::io::Result<()> {
+        #[cfg(feature = "enable")]
+        match options.format {
+            crate::Format::Table | crate::Format::Markdown => {
+                crate::process::print_timings(self, to, options)?
+            }
+            crate::Format::Folded => crate::export::write_folded(self, to)?,
+            crate::Format::Html => crate::process::print_html(self, to, options)?,
+            crate::Format::Binary => crate::trace::write(to)?,
+        }
+        // Still a valid trace, so it can be read back
+        #[cfg(not(feature = "enable"))]
+        if options.format == crate::Format::Binary {
+            crate::trace::write(to)?;
+        }
+        Ok(())
+    }
+}
+
+impl ScopeStats {
+    #[allow(unused)]
+    pub(crate) fn from_node(node: &ScopeNode, total: std::time::Duration, thread: usize) -> Self {
+        let sum = node.sum;
+        let percent = if !total.is_zero() {
+            (sum.as_secs_f64() / total.as_secs_f64()) * 100.0
+        } else {
+            100.0
+        };
+        let average = sum / node.count.max(1) as u32;
+        let mut stats = Self {
+            name: node.name.clone(),
+            depth: node.depth,
+            percent_app: percent,
+            total_real: sum,
+            percent_cpu: percent,
+            total_cpu: sum,
+            average,
+            stddev: std::time::Duration::ZERO,
+            cv: 0.0,
+            min: node.min,
+            max: node.max,
+            shutdown: node.shutdown,
+            wait: node.wait,
+            calls: node.count,
+            sample_rate: None,
+            energy: None,
+            cores: Vec::new(),
+            voluntary_switches: node.voluntary_switches,
+            involuntary_switches: node.involuntary_switches,
+            minor_faults: node.minor_faults,
+            major_faults: node.major_faults,
+            cycles: node.cycles,
+            instructions: node.instructions,
+            cache_misses: node.cache_misses,
+            counters: node.counters.clone(),
+            slow_calls: node.slow_calls.clone(),
+            aggregates: Vec::new(),
+            processes: Vec::new(),
+            owner: None,
+            description: None,
+            thread,
+            durations: match crate::aggregate::any() {
+                true => node.durations.clone(),
+                false => Vec::new(),
+            },
+            moments: (sum.as_secs_f64(), node.sum_squares),
+        };
+        for usage in &node.cores {
+            stats.add_core(usage.clone());
+        }
+        stats.update_stddev();
+        stats
+    }
+
+    fn add_core(&mut self, usage: CoreUsage) {
+        match self.cores.binary_search_by_key(&usage.core, |c| c.core) {
+            Ok(i) => self.cores[i].calls += usage.calls,
+            Err(i) => {
+                #[cfg(all(feature = "enable", feature = "cores"))]
+                let usage = CoreUsage {
+                    numa_node: crate::cores::numa_node(usage.core),
+                    ..usage
+                };
+                self.cores.insert(i, usage)
+            }
+        }
+    }
+
+    /// Total of the counter `name`, see [`prof_count!`](crate::prof_count).
+    pub fn counter(&self, name: &str) -> Option<u64> {
+        self.counters
+            .iter()
+            .find_map(|(n, value)| (n == name).then_some(*value))
+    }
+
+    /// Value of the [aggregator](crate::Aggregator) `name`.
+    pub fn aggregate(&self, name: &str) -> Option<f64> {
+        self.aggregates
+            .iter()
+            .find_map(|(n, value)| (n == name).then_some(*value))
+    }
+
+    /// Rate per second of the counter `name`, relative to the real time of the scope.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::{prof, prof_count};
+    ///
+    /// fn main() {
+    ///   {
+    ///     prof_count!("decode", bytes = 1024);
+    ///     std::thread::sleep(std::time::Duration::from_millis(1));
+    ///   }
+    ///   let report = profi::report();
+    ///   # #[cfg(feature = "enable")]
+    ///   # {
+    ///   let decode = report.scope("decode").unwrap();
+    ///   assert_eq!(decode.counter("bytes"), Some(1024));
+    ///   assert!(decode.rate("bytes").unwrap() > 0.0);
+    ///   # }
+    /// }
+    /// ```
+    pub fn rate(&self, name: &str) -> Option<f64> {
+        let value = self.counter(name)?;
+        let secs = self.total_real.as_secs_f64();
+        Some(if secs > 0.0 { value as f64 / secs } else { 0.0 })
+    }
+
+    /// Number of calls started on each NUMA node, sorted by node.
+    ///
+    /// Only recorded with the `cores` feature.
+    pub fn numa_nodes(&self) -> Vec<(usize, usize)> {
+        let mut nodes = Vec::<(usize, usize)>::new();
+        for c in &self.cores {
+            let Some(node) = c.numa_node else { continue };
+            match nodes.binary_search_by_key(&node, |n| n.0) {
+                Ok(i) => nodes[i].1 += c.calls,
+                Err(i) => nodes.insert(i, (node, c.calls)),
+            }
+        }
+        nodes
+    }
+
+    #[allow(unused)]
+    pub(crate) fn merge(&mut self, other: ScopeStats) {
+        self.depth = self.depth.min(other.depth);
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.calls += other.calls;
+        for usage in other.cores {
+            self.add_core(usage);
+        }
+        self.voluntary_switches += other.voluntary_switches;
+        self.involuntary_switches += other.involuntary_switches;
+        self.minor_faults += other.minor_faults;
+        self.major_faults += other.major_faults;
+        self.cycles += other.cycles;
+        self.instructions += other.instructions;
+        self.cache_misses += other.cache_misses;
+        for process in other.processes {
+            if !self.processes.contains(&process) {
+                self.processes.push(process);
+            }
+        }
+        self.slow_calls.extend(other.slow_calls);
+        self.durations.extend(other.durations);
+        self.moments.0 += other.moments.0;
+        self.moments.1 += other.moments.1;
+        self.update_stddev();
+        for (name, value) in other.counters {
+            match self.counters.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, total)) => *total += value,
+                None => self.counters.push((name, value)),
+            }
+        }
+        if self.thread != other.thread {
+            self.total_cpu += other.total_cpu;
+            self.total_real = self.total_real.max(other.total_real);
+            self.shutdown = self.shutdown.max(other.shutdown);
+            self.wait = self.wait.max(other.wait);
+        }
+        self.average = self.total_cpu / self.calls.max(1) as u32;
+    }
+
+    /// Merges the stats of another scope of the same [`group`](crate::group),
+    /// whose calls are added to the time of this one when they're on the same thread.
+    #[allow(unused)]
+    pub(crate) fn merge_group(&mut self, other: ScopeStats) {
+        if self.thread == other.thread {
+            self.total_real += other.total_real;
+            self.total_cpu += other.total_cpu;
+            self.shutdown += other.shutdown;
+            self.wait += other.wait;
+        }
+        self.merge(other);
+    }
+
+    /// Computes the standard deviation and the coefficient of variation from the moments of the calls.
+    fn update_stddev(&mut self) {
+        let (sum, squares) = self.moments;
+        if self.calls < 2 || sum <= 0.0 {
+            self.stddev = std::time::Duration::ZERO;
+            self.cv = 0.0;
+            return;
+        }
+        let n = self.calls as f64;
+        let mean = sum / n;
+        let variance = ((squares - n * mean * mean) / (n - 1.0)).max(0.0);
+        self.stddev = std::time::Duration::from_secs_f64(variance.sqrt());
+        self.cv = variance.sqrt() / mean * 100.0;
+    }
+
+    /// Part of the real time spent before [`begin_shutdown`](crate::begin_shutdown) was called.
+    pub fn steady_state(&self) -> std::time::Duration {
+        self.total_real.saturating_sub(self.shutdown)
+    }
+
+    #[allow(unused)]
+    pub(crate) fn update_percent(
+        &mut self,
+        total_app: std::time::Duration,
+        total_cpu: std::time::Duration,
+    ) {
+        self.percent_app = (self.total_real.as_secs_f64() / total_app.as_secs_f64()) * 100.;
+        self.percent_cpu = (self.total_cpu.as_secs_f64() / total_cpu.as_secs_f64()) * 100.;
+    }
+}
+
+impl ScopeNode {
+    /// Number of times the scope was called.
+    pub fn calls(&self) -> usize {
+        self.count
+    }
+
+    /// Combined time of all calls.
+    pub fn total(&self) -> std::time::Duration {
+        self.sum
+    }
+
+    /// Time spent in the scope itself, excluding its children.
+    pub fn self_time(&self) -> std::time::Duration {
+        let children = self
+            .children
+            .iter()
+            .map(ScopeNode::total)
+            .sum::<std::time::Duration>();
+        self.total().saturating_sub(children)
+    }
+
+    /// Returns this scope followed by all its descendants, in depth-first order.
+    pub fn iter(&self) -> impl Iterator<Item = &ScopeNode> {
+        let children: Box<dyn Iterator<Item = &ScopeNode>> =
+            Box::new(self.children.iter().flat_map(ScopeNode::iter));
+        std::iter::once(self).chain(children)
+    }
+}
+
+/// Returns the profiling results collected until now.
+///
+/// Includes the scopes of all threads, even the ones that are still running.
+/// Scopes that are still open are measured until now.
+pub fn report() -> Report {
+    #[cfg(feature = "enable")]
+    {
+        crate::measure::GLOBAL_PROFILER.report()
+    }
+    #[cfg(not(feature = "enable"))]
+    {
+        Report::default()
+    }
+}
+
+/// Returns the profiling results collected until now, or an error if the scopes of a thread were unbalanced.
+///
+/// [`report`] ignores the ends of scopes that were never started, use it to catch them, like in a test.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   {
+///     prof!(parse);
+///   }
+///   let report = profi::try_report().unwrap();
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(report.threads[0].unmatched, 0);
+/// }
+/// ```
+///
+/// A guard moved to another thread ends its scope there:
+/// ```
+/// fn main() {
+///   let started = std::time::Instant::now();
+///   let guard = profi::prof_guard!("moved");
+///   std::thread::spawn(move || drop(guard)).join().unwrap();
+///   std::thread::sleep(std::time::Duration::from_millis(5));
+///   let err = profi::try_report();
+///   # #[cfg(feature = "enable")]
+///   # {
+///   assert!(matches!(err, Err(profi::Error::Unbalanced { ends: 1, .. })));
+///   // Still open in the thread that started it, so it's measured until the report
+///   let report = profi::report();
+///   let elapsed = started.elapsed();
+///   assert_eq!(report.threads[0].unmatched, 0);
+///   let moved = report.scope("moved").unwrap();
+///   assert_eq!(moved.calls, 1);
+///   assert!(moved.total_real >= std::time::Duration::from_millis(5) && moved.total_real <= elapsed);
+///   # }
+/// }
+/// ```
+pub fn try_report() -> Result<Report, crate::Error> {
+    let report = report();
+    let unbalanced = crate::Error::unbalanced(&report).next();
+    match unbalanced {
+        Some(e) => Err(e),
+        None => Ok(report),
+    }
+}
+
+/// Returns the report of all the scopes measured until now, formatted like [`print_on_exit!`](crate::print_on_exit).
+///
+/// Useful where the program never exits, like WASM in the browser, to show the report in the page or log it to the console.
+/// Use [`Report::print`] to choose the [`PrintOptions`](crate::PrintOptions).
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   {
+///     prof!(frame);
+///   }
+///   let report = profi::report_string();
+///   # #[cfg(feature = "enable")]
+///   assert!(report.contains("frame"));
+/// }
+/// ```
+pub fn report_string() -> String {
+    let mut out = Vec::new();
+    // Writing to a `Vec` can't fail
+    let _ = report().print(&mut out, &Default::default());
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Returns the profiling results collected since the start or the last [`reset`].
+///
+/// Same as [`report()`], useful alongside [`reset`] for delimiting profiling epochs like frames or levels.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   for level in 0..3 {
+///     for _ in 0..10 {
+///       prof!(frame);
+///     }
+///     let snapshot = profi::snapshot();
+///     # #[cfg(feature = "enable")]
+///     assert_eq!(snapshot.scope("frame").unwrap().calls, 10);
+///     profi::reset();
+///   }
+/// }
+/// ```
+pub fn snapshot() -> Report {
+    report()
+}
+
+/// Discards all the measures taken until now, on all threads.
+///
+/// Guards that are alive are not affected, only the time after the reset is measured for them.
+pub fn reset() {
+    #[cfg(feature = "enable")]
+    crate::measure::GLOBAL_PROFILER.reset()
+}
@@ -0,0 +1,140 @@
+//! Limits on the measures and scopes kept by each thread.
+
+/// Maximum measures kept in memory by each thread, `0` if unlimited.
+#[cfg(feature = "enable")]
+static MAX_MEASURES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+/// Maximum scopes kept by each thread, `0` if unlimited.
+#[cfg(feature = "enable")]
+static MAX_SCOPES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// Limits on the data kept by each thread, so instrumentation that grows without bound can't take down the process.
+///
+/// - [`max_measures`](Self::max_measures): when a thread stores more measures, its closed scopes are folded into aggregates,
+///   like the `bounded-memory` feature does for every call.
+/// - [`max_scopes`](Self::max_scopes): when a thread has more distinct scopes, like names built with `fmt = ...` from unbounded ids,
+///   the ones with the least time are evicted into an `<evicted>` row of their parent, which keeps their calls and time.
+///
+/// The number of evicted scopes is noted after the table and in the [diagnostics](crate::PrintOptions::diagnostics).
+///
+/// # Example
+/// ```
+/// use profi::{prof, Retention};
+///
+/// fn main() {
+///   profi::set_retention(Retention::new().max_measures(50).max_scopes(8));
+///   for i in 0..100 {
+///     prof!(fmt = "request {i}");
+///   }
+///   let report = profi::report();
+///   # #[cfg(feature = "enable")]
+///   # {
+///   assert!(report.scopes.len() <= 8);
+///   assert!(report.scopes.iter().any(|s| s.name == "<evicted>"));
+///   assert_eq!(report.scopes.iter().map(|s| s.calls).sum::<usize>(), 100);
+///   # }
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Retention {
+    max_measures: Option<usize>,
+    max_scopes: Option<usize>,
+}
+
+impl Retention {
+    /// No limits, the default.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Folds the closed scopes of a thread into aggregates when it stores more than `max` measures.
+    ///
+    /// The folded calls are still counted and timed, but their individual durations are lost,
+    /// so [`ScopeNode::durations`](crate::ScopeNode::durations), the frames and the speedscope export don't include them.
+    /// Applied before [`set_memory_limit`](crate::set_memory_limit).
+    pub fn max_measures(mut self, max: usize) -> Self {
+        self.max_measures = Some(max);
+        self
+    }
+
+    /// Evicts the scopes with the least time of a thread when it has more than `max`,
+    /// down to three quarters of it so it doesn't happen on every call.
+    ///
+    /// The evicted scopes of each parent are merged into its `<evicted>` row, with their calls and time but without their children.
+    /// The folded scopes are limited while running, and the rest when the report is built.
+    pub fn max_scopes(mut self, max: usize) -> Self {
+        self.max_scopes = Some(max);
+        self
+    }
+}
+
+/// Limits the data kept by each thread from now on, see [`Retention`].
+#[allow(unused_variables)]
+pub fn set_retention(retention: Retention) {
+    #[cfg(feature = "enable")]
+    {
+        use std::sync::atomic::Ordering;
+
+        MAX_MEASURES.store(retention.max_measures.unwrap_or(0), Ordering::Relaxed);
+        MAX_SCOPES.store(retention.max_scopes.unwrap_or(0), Ordering::Relaxed);
+        crate::hooks::set(
+            crate::hooks::RETENTION,
+            retention.max_measures.is_some_and(|max| max != 0),
+        );
+    }
+}
+
+/// Maximum scopes kept by each thread, `None` if unlimited.
+#[cfg(feature = "enable")]
+pub(crate) fn max_scopes() -> Option<usize> {
+    match MAX_SCOPES.load(std::sync::atomic::Ordering::Relaxed) {
+        0 => None,
+        max => Some(max),
+    }
+}
+
+/// If a thread with `len` measures must [`fold`] its closed scopes.
+#[cfg(feature = "enable")]
+#[inline(always)]
+pub(crate) fn exceeded(len: usize) -> bool {
+    let max = MAX_MEASURES.load(std::sync::atomic::Ordering::Relaxed);
+    max != 0 && len > max
+}
+
+/// Folds the closed scopes of `measures` into the aggregates of the thread.
+#[cfg(feature = "enable")]
+#[cold]
+pub(crate) fn fold(
+    thread: &crate::measure::SharedMeasures,
+    measures: &mut Vec<crate::measure::Measure>,
+) {
+    use crate::measure::MeasureType;
+
+    let spill = thread.spill.get().cloned().flatten();
+    // The spilled measures are read back one chunk at a time
+    let all = std::mem::take(measures)
+        .into_iter()
+        .map(|m| match m.ty {
+            MeasureType::Spilled { offset, len } => {
+                let spilled = spill.as_ref().map(|s| s.read_back(offset, len));
+                (None, spilled.unwrap_or_default())
+            }
+            _ => (Some(m), Vec::new()),
+        })
+        .flat_map(|(m, spilled)| m.into_iter().chain(spilled));
+    let mut folded = thread.folded.lock().unwrap_or_else(|e| e.into_inner());
+    *measures = folded.fold_closed(all);
+    evict_folded(&mut folded);
+    // Everything spilled was folded
+    if let Some(spill) = spill {
+        spill.clear();
+    }
+}
+
+/// Evicts the folded scopes if there are more than the limit.
+#[cfg(feature = "enable")]
+#[inline(always)]
+pub(crate) fn evict_folded(folded: &mut crate::process::Folded) {
+    if let Some(max) = max_scopes() {
+        folded.evict(max);
+    }
+}
@@ -0,0 +1,143 @@
+//! Summary of several runs of the same program.
+
+use crate::Report;
+
+/// Mean and standard deviation of each scope across several runs, obtained with [`aggregate_runs`].
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   let mut runs = Vec::new();
+///   for _ in 0..5 {
+///     {
+///       prof!(work);
+///       std::thread::sleep(std::time::Duration::from_millis(1));
+///     }
+///     runs.push(profi::report());
+///     profi::reset();
+///   }
+///   let summary = profi::aggregate_runs(&runs);
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(summary.scope("work").unwrap().runs, 5);
+///   summary.print(std::io::stdout(), &Default::default()).unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultiRunReport {
+    /// Number of reports aggregated.
+    pub runs: usize,
+    /// Time since profiling started until each report was taken.
+    pub wall_time: RunStats,
+    /// Scopes of all runs, in the order they first appear.
+    pub scopes: Vec<RunStats>,
+}
+
+/// Real time of a scope across runs.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunStats {
+    pub name: String,
+    /// Number of runs where the scope appears, the rest of fields only include them.
+    pub runs: usize,
+    pub mean: std::time::Duration,
+    /// Sample standard deviation, zero with a single run.
+    pub stddev: std::time::Duration,
+    pub min: std::time::Duration,
+    pub max: std::time::Duration,
+    /// Average number of calls per run.
+    pub calls: f64,
+}
+
+/// Computes the mean and standard deviation of each scope across `reports`, usually the same program run several times.
+///
+/// Reports can be read from files saved by each run, for example with the `serde` feature.
+pub fn aggregate_runs(reports: &[Report]) -> MultiRunReport {
+    let mut scopes = Vec::<(String, Vec<std::time::Duration>, usize)>::new();
+    for report in reports {
+        for scope in &report.scopes {
+            match scopes.iter_mut().find(|(name, ..)| *name == scope.name) {
+                Some((_, times, calls)) => {
+                    times.push(scope.total_real);
+                    *calls += scope.calls;
+                }
+                None => scopes.push((scope.name.clone(), vec![scope.total_real], scope.calls)),
+            }
+        }
+    }
+    let wall_times = reports.iter().map(|r| r.wall_time).collect::<Vec<_>>();
+    MultiRunReport {
+        runs: reports.len(),
+        wall_time: RunStats::new("Wall time".into(), &wall_times, 0),
+        scopes: scopes
+            .into_iter()
+            .map(|(name, times, calls)| RunStats::new(name, &times, calls))
+            .collect(),
+    }
+}
+
+impl RunStats {
+    fn new(name: String, times: &[std::time::Duration], calls: usize) -> Self {
+        let runs = times.len();
+        if runs == 0 {
+            return Self {
+                name,
+                ..Default::default()
+            };
+        }
+        let mean = times.iter().map(|t| t.as_secs_f64()).sum::<f64>() / runs as f64;
+        let variance = match runs {
+            1 => 0.0,
+            _ => {
+                times
+                    .iter()
+                    .map(|t| (t.as_secs_f64() - mean).powi(2))
+                    .sum::<f64>()
+                    / (runs - 1) as f64
+            }
+        };
+        Self {
+            name,
+            runs,
+            mean: std::time::Duration::from_secs_f64(mean),
+            stddev: std::time::Duration::from_secs_f64(variance.sqrt()),
+            min: times.iter().copied().min().unwrap_or_default(),
+            max: times.iter().copied().max().unwrap_or_default(),
+            calls: calls as f64 / runs as f64,
+        }
+    }
+
+    /// Coefficient of variation, the standard deviation as a percentage of the mean.
+    pub fn cv(&self) -> f64 {
+        if self.mean.is_zero() {
+            return 0.0;
+        }
+        self.stddev.as_secs_f64() / self.mean.as_secs_f64() * 100.0
+    }
+}
+
+impl MultiRunReport {
+    /// Returns the statistics of the scope `name`, if it appears in any run.
+    pub fn scope(&self, name: &str) -> Option<&RunStats> {
+        self.scopes.iter().find(|s| s.name == name)
+    }
+
+    /// Prints a table with the mean ± standard deviation of each scope, followed by the wall time.
+    ///
+    /// Scopes whose coefficient of variation is above [`PrintOptions::cv_threshold`](crate::PrintOptions::cv_threshold) are marked with `⚠`.
+    #[allow(unused_variables, unused_mut)]
+    pub fn print(
+        &self,
+        mut to: impl std::io::Write,
+        options: &crate::PrintOptions,
+    ) -> std::io::Result<()> {
+        #[cfg(feature = "enable")]
+        {
+            let table = crate::process::create_runs_table(self, options);
+            crate::process::write_rendered(&mut to, &table, options.format)?;
+        }
+        Ok(())
+    }
+}
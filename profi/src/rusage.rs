@@ -0,0 +1,89 @@
+//! Per-thread resource usage counters, read with `getrusage(RUSAGE_THREAD)` and the hardware performance counters.
+
+/// Counters of the current thread at a specific point in time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Usage {
+    pub(crate) voluntary_switches: u64,
+    pub(crate) involuntary_switches: u64,
+    pub(crate) minor_faults: u64,
+    pub(crate) major_faults: u64,
+    pub(crate) cycles: u64,
+    pub(crate) instructions: u64,
+    pub(crate) cache_misses: u64,
+}
+
+impl Usage {
+    /// Counters of the current thread, all zeros if not supported.
+    #[inline(always)]
+    #[allow(unused)]
+    pub(crate) fn current() -> Self {
+        #[allow(unused_mut)]
+        let mut current = Self::default();
+        #[cfg(all(
+            any(feature = "context-switches", feature = "page-faults"),
+            target_os = "linux"
+        ))]
+        {
+            // SAFETY: `rusage` is plain data, and it's only read if `getrusage` succeeds
+            let mut usage = unsafe { std::mem::zeroed::<libc::rusage>() };
+            if unsafe { libc::getrusage(libc::RUSAGE_THREAD, &mut usage) } == 0 {
+                current.voluntary_switches = usage.ru_nvcsw as u64;
+                current.involuntary_switches = usage.ru_nivcsw as u64;
+                current.minor_faults = usage.ru_minflt as u64;
+                current.major_faults = usage.ru_majflt as u64;
+            }
+        }
+        #[cfg(all(feature = "perf", target_os = "linux"))]
+        if let Some([cycles, instructions, cache_misses]) = crate::perf::read() {
+            current.cycles = cycles;
+            current.instructions = instructions;
+            current.cache_misses = cache_misses;
+        }
+        current
+    }
+
+    /// Difference between `self` and an earlier reading.
+    pub(crate) fn since(self, earlier: Self) -> Self {
+        Self {
+            voluntary_switches: self
+                .voluntary_switches
+                .saturating_sub(earlier.voluntary_switches),
+            involuntary_switches: self
+                .involuntary_switches
+                .saturating_sub(earlier.involuntary_switches),
+            minor_faults: self.minor_faults.saturating_sub(earlier.minor_faults),
+            major_faults: self.major_faults.saturating_sub(earlier.major_faults),
+            cycles: self.cycles.saturating_sub(earlier.cycles),
+            instructions: self.instructions.saturating_sub(earlier.instructions),
+            cache_misses: self.cache_misses.saturating_sub(earlier.cache_misses),
+        }
+    }
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, rhs: Self) {
+        self.voluntary_switches += rhs.voluntary_switches;
+        self.involuntary_switches += rhs.involuntary_switches;
+        self.minor_faults += rhs.minor_faults;
+        self.major_faults += rhs.major_faults;
+        self.cycles += rhs.cycles;
+        self.instructions += rhs.instructions;
+        self.cache_misses += rhs.cache_misses;
+    }
+}
+
+impl std::ops::Mul<u64> for Usage {
+    type Output = Self;
+
+    fn mul(self, rhs: u64) -> Self {
+        Self {
+            voluntary_switches: self.voluntary_switches * rhs,
+            involuntary_switches: self.involuntary_switches * rhs,
+            minor_faults: self.minor_faults * rhs,
+            major_faults: self.major_faults * rhs,
+            cycles: self.cycles * rhs,
+            instructions: self.instructions * rhs,
+            cache_misses: self.cache_misses * rhs,
+        }
+    }
+}
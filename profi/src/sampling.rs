@@ -0,0 +1,172 @@
+//! Automatic sampling of scopes whose recording overhead is too high compared to their own time,
+//! and fixed sampling of the call sites with `sample = 1 / N`.
+
+/// Budget as the bits of an `f64`, `0` if disabled.
+#[cfg(feature = "enable")]
+static BUDGET: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// Number of recorded calls between each stride adjustment.
+#[cfg(feature = "enable")]
+const WINDOW: u64 = 64;
+#[cfg(feature = "enable")]
+const MAX_STRIDE: u64 = 1024;
+
+/// Limits the recording overhead of each scope to a fraction of its own measured time.
+///
+/// When recording a scope costs more than `budget` of its time (e.g. `0.05` for 5%), only one every N calls is recorded,
+/// doubling N until it fits in the budget. If the scope becomes slower N is halved again.
+///
+/// Sampled scopes show the percentage of recorded calls next to their calls, see [`ScopeStats::sample_rate`](crate::ScopeStats::sample_rate).
+/// The times and calls of sampled scopes only include the recorded calls, while the average stays representative.
+///
+/// Only applies to [`prof!`](crate::prof) and [`prof_guard!`](crate::prof_guard), `None` disables it (the default).
+///
+/// # Example
+/// ```
+/// use profi::{prof, print_on_exit};
+///
+/// fn hot() {
+///   prof!();
+/// }
+///
+/// fn main() {
+///   print_on_exit!();
+///   profi::set_overhead_budget(Some(0.05));
+///   for _ in 0..10_000 {
+///     hot();
+///   }
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn set_overhead_budget(budget: Option<f64>) {
+    #[cfg(feature = "enable")]
+    {
+        let bits = budget.filter(|b| *b > 0.0).map_or(0, f64::to_bits);
+        BUDGET.store(bits, std::sync::atomic::Ordering::Release);
+        crate::hooks::set(crate::hooks::BUDGET, bits != 0);
+    }
+}
+
+/// Current budget, `None` if disabled.
+#[cfg(feature = "enable")]
+#[inline(always)]
+pub(crate) fn budget() -> Option<f64> {
+    match BUDGET.load(std::sync::atomic::Ordering::Acquire) {
+        0 => None,
+        bits => Some(f64::from_bits(bits)),
+    }
+}
+
+/// Estimated cost of recording a single measure, calibrated on first use.
+#[cfg(feature = "enable")]
+fn measure_cost() -> std::time::Duration {
+    static COST: std::sync::OnceLock<std::time::Duration> = std::sync::OnceLock::new();
+
+    *COST.get_or_init(|| {
+        const N: u32 = 10_000;
+        let mut measures = Vec::with_capacity(N as usize);
+        let start = crate::clock::Instant::now();
+        for _ in 0..N {
+            measures.push(crate::measure::Measure::end(crate::clock::Instant::now()));
+        }
+        let elapsed = start.elapsed();
+        std::hint::black_box(measures);
+        elapsed / N
+    })
+}
+
+/// Sampling state of a scope in a single thread.
+#[cfg(feature = "enable")]
+#[derive(Debug, Clone)]
+pub(crate) struct Sampler {
+    pub(crate) name: crate::Str,
+    /// Calls seen, including the skipped ones.
+    pub(crate) seen: u64,
+    pub(crate) recorded: u64,
+    /// Records one every `stride` calls.
+    stride: u64,
+    window_calls: u64,
+    window_time: std::time::Duration,
+}
+
+#[cfg(feature = "enable")]
+impl Sampler {
+    pub(crate) fn new(name: crate::Str) -> Self {
+        Self {
+            name,
+            seen: 0,
+            recorded: 0,
+            stride: 1,
+            window_calls: 0,
+            window_time: std::time::Duration::ZERO,
+        }
+    }
+
+    /// Returns `true` if the call should be recorded.
+    #[inline(always)]
+    pub(crate) fn sample(&mut self) -> bool {
+        let record = self.seen % self.stride == 0;
+        self.seen += 1;
+        self.recorded += u64::from(record);
+        record
+    }
+
+    /// Adjusts the stride with the duration of a recorded call.
+    pub(crate) fn observe(&mut self, duration: std::time::Duration, budget: f64) {
+        self.window_calls += 1;
+        self.window_time += duration;
+        if self.window_calls < WINDOW {
+            return;
+        }
+        // Each call records two measures
+        let overhead = (measure_cost() * 2 * self.window_calls as u32).as_secs_f64();
+        let time = self.window_time.as_secs_f64();
+        if overhead > budget * time {
+            self.stride = (self.stride * 2).min(MAX_STRIDE);
+        } else if overhead < budget * time / 4.0 {
+            self.stride = (self.stride / 2).max(1);
+        }
+        self.window_calls = 0;
+        self.window_time = std::time::Duration::ZERO;
+    }
+
+    /// Forgets the calls seen, keeping the current stride.
+    pub(crate) fn reset(&mut self) {
+        self.seen = 0;
+        self.recorded = 0;
+    }
+}
+
+/// Call sites recorded once every N calls with `sample = 1 / N`, with their name and N.
+#[cfg(feature = "enable")]
+#[allow(clippy::type_complexity)]
+static FIXED_RATES: std::sync::Mutex<
+    Vec<(&'static crate::zz_private::SampleCounter, crate::Str, usize)>,
+> = std::sync::Mutex::new(Vec::new());
+
+/// Remembers that only one every `every` calls of the call site `site` are recorded, called on its first call.
+#[cfg(feature = "enable")]
+pub(crate) fn set_fixed_rate(
+    site: &'static crate::zz_private::SampleCounter,
+    name: &crate::names::Name,
+    every: usize,
+) {
+    let mut rates = FIXED_RATES.lock().unwrap_or_else(|e| e.into_inner());
+    if !rates.iter().any(|(s, _, _)| std::ptr::eq(*s, site)) {
+        rates.push((site, name.clone().into(), every));
+    }
+}
+
+/// Names of the scopes with a fixed sample rate and their N, the largest one if several call sites share a name.
+#[cfg(feature = "enable")]
+pub(crate) fn fixed_rates() -> Vec<(crate::Str, usize)> {
+    let rates = FIXED_RATES.lock().unwrap_or_else(|e| e.into_inner());
+    let mut names: Vec<(crate::Str, usize)> = Vec::new();
+    for (_, name, every) in rates.iter() {
+        match names.iter_mut().find(|(n, _)| n == name) {
+            Some((_, rate)) => *rate = (*rate).max(*every),
+            None => names.push((name.clone(), *every)),
+        }
+    }
+    names
+}
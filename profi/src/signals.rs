@@ -0,0 +1,403 @@
+//! Reports written when the process receives a signal.
+//!
+//! Signal handlers can only do async-signal-safe work, so the handler just writes the signal number into a pipe,
+//! and a background thread builds and writes the report.
+//!
+//! The disposition a signal had before is saved and restored when profi stops handling it.
+//! If the application already had a handler, like the ones of tokio's `ctrl_c` or the `ctrlc` crate,
+//! it's called after profi's work instead of exiting, so the application still shuts down on its own.
+//! Signals that were ignored, like `SIGINT` in background jobs, stay ignored when stopping or interrupting.
+//!
+//! On Windows only stopping is handled, with a console control handler, which already runs in its own thread.
+
+/// Signal that triggers a dump, see [`dump_on_signal`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// `SIGUSR1`, like `kill -USR1 <pid>`.
+    Usr1,
+    /// `SIGUSR2`.
+    Usr2,
+    /// `SIGHUP`, sent when the terminal is closed.
+    Hup,
+}
+
+/// Writes a report of all threads to `path` every time the process receives `signal`.
+///
+/// The report includes the threads that are still running, measuring the open scopes until the signal arrived,
+/// so a job that hangs can be inspected without stopping it: `kill -USR1 <pid>`.
+/// Each dump replaces the previous one, and is printed with the default [`PrintOptions`](crate::PrintOptions).
+/// A handler the application already had for `signal` is still called after each dump.
+///
+/// Returns an error if the handler can't be installed, or on platforms other than Linux.
+///
+/// # Example
+/// ```
+/// use profi::{prof, Signal};
+///
+/// fn main() {
+///   profi::dump_on_signal(Signal::Usr1, std::env::temp_dir().join("profi-dump.txt")).unwrap();
+///   prof!(long_job);
+///   // ...
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn dump_on_signal(signal: Signal, path: impl Into<std::path::PathBuf>) -> std::io::Result<()> {
+    #[cfg(all(feature = "enable", target_os = "linux"))]
+    {
+        let signal = signal.number();
+        let path = path.into();
+        let mut dumps = DUMPS.lock().unwrap_or_else(|e| e.into_inner());
+        match dumps.iter_mut().find(|(s, _)| *s == signal) {
+            Some((_, p)) => *p = path,
+            None => dumps.push((signal, path)),
+        }
+        install(signal)
+    }
+    #[cfg(all(feature = "enable", not(target_os = "linux")))]
+    return Err(std::io::ErrorKind::Unsupported.into());
+    #[cfg(not(feature = "enable"))]
+    Ok(())
+}
+
+#[cfg(all(feature = "enable", target_os = "linux"))]
+impl Signal {
+    fn number(self) -> libc::c_int {
+        match self {
+            Signal::Usr1 => libc::SIGUSR1,
+            Signal::Usr2 => libc::SIGUSR2,
+            Signal::Hup => libc::SIGHUP,
+        }
+    }
+}
+
+/// Path written by each signal registered with [`dump_on_signal`].
+#[cfg(all(feature = "enable", target_os = "linux"))]
+static DUMPS: std::sync::Mutex<Vec<(libc::c_int, std::path::PathBuf)>> =
+    std::sync::Mutex::new(Vec::new());
+/// Options of the [`print_on_exit!`](crate::print_on_exit) that is alive, printed on `SIGINT`.
+#[cfg(all(feature = "enable", target_os = "linux"))]
+static INTERRUPT: std::sync::Mutex<Option<crate::PrintOptions>> = std::sync::Mutex::new(None);
+/// Where the report is written if the process is stopped, see [`ExitPrinter::report_on_stop`](crate::ExitPrinter::report_on_stop).
+#[cfg(all(feature = "enable", any(target_os = "linux", windows)))]
+static STOP: std::sync::Mutex<Option<(std::path::PathBuf, crate::PrintOptions)>> =
+    std::sync::Mutex::new(None);
+/// Disposition of each handled signal before [`install`], restored by [`uninstall`].
+#[cfg(all(feature = "enable", target_os = "linux"))]
+static PREVIOUS: std::sync::Mutex<Vec<(libc::c_int, libc::sigaction)>> =
+    std::sync::Mutex::new(Vec::new());
+/// Write end of the pipe read by the signal thread, `-1` until it's created.
+#[cfg(all(feature = "enable", target_os = "linux"))]
+static PIPE: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(-1);
+
+/// Prints the report with `options` to stderr if the program is interrupted with Ctrl+C while `print_on_exit!` is alive.
+///
+/// The writer of `print_on_exit!` belongs to the interrupted thread, so stderr is used instead.
+#[cfg(feature = "enable")]
+#[allow(unused_variables)]
+pub(crate) fn print_on_interrupt(options: &crate::PrintOptions) {
+    #[cfg(target_os = "linux")]
+    if !ignored(libc::SIGINT) {
+        *INTERRUPT.lock().unwrap_or_else(|e| e.into_inner()) = Some(options.clone());
+        if let Err(e) = install(libc::SIGINT) {
+            eprintln!(
+                "[profi] Could not handle Ctrl+C, the report won't be printed if interrupted: {e}"
+            );
+        }
+    }
+}
+
+/// Restores the previous behaviour of Ctrl+C, called when `print_on_exit!` prints the report.
+#[cfg(feature = "enable")]
+pub(crate) fn stop_interrupt() {
+    #[cfg(target_os = "linux")]
+    if INTERRUPT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .is_some()
+    {
+        uninstall(libc::SIGINT);
+    }
+}
+
+/// Writes the report with `options` to `path` if the OS stops the process, with `SIGTERM` on Linux,
+/// or by closing the console or shutting down on Windows.
+#[cfg(feature = "enable")]
+#[allow(unused_variables)]
+pub(crate) fn report_on_stop(
+    path: std::path::PathBuf,
+    options: &crate::PrintOptions,
+) -> std::io::Result<()> {
+    #[cfg(target_os = "linux")]
+    if ignored(libc::SIGTERM) {
+        return Ok(());
+    }
+    #[cfg(any(target_os = "linux", windows))]
+    {
+        *STOP.lock().unwrap_or_else(|e| e.into_inner()) = Some((path, options.clone()));
+    }
+    #[cfg(target_os = "linux")]
+    return install(libc::SIGTERM);
+    #[cfg(windows)]
+    {
+        // SAFETY: `console_handler` matches the signature of a `PHANDLER_ROUTINE`
+        if unsafe { SetConsoleCtrlHandler(Some(console_handler), 1) } == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        return Ok(());
+    }
+    #[cfg(not(any(target_os = "linux", windows)))]
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Restores the previous behaviour when stopped, called when the report is printed as usual.
+#[cfg(feature = "enable")]
+pub(crate) fn stop_report_on_stop() {
+    #[cfg(any(target_os = "linux", windows))]
+    if STOP
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .is_some()
+    {
+        #[cfg(target_os = "linux")]
+        uninstall(libc::SIGTERM);
+        // SAFETY: removes the handler added by `report_on_stop`
+        #[cfg(windows)]
+        unsafe {
+            SetConsoleCtrlHandler(Some(console_handler), 0)
+        };
+    }
+}
+
+#[cfg(all(feature = "enable", windows))]
+#[link(name = "kernel32")]
+extern "system" {
+    fn SetConsoleCtrlHandler(
+        handler: Option<unsafe extern "system" fn(u32) -> i32>,
+        add: i32,
+    ) -> i32;
+}
+
+/// Called by Windows in a new thread, handles Ctrl+Break, closing the console and shutting down.
+///
+/// Logging off is ignored, services receive it whenever any user logs off.
+#[cfg(all(feature = "enable", windows))]
+unsafe extern "system" fn console_handler(event: u32) -> i32 {
+    const CTRL_BREAK_EVENT: u32 = 1;
+    const CTRL_CLOSE_EVENT: u32 = 2;
+    const CTRL_SHUTDOWN_EVENT: u32 = 6;
+    /// Exit code of a process stopped by a console event
+    const STATUS_CONTROL_C_EXIT: u32 = 0xC000013A;
+
+    match event {
+        CTRL_BREAK_EVENT | CTRL_CLOSE_EVENT | CTRL_SHUTDOWN_EVENT => {
+            stopped(STATUS_CONTROL_C_EXIT as i32)
+        }
+        _ => 0,
+    }
+}
+
+#[cfg(all(feature = "enable", target_os = "linux"))]
+extern "C" fn handler(signal: libc::c_int) {
+    let byte = signal as u8;
+    let pipe = PIPE.load(std::sync::atomic::Ordering::Relaxed);
+    // SAFETY: `write` is async-signal-safe, and the byte outlives the call
+    unsafe { libc::write(pipe, (&byte as *const u8).cast(), 1) };
+}
+
+/// Starts the signal thread if needed and handles `signal` with [`handler`], saving its previous disposition.
+#[cfg(all(feature = "enable", target_os = "linux"))]
+fn install(signal: libc::c_int) -> std::io::Result<()> {
+    static STARTED: std::sync::OnceLock<Result<(), std::io::ErrorKind>> =
+        std::sync::OnceLock::new();
+
+    STARTED
+        .get_or_init(|| {
+            let mut fds = [0; 2];
+            // SAFETY: `fds` has room for both ends
+            if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+                return Err(std::io::Error::last_os_error().kind());
+            }
+            PIPE.store(fds[1], std::sync::atomic::Ordering::Relaxed);
+            std::thread::Builder::new()
+                .name("profi-signals".into())
+                .spawn(move || listen(fds[0]))
+                .map(drop)
+                .map_err(|e| e.kind())
+        })
+        .map_err(std::io::Error::from)?;
+
+    let mut previous = PREVIOUS.lock().unwrap_or_else(|e| e.into_inner());
+    if previous.iter().any(|(s, _)| *s == signal) {
+        return Ok(());
+    }
+    // SAFETY: `action` is fully initialized, `handler` is async-signal-safe and `old` has room for the previous one
+    unsafe {
+        let mut action = std::mem::zeroed::<libc::sigaction>();
+        action.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        let mut old = std::mem::zeroed::<libc::sigaction>();
+        if libc::sigaction(signal, &action, &mut old) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        previous.push((signal, old));
+    }
+    Ok(())
+}
+
+/// Restores the disposition `signal` had before [`install`].
+#[cfg(all(feature = "enable", target_os = "linux"))]
+fn uninstall(signal: libc::c_int) {
+    let mut previous = PREVIOUS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(i) = previous.iter().position(|(s, _)| *s == signal) {
+        let (_, old) = previous.swap_remove(i);
+        // SAFETY: `old` was returned by `sigaction` for this signal
+        unsafe { libc::sigaction(signal, &old, std::ptr::null_mut()) };
+    }
+}
+
+/// Whether `signal` is currently ignored.
+#[cfg(all(feature = "enable", target_os = "linux"))]
+fn ignored(signal: libc::c_int) -> bool {
+    // SAFETY: only reads the current disposition into `current`
+    unsafe {
+        let mut current = std::mem::zeroed::<libc::sigaction>();
+        libc::sigaction(signal, std::ptr::null(), &mut current) == 0
+            && current.sa_sigaction == libc::SIG_IGN
+    }
+}
+
+/// Whether the application had its own handler for `signal` before [`install`].
+#[cfg(all(feature = "enable", target_os = "linux"))]
+fn has_handler(signal: libc::c_int) -> bool {
+    PREVIOUS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .any(|(s, old)| {
+            *s == signal && old.sa_sigaction != libc::SIG_DFL && old.sa_sigaction != libc::SIG_IGN
+        })
+}
+
+/// Lets `signal` do what it did before [`install`], by restoring that disposition and raising it again.
+///
+/// The default one stops the process, a handler of the application is called and `signal` is handled again afterwards.
+#[cfg(all(feature = "enable", target_os = "linux"))]
+fn forward(signal: libc::c_int) {
+    let handled = has_handler(signal);
+    uninstall(signal);
+    // SAFETY: raising a signal is always valid, it's delivered to this thread before `raise` returns
+    unsafe { libc::raise(signal) };
+    if handled {
+        let _ = install(signal);
+    }
+}
+
+/// Body of the signal thread, reads the signals from the pipe and writes the reports.
+#[cfg(all(feature = "enable", target_os = "linux"))]
+fn listen(pipe: libc::c_int) {
+    loop {
+        let mut byte = 0u8;
+        // SAFETY: `byte` has room for the single byte read
+        match unsafe { libc::read(pipe, (&mut byte as *mut u8).cast(), 1) } {
+            1 => {}
+            -1 if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted => {
+                continue
+            }
+            _ => return,
+        }
+        let signal = libc::c_int::from(byte);
+        if signal == libc::SIGINT {
+            interrupted();
+            continue;
+        }
+        if signal == libc::SIGTERM {
+            terminated();
+            continue;
+        }
+        let path = DUMPS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .find(|(s, _)| *s == signal)
+            .map(|(_, path)| path.clone());
+        if let Some(path) = path {
+            let report = crate::measure::GLOBAL_PROFILER.report();
+            let written = std::fs::File::create(&path)
+                .and_then(|file| report.print(std::io::BufWriter::new(file), &Default::default()));
+            if let Err(e) = written {
+                eprintln!(
+                    "[profi] Could not dump the report to {}: {e}",
+                    path.display()
+                );
+            }
+        }
+        if has_handler(signal) {
+            forward(signal);
+        }
+    }
+}
+
+/// Prints the report of `print_on_exit!` and stops like the default handler of `SIGINT`,
+/// or calls the handler of the application, which prints it when `print_on_exit!` is dropped.
+#[cfg(all(feature = "enable", target_os = "linux"))]
+fn interrupted() {
+    // Keeps the lock so `print_on_exit!` can't print it again while stopping
+    let options = INTERRUPT.lock().unwrap_or_else(|e| e.into_inner());
+    if has_handler(libc::SIGINT) {
+        drop(options);
+        return forward(libc::SIGINT);
+    }
+    if let Some(options) = options.as_ref() {
+        if crate::filter::is_profiling_enabled() {
+            let report = crate::measure::GLOBAL_PROFILER.report();
+            let _ = crate::ReportSink::write_report(&mut std::io::stderr(), &report, options);
+        }
+    }
+    forward(libc::SIGINT);
+}
+
+/// Writes the report registered with [`report_on_stop`] and stops like the default handler of `SIGTERM`,
+/// or calls the handler of the application, which writes it when the [`ExitPrinter`](crate::ExitPrinter) is dropped.
+#[cfg(all(feature = "enable", target_os = "linux"))]
+fn terminated() {
+    // Keeps the lock so the `ExitPrinter` can't print it again while stopping
+    let stop = STOP.lock().unwrap_or_else(|e| e.into_inner());
+    if has_handler(libc::SIGTERM) {
+        drop(stop);
+        return forward(libc::SIGTERM);
+    }
+    if let Some((path, options)) = stop.as_ref() {
+        write_stop_report(path, options);
+    }
+    forward(libc::SIGTERM);
+}
+
+/// Writes the report to `path` if profiling is enabled, when the process is stopped.
+#[cfg(all(feature = "enable", any(target_os = "linux", windows)))]
+fn write_stop_report(path: &std::path::Path, options: &crate::PrintOptions) {
+    if crate::filter::is_profiling_enabled() {
+        let report = crate::measure::GLOBAL_PROFILER.report();
+        let written = std::fs::File::create(path)
+            .and_then(|file| report.print(std::io::BufWriter::new(file), options));
+        if let Err(e) = written {
+            eprintln!(
+                "[profi] Could not write the report to {}: {e}",
+                path.display()
+            );
+        }
+    }
+}
+
+/// Writes the report registered with [`report_on_stop`] and exits with `code`.
+#[cfg(all(feature = "enable", windows))]
+fn stopped(code: i32) -> ! {
+    // Keeps the lock so the `ExitPrinter` can't print it again while exiting
+    let stop = STOP.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((path, options)) = stop.as_ref() {
+        write_stop_report(path, options);
+    }
+    std::process::exit(code)
+}
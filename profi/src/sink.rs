@@ -0,0 +1,109 @@
+//! Destinations of the report printed on exit.
+
+/// Where [`print_on_exit!`](crate::print_on_exit) and [`ExitPrinter`](crate::ExitPrinter) send the report when the program exits.
+///
+/// Implemented for every [`std::io::Write`], which prints the report with [`Report::print`](crate::Report::print).
+/// Implement it to send the report somewhere that isn't a stream, like an HTTP endpoint, syslog or Android's logcat.
+///
+/// # Example
+/// ```
+/// use profi::{print_on_exit, PrintOptions, Report, ReportSink};
+///
+/// /// Logs each line of the report with a tag, like logcat
+/// struct Logcat(&'static str);
+///
+/// impl ReportSink for Logcat {
+///   fn write_report(&mut self, report: &Report, options: &PrintOptions) -> std::io::Result<()> {
+///     let mut text = Vec::new();
+///     report.print(&mut text, options)?;
+///     for line in String::from_utf8_lossy(&text).lines() {
+///       eprintln!("I/{}: {line}", self.0);
+///     }
+///     Ok(())
+///   }
+/// }
+///
+/// fn main() {
+///   print_on_exit!(to = Logcat("profi"));
+///   profi::prof!(work);
+/// }
+/// ```
+pub trait ReportSink {
+    /// Sends `report`, formatted with `options` if it's written as text.
+    ///
+    /// Errors are sent to [`on_error`](crate::on_error), which prints them to stderr by default.
+    fn write_report(
+        &mut self,
+        report: &crate::Report,
+        options: &crate::PrintOptions,
+    ) -> std::io::Result<()>;
+}
+
+impl<W: std::io::Write> ReportSink for W {
+    fn write_report(
+        &mut self,
+        report: &crate::Report,
+        options: &crate::PrintOptions,
+    ) -> std::io::Result<()> {
+        report.print(self, options)
+    }
+}
+
+/// Sends the report to `fallback` if it can't be sent to `primary`, so it isn't lost when the disk is full or the pipe is closed.
+///
+/// Created by [`print_on_exit!`](crate::print_on_exit) with `fallback = stderr`, `fallback = stdout` or `fallback = <sink>`,
+/// or passed to [`ExitPrinter::to`](crate::ExitPrinter::to).
+/// The error of `primary` is printed to stderr before trying `fallback`.
+///
+/// # Example
+/// ```
+/// use profi::{Fallback, PrintOptions, ReportSink};
+/// use std::io::{Error, ErrorKind, Write};
+///
+/// /// A file in a full disk
+/// struct Full;
+///
+/// impl Write for Full {
+///   fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+///     Err(Error::new(ErrorKind::Other, "no space left on device"))
+///   }
+///   fn flush(&mut self) -> std::io::Result<()> {
+///     Ok(())
+///   }
+/// }
+///
+/// fn main() {
+///   profi::prof!(work);
+///   let mut sink = Fallback::new(Full, Vec::new());
+///   sink.write_report(&profi::report(), &PrintOptions::new()).unwrap();
+///   # #[cfg(feature = "enable")]
+///   assert!(!sink.fallback.is_empty());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Fallback<P, F> {
+    /// Tried first.
+    pub primary: P,
+    /// Used if `primary` fails.
+    pub fallback: F,
+}
+
+impl<P: ReportSink, F: ReportSink> Fallback<P, F> {
+    /// Sends the report to `fallback` if `primary` fails.
+    pub fn new(primary: P, fallback: F) -> Self {
+        Self { primary, fallback }
+    }
+}
+
+impl<P: ReportSink, F: ReportSink> ReportSink for Fallback<P, F> {
+    fn write_report(
+        &mut self,
+        report: &crate::Report,
+        options: &crate::PrintOptions,
+    ) -> std::io::Result<()> {
+        self.primary.write_report(report, options).or_else(|e| {
+            eprintln!("[profi] Could not print the results: {e}, using the fallback");
+            self.fallback.write_report(report, options)
+        })
+    }
+}
@@ -0,0 +1,76 @@
+//! Warnings through the `log` crate for calls slower than a threshold, emitted as soon as they end.
+
+/// Threshold in nanoseconds, `0` if disabled.
+#[cfg(feature = "enable")]
+static THRESHOLD: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// Emits a warning through the [`log`](https://docs.rs/log) crate for every call of any scope that takes longer than `threshold`,
+/// like `'load_texture' took 230.12ms` with target `profi`.
+///
+/// The warning is emitted when the scope ends, so rare slow calls show up while the program is still running
+/// instead of only in the table at the end.
+/// Nested scopes are checked on their own, a slow child and its parent are both logged.
+///
+/// The default is `None`, which disables it.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   // Any logger, like env_logger, prints the warnings
+///   profi::set_slow_warning(Some(std::time::Duration::from_millis(100)));
+///   {
+///     prof!(load_texture);
+///     std::thread::sleep(std::time::Duration::from_millis(150));
+///   }
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn set_slow_warning(threshold: Option<std::time::Duration>) {
+    #[cfg(feature = "enable")]
+    {
+        let nanos = threshold.map_or(0, |t| t.as_nanos().clamp(1, u64::MAX as u128) as u64);
+        THRESHOLD.store(nanos, std::sync::atomic::Ordering::Relaxed);
+        crate::hooks::set(crate::hooks::SLOW_LOG, nanos != 0);
+    }
+}
+
+/// Warns if the innermost open scope of `measures` is slower than the threshold, closing it at `end`.
+#[cfg(feature = "enable")]
+#[inline(always)]
+pub(crate) fn closed(measures: crate::measure::Measures<'_>, end: crate::clock::Instant) {
+    use crate::measure::MeasureType;
+
+    let threshold = THRESHOLD.load(std::sync::atomic::Ordering::Relaxed);
+    if threshold == 0 {
+        return;
+    }
+    // Skip the scopes that are already closed
+    let mut closed = 0usize;
+    let mut polled = std::time::Duration::ZERO;
+    // The polls that didn't complete their future are not calls
+    let Some(Some((name, start))) = measures.iter().rev().find_map(|m| match &m.ty {
+        MeasureType::End => {
+            closed += 1;
+            None
+        }
+        MeasureType::Start { .. } | MeasureType::Resume { .. } if closed > 0 => {
+            closed -= 1;
+            None
+        }
+        MeasureType::Polled { duration } if closed == 0 => {
+            polled += *duration;
+            None
+        }
+        MeasureType::Start { name } => Some(Some((name.clone(), m.time))),
+        MeasureType::Resume { .. } => Some(None),
+        _ => None,
+    }) else {
+        return;
+    };
+    let duration = end.saturating_duration_since(start) + polled;
+    if duration > std::time::Duration::from_nanos(threshold) {
+        log::warn!(target: "profi", "'{name}' took {duration:.2?}");
+    }
+}
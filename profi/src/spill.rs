@@ -0,0 +1,436 @@
+//! Spilling of the measures of a thread to a temporary file once they take too much memory.
+
+#[cfg(feature = "enable")]
+use crate::{
+    measure::{Measure, MeasureType},
+    names::{Id, Name},
+};
+
+/// Maximum bytes of measures kept in memory by each thread, `0` if unlimited.
+#[cfg(feature = "enable")]
+static LIMIT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// Moves the closed scopes of each thread to a temporary file when its measures take more than `limit` bytes.
+///
+/// Every call is stored until the report is built, so profiling millions of calls can take gigabytes.
+/// With a limit, the closed scopes are written to a file in [`std::env::temp_dir`] and read back when building a report,
+/// so the memory of the profiled application stays bounded while every call is still reported.
+///
+/// The limit is approximate, the names of the scopes are not counted, and only the open scopes are always kept in memory.
+/// If the file can't be written, the measures stay in memory and a warning is printed.
+///
+/// The default is `None`, which keeps everything in memory.
+/// Has no effect with the `bounded-memory` feature, which already folds every call as soon as it ends.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   // 64 KiB per thread
+///   profi::set_memory_limit(Some(64 * 1024));
+///   for _ in 0..100_000 {
+///     prof!(tiny);
+///   }
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(profi::report().scope("tiny").unwrap().calls, 100_000);
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn set_memory_limit(limit: Option<usize>) {
+    #[cfg(feature = "enable")]
+    {
+        LIMIT.store(limit.unwrap_or(0), std::sync::atomic::Ordering::Relaxed);
+        crate::hooks::set(crate::hooks::SPILL, limit.is_some_and(|limit| limit != 0));
+    }
+}
+
+/// Temporary file with the measures spilled by a thread.
+#[cfg(feature = "enable")]
+#[derive(Debug)]
+pub(crate) struct Spill {
+    #[cfg_attr(unix, allow(dead_code))]
+    path: std::path::PathBuf,
+    /// The file and its length
+    file: std::sync::Mutex<(std::fs::File, u64)>,
+    /// Set when writing fails, the measures are kept in memory from then on
+    failed: std::sync::atomic::AtomicBool,
+}
+
+#[cfg(feature = "enable")]
+impl Spill {
+    fn create(thread: usize) -> std::io::Result<Self> {
+        let path =
+            std::env::temp_dir().join(format!("profi-{}-{thread}.spill", std::process::id()));
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        // The open file can still be used, and it's removed even if the process never drops the profiler
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&path);
+        Ok(Self {
+            path,
+            file: std::sync::Mutex::new((file, 0)),
+            failed: Default::default(),
+        })
+    }
+
+    /// Appends `measures` to the file, returns their offset and length in bytes.
+    fn write(&self, measures: &[Measure]) -> std::io::Result<(u64, u64)> {
+        use std::io::{Seek, Write};
+
+        let mut bytes = Vec::new();
+        for m in measures {
+            encode(m, &mut bytes);
+        }
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let (file, len) = &mut *file;
+        let offset = *len;
+        file.seek(std::io::SeekFrom::Start(offset))?;
+        file.write_all(&bytes)?;
+        *len += bytes.len() as u64;
+        Ok((offset, bytes.len() as u64))
+    }
+
+    /// Reads back the measures written at `offset`.
+    fn read(&self, offset: u64, len: u64) -> std::io::Result<Vec<Measure>> {
+        use std::io::{Read, Seek};
+
+        let mut bytes = vec![0; len as usize];
+        {
+            let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+            file.0.seek(std::io::SeekFrom::Start(offset))?;
+            file.0.read_exact(&mut bytes)?;
+        }
+        let mut bytes = &bytes[..];
+        let mut measures = Vec::new();
+        while !bytes.is_empty() {
+            measures.push(decode(&mut bytes)?);
+        }
+        Ok(measures)
+    }
+
+    /// Measures of a `Spilled` measure, empty if they can't be read back.
+    ///
+    /// The spilled measures are balanced, so the rest are still valid without them.
+    pub(crate) fn read_back(&self, offset: u64, len: u64) -> Vec<Measure> {
+        self.read(offset, len).unwrap_or_else(|e| {
+            eprintln!("[profi] Couldn't read back the spilled measures: {e}");
+            Vec::new()
+        })
+    }
+
+    /// Discards all the spilled measures.
+    pub(crate) fn clear(&self) {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = file.0.set_len(0);
+        file.1 = 0;
+    }
+}
+
+#[cfg(all(feature = "enable", not(unix)))]
+impl Drop for Spill {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// If `len` measures take more than the limit, and must be [spilled](spill).
+#[cfg(feature = "enable")]
+#[inline(always)]
+pub(crate) fn exceeded(len: usize) -> bool {
+    let limit = LIMIT.load(std::sync::atomic::Ordering::Relaxed);
+    limit != 0 && len * std::mem::size_of::<Measure>() > limit
+}
+
+/// Replaces each run of closed scopes of `measures` with a `Spilled` measure pointing to the file.
+///
+/// The open scopes split the runs, so every run is balanced and the order of the measures is kept.
+#[cfg(feature = "enable")]
+#[cold]
+pub(crate) fn spill(thread: &crate::measure::SharedMeasures, measures: &mut Vec<Measure>) {
+    use std::sync::atomic::Ordering;
+
+    let spill = thread.spill.get_or_init(|| match Spill::create(thread.id) {
+        Ok(spill) => Some(std::sync::Arc::new(spill)),
+        Err(e) => {
+            eprintln!(
+                "[profi] Couldn't create the spill file, the measures are kept in memory: {e}"
+            );
+            None
+        }
+    });
+    let Some(spill) = spill
+        .as_deref()
+        .filter(|s| !s.failed.load(Ordering::Relaxed))
+    else {
+        return;
+    };
+
+    let mut open = Vec::new();
+    for (i, m) in measures.iter().enumerate() {
+        match m.ty {
+            MeasureType::Start { .. } | MeasureType::Resume { .. } => open.push(i),
+            MeasureType::End => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+    let mut open = open.into_iter().peekable();
+    let mut kept = Vec::with_capacity(4096);
+    let mut run = Vec::new();
+    for (i, m) in std::mem::take(measures).into_iter().enumerate() {
+        let is_open = open.next_if_eq(&i).is_some();
+        if !is_open && !matches!(m.ty, MeasureType::Spilled { .. }) {
+            run.push(m);
+            continue;
+        }
+        flush(spill, &mut run, &mut kept);
+        push(&mut kept, m);
+    }
+    flush(spill, &mut run, &mut kept);
+    *measures = kept;
+}
+
+/// Writes `run` to the file and adds a `Spilled` measure to `kept`, or adds `run` itself if it fails.
+#[cfg(feature = "enable")]
+fn flush(spill: &Spill, run: &mut Vec<Measure>, kept: &mut Vec<Measure>) {
+    let Some(last) = run.last() else {
+        return;
+    };
+    if spill.failed.load(std::sync::atomic::Ordering::Relaxed) {
+        kept.append(run);
+        return;
+    }
+    match spill.write(run) {
+        Ok((offset, len)) => {
+            let spilled = Measure {
+                time: last.time,
+                ty: MeasureType::Spilled { offset, len },
+                #[cfg(feature = "cores")]
+                core: u32::MAX,
+                #[cfg(any(
+                    feature = "context-switches",
+                    feature = "page-faults",
+                    feature = "perf"
+                ))]
+                usage: Default::default(),
+            };
+            run.clear();
+            push(kept, spilled);
+        }
+        Err(e) => {
+            eprintln!(
+                "[profi] Couldn't write the spill file, the measures are kept in memory: {e}"
+            );
+            spill
+                .failed
+                .store(true, std::sync::atomic::Ordering::Relaxed);
+            kept.append(run);
+        }
+    }
+}
+
+/// Pushes `m`, merging it with the last `Spilled` measure if they are contiguous in the file.
+#[cfg(feature = "enable")]
+fn push(kept: &mut Vec<Measure>, m: Measure) {
+    if let (
+        Some(Measure {
+            ty: MeasureType::Spilled { offset, len },
+            time,
+            ..
+        }),
+        MeasureType::Spilled {
+            offset: next,
+            len: next_len,
+        },
+    ) = (kept.last_mut(), &m.ty)
+    {
+        if *offset + *len == *next {
+            *len += next_len;
+            *time = m.time;
+            return;
+        }
+    }
+    kept.push(m)
+}
+
+/// `measures` with the spilled ones read back from `spill`, borrowed if none were spilled.
+#[cfg(feature = "enable")]
+pub(crate) fn expand<'a>(
+    measures: &'a [Measure],
+    spill: Option<&Spill>,
+) -> std::borrow::Cow<'a, [Measure]> {
+    let Some(spill) = spill.filter(|_| {
+        measures
+            .iter()
+            .any(|m| matches!(m.ty, MeasureType::Spilled { .. }))
+    }) else {
+        return std::borrow::Cow::Borrowed(measures);
+    };
+    let mut expanded = Vec::with_capacity(measures.len());
+    for m in measures {
+        match m.ty {
+            MeasureType::Spilled { offset, len } => expanded.extend(spill.read_back(offset, len)),
+            _ => expanded.push(m.clone()),
+        }
+    }
+    std::borrow::Cow::Owned(expanded)
+}
+
+#[cfg(feature = "enable")]
+fn encode(m: &Measure, bytes: &mut Vec<u8>) {
+    fn string(s: &str, bytes: &mut Vec<u8>) {
+        bytes.extend((s.len() as u64).to_le_bytes());
+        bytes.extend(s.as_bytes());
+    }
+
+    let nanos = |d: std::time::Duration| d.as_nanos() as u64;
+    let (tag, value) = match &m.ty {
+        MeasureType::Start { .. } => (0u8, 0),
+        MeasureType::Resume { .. } => (1, 0),
+        MeasureType::End => (2, 0),
+        MeasureType::Count { value, .. } => (3, *value),
+        MeasureType::Slow { call } => (4, nanos(call.duration)),
+        MeasureType::Wait { duration } => (5, nanos(*duration)),
+        MeasureType::Pause { duration } => (6, nanos(*duration)),
+        MeasureType::Coalesced { calls } => (7, *calls as u64),
+        MeasureType::Field { .. } => (8, 0),
+        MeasureType::Parent { path } => (9, path.len() as u64),
+        MeasureType::Polled { duration } => (10, nanos(*duration)),
+        MeasureType::Sampled { every } => (11, *every as u64),
+        MeasureType::Spilled { .. } => {
+            unreachable!("[profi] spilled measures are never spilled again")
+        }
+    };
+    bytes.push(tag);
+    bytes.extend(
+        nanos(
+            m.time
+                .saturating_duration_since(crate::clock::Instant::ZERO),
+        )
+        .to_le_bytes(),
+    );
+    bytes.extend(value.to_le_bytes());
+    match &m.ty {
+        MeasureType::Start { name } | MeasureType::Resume { name } => string(name, bytes),
+        MeasureType::Count { name, .. } => string(name.get(), bytes),
+        MeasureType::Slow { call } => string(&call.context, bytes),
+        MeasureType::Field { field } => {
+            let (key, value) = &**field;
+            string(key, bytes);
+            string(value, bytes);
+        }
+        MeasureType::Parent { path } => path.iter().for_each(|name| string(name, bytes)),
+        _ => {}
+    }
+    #[cfg(feature = "cores")]
+    bytes.extend(m.core.to_le_bytes());
+    #[cfg(any(
+        feature = "context-switches",
+        feature = "page-faults",
+        feature = "perf"
+    ))]
+    for counter in [
+        m.usage.voluntary_switches,
+        m.usage.involuntary_switches,
+        m.usage.minor_faults,
+        m.usage.major_faults,
+        m.usage.cycles,
+        m.usage.instructions,
+        m.usage.cache_misses,
+    ] {
+        bytes.extend(counter.to_le_bytes());
+    }
+}
+
+#[cfg(feature = "enable")]
+fn decode(bytes: &mut &[u8]) -> std::io::Result<Measure> {
+    fn take<'a>(bytes: &mut &'a [u8], n: usize) -> std::io::Result<&'a [u8]> {
+        if bytes.len() < n {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        let (taken, rest) = bytes.split_at(n);
+        *bytes = rest;
+        Ok(taken)
+    }
+    fn u64(bytes: &mut &[u8]) -> std::io::Result<u64> {
+        Ok(u64::from_le_bytes(take(bytes, 8)?.try_into().unwrap()))
+    }
+    fn string(bytes: &mut &[u8]) -> std::io::Result<String> {
+        let len = u64(bytes)? as usize;
+        String::from_utf8(take(bytes, len)?.to_vec())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    let tag = take(bytes, 1)?[0];
+    let time = crate::clock::Instant::ZERO + std::time::Duration::from_nanos(u64(bytes)?);
+    let value = u64(bytes)?;
+    let ty = match tag {
+        0 => MeasureType::Start {
+            name: Name::new(string(bytes)?),
+        },
+        1 => MeasureType::Resume {
+            name: Name::new(string(bytes)?),
+        },
+        2 => MeasureType::End,
+        3 => MeasureType::Count {
+            name: Id::new(string(bytes)?),
+            value,
+        },
+        4 => MeasureType::Slow {
+            call: Box::new(crate::SlowCall {
+                duration: std::time::Duration::from_nanos(value),
+                context: string(bytes)?,
+            }),
+        },
+        5 => MeasureType::Wait {
+            duration: std::time::Duration::from_nanos(value),
+        },
+        6 => MeasureType::Pause {
+            duration: std::time::Duration::from_nanos(value),
+        },
+        7 => MeasureType::Coalesced {
+            calls: value as usize,
+        },
+        8 => MeasureType::Field {
+            field: Box::new((Name::new(string(bytes)?), string(bytes)?.into())),
+        },
+        9 => MeasureType::Parent {
+            path: (0..value)
+                .map(|_| string(bytes).map(Name::new))
+                .collect::<std::io::Result<_>>()?,
+        },
+        10 => MeasureType::Polled {
+            duration: std::time::Duration::from_nanos(value),
+        },
+        11 => MeasureType::Sampled {
+            every: value as usize,
+        },
+        _ => return Err(std::io::ErrorKind::InvalidData.into()),
+    };
+    Ok(Measure {
+        ty,
+        time,
+        #[cfg(feature = "cores")]
+        core: u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()),
+        #[cfg(any(
+            feature = "context-switches",
+            feature = "page-faults",
+            feature = "perf"
+        ))]
+        usage: crate::rusage::Usage {
+            voluntary_switches: u64(bytes)?,
+            involuntary_switches: u64(bytes)?,
+            minor_faults: u64(bytes)?,
+            major_faults: u64(bytes)?,
+            cycles: u64(bytes)?,
+            instructions: u64(bytes)?,
+            cache_misses: u64(bytes)?,
+        },
+    })
+}
@@ -0,0 +1,245 @@
+//! Breakdown of the startup and shutdown time of an application.
+
+/// Builder for the startup profiling preset, see [`startup_mode`].
+///
+/// # Example
+/// ```
+/// use profi::{milestone, prof, StartupMode};
+///
+/// fn main() {
+///   let baseline = std::env::temp_dir().join("profi-startup.baseline");
+///   StartupMode::new().baseline(baseline).start();
+///   {
+///     prof!(load_config);
+///   }
+///   milestone!("config loaded");
+///   // Prints the breakdown of everything until now
+///   profi::ready();
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+#[allow(dead_code)]
+pub struct StartupMode {
+    baseline: Option<std::path::PathBuf>,
+    options: crate::PrintOptions,
+}
+
+#[cfg(feature = "enable")]
+static STARTUP: std::sync::Mutex<Option<StartupMode>> = std::sync::Mutex::new(None);
+
+impl StartupMode {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// File with the results of a previous startup to compare against.
+    ///
+    /// If it doesn't exist it's created with the results of this startup, delete it to record a new baseline.
+    pub fn baseline(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.baseline = Some(path.into());
+        self
+    }
+
+    /// Options used for printing the breakdown.
+    pub fn options(mut self, options: crate::PrintOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Starts profiling the startup, the breakdown is printed to stderr when [`ready`] is called.
+    pub fn start(self) {
+        #[cfg(feature = "enable")]
+        {
+            crate::measure::GLOBAL_PROFILER.start();
+            *STARTUP.lock().unwrap_or_else(|e| e.into_inner()) = Some(self);
+        }
+    }
+}
+
+/// Profiles the startup of the application until [`ready`] is called, targeting the cold start of CLIs and services.
+///
+/// When ready, a breakdown is printed to stderr with the time until ready, the time until the first [`milestone!`](crate::milestone),
+/// and how much each scope contributed to the startup.
+///
+/// Shorthand for `StartupMode::new().start()`, use [`StartupMode`] to compare against a baseline.
+pub fn startup_mode() {
+    StartupMode::new().start()
+}
+
+/// Marks the end of the startup started with [`startup_mode`], and prints its breakdown.
+///
+/// Also records a `ready` milestone. Does nothing if startup mode is not active or `ready` was already called.
+pub fn ready() {
+    #[cfg(feature = "enable")]
+    {
+        let time = crate::clock::Instant::now();
+        let Some(startup) = STARTUP.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return;
+        };
+        crate::measure::GLOBAL_PROFILER.milestone("ready".into(), time);
+        let report = crate::measure::GLOBAL_PROFILER.report();
+        if let Err(e) = startup.print(&report, std::io::stderr()) {
+            eprintln!("[profi] Could not print the startup breakdown: {e}");
+        }
+    }
+}
+
+/// Durations of a startup, keyed by scope name plus `@ready` and `@first_milestone`.
+#[cfg(feature = "enable")]
+type Baseline = std::collections::HashMap<String, std::time::Duration>;
+
+#[cfg(feature = "enable")]
+impl StartupMode {
+    fn print(&self, report: &crate::Report, mut to: impl std::io::Write) -> std::io::Result<()> {
+        let options = &self.options;
+        let ready = report
+            .milestones
+            .last()
+            .map(|m| m.elapsed)
+            .unwrap_or_default();
+        let mut current = Baseline::new();
+        current.insert("@ready".into(), ready);
+        if let Some(first) = report.milestones.first().filter(|m| m.name != "ready") {
+            current.insert("@first_milestone".into(), first.elapsed);
+        }
+        for scope in &report.scopes {
+            current.insert(scope.name.clone(), scope.total_real);
+        }
+
+        let baseline = match &self.baseline {
+            Some(path) if path.exists() => Some(read_baseline(path)?),
+            Some(path) => {
+                write_baseline(path, &current)?;
+                None
+            }
+            None => None,
+        };
+        let compared =
+            |key: &str, time: std::time::Duration| match baseline.as_ref().and_then(|b| b.get(key))
+            {
+                Some(&before) => format!(
+                    "{} (baseline {}, {})",
+                    options.fmt_duration(time),
+                    options.fmt_duration(before),
+                    change(before, time)
+                ),
+                None => options.fmt_duration(time),
+            };
+
+        writeln!(to, "Startup")?;
+        writeln!(to, "Ready after {}", compared("@ready", ready))?;
+        if let Some(first) = report.milestones.first().filter(|m| m.name != "ready") {
+            writeln!(
+                to,
+                "First milestone {:?} after {}",
+                first.name,
+                compared("@first_milestone", first.elapsed)
+            )?;
+        }
+
+        let mut table = comfy_table::Table::new();
+        table.load_preset(comfy_table::presets::UTF8_FULL);
+        let mut header = vec!["Name", "Time", "% Startup"];
+        if baseline.is_some() {
+            header.extend(["Baseline", "Change"]);
+        }
+        table.set_header(header);
+        for scope in &report.scopes {
+            let percent = if ready.is_zero() {
+                0.0
+            } else {
+                scope.total_real.as_secs_f64() / ready.as_secs_f64() * 100.0
+            };
+            let mut row = vec![
+                scope.name.clone(),
+                options.fmt_duration(scope.total_real),
+                options.fmt_percent(percent),
+            ];
+            if let Some(baseline) = &baseline {
+                match baseline.get(&scope.name) {
+                    Some(&before) => row.extend([
+                        options.fmt_duration(before),
+                        change(before, scope.total_real),
+                    ]),
+                    None => row.extend(["-".into(), "new".into()]),
+                }
+            }
+            table.add_row(row);
+        }
+        writeln!(to, "{table}")
+    }
+}
+
+/// Relative change from `before` to `after`, like `+5.00%`.
+#[cfg(feature = "enable")]
+fn change(before: std::time::Duration, after: std::time::Duration) -> String {
+    if before.is_zero() {
+        return "-".into();
+    }
+    let change = (after.as_secs_f64() / before.as_secs_f64() - 1.0) * 100.0;
+    format!("{change:+.2}%")
+}
+
+/// Each line is the key and the duration in nanoseconds, separated by the last tab.
+#[cfg(feature = "enable")]
+fn read_baseline(path: &std::path::Path) -> std::io::Result<Baseline> {
+    let baseline = std::fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| {
+            let (key, nanos) = line.rsplit_once('\t')?;
+            let nanos = nanos.trim().parse().ok()?;
+            Some((key.to_string(), std::time::Duration::from_nanos(nanos)))
+        })
+        .collect();
+    Ok(baseline)
+}
+
+#[cfg(feature = "enable")]
+fn write_baseline(path: &std::path::Path, baseline: &Baseline) -> std::io::Result<()> {
+    let mut lines = baseline
+        .iter()
+        .map(|(key, time)| format!("{}\t{}\n", key.replace(['\t', '\n'], " "), time.as_nanos()))
+        .collect::<Vec<_>>();
+    lines.sort();
+    std::fs::write(path, lines.concat())
+}
+
+/// Marks the beginning of the shutdown of the application, like flushing buffers or joining threads.
+///
+/// From then on the report splits the real time of each scope into "Steady state" and "Shutdown" columns,
+/// so a long teardown doesn't hide inside the totals. Calls that were running at that point are split between both.
+///
+/// Also records a `shutdown` milestone. Only the first call has effect.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn flush() {
+///   prof!(flush);
+///   // ...
+/// }
+///
+/// fn main() {
+///   for _ in 0..10 {
+///     flush();
+///   }
+///   profi::begin_shutdown();
+///   flush();
+///
+///   let report = profi::report();
+///   # #[cfg(feature = "enable")]
+///   # {
+///   let flush = report.scope("flush").unwrap();
+///   assert!(report.shutdown.is_some());
+///   assert!(flush.shutdown <= flush.total_real);
+///   # }
+/// }
+/// ```
+pub fn begin_shutdown() {
+    #[cfg(feature = "enable")]
+    {
+        let time = crate::clock::Instant::now();
+        crate::measure::GLOBAL_PROFILER.begin_shutdown(time);
+    }
+}
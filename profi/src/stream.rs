@@ -0,0 +1,336 @@
+//! Streaming of completed scopes while the program runs.
+
+/// Version of the lines written by [`stream_jsonl`], starting every stream.
+///
+/// Captures without it were written with version 1, which had the same scope lines as version 2.
+/// Before version 3 there was no `polled_ns`, and the later polls of a future continued its last call.
+pub const STREAM_FORMAT_VERSION: u8 = 3;
+
+/// Completed scope sent to the writer thread.
+#[cfg(feature = "enable")]
+pub(crate) struct Record {
+    thread: std::sync::Arc<crate::measure::SharedMeasures>,
+    name: crate::names::Name,
+    depth: usize,
+    resumed: bool,
+    start: crate::clock::Instant,
+    end: crate::clock::Instant,
+    /// Time of the earlier polls of its future.
+    polled: std::time::Duration,
+}
+
+#[cfg(feature = "enable")]
+struct Stream {
+    send: std::sync::mpsc::Sender<Record>,
+    started: crate::clock::Instant,
+    /// Identifies the stream of each [`StreamHandle`].
+    id: u64,
+}
+
+#[cfg(feature = "enable")]
+static ACTIVE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+#[cfg(feature = "enable")]
+static STREAM: std::sync::RwLock<Option<Stream>> = std::sync::RwLock::new(None);
+#[cfg(feature = "enable")]
+static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// Appends one JSON object per completed scope to the file at `path` while the program runs.
+///
+/// Lines are buffered and flushed from a background thread every `flush_every`, so at most that much data is lost if the program crashes,
+/// and the file can be tailed by external tools.
+///
+/// Each line looks like:
+/// ```json
+/// {"thread":0,"thread_name":"main","name":"work","depth":1,"resumed":false,"start_ns":1200,"duration_ns":53000,"polled_ns":0}
+/// ```
+/// - `thread` numbers the threads in the order they started profiling.
+/// - `start_ns` is relative to when the stream started.
+/// - `resumed` is `true` for the polls of a [`prof_future!`](crate::prof_future) that didn't complete it,
+///   their time is added to the one that does as `polled_ns`, on any thread.
+///
+/// Every time a stream starts it first writes a line with the [`STREAM_FORMAT_VERSION`] and the [`VERSION`](crate::VERSION) of `profi`:
+/// ```json
+/// {"format":3,"profi":"0.1.2"}
+/// ```
+/// Readers skip it, and fail on captures written with a newer format.
+///
+/// Only one stream can be active, starting a new one stops the previous.
+/// Streaming stops and the remaining lines are flushed when the returned handle is dropped.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   let path = std::env::temp_dir().join("profi-stream.jsonl");
+///   let stream = profi::export::stream_jsonl(&path, std::time::Duration::from_secs(1)).unwrap();
+///   {
+///     prof!(work);
+///   }
+///   stream.stop();
+///   # #[cfg(feature = "enable")]
+///   assert!(std::fs::read_to_string(&path).unwrap().contains(r#""name":"work""#));
+/// }
+/// ```
+#[must_use = "streaming stops when the handle is dropped"]
+#[allow(unused_variables)]
+pub fn stream_jsonl(
+    path: impl AsRef<std::path::Path>,
+    flush_every: std::time::Duration,
+) -> std::io::Result<StreamHandle> {
+    #[cfg(feature = "enable")]
+    {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(start(std::io::BufWriter::new(file), flush_every))
+    }
+    #[cfg(not(feature = "enable"))]
+    Ok(StreamHandle(None))
+}
+
+/// Same as [`stream_jsonl`], but writes to `profi-<pid>.jsonl` inside `dir`, replacing it if it exists.
+///
+/// Meant for programs that spawn worker processes: each process calls it at its start,
+/// and the coordinator combines all the files with [`merge_files`](crate::merge_files) when they're done.
+#[must_use = "streaming stops when the handle is dropped"]
+#[allow(unused_variables)]
+pub fn stream_process(
+    dir: impl AsRef<std::path::Path>,
+    flush_every: std::time::Duration,
+) -> std::io::Result<StreamHandle> {
+    #[cfg(feature = "enable")]
+    {
+        let path = dir
+            .as_ref()
+            .join(format!("profi-{}.jsonl", std::process::id()));
+        let file = std::fs::File::create(path)?;
+        Ok(start(std::io::BufWriter::new(file), flush_every))
+    }
+    #[cfg(not(feature = "enable"))]
+    Ok(StreamHandle(None))
+}
+
+/// Same as [`stream_jsonl`], but compresses the lines with [zstd](https://facebook.github.io/zstd/), at the given compression `level` (`1` to `22`, `3` is a good default).
+///
+/// Each flush ends a zstd frame, so if the program crashes all the frames before the last flush can still be decompressed,
+/// and captures of several gigabytes stay manageable.
+/// The file can be read with `zstd -dc` or any zstd decoder that supports concatenated frames.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   let path = std::env::temp_dir().join("profi-stream.jsonl.zst");
+///   let stream = profi::export::stream_jsonl_zstd(&path, std::time::Duration::from_secs(1), 3).unwrap();
+///   {
+///     prof!(work);
+///   }
+///   stream.stop();
+/// }
+/// ```
+#[cfg(feature = "zstd")]
+#[must_use = "streaming stops when the handle is dropped"]
+#[allow(unused_variables)]
+pub fn stream_jsonl_zstd(
+    path: impl AsRef<std::path::Path>,
+    flush_every: std::time::Duration,
+    level: i32,
+) -> std::io::Result<StreamHandle> {
+    #[cfg(feature = "enable")]
+    {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        let to = ZstdFrames {
+            file: Some(std::io::BufWriter::new(file)),
+            frame: None,
+            level,
+        };
+        Ok(start(to, flush_every))
+    }
+    #[cfg(not(feature = "enable"))]
+    Ok(StreamHandle(None))
+}
+
+/// Starts the writer thread, which writes the records to `to` and flushes it every `flush_every`.
+#[cfg(feature = "enable")]
+fn start(
+    mut to: impl std::io::Write + Send + 'static,
+    flush_every: std::time::Duration,
+) -> StreamHandle {
+    let (send, receive) = std::sync::mpsc::channel::<Record>();
+    let started = crate::clock::Instant::now();
+    let thread = std::thread::spawn(move || {
+        let mut line = format!(
+            "{{\"format\":{STREAM_FORMAT_VERSION},\"profi\":{}}}\n",
+            crate::export::json_string(crate::VERSION)
+        );
+        if let Err(e) = to.write_all(line.as_bytes()) {
+            eprintln!("[profi] Streaming to JSON Lines failed: {e}");
+            return;
+        }
+        loop {
+            let result = match receive.recv_timeout(flush_every) {
+                Ok(record) => {
+                    line.clear();
+                    write_record(&mut line, &record, started);
+                    to.write_all(line.as_bytes())
+                }
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => to.flush(),
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+            if let Err(e) = result {
+                eprintln!("[profi] Streaming to JSON Lines failed: {e}");
+                return;
+            }
+        }
+        if let Err(e) = to.flush() {
+            eprintln!("[profi] Streaming to JSON Lines failed: {e}");
+        }
+    });
+    let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    *STREAM.write().unwrap_or_else(|e| e.into_inner()) = Some(Stream { send, started, id });
+    ACTIVE.store(true, std::sync::atomic::Ordering::Release);
+    crate::hooks::set(crate::hooks::STREAM, true);
+    StreamHandle(Some((id, thread)))
+}
+
+/// Compresses the written data into a new zstd frame after each flush.
+#[cfg(all(feature = "enable", feature = "zstd"))]
+struct ZstdFrames {
+    /// `None` while a frame is open.
+    file: Option<std::io::BufWriter<std::fs::File>>,
+    frame: Option<zstd::stream::write::Encoder<'static, std::io::BufWriter<std::fs::File>>>,
+    level: i32,
+}
+
+#[cfg(all(feature = "enable", feature = "zstd"))]
+impl std::io::Write for ZstdFrames {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(file) = self.file.take() {
+            self.frame = Some(zstd::stream::write::Encoder::new(file, self.level)?);
+        }
+        match &mut self.frame {
+            Some(frame) => frame.write(buf),
+            None => Err(std::io::Error::other("zstd encoder failed")),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if let Some(frame) = self.frame.take() {
+            let mut file = frame.finish()?;
+            file.flush()?;
+            self.file = Some(file);
+        }
+        Ok(())
+    }
+}
+
+/// Handle of a stream started with [`stream_jsonl`], streaming stops when it's dropped.
+#[derive(Debug)]
+pub struct StreamHandle(Option<(u64, std::thread::JoinHandle<()>)>);
+
+impl StreamHandle {
+    /// Stops streaming and waits until all the lines are written.
+    pub fn stop(self) {}
+}
+
+impl Drop for StreamHandle {
+    fn drop(&mut self) {
+        #[allow(unused_variables)]
+        if let Some((id, thread)) = self.0.take() {
+            #[cfg(feature = "enable")]
+            {
+                // Only stop the stream if it hasn't been replaced by a newer one
+                let mut stream = STREAM.write().unwrap_or_else(|e| e.into_inner());
+                if stream.as_ref().is_some_and(|s| s.id == id) {
+                    ACTIVE.store(false, std::sync::atomic::Ordering::Release);
+                    crate::hooks::set(crate::hooks::STREAM, false);
+                    *stream = None;
+                }
+            }
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Sends the innermost open scope of `measures` to the stream, if any, closing it at `end`.
+#[cfg(feature = "enable")]
+#[inline(always)]
+pub(crate) fn closed(
+    thread: &std::sync::Arc<crate::measure::SharedMeasures>,
+    measures: crate::measure::Measures<'_>,
+    depth: usize,
+    end: crate::clock::Instant,
+) {
+    use crate::measure::MeasureType;
+
+    if !ACTIVE.load(std::sync::atomic::Ordering::Acquire) {
+        return;
+    }
+    let stream = STREAM.read().unwrap_or_else(|e| e.into_inner());
+    let Some(stream) = stream.as_ref() else {
+        return;
+    };
+    // Skip the scopes that are already closed
+    let mut closed = 0usize;
+    let mut polled = std::time::Duration::ZERO;
+    for m in measures.iter().rev() {
+        match &m.ty {
+            MeasureType::End => closed += 1,
+            MeasureType::Polled { duration } if closed == 0 => polled += *duration,
+            MeasureType::Start { .. } | MeasureType::Resume { .. } if closed > 0 => closed -= 1,
+            MeasureType::Start { name } | MeasureType::Resume { name } => {
+                // Scopes opened before the stream started are not sent
+                if m.time >= stream.started {
+                    let _ = stream.send.send(Record {
+                        thread: thread.clone(),
+                        name: name.clone(),
+                        depth,
+                        resumed: matches!(m.ty, MeasureType::Resume { .. }),
+                        start: m.time,
+                        end,
+                        polled,
+                    });
+                }
+                return;
+            }
+            MeasureType::Count { .. }
+            | MeasureType::Field { .. }
+            | MeasureType::Parent { .. }
+            | MeasureType::Slow { .. }
+            | MeasureType::Wait { .. }
+            | MeasureType::Pause { .. }
+            | MeasureType::Coalesced { .. }
+            | MeasureType::Polled { .. }
+            | MeasureType::Sampled { .. }
+            | MeasureType::Spilled { .. } => {}
+        }
+    }
+}
+
+#[cfg(feature = "enable")]
+fn write_record(line: &mut String, record: &Record, started: crate::clock::Instant) {
+    use std::fmt::Write;
+
+    let _ = write!(line, "{{\"thread\":{},\"thread_name\":", record.thread.id);
+    match &record.thread.name {
+        Some(name) => line.push_str(&crate::export::json_string(name)),
+        None => line.push_str("null"),
+    }
+    line.push_str(",\"name\":");
+    line.push_str(&crate::export::json_string(record.name.get()));
+    let _ = writeln!(
+        line,
+        ",\"depth\":{},\"resumed\":{},\"start_ns\":{},\"duration_ns\":{},\"polled_ns\":{}}}",
+        record.depth,
+        record.resumed,
+        record.start.duration_since(started).as_nanos(),
+        record.end.duration_since(record.start).as_nanos(),
+        record.polled.as_nanos(),
+    );
+}
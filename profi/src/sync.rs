@@ -0,0 +1,344 @@
+//! Locks that measure the time spent waiting for them and holding them.
+
+use crate::zz_private::ScopeGuard;
+
+/// A [`std::sync::Mutex`] that measures how long it's waited for and held.
+///
+/// Each [`lock`](Self::lock) is measured as a scope named after the mutex, with two children:
+/// - `name::wait`: time blocked until the lock was acquired, with a `contended` counter of the times it was already locked.
+/// - `name::held`: time from acquiring the lock until the guard is dropped.
+///
+/// Like other scopes, the guards should be dropped in the reverse order they were created.
+///
+/// # Example
+/// ```
+/// use profi::{print_on_exit, Mutex};
+///
+/// static QUEUE: Mutex<Vec<u32>> = Mutex::new("queue", Vec::new());
+///
+/// fn main() {
+///   print_on_exit!();
+///   std::thread::scope(|s| {
+///     for i in 0..4 {
+///       s.spawn(move || QUEUE.lock().unwrap().push(i));
+///     }
+///   });
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(profi::report().scope("queue::held").unwrap().calls, 4);
+/// }
+/// ```
+pub struct Mutex<T: ?Sized> {
+    name: &'static str,
+    /// `wait` and `held` scope names
+    names: std::sync::OnceLock<[&'static str; 2]>,
+    inner: std::sync::Mutex<T>,
+}
+
+/// Guard of a [`Mutex`], its scopes are closed when it's dropped.
+pub struct MutexGuard<'a, T: ?Sized> {
+    /// Declared first so the lock is released before closing the scopes
+    inner: std::sync::MutexGuard<'a, T>,
+    _held: ScopeGuard,
+    _scope: ScopeGuard,
+}
+
+impl<T> Mutex<T> {
+    /// Creates a mutex named `name` holding `value`.
+    pub const fn new(name: &'static str, value: T) -> Self {
+        Self {
+            name,
+            names: std::sync::OnceLock::new(),
+            inner: std::sync::Mutex::new(value),
+        }
+    }
+
+    /// Consumes the mutex, returning the value.
+    pub fn into_inner(self) -> std::sync::LockResult<T> {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Acquires the mutex, blocking the thread until it's available, like [`std::sync::Mutex::lock`].
+    pub fn lock(&self) -> std::sync::LockResult<MutexGuard<'_, T>> {
+        let [wait, held] = self.names();
+        let scope = ScopeGuard::new(self.name);
+        let waiting = ScopeGuard::new(wait);
+        let locked = match self.inner.try_lock() {
+            Ok(guard) => Ok(guard),
+            Err(std::sync::TryLockError::Poisoned(e)) => Err(e),
+            Err(std::sync::TryLockError::WouldBlock) => {
+                crate::zz_private::count("contended", 1);
+                self.inner.lock()
+            }
+        };
+        drop(waiting);
+        let guard = |inner| MutexGuard {
+            inner,
+            _held: ScopeGuard::new(held),
+            _scope: scope,
+        };
+        map_lock(locked, guard)
+    }
+
+    /// Acquires the mutex if it's available, like [`std::sync::Mutex::try_lock`].
+    ///
+    /// Only the time it's held is measured, and attempts that fail are not counted as calls.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::Mutex;
+    ///
+    /// let counter = Mutex::new("counter", 0);
+    /// let guard = counter.lock().unwrap();
+    /// assert!(counter.try_lock().is_err());
+    /// drop(guard);
+    /// # #[cfg(feature = "enable")]
+    /// assert_eq!(profi::report().scope("counter").unwrap().calls, 1);
+    /// ```
+    pub fn try_lock(&self) -> std::sync::TryLockResult<MutexGuard<'_, T>> {
+        let [_, held] = self.names();
+        // Opened once it's locked, so a failed attempt is not a call
+        let guard = |inner| MutexGuard {
+            inner,
+            _scope: ScopeGuard::new(self.name),
+            _held: ScopeGuard::new(held),
+        };
+        match self.inner.try_lock() {
+            Ok(inner) => Ok(guard(inner)),
+            Err(std::sync::TryLockError::Poisoned(e)) => Err(std::sync::TryLockError::Poisoned(
+                std::sync::PoisonError::new(guard(e.into_inner())),
+            )),
+            Err(std::sync::TryLockError::WouldBlock) => Err(std::sync::TryLockError::WouldBlock),
+        }
+    }
+
+    /// Mutable access to the value without locking, since the mutex is borrowed mutably.
+    pub fn get_mut(&mut self) -> std::sync::LockResult<&mut T> {
+        self.inner.get_mut()
+    }
+
+    fn names(&self) -> [&'static str; 2] {
+        *self
+            .names
+            .get_or_init(|| [child(self.name, "wait"), child(self.name, "held")])
+    }
+}
+
+impl<T: ?Sized> std::ops::Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: ?Sized> std::ops::DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mutex")
+            .field("name", &self.name)
+            .field("inner", &&self.inner)
+            .finish()
+    }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&*self.inner, f)
+    }
+}
+
+/// A [`std::sync::RwLock`] that measures how long it's waited for and held.
+///
+/// Like [`Mutex`], each [`read`](Self::read) and [`write`](Self::write) is measured as a scope named after the lock,
+/// with a `name::wait` child for the time blocked, and a `name::read` or `name::write` child for the time it's held.
+///
+/// # Example
+/// ```
+/// use profi::{print_on_exit, RwLock};
+///
+/// fn main() {
+///   print_on_exit!();
+///   let config = RwLock::new("config", String::from("v1"));
+///   assert_eq!(*config.read().unwrap(), "v1");
+///   *config.write().unwrap() = String::from("v2");
+/// }
+/// ```
+pub struct RwLock<T: ?Sized> {
+    name: &'static str,
+    /// `wait`, `read` and `write` scope names
+    names: std::sync::OnceLock<[&'static str; 3]>,
+    inner: std::sync::RwLock<T>,
+}
+
+/// Shared guard of a [`RwLock`], its scopes are closed when it's dropped.
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    /// Declared first so the lock is released before closing the scopes
+    inner: std::sync::RwLockReadGuard<'a, T>,
+    _held: ScopeGuard,
+    _scope: ScopeGuard,
+}
+
+/// Exclusive guard of a [`RwLock`], its scopes are closed when it's dropped.
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    /// Declared first so the lock is released before closing the scopes
+    inner: std::sync::RwLockWriteGuard<'a, T>,
+    _held: ScopeGuard,
+    _scope: ScopeGuard,
+}
+
+impl<T> RwLock<T> {
+    /// Creates a lock named `name` holding `value`.
+    pub const fn new(name: &'static str, value: T) -> Self {
+        Self {
+            name,
+            names: std::sync::OnceLock::new(),
+            inner: std::sync::RwLock::new(value),
+        }
+    }
+
+    /// Consumes the lock, returning the value.
+    pub fn into_inner(self) -> std::sync::LockResult<T> {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Acquires shared access, blocking the thread while it's locked for writing, like [`std::sync::RwLock::read`].
+    pub fn read(&self) -> std::sync::LockResult<RwLockReadGuard<'_, T>> {
+        let [wait, read, _] = self.names();
+        let scope = ScopeGuard::new(self.name);
+        let waiting = ScopeGuard::new(wait);
+        let locked = match self.inner.try_read() {
+            Ok(guard) => Ok(guard),
+            Err(std::sync::TryLockError::Poisoned(e)) => Err(e),
+            Err(std::sync::TryLockError::WouldBlock) => {
+                crate::zz_private::count("contended", 1);
+                self.inner.read()
+            }
+        };
+        drop(waiting);
+        let guard = |inner| RwLockReadGuard {
+            inner,
+            _held: ScopeGuard::new(read),
+            _scope: scope,
+        };
+        map_lock(locked, guard)
+    }
+
+    /// Acquires exclusive access, blocking the thread while it's locked, like [`std::sync::RwLock::write`].
+    pub fn write(&self) -> std::sync::LockResult<RwLockWriteGuard<'_, T>> {
+        let [wait, _, write] = self.names();
+        let scope = ScopeGuard::new(self.name);
+        let waiting = ScopeGuard::new(wait);
+        let locked = match self.inner.try_write() {
+            Ok(guard) => Ok(guard),
+            Err(std::sync::TryLockError::Poisoned(e)) => Err(e),
+            Err(std::sync::TryLockError::WouldBlock) => {
+                crate::zz_private::count("contended", 1);
+                self.inner.write()
+            }
+        };
+        drop(waiting);
+        let guard = |inner| RwLockWriteGuard {
+            inner,
+            _held: ScopeGuard::new(write),
+            _scope: scope,
+        };
+        map_lock(locked, guard)
+    }
+
+    /// Mutable access to the value without locking, since the lock is borrowed mutably.
+    pub fn get_mut(&mut self) -> std::sync::LockResult<&mut T> {
+        self.inner.get_mut()
+    }
+
+    fn names(&self) -> [&'static str; 3] {
+        *self.names.get_or_init(|| {
+            [
+                child(self.name, "wait"),
+                child(self.name, "read"),
+                child(self.name, "write"),
+            ]
+        })
+    }
+}
+
+impl<T: ?Sized> std::ops::Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: ?Sized> std::ops::Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: ?Sized> std::ops::DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RwLock")
+            .field("name", &self.name)
+            .field("inner", &&self.inner)
+            .finish()
+    }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&*self.inner, f)
+    }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&*self.inner, f)
+    }
+}
+
+/// Wraps the guard of a lock result, keeping it poisoned.
+fn map_lock<G, W>(
+    locked: std::sync::LockResult<G>,
+    wrap: impl FnOnce(G) -> W,
+) -> std::sync::LockResult<W> {
+    match locked {
+        Ok(guard) => Ok(wrap(guard)),
+        Err(e) => Err(std::sync::PoisonError::new(wrap(e.into_inner()))),
+    }
+}
+
+/// `name::suffix`, leaked once for each distinct name so locking doesn't allocate.
+fn child(name: &str, suffix: &str) -> &'static str {
+    static NAMES: std::sync::Mutex<std::collections::BTreeSet<&'static str>> =
+        std::sync::Mutex::new(std::collections::BTreeSet::new());
+
+    let child = format!("{name}::{suffix}");
+    let mut names = NAMES
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    match names.get(child.as_str()) {
+        Some(name) => name,
+        None => {
+            let name = Box::leak(child.into_boxed_str());
+            names.insert(name);
+            name
+        }
+    }
+}
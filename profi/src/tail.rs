@@ -0,0 +1,221 @@
+//! Logging of the calls of watched scopes that are slower than their p99 so far.
+
+#[cfg(feature = "enable")]
+type Metadata = dyn Fn() -> String + Send + Sync;
+
+/// `true` if any scope is watched, avoids locking `WATCHED` on every call.
+#[cfg(feature = "enable")]
+static ACTIVE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+#[cfg(feature = "enable")]
+static WATCHED: std::sync::RwLock<Vec<Watched>> = std::sync::RwLock::new(Vec::new());
+#[cfg(feature = "enable")]
+static EVENTS: std::sync::Mutex<std::collections::VecDeque<crate::TailEvent>> =
+    std::sync::Mutex::new(std::collections::VecDeque::new());
+
+/// Calls of a scope seen before its p99 is considered representative.
+#[cfg(feature = "enable")]
+const WARMUP: u64 = 100;
+/// Events kept, the oldest ones are discarded first.
+#[cfg(feature = "enable")]
+const MAX_EVENTS: usize = 1024;
+
+/// Adds the scope `name` to the watch list, logging every call slower than the 99th percentile of its previous calls.
+///
+/// Each logged call is a [`TailEvent`](crate::TailEvent) with the time it ended, and is printed in a "Tail events" section after the table.
+/// The percentile is tracked from the first call, but nothing is logged during the first 100 calls, while it's still unreliable.
+/// Only the last 1024 events are kept.
+///
+/// The cost of a watched scope is a lock and a histogram update per call, so it's meant for a few latency-sensitive scopes.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   profi::watch_tail("request");
+///   for i in 0..1000 {
+///     prof!(request);
+///     if i == 500 {
+///       std::thread::sleep(std::time::Duration::from_millis(2));
+///     }
+///   }
+///   let report = profi::report();
+///   # #[cfg(feature = "enable")]
+///   assert!(report.tail_events.iter().any(|e| e.duration >= std::time::Duration::from_millis(2)));
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn watch_tail(name: impl Into<String>) {
+    #[cfg(feature = "enable")]
+    watch(name.into(), None);
+}
+
+/// Same as [`watch_tail`], but attaches the string returned by `metadata` to each event.
+///
+/// `metadata` is called in the thread that closed the scope and only for logged calls, so it can read thread-local state like the id of the current request.
+/// It must not open scopes itself.
+///
+/// # Example
+/// ```
+/// thread_local! {
+///   static REQUEST: std::cell::Cell<u32> = const { std::cell::Cell::new(0) };
+/// }
+///
+/// fn main() {
+///   profi::watch_tail_with("request", || format!("id = {}", REQUEST.get()));
+///   for id in 0..200 {
+///     REQUEST.set(id);
+///     profi::prof!(request);
+///   }
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn watch_tail_with(
+    name: impl Into<String>,
+    metadata: impl Fn() -> String + Send + Sync + 'static,
+) {
+    #[cfg(feature = "enable")]
+    watch(name.into(), Some(std::sync::Arc::new(metadata)));
+}
+
+#[cfg(feature = "enable")]
+fn watch(name: String, metadata: Option<std::sync::Arc<Metadata>>) {
+    let mut watched = WATCHED.write().unwrap_or_else(|e| e.into_inner());
+    match watched.iter_mut().find(|w| w.name == name) {
+        Some(w) => w.metadata = metadata,
+        None => watched.push(Watched {
+            name,
+            metadata,
+            histogram: Default::default(),
+        }),
+    }
+    ACTIVE.store(true, std::sync::atomic::Ordering::Release);
+    crate::hooks::set(crate::hooks::TAIL, true);
+}
+
+#[cfg(feature = "enable")]
+struct Watched {
+    name: String,
+    metadata: Option<std::sync::Arc<Metadata>>,
+    histogram: std::sync::Mutex<Histogram>,
+}
+
+/// Durations grouped in buckets that grow by ~3%, so the percentile is approximate.
+#[cfg(feature = "enable")]
+#[derive(Default)]
+struct Histogram {
+    buckets: std::collections::BTreeMap<u32, u64>,
+    calls: u64,
+}
+
+#[cfg(feature = "enable")]
+impl Histogram {
+    /// Each power of two is split in 32 buckets.
+    const STEPS: f64 = 32.0;
+
+    fn bucket(duration: std::time::Duration) -> u32 {
+        ((duration.as_nanos() as f64 + 1.0).log2() * Self::STEPS) as u32
+    }
+
+    /// Upper bound of the bucket with the 99th percentile, `None` during the warmup.
+    fn p99(&self) -> Option<std::time::Duration> {
+        if self.calls < WARMUP {
+            return None;
+        }
+        let above = self.calls / 100;
+        let mut seen = 0;
+        let (&bucket, _) = self.buckets.iter().rev().find(|(_, &calls)| {
+            seen += calls;
+            seen > above
+        })?;
+        let nanos = (f64::from(bucket + 1) / Self::STEPS).exp2() - 1.0;
+        Some(std::time::Duration::from_nanos(nanos as u64))
+    }
+
+    fn add(&mut self, duration: std::time::Duration) {
+        *self.buckets.entry(Self::bucket(duration)).or_default() += 1;
+        self.calls += 1;
+    }
+}
+
+/// Logs the innermost open scope of `measures` if it's watched and slower than its p99, closing it at `end`.
+#[cfg(feature = "enable")]
+#[inline(always)]
+pub(crate) fn closed(
+    thread: &crate::measure::SharedMeasures,
+    measures: crate::measure::Measures<'_>,
+    end: crate::clock::Instant,
+) {
+    use crate::measure::MeasureType;
+
+    if !ACTIVE.load(std::sync::atomic::Ordering::Acquire) {
+        return;
+    }
+    // Skip the scopes that are already closed
+    let mut closed = 0usize;
+    let mut polled = std::time::Duration::ZERO;
+    // The polls that didn't complete their future are not calls
+    let Some(Some((name, start))) = measures.iter().rev().find_map(|m| match &m.ty {
+        MeasureType::End => {
+            closed += 1;
+            None
+        }
+        MeasureType::Start { .. } | MeasureType::Resume { .. } if closed > 0 => {
+            closed -= 1;
+            None
+        }
+        MeasureType::Polled { duration } if closed == 0 => {
+            polled += *duration;
+            None
+        }
+        MeasureType::Start { name } => Some(Some((name, m.time))),
+        MeasureType::Resume { .. } => Some(None),
+        _ => None,
+    }) else {
+        return;
+    };
+    let watched = WATCHED.read().unwrap_or_else(|e| e.into_inner());
+    let Some(watched) = watched.iter().find(|w| w.name == **name) else {
+        return;
+    };
+    let duration = end.duration_since(start) + polled;
+    let p99 = {
+        let mut histogram = watched.histogram.lock().unwrap_or_else(|e| e.into_inner());
+        let p99 = histogram.p99();
+        histogram.add(duration);
+        p99
+    };
+    let Some(p99) = p99.filter(|&p99| duration > p99) else {
+        return;
+    };
+    let event = crate::TailEvent {
+        scope: watched.name.clone(),
+        thread: thread.name.clone(),
+        elapsed: end.saturating_duration_since(crate::measure::GLOBAL_PROFILER.start()),
+        duration,
+        p99,
+        metadata: watched.metadata.as_ref().map(|f| f()),
+    };
+    let mut events = EVENTS.lock().unwrap_or_else(|e| e.into_inner());
+    if events.len() == MAX_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(event);
+}
+
+/// Events logged since the start or the last reset.
+#[cfg(feature = "enable")]
+pub(crate) fn events() -> Vec<crate::TailEvent> {
+    EVENTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .cloned()
+        .collect()
+}
+
+/// Discards the logged events, the percentiles of the watched scopes are kept.
+#[cfg(feature = "enable")]
+pub(crate) fn reset() {
+    EVENTS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
@@ -0,0 +1,117 @@
+//! Threads whose scopes are shown beneath the scope that spawned them.
+//!
+//! The scopes of a thread spawned with [`std::thread::spawn`] are roots of their own, apart from the scope that started the work.
+//! [`spawn_profiled`] and [`Builder`] run the closure in a scope that is shown beneath the scopes that were open when the thread was spawned,
+//! both in the merged table and in the flamegraphs.
+//!
+//! The scope starts when the thread is spawned, and its `spawn latency` child is the time until the closure started running.
+//!
+//! # Example
+//! ```
+//! fn main() {
+//!   profi::prof!(load);
+//!   let worker = profi::spawn_profiled("decode", || {
+//!     profi::prof!(inflate);
+//!     2 + 2
+//!   });
+//!   assert_eq!(worker.join().unwrap(), 4);
+//!
+//!   let report = profi::report();
+//!   # #[cfg(feature = "enable")]
+//!   # {
+//!   let depth = |name| report.scope(name).unwrap().depth;
+//!   assert_eq!((depth("load"), depth("decode"), depth("inflate")), (0, 1, 2));
+//!   assert_eq!(depth("spawn latency"), 2);
+//!   # }
+//! }
+//! ```
+
+use std::thread::JoinHandle;
+
+/// Spawns a thread that runs `f` inside the scope `name`, shown beneath the scopes that are open when it's called.
+///
+/// The thread is named after the scope, use [`Builder`] to choose another name or the stack size.
+///
+/// # Panics
+/// Like [`std::thread::spawn`], if the thread can't be created.
+pub fn spawn_profiled<F, T>(name: impl Into<String>, f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    Builder::new()
+        .spawn_profiled(name, f)
+        .expect("failed to spawn thread")
+}
+
+/// Configuration of a profiled thread, like [`std::thread::Builder`].
+///
+/// # Example
+/// ```
+/// fn main() {
+///   profi::prof!(serve);
+///   let worker = profi::thread::Builder::new()
+///     .name("worker-1".into())
+///     .stack_size(64 * 1024)
+///     .spawn_profiled("handle", || {
+///       profi::prof!(parse);
+///     })
+///     .unwrap();
+///   worker.join().unwrap();
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Builder {
+    name: Option<String>,
+    stack_size: Option<usize>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names the thread, by default it's named after the scope.
+    pub fn name(mut self, name: String) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Sets the size of the stack of the thread, see [`std::thread::Builder::stack_size`].
+    pub fn stack_size(mut self, size: usize) -> Self {
+        self.stack_size = Some(size);
+        self
+    }
+
+    /// Spawns the thread, which runs `f` inside the scope `name`, see [`spawn_profiled`].
+    ///
+    /// Returns the error of [`std::thread::Builder::spawn`] if the thread can't be created.
+    pub fn spawn_profiled<F, T>(
+        self,
+        name: impl Into<String>,
+        f: F,
+    ) -> std::io::Result<JoinHandle<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let name = name.into();
+        let mut builder =
+            std::thread::Builder::new().name(self.name.unwrap_or_else(|| name.clone()));
+        if let Some(size) = self.stack_size {
+            builder = builder.stack_size(size);
+        }
+        #[cfg(feature = "enable")]
+        let (path, spawned) = (
+            crate::measure::THREAD_PROFILER.with_borrow(|thread| thread.path()),
+            crate::clock::Instant::now(),
+        );
+        builder.spawn(move || {
+            #[cfg(feature = "enable")]
+            let _guard = crate::zz_private::ScopeGuard::spawned(name, &path, spawned);
+            #[cfg(not(feature = "enable"))]
+            let _ = name;
+            f()
+        })
+    }
+}
@@ -0,0 +1,109 @@
+//! Sleeps and timeouts of [`tokio`](https://docs.rs/tokio) that are reported as intentional waits.
+//!
+//! A scope that awaits a sleep is not slow, it's waiting on purpose.
+//! Using these instead of the ones in `tokio::time` adds the waited time to the "Intentional wait" column of all the open scopes,
+//! so it isn't mistaken for slow computation.
+//!
+//! The functions of `tokio` can't be intercepted, so only the waits that go through this module are classified.
+//!
+//! # Example
+//! ```
+//! use std::time::Duration;
+//!
+//! async fn retry() {
+//!   profi::prof!(retry);
+//!   // Backoff, not slow computation
+//!   profi::tokio::sleep(Duration::from_millis(5)).await;
+//! }
+//!
+//! fn main() {
+//!   let runtime = tokio::runtime::Builder::new_current_thread()
+//!     .enable_time()
+//!     .build()
+//!     .unwrap();
+//!   runtime.block_on(retry());
+//!
+//!   let report = profi::report();
+//!   assert!(report.scope("retry").unwrap().wait >= Duration::from_millis(5));
+//! }
+//! ```
+
+use std::{future::Future, time::Duration};
+
+/// Same as [`tokio::time::sleep`], but the time is reported as an intentional wait.
+pub fn sleep(duration: Duration) -> Waiting<::tokio::time::Sleep> {
+    Waiting::new(::tokio::time::sleep(duration))
+}
+
+/// Same as [`tokio::time::sleep_until`], but the time is reported as an intentional wait.
+pub fn sleep_until(deadline: ::tokio::time::Instant) -> Waiting<::tokio::time::Sleep> {
+    Waiting::new(::tokio::time::sleep_until(deadline))
+}
+
+/// Same as [`tokio::time::timeout`], but the time spent waiting for `future` is reported as an intentional wait.
+///
+/// The time spent polling `future` is still measured as usual.
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Waiting<::tokio::time::Timeout<F>> {
+    Waiting::new(::tokio::time::timeout(duration, future))
+}
+
+/// Future that reports the time it isn't being polled as an intentional wait of the open scopes.
+///
+/// The wait is reported when it completes or when it's dropped.
+pub struct Waiting<F> {
+    future: F,
+    /// First poll and time spent polling, `None` until polled and after reporting
+    #[cfg(feature = "enable")]
+    started: Option<(crate::clock::Instant, Duration)>,
+}
+
+impl<F> Waiting<F> {
+    pub fn new(future: F) -> Self {
+        Self {
+            future,
+            #[cfg(feature = "enable")]
+            started: None,
+        }
+    }
+
+    #[cfg(feature = "enable")]
+    fn report(&mut self, now: crate::clock::Instant) {
+        if let Some((start, polled)) = self.started.take() {
+            let wait = now.duration_since(start).saturating_sub(polled);
+            crate::measure::THREAD_PROFILER.with_borrow_mut(|thread| thread.wait(wait));
+        }
+    }
+}
+
+impl<F: Future> Future for Waiting<F> {
+    type Output = F::Output;
+
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        // SAFETY: `future` is never moved out of `self`, the rest of fields are not pinned
+        let this = unsafe { self.get_unchecked_mut() };
+        #[cfg(feature = "enable")]
+        let start = crate::clock::Instant::now();
+        // SAFETY: see above
+        let poll = unsafe { std::pin::Pin::new_unchecked(&mut this.future) }.poll(cx);
+        #[cfg(feature = "enable")]
+        {
+            let now = crate::clock::Instant::now();
+            let (_, polled) = this.started.get_or_insert((start, Duration::ZERO));
+            *polled += now.duration_since(start);
+            if poll.is_ready() {
+                this.report(now);
+            }
+        }
+        poll
+    }
+}
+
+#[cfg(feature = "enable")]
+impl<F> Drop for Waiting<F> {
+    fn drop(&mut self) {
+        self.report(crate::clock::Instant::now())
+    }
+}
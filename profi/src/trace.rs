@@ -0,0 +1,404 @@
+//! Compact binary traces of the raw measures, to capture on one machine and analyse on another.
+//!
+//! A trace keeps every call of every thread, like the program's own memory, but takes a few bytes per measure:
+//! timestamps are stored as the difference with the previous one, numbers as variable-length integers,
+//! and each name is written once and then referred to by its index.
+//!
+//! Write one with [`write`] or `print_on_exit!(to = file, format = binary)`,
+//! and build the report from it later with [`read`], which can then be printed in any other format.
+//! Scopes folded with the `bounded-memory` feature or a [`Retention`](crate::Retention) are not part of the trace,
+//! neither are the cores and resource usage of each measure.
+//!
+//! # Example
+//! ```
+//! use profi::{prof, Format, PrintOptions};
+//!
+//! fn main() {
+//!   for _ in 0..10 {
+//!     prof!(work);
+//!   }
+//!   let path = std::env::temp_dir().join("profi-trace.bin");
+//!   let file = std::fs::File::create(&path).unwrap();
+//!   profi::report().print(file, &PrintOptions::new().format(Format::Binary)).unwrap();
+//!
+//!   // Later, maybe on another machine
+//!   let report = profi::trace::read(&path).unwrap();
+//!   # #[cfg(feature = "enable")]
+//!   assert_eq!(report.scope("work").unwrap().calls, 10);
+//!   report.print(std::io::stdout(), &Default::default()).unwrap();
+//! }
+//! ```
+
+#[cfg(feature = "enable")]
+use crate::{
+    measure::{Measure, MeasureType},
+    names::{Id, Name},
+};
+
+/// Version of the trace format written by this version of `profi`.
+///
+/// Every trace starts with `PROFI-T` followed by this byte, and since version 2 by the [`VERSION`](crate::VERSION) of `profi` that wrote it.
+/// Traces with an older format can still be read, newer ones fail asking to update `profi`.
+///
+/// Before version 3, every poll of a [`prof_future!`](crate::prof_future) after the first one continued the last call of its scope,
+/// they are read as calls of their own.
+pub const FORMAT_VERSION: u8 = 3;
+
+/// First bytes of every trace, followed by the version of the format.
+const MAGIC: &[u8; 7] = b"PROFI-T";
+
+/// Writes the measures of every thread taken until now as a binary trace, see the [module](self) documentation.
+///
+/// The same as printing any report with [`Format::Binary`](crate::Format::Binary).
+/// Writes an empty trace if the `enable` feature is disabled.
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   {
+///     prof!(work);
+///   }
+///   let mut trace = Vec::new();
+///   profi::trace::write(&mut trace).unwrap();
+/// }
+/// ```
+#[allow(unused_mut, unused_variables)]
+pub fn write(mut to: impl std::io::Write) -> std::io::Result<()> {
+    #[cfg(feature = "enable")]
+    to.write_all(&crate::measure::GLOBAL_PROFILER.trace())?;
+    #[cfg(not(feature = "enable"))]
+    to.write_all(&header())?;
+    Ok(())
+}
+
+/// Builds a report from the binary trace at `path`, written by [`write`] or [`Format::Binary`](crate::Format::Binary).
+///
+/// Each thread of the trace is a thread of the report.
+/// Fails with [`InvalidData`](std::io::ErrorKind::InvalidData) if the file is not a trace or was written with a newer [`FORMAT_VERSION`],
+/// or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) if it's truncated.
+///
+/// Returns an empty report if the `enable` feature is disabled.
+pub fn read(path: impl AsRef<std::path::Path>) -> std::io::Result<crate::Report> {
+    read_from(&std::fs::read(path)?)
+}
+
+/// Builds a report from a binary trace held in memory, see [`read`].
+///
+/// # Example
+/// ```
+/// use profi::prof;
+///
+/// fn main() {
+///   {
+///     prof!(work);
+///   }
+///   let mut trace = Vec::new();
+///   profi::trace::write(&mut trace).unwrap();
+///
+///   let report = profi::trace::read_from(&trace).unwrap();
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(report.scope("work").unwrap().calls, 1);
+///   assert!(profi::trace::read_from(b"not a trace").is_err());
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn read_from(bytes: &[u8]) -> std::io::Result<crate::Report> {
+    #[cfg(feature = "enable")]
+    {
+        let threads = decode(bytes)?;
+        let folded = crate::process::Folded::default();
+        // Scopes still open when the trace was written end with it
+        let end = threads
+            .iter()
+            .flat_map(|(_, measures)| measures.iter().map(|m| m.time))
+            .max()
+            .unwrap_or_else(crate::clock::Instant::now);
+        Ok(crate::process::into_report(
+            threads
+                .iter()
+                .map(|(name, measures)| (name.as_deref(), &folded, &measures[..])),
+            end,
+        ))
+    }
+    #[cfg(not(feature = "enable"))]
+    {
+        read_header(&mut &bytes[..])?;
+        Ok(crate::Report::default())
+    }
+}
+
+/// Magic bytes, format and version of `profi` that start every trace.
+fn header() -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(FORMAT_VERSION);
+    varint(crate::VERSION.len() as u64, &mut bytes);
+    bytes.extend(crate::VERSION.as_bytes());
+    bytes
+}
+
+/// Reads the start of a trace, returning its format.
+fn read_header(bytes: &mut &[u8]) -> std::io::Result<u8> {
+    if take(bytes, MAGIC.len()).ok() != Some(MAGIC) {
+        return Err(invalid("not a profi trace"));
+    }
+    let format = take(bytes, 1)?[0];
+    // Version 1 didn't have the version of profi
+    let written_by = match format {
+        1 => None,
+        _ => {
+            let len = read_varint(bytes)?;
+            Some(string(bytes, len)?)
+        }
+    };
+    if format > FORMAT_VERSION {
+        return Err(invalid(&format!(
+            "trace written by profi {} with format {format}, this version only reads up to {FORMAT_VERSION}, update profi to read it",
+            written_by.as_deref().unwrap_or("?"),
+        )));
+    }
+    Ok(format)
+}
+
+/// Encodes `threads`, each with its name and measures, as a trace.
+#[cfg(feature = "enable")]
+pub(crate) fn encode<'a>(
+    threads: impl IntoIterator<Item = (Option<&'a str>, &'a [Measure])>,
+) -> Vec<u8> {
+    let mut bytes = header();
+    let mut names = std::collections::HashMap::<&str, u64>::new();
+    for (name, measures) in threads {
+        match name {
+            Some(name) => {
+                varint(name.len() as u64 + 1, &mut bytes);
+                bytes.extend(name.as_bytes());
+            }
+            None => varint(0, &mut bytes),
+        }
+        varint(measures.len() as u64, &mut bytes);
+        let mut last = 0;
+        for m in measures {
+            let nanos = |d: std::time::Duration| d.as_nanos() as u64;
+            let tag = match &m.ty {
+                MeasureType::Start { .. } => 0u8,
+                MeasureType::Resume { .. } => 1,
+                MeasureType::End => 2,
+                MeasureType::Count { .. } => 3,
+                MeasureType::Slow { .. } => 4,
+                MeasureType::Wait { .. } => 5,
+                MeasureType::Pause { .. } => 6,
+                MeasureType::Coalesced { .. } => 7,
+                MeasureType::Field { .. } => 8,
+                MeasureType::Parent { .. } => 9,
+                MeasureType::Polled { .. } => 10,
+                MeasureType::Sampled { .. } => 11,
+                MeasureType::Spilled { .. } => {
+                    unreachable!("[profi] spilled measures are read back before tracing")
+                }
+            };
+            bytes.push(tag);
+            let time = nanos(
+                m.time
+                    .saturating_duration_since(crate::clock::Instant::ZERO),
+            );
+            // Measures are mostly in order, zigzag encoded in case one isn't
+            let delta = time.wrapping_sub(last) as i64;
+            varint(((delta << 1) ^ (delta >> 63)) as u64, &mut bytes);
+            last = time;
+            match &m.ty {
+                MeasureType::Start { name } | MeasureType::Resume { name } => {
+                    intern(name, &mut names, &mut bytes)
+                }
+                MeasureType::End => {}
+                MeasureType::Count { name, value } => {
+                    intern(name.get(), &mut names, &mut bytes);
+                    varint(*value, &mut bytes);
+                }
+                MeasureType::Slow { call } => {
+                    varint(nanos(call.duration), &mut bytes);
+                    intern(&call.context, &mut names, &mut bytes);
+                }
+                MeasureType::Wait { duration }
+                | MeasureType::Pause { duration }
+                | MeasureType::Polled { duration } => varint(nanos(*duration), &mut bytes),
+                MeasureType::Coalesced { calls } => varint(*calls as u64, &mut bytes),
+                MeasureType::Sampled { every } => varint(*every as u64, &mut bytes),
+                MeasureType::Field { field } => {
+                    let (key, value) = &**field;
+                    intern(key, &mut names, &mut bytes);
+                    intern(value, &mut names, &mut bytes);
+                }
+                MeasureType::Parent { path } => {
+                    varint(path.len() as u64, &mut bytes);
+                    path.iter()
+                        .for_each(|name| intern(name, &mut names, &mut bytes));
+                }
+                MeasureType::Spilled { .. } => {}
+            }
+        }
+    }
+    bytes
+}
+
+/// Appends the index of `name`, followed by the name itself the first time it's used.
+#[cfg(feature = "enable")]
+fn intern<'a>(
+    name: &'a str,
+    names: &mut std::collections::HashMap<&'a str, u64>,
+    bytes: &mut Vec<u8>,
+) {
+    let next = names.len() as u64;
+    let index = *names.entry(name).or_insert(next);
+    varint(index, bytes);
+    if index == next {
+        varint(name.len() as u64, bytes);
+        bytes.extend(name.as_bytes());
+    }
+}
+
+/// Name and measures of each thread of a trace.
+#[cfg(feature = "enable")]
+fn decode(mut bytes: &[u8]) -> std::io::Result<Vec<(Option<String>, Vec<Measure>)>> {
+    let bytes = &mut bytes;
+    let format = read_header(bytes)?;
+    let mut names = Vec::<crate::Str>::new();
+    let mut threads = Vec::new();
+    while !bytes.is_empty() {
+        let thread = match read_varint(bytes)? {
+            0 => None,
+            len => Some(string(bytes, len - 1)?),
+        };
+        let len = read_varint(bytes)?;
+        let mut measures = Vec::with_capacity(len.min(bytes.len() as u64) as usize);
+        let mut time = 0u64;
+        for _ in 0..len {
+            let tag = take(bytes, 1)?[0];
+            let zigzag = read_varint(bytes)?;
+            time = time.wrapping_add(((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64)) as u64);
+            let nanos = std::time::Duration::from_nanos;
+            let ty = match tag {
+                0 => MeasureType::Start {
+                    name: Name::new(name(bytes, &mut names)?),
+                },
+                1 if format < 3 => MeasureType::Start {
+                    name: Name::new(name(bytes, &mut names)?),
+                },
+                1 => MeasureType::Resume {
+                    name: Name::new(name(bytes, &mut names)?),
+                },
+                2 => MeasureType::End,
+                3 => MeasureType::Count {
+                    name: Id::new(name(bytes, &mut names)?),
+                    value: read_varint(bytes)?,
+                },
+                4 => {
+                    let duration = nanos(read_varint(bytes)?);
+                    MeasureType::Slow {
+                        call: Box::new(crate::SlowCall {
+                            duration,
+                            context: name(bytes, &mut names)?.into_owned(),
+                        }),
+                    }
+                }
+                5 => MeasureType::Wait {
+                    duration: nanos(read_varint(bytes)?),
+                },
+                6 => MeasureType::Pause {
+                    duration: nanos(read_varint(bytes)?),
+                },
+                7 => MeasureType::Coalesced {
+                    calls: read_varint(bytes)? as usize,
+                },
+                8 => MeasureType::Field {
+                    field: Box::new((
+                        Name::new(name(bytes, &mut names)?),
+                        name(bytes, &mut names)?,
+                    )),
+                },
+                9 => {
+                    let len = read_varint(bytes)?;
+                    MeasureType::Parent {
+                        path: (0..len)
+                            .map(|_| name(bytes, &mut names).map(Name::new))
+                            .collect::<std::io::Result<_>>()?,
+                    }
+                }
+                10 => MeasureType::Polled {
+                    duration: nanos(read_varint(bytes)?),
+                },
+                11 => MeasureType::Sampled {
+                    every: read_varint(bytes)? as usize,
+                },
+                _ => return Err(invalid("unknown measure")),
+            };
+            measures.push(Measure {
+                ty,
+                time: crate::clock::Instant::ZERO + nanos(time),
+                #[cfg(feature = "cores")]
+                core: u32::MAX,
+                #[cfg(any(
+                    feature = "context-switches",
+                    feature = "page-faults",
+                    feature = "perf"
+                ))]
+                usage: Default::default(),
+            });
+        }
+        threads.push((thread, measures));
+    }
+    Ok(threads)
+}
+
+/// Name read by [`intern`] from the trace, `names` are the ones read until now.
+#[cfg(feature = "enable")]
+fn name(bytes: &mut &[u8], names: &mut Vec<crate::Str>) -> std::io::Result<crate::Str> {
+    let index = read_varint(bytes)? as usize;
+    if index == names.len() {
+        let len = read_varint(bytes)?;
+        names.push(string(bytes, len)?.into());
+    }
+    names
+        .get(index)
+        .cloned()
+        .ok_or_else(|| invalid("unknown name"))
+}
+
+/// Appends `value` as a LEB128 variable-length integer.
+fn varint(mut value: u64, bytes: &mut Vec<u8>) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> std::io::Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = take(bytes, 1)?[0];
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("integer too long"))
+}
+
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> std::io::Result<&'a [u8]> {
+    if bytes.len() < n {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    let (taken, rest) = bytes.split_at(n);
+    *bytes = rest;
+    Ok(taken)
+}
+
+fn string(bytes: &mut &[u8], len: u64) -> std::io::Result<String> {
+    let len = usize::try_from(len).map_err(|_| invalid("string too long"))?;
+    String::from_utf8(take(bytes, len)?.to_vec()).map_err(|e| invalid(&e.to_string()))
+}
+
+fn invalid(error: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, error.to_owned())
+}
@@ -0,0 +1,109 @@
+//! Integration with the [`tracing`](https://docs.rs/tracing) ecosystem.
+//!
+//! [`ProfiLayer`] feeds the spans of `tracing` into `profi`, so code already instrumented with `tracing` gets the same table as [`prof!`](crate::prof).
+//!
+//! In the other direction, every [`prof!`](crate::prof) scope enters a `TRACE` span with target `profi` and the scope name in the `name` field.
+//! These spans are ignored by [`ProfiLayer`], so they're never measured twice.
+
+/// [`Layer`](tracing_subscriber::Layer) that profiles every span while it's entered.
+///
+/// Spans are measured like [`prof_future!`](crate::prof_future): entering the same span again continues its call instead of counting a new one,
+/// so instrumented futures only measure the time they're being polled.
+///
+/// Spans rejected by the [filter](crate::set_filter) are ignored.
+///
+/// # Example
+/// ```
+/// use tracing_subscriber::prelude::*;
+///
+/// fn main() {
+///   profi::print_on_exit!();
+///   tracing_subscriber::registry()
+///     .with(profi::tracing::ProfiLayer::new())
+///     .init();
+///
+///   let _span = tracing::info_span!("work").entered();
+///   // ...
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProfiLayer {
+    _private: (),
+}
+
+impl ProfiLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Marks the spans that are profiled.
+#[cfg(feature = "enable")]
+struct Profiled {
+    entered: bool,
+}
+
+impl<S> tracing_subscriber::Layer<S> for ProfiLayer
+where
+    S: ::tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    #[allow(unused_variables)]
+    fn on_new_span(
+        &self,
+        attrs: &::tracing::span::Attributes<'_>,
+        id: &::tracing::span::Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        #[cfg(feature = "enable")]
+        {
+            let metadata = attrs.metadata();
+            // Emitted by `prof!`, which is already measured
+            if metadata.target() == "profi" || !crate::filter::is_enabled(metadata.name()) {
+                return;
+            }
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(Profiled { entered: false });
+            }
+        }
+    }
+
+    #[allow(unused_variables)]
+    fn on_enter(&self, id: &::tracing::span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        #[cfg(feature = "enable")]
+        {
+            let Some(span) = ctx.span(id) else {
+                return;
+            };
+            let Some(entered) = span
+                .extensions_mut()
+                .get_mut::<Profiled>()
+                .map(|p| std::mem::replace(&mut p.entered, true))
+            else {
+                return;
+            };
+            let name = crate::names::Name::new(span.name());
+            crate::measure::THREAD_PROFILER.with_borrow_mut(|thread| {
+                if entered {
+                    thread.resume(name)
+                } else {
+                    thread.push(name)
+                }
+            });
+        }
+    }
+
+    #[allow(unused_variables)]
+    fn on_exit(&self, id: &::tracing::span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        #[cfg(feature = "enable")]
+        {
+            // Do the measure as early as possible
+            let time = crate::clock::Instant::now();
+            let Some(span) = ctx.span(id) else {
+                return;
+            };
+            if span.extensions().get::<Profiled>().is_some() {
+                crate::measure::THREAD_PROFILER.with_borrow_mut(|thread| thread.pop(time));
+            }
+        }
+    }
+}
@@ -0,0 +1,27 @@
+//! Browser support, where `std::time`, environment variables and process ids are not available.
+//!
+//! The browser runs a single thread that never exits, so the report is taken with [`report_string`](crate::report_string)
+//! instead of relying on `print_on_exit!`.
+
+thread_local! {
+    /// `performance` of the current page, `None` in workers.
+    static PERFORMANCE: Option<web_sys::Performance> =
+        web_sys::window().and_then(|window| window.performance());
+}
+
+/// Time since the page was loaded, from `performance.now()`.
+///
+/// Falls back to `Date.now()` where there's no window, which is not monotonic.
+pub(crate) fn now() -> std::time::Duration {
+    let ms = PERFORMANCE.with(|performance| {
+        performance
+            .as_ref()
+            .map_or_else(js_sys::Date::now, web_sys::Performance::now)
+    });
+    std::time::Duration::from_secs_f64(ms / 1000.0)
+}
+
+/// Wall-clock time, from `Date.now()`.
+pub(crate) fn system_time() -> std::time::SystemTime {
+    std::time::UNIX_EPOCH + std::time::Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+}
@@ -0,0 +1,211 @@
+//! GPU time of [`wgpu`](https://docs.rs/wgpu) passes, shown next to the CPU scopes.
+//!
+//! [`GpuProfiler`] writes a timestamp at the start and end of each named pass, and reads them back on a later frame,
+//! once the GPU is done, recording each pass as a call of a scope with [`record`](crate::record).
+//! The passes are placed inside the scope that is open when they are collected.
+//!
+//! The device must be requested with [`Features::TIMESTAMP_QUERY`](::wgpu::Features::TIMESTAMP_QUERY), otherwise nothing is measured.
+//!
+//! # Example
+//! ```no_run
+//! fn frame(device: &wgpu::Device, queue: &wgpu::Queue, gpu: &mut profi::wgpu::GpuProfiler) {
+//!   profi::prof!(frame);
+//!   // Records the passes of a previous frame that the GPU finished
+//!   gpu.collect(device);
+//!
+//!   let mut encoder = device.create_command_encoder(&Default::default());
+//!   {
+//!     let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+//!       label: Some("simulate"),
+//!       timestamp_writes: gpu.compute_pass("gpu::simulate"),
+//!     });
+//!     // ...
+//!   }
+//!   gpu.resolve(&mut encoder);
+//!   queue.submit([encoder.finish()]);
+//! }
+//! ```
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Measures the time the GPU spends on each pass, see the [module docs](self).
+pub struct GpuProfiler {
+    /// `None` if the device doesn't support timestamp queries
+    queries: Option<Queries>,
+    /// Names of the passes of the current frame, each uses two queries
+    passes: Mutex<Vec<String>>,
+    /// Passes of a previous frame, being read back
+    pending: Option<Pending>,
+}
+
+struct Queries {
+    set: ::wgpu::QuerySet,
+    resolve: ::wgpu::Buffer,
+    readback: ::wgpu::Buffer,
+    /// Maximum number of passes in a frame
+    capacity: u32,
+    /// Nanoseconds per tick
+    period: f32,
+}
+
+struct Pending {
+    passes: Vec<String>,
+    /// One of `WAITING`, `MAPPED` or `FAILED`, `None` until the readback buffer is requested
+    state: Option<Arc<AtomicU8>>,
+}
+
+const WAITING: u8 = 0;
+const MAPPED: u8 = 1;
+const FAILED: u8 = 2;
+
+impl GpuProfiler {
+    /// Creates the queries for up to `max_passes` passes in each frame, the rest are not measured.
+    ///
+    /// Measures nothing if the device wasn't requested with [`Features::TIMESTAMP_QUERY`](::wgpu::Features::TIMESTAMP_QUERY),
+    /// or if the `enable` feature is disabled.
+    pub fn new(device: &::wgpu::Device, queue: &::wgpu::Queue, max_passes: u32) -> Self {
+        let supported = device
+            .features()
+            .contains(::wgpu::Features::TIMESTAMP_QUERY);
+        if cfg!(feature = "enable") && !supported {
+            eprintln!("[profi] The device doesn't support timestamp queries, request Features::TIMESTAMP_QUERY to measure the GPU");
+        }
+        let queries = (cfg!(feature = "enable") && supported).then(|| {
+            let capacity = max_passes.max(1);
+            let count = capacity * 2;
+            let size = u64::from(count) * u64::from(::wgpu::QUERY_SIZE);
+            Queries {
+                set: device.create_query_set(&::wgpu::QuerySetDescriptor {
+                    label: Some("profi"),
+                    ty: ::wgpu::QueryType::Timestamp,
+                    count,
+                }),
+                resolve: device.create_buffer(&::wgpu::BufferDescriptor {
+                    label: Some("profi resolve"),
+                    size,
+                    usage: ::wgpu::BufferUsages::QUERY_RESOLVE | ::wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                }),
+                readback: device.create_buffer(&::wgpu::BufferDescriptor {
+                    label: Some("profi readback"),
+                    size,
+                    usage: ::wgpu::BufferUsages::MAP_READ | ::wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                capacity,
+                period: queue.get_timestamp_period(),
+            }
+        });
+        Self {
+            queries,
+            passes: Mutex::new(Vec::new()),
+            pending: None,
+        }
+    }
+
+    /// Timestamp writes that measure a render pass as the scope `name`, for its [`RenderPassDescriptor`](::wgpu::RenderPassDescriptor).
+    ///
+    /// `None` if it can't be measured, because timestamps are not supported, there are more than `max_passes` in the frame,
+    /// or the previous results are still being read back.
+    pub fn render_pass(
+        &self,
+        name: impl Into<String>,
+    ) -> Option<::wgpu::RenderPassTimestampWrites<'_>> {
+        let (query_set, index) = self.timestamps(name.into())?;
+        Some(::wgpu::RenderPassTimestampWrites {
+            query_set,
+            beginning_of_pass_write_index: Some(index),
+            end_of_pass_write_index: Some(index + 1),
+        })
+    }
+
+    /// Timestamp writes that measure a compute pass as the scope `name`, for its [`ComputePassDescriptor`](::wgpu::ComputePassDescriptor).
+    ///
+    /// `None` if it can't be measured, see [`GpuProfiler::render_pass`].
+    pub fn compute_pass(
+        &self,
+        name: impl Into<String>,
+    ) -> Option<::wgpu::ComputePassTimestampWrites<'_>> {
+        let (query_set, index) = self.timestamps(name.into())?;
+        Some(::wgpu::ComputePassTimestampWrites {
+            query_set,
+            beginning_of_pass_write_index: Some(index),
+            end_of_pass_write_index: Some(index + 1),
+        })
+    }
+
+    /// Query set and index of the first of the two queries of the pass `name`.
+    fn timestamps(&self, name: String) -> Option<(&::wgpu::QuerySet, u32)> {
+        let queries = self.queries.as_ref().filter(|_| self.pending.is_none())?;
+        let mut passes = self.passes.lock().unwrap_or_else(|e| e.into_inner());
+        if passes.len() as u32 >= queries.capacity {
+            return None;
+        }
+        passes.push(name);
+        Some((&queries.set, (passes.len() as u32 - 1) * 2))
+    }
+
+    /// Copies the timestamps of the passes of this frame so they can be read back, call it before finishing `encoder`.
+    pub fn resolve(&mut self, encoder: &mut ::wgpu::CommandEncoder) {
+        let Some(queries) = &self.queries else {
+            return;
+        };
+        let passes = std::mem::take(self.passes.get_mut().unwrap_or_else(|e| e.into_inner()));
+        if passes.is_empty() {
+            return;
+        }
+        let count = passes.len() as u32 * 2;
+        let size = u64::from(count) * u64::from(::wgpu::QUERY_SIZE);
+        encoder.resolve_query_set(&queries.set, 0..count, &queries.resolve, 0);
+        encoder.copy_buffer_to_buffer(&queries.resolve, 0, &queries.readback, 0, size);
+        self.pending = Some(Pending {
+            passes,
+            state: None,
+        });
+    }
+
+    /// Records the passes of a previous frame if the GPU already finished them, call it once per frame after submitting.
+    ///
+    /// Each pass is recorded as a call of its scope inside the innermost open scope, see [`record`](crate::record).
+    pub fn collect(&mut self, device: &::wgpu::Device) {
+        let (Some(queries), Some(pending)) = (&self.queries, &mut self.pending) else {
+            return;
+        };
+        let size = pending.passes.len() as u64 * 2 * u64::from(::wgpu::QUERY_SIZE);
+        let state = pending.state.get_or_insert_with(|| {
+            let state = Arc::new(AtomicU8::new(WAITING));
+            let mapped = state.clone();
+            queries
+                .readback
+                .slice(..size)
+                .map_async(::wgpu::MapMode::Read, move |result| {
+                    let value = if result.is_ok() { MAPPED } else { FAILED };
+                    mapped.store(value, Ordering::Release);
+                });
+            state
+        });
+        device.poll(::wgpu::Maintain::Poll);
+        match state.load(Ordering::Acquire) {
+            WAITING => return,
+            MAPPED => {}
+            _ => {
+                self.pending = None;
+                return;
+            }
+        }
+
+        let ticks = {
+            let data = queries.readback.slice(..size).get_mapped_range();
+            data.chunks_exact(8)
+                .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        queries.readback.unmap();
+        let pending = self.pending.take().unwrap();
+        for (name, ticks) in pending.passes.into_iter().zip(ticks.chunks_exact(2)) {
+            let nanos = ticks[1].saturating_sub(ticks[0]) as f64 * f64::from(queries.period);
+            crate::record(name, std::time::Duration::from_nanos(nanos as u64));
+        }
+    }
+}
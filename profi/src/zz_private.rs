@@ -3,96 +3,709 @@
 //! Always prefer the macros [`prof!`](crate::prof), [`prof_guard!`](crate::prof_guard) and [`print_on_exit!`](crate::print_on_exit).
 
 #[cfg(feature = "enable")]
-use crate::Str;
+use crate::{names::Name, Str};
 #[cfg(not(feature = "enable"))]
 type Str = String;
 
 /// Explicit guard returned by [`prof_guard!`](crate::prof_guard!).
-/// 
+///
 /// It starts profiling on creation, and stops when dropped.
-/// 
+///
 /// # Example
 /// ```
 /// let guard = profi::prof_guard!("explicit");
 /// // ...
 /// drop(guard);
 /// ```
-#[repr(transparent)]
-pub struct ScopeGuard(Guard<()>);
+///
+/// If the scope is rejected by the [filter](crate::set_filter), the guard does nothing.
+///
+/// With the `tracing` feature it also enters a `tracing` span with target `profi` while alive.
+#[allow(dead_code)]
+pub struct ScopeGuard {
+    guard: Option<Closer>,
+    /// Depth of the scope in the thread, `1` is the outermost one
+    #[cfg(feature = "enable")]
+    depth: usize,
+    /// Declared after `guard` so it's dropped after the scope is closed
+    #[cfg(feature = "enable")]
+    sampled: Option<Sampled>,
+    #[cfg(feature = "tracing")]
+    /// Boxed to keep the guard small, only allocated if a subscriber records it
+    span: Option<Box<::tracing::span::EnteredSpan>>,
+}
+
+/// Calls of a [`prof!`](crate::prof) call site with `sample = 1 / N`.
+#[doc(hidden)]
+pub struct SampleCounter(#[allow(dead_code)] std::sync::atomic::AtomicUsize);
+
+impl SampleCounter {
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Self(std::sync::atomic::AtomicUsize::new(0))
+    }
+}
+
+/// Name of a [`prof!`](crate::prof) call site, registered the first time it's reached to find collisions,
+/// see [`Report::name_collisions`](crate::Report::name_collisions).
+///
+/// Also caches if the scope is enabled, so the [filter](crate::set_filter) isn't checked on every call,
+/// and the interned name, so it's only looked up once.
+#[doc(hidden)]
+pub struct ScopeName {
+    #[allow(dead_code)]
+    registered: std::sync::atomic::AtomicBool,
+    #[cfg(feature = "enable")]
+    filter: crate::filter::Cached,
+    #[cfg(feature = "enable")]
+    interned: crate::names::Cached,
+}
+
+impl ScopeName {
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Self {
+            registered: std::sync::atomic::AtomicBool::new(false),
+            #[cfg(feature = "enable")]
+            filter: crate::filter::Cached::new(),
+            #[cfg(feature = "enable")]
+            interned: crate::names::Cached::new(),
+        }
+    }
+
+    /// Name inferred from the enclosing function.
+    #[inline(always)]
+    pub fn inferred(&'static self, name: &'static str) -> SiteName {
+        self.register(name, true)
+    }
+
+    /// Name given as a literal or an identifier.
+    #[inline(always)]
+    pub fn explicit(&'static self, name: &'static str) -> SiteName {
+        self.register(name, false)
+    }
+
+    /// Name given as a literal or an identifier, with the description of `prof!(name, desc = "...")`.
+    #[inline(always)]
+    #[allow(unused_variables)]
+    pub fn described(&'static self, name: &'static str, desc: &'static str) -> SiteName {
+        #[cfg(feature = "enable")]
+        if !self.registered.load(std::sync::atomic::Ordering::Relaxed) {
+            crate::set_description(name, desc);
+        }
+        self.register(name, false)
+    }
+
+    #[inline(always)]
+    #[allow(unused_variables)]
+    fn register(&'static self, name: &'static str, inferred: bool) -> SiteName {
+        #[cfg(feature = "enable")]
+        if !self.registered.load(std::sync::atomic::Ordering::Relaxed) {
+            self.registered
+                .store(true, std::sync::atomic::Ordering::Relaxed);
+            crate::collisions::register(name, inferred);
+        }
+        SiteName { name, site: self }
+    }
+}
+
+/// Name of a scope along with its call site, returned by [`ScopeName`].
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub struct SiteName {
+    name: &'static str,
+    site: &'static ScopeName,
+}
+
+/// Name of a scope, either a [`SiteName`] or anything that converts to a string.
+#[doc(hidden)]
+pub trait IntoScopeName {
+    /// Returns the name if the scope should be profiled.
+    #[cfg(feature = "enable")]
+    fn enabled(self) -> Option<Name>;
+
+    /// Returns the name without checking the filter, when no hook is active.
+    #[cfg(feature = "enable")]
+    fn name(self) -> Name;
+}
+
+impl<T: Into<Str>> IntoScopeName for T {
+    #[cfg(feature = "enable")]
+    #[inline(always)]
+    fn enabled(self) -> Option<Name> {
+        let name = self.into();
+        crate::filter::is_enabled(&name).then(|| Name::new(name))
+    }
+
+    #[cfg(feature = "enable")]
+    #[inline(always)]
+    fn name(self) -> Name {
+        Name::new(self)
+    }
+}
+
+impl IntoScopeName for SiteName {
+    #[cfg(feature = "enable")]
+    #[inline(always)]
+    fn enabled(self) -> Option<Name> {
+        self.site.filter.is_enabled(self.name).then(|| self.name())
+    }
+
+    #[cfg(feature = "enable")]
+    #[inline(always)]
+    fn name(self) -> Name {
+        self.site.interned.get(self.name).into()
+    }
+}
+
+/// Reports the duration of a sampled call, see [`crate::set_overhead_budget`].
+#[cfg(feature = "enable")]
+struct Sampled {
+    id: usize,
+    start: crate::clock::Instant,
+}
+
+#[cfg(feature = "enable")]
+impl Drop for Sampled {
+    fn drop(&mut self) {
+        if let Some(budget) = crate::sampling::budget() {
+            let duration = self.start.elapsed();
+            crate::measure::THREAD_PROFILER
+                .with_borrow_mut(|thread| thread.observe(self.id, duration, budget));
+        }
+    }
+}
 
 impl ScopeGuard {
     #[inline(always)]
     #[allow(unused)]
     #[doc(hidden)]
-    pub fn new(name: impl Into<Str>) -> Self {
-        Self(Guard::new((), name))
+    pub fn new(name: impl IntoScopeName) -> Self {
+        #[cfg(feature = "enable")]
+        {
+            if !crate::hooks::any() {
+                return Self::start(name.name(), None);
+            }
+            let Some(name) = name.enabled() else {
+                return Self::disabled();
+            };
+            let sampled = match crate::sampling::budget() {
+                Some(_) => match crate::measure::THREAD_PROFILER
+                    .with_borrow_mut(|thread| thread.sample(&name))
+                {
+                    Some(id) => Some(id),
+                    // Skipped by the sampler
+                    None => return Self::disabled(),
+                },
+                None => None,
+            };
+            Self::start(name, sampled)
+        }
+        #[cfg(not(feature = "enable"))]
+        Self::disabled()
+    }
+
+    /// Records one every `every` calls of the call site counted by `calls`, see [`prof!`](crate::prof).
+    #[inline(always)]
+    #[allow(unused)]
+    #[doc(hidden)]
+    pub fn sampled(name: impl IntoScopeName, calls: &'static SampleCounter, every: usize) -> Self {
+        #[cfg(feature = "enable")]
+        {
+            let every = every.max(1);
+            let call = calls.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            if call % every != 0 {
+                return Self::disabled();
+            }
+            let name = if crate::hooks::any() {
+                let Some(name) = name.enabled() else {
+                    return Self::disabled();
+                };
+                name
+            } else {
+                name.name()
+            };
+            if every == 1 {
+                return Self::start(name, None);
+            }
+            if call == 0 {
+                crate::sampling::set_fixed_rate(calls, &name, every);
+            }
+            let guard = Self::start(name, None);
+            if guard.guard.is_some() {
+                crate::measure::THREAD_PROFILER
+                    .with_borrow_mut(|thread| thread.sampled(guard.depth, every));
+            }
+            guard
+        }
+        #[cfg(not(feature = "enable"))]
+        Self::disabled()
+    }
+
+    /// Opens the scope in the current thread.
+    #[cfg(feature = "enable")]
+    #[inline(always)]
+    fn start(name: Name, sampled: Option<usize>) -> Self {
+        #[cfg(feature = "tracing")]
+        let span = Some(::tracing::trace_span!(target: "profi", "scope", name = &*name))
+            .filter(|span| !span.is_disabled())
+            .map(|span| Box::new(span.entered()));
+        let opened = crate::measure::THREAD_PROFILER.try_with(|thread| {
+            let mut thread = thread.borrow_mut();
+            (thread.push(name), thread.clock)
+        });
+        // In the destructor of a thread local after the profiler of the thread was destroyed
+        let Ok((depth, clock)) = opened else {
+            return Self::disabled();
+        };
+        Self {
+            #[cfg(feature = "tracing")]
+            span,
+            depth,
+            guard: Some(Closer(clock)),
+            sampled: sampled.map(|id| Sampled {
+                id,
+                start: clock.now(),
+            }),
+        }
+    }
+
+    /// Opens the scope `name` beneath `path` as if it started at `spawned`, with a `spawn latency` child
+    /// from `spawned` until now, see [`crate::thread`].
+    #[cfg(feature = "enable")]
+    pub(crate) fn spawned(
+        name: impl IntoScopeName,
+        path: &[Name],
+        spawned: crate::clock::Instant,
+    ) -> Self {
+        let Some(name) = name.enabled() else {
+            return Self::disabled();
+        };
+        let (depth, clock) = crate::measure::THREAD_PROFILER.with_borrow_mut(|thread| {
+            let depth = thread.push_at(name, spawned);
+            let started = thread.clock.now().max(spawned);
+            thread.record_calls(Name::new("spawn latency"), [(spawned, started)]);
+            (depth, thread.clock)
+        });
+        let mut guard = Self {
+            #[cfg(feature = "tracing")]
+            span: None,
+            depth,
+            guard: Some(Closer(clock)),
+            sampled: None,
+        };
+        guard.attach(path);
+        guard
+    }
+
+    /// Guard that does nothing.
+    fn disabled() -> Self {
+        Self {
+            guard: None,
+            #[cfg(feature = "enable")]
+            depth: 0,
+            #[cfg(feature = "enable")]
+            sampled: None,
+            #[cfg(feature = "tracing")]
+            span: None,
+        }
+    }
+
+    /// Renames the scope, useful when the interesting name is only known after the work started.
+    ///
+    /// The whole call is recorded with the new name, so it gets its own row in the table.
+    ///
+    /// # Example
+    /// ```
+    /// fn lookup(cache: &[u32], key: u32) -> bool {
+    ///   let mut guard = profi::prof_guard!("lookup");
+    ///   let hit = cache.contains(&key);
+    ///   if hit {
+    ///     guard.set_name("lookup (hit)");
+    ///   }
+    ///   hit
+    /// }
+    ///
+    /// fn main() {
+    ///   lookup(&[1, 2, 3], 2);
+    ///   lookup(&[1, 2, 3], 4);
+    ///   let report = profi::report();
+    ///   # #[cfg(feature = "enable")]
+    ///   # {
+    ///   assert_eq!(report.scope("lookup (hit)").unwrap().calls, 1);
+    ///   assert_eq!(report.scope("lookup").unwrap().calls, 1);
+    ///   # }
+    /// }
+    /// ```
+    #[allow(unused_variables)]
+    pub fn set_name(&mut self, name: impl Into<Str>) {
+        #[cfg(feature = "enable")]
+        if self.guard.is_some() {
+            let name = name.into();
+            crate::measure::THREAD_PROFILER
+                .with_borrow_mut(|thread| thread.rename(self.depth, |_| name));
+        }
+    }
+
+    /// Appends `key=value` to the name of the scope, like `query{cache=miss}`.
+    ///
+    /// Calls with different tags get their own rows in the table, several tags are separated by commas.
+    ///
+    /// # Example
+    /// ```
+    /// fn main() {
+    ///   for i in 0..4 {
+    ///     let mut guard = profi::prof_guard!("query");
+    ///     guard.tag("cache", if i % 2 == 0 { "hit" } else { "miss" });
+    ///     guard.tag("shard", 1);
+    ///   }
+    ///   let report = profi::report();
+    ///   # #[cfg(feature = "enable")]
+    ///   assert_eq!(report.scope("query{cache=miss,shard=1}").unwrap().calls, 2);
+    /// }
+    /// ```
+    #[allow(unused_variables)]
+    pub fn tag(&mut self, key: &str, value: impl std::fmt::Display) {
+        #[cfg(feature = "enable")]
+        if self.guard.is_some() {
+            crate::measure::THREAD_PROFILER.with_borrow_mut(|thread| {
+                thread.rename(self.depth, |name| {
+                    let tagged = match name.strip_suffix('}') {
+                        Some(tags) if tags.contains('{') => format!("{tags},{key}={value}}}"),
+                        _ => format!("{name}{{{key}={value}}}"),
+                    };
+                    tagged.into()
+                })
+            });
+        }
+    }
+
+    /// Sets the field `key` of this call to `value`, setting it again replaces the previous value.
+    ///
+    /// Unlike [`tag`](Self::tag), the calls stay in the same row unless the report is grouped by `key`,
+    /// see [`Report::group_by`](crate::Report::group_by). Used by `fields = { .. }` in [`prof!`](crate::prof).
+    ///
+    /// # Example
+    /// ```
+    /// fn main() {
+    ///   for i in 0..4 {
+    ///     let mut guard = profi::prof_guard!("query");
+    ///     guard.field("cache", if i % 2 == 0 { "hit" } else { "miss" });
+    ///   }
+    ///   let report = profi::report();
+    ///   # #[cfg(feature = "enable")]
+    ///   # {
+    ///   assert_eq!(report.scope("query").unwrap().calls, 4);
+    ///   assert_eq!(report.group_by("cache").scope("query [cache=miss]").unwrap().calls, 2);
+    ///   # }
+    /// }
+    /// ```
+    #[allow(unused_variables)]
+    pub fn field(&mut self, key: impl Into<Str>, value: impl Into<Str>) {
+        #[cfg(feature = "enable")]
+        if self.guard.is_some() {
+            let (key, value) = (Name::new(key), value.into());
+            crate::measure::THREAD_PROFILER
+                .with_borrow_mut(|thread| thread.field(self.depth, key, value));
+        }
+    }
+
+    /// Shows the scope beneath `path`, the open scopes of another thread, if it's the outermost one of this thread.
+    #[cfg(feature = "enable")]
+    pub(crate) fn attach(&mut self, path: &[Name]) {
+        if self.guard.is_some() {
+            crate::measure::THREAD_PROFILER
+                .with_borrow_mut(|thread| thread.parent(self.depth, path.into()));
+        }
+    }
+
+    /// Records the string returned by `context` if the scope takes longer than `threshold`.
+    ///
+    /// `context` is only called for slow calls, so the common path has no extra cost.
+    /// The slowest calls of each scope are printed in a "Slow calls" section after the table.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// fn query(id: u32) {
+    ///   let _guard = profi::prof_guard!("query").on_slow(Duration::from_millis(1), || format!("id = {id}"));
+    ///   std::thread::sleep(Duration::from_millis(u64::from(id)));
+    /// }
+    ///
+    /// fn main() {
+    ///   profi::print_on_exit!();
+    ///   query(0);
+    ///   query(2);
+    /// }
+    /// ```
+    pub fn on_slow<F: FnOnce() -> String>(
+        self,
+        threshold: std::time::Duration,
+        context: F,
+    ) -> SlowGuard<F> {
+        SlowGuard {
+            #[cfg(feature = "enable")]
+            start: crate::clock::Instant::now(),
+            guard: Some(self),
+            threshold,
+            context: Some(context),
+        }
+    }
+
+    /// Closes the scope at `time`.
+    #[cfg(feature = "enable")]
+    fn close_at(self, time: crate::clock::Instant) {
+        if let Some(guard) = self.guard {
+            guard.pop_at(time);
+            std::mem::forget(guard);
+        }
+    }
+
+    /// Profiles `future` only while it's being polled, see [`prof_future!`](crate::prof_future).
+    #[inline(always)]
+    pub fn instrument<F: std::future::Future>(
+        future: F,
+        name: impl IntoScopeName,
+    ) -> Instrumented<F> {
+        Instrumented::new(future, name)
+    }
+}
+
+/// Guard returned by [`ScopeGuard::on_slow`].
+#[allow(dead_code)]
+pub struct SlowGuard<F: FnOnce() -> String> {
+    guard: Option<ScopeGuard>,
+    #[cfg(feature = "enable")]
+    start: crate::clock::Instant,
+    threshold: std::time::Duration,
+    context: Option<F>,
+}
+
+#[cfg(feature = "enable")]
+impl<F: FnOnce() -> String> Drop for SlowGuard<F> {
+    fn drop(&mut self) {
+        // Do the measure as early as possible
+        let time = crate::clock::Instant::now();
+        let Some(guard) = self.guard.take() else {
+            return;
+        };
+        let duration = time.duration_since(self.start);
+        if guard.guard.is_some() && duration > self.threshold {
+            if let Some(context) = self.context.take() {
+                let call = crate::SlowCall {
+                    duration,
+                    context: context(),
+                };
+                crate::measure::THREAD_PROFILER.with_borrow_mut(|thread| thread.slow(call));
+            }
+        }
+        guard.close_at(time);
+    }
+}
+
+/// Future returned by [`prof_future!`](crate::prof_future!).
+///
+/// Only accumulates time while the inner future is being polled, on whichever thread polls it.
+/// All polls are counted as a single call, added when the future completes or is dropped.
+pub struct Instrumented<F> {
+    future: F,
+    /// `None` if rejected by the filter, or once the call was added
+    #[cfg(feature = "enable")]
+    name: Option<Name>,
+    /// Time spent in the polls that didn't complete the future
+    #[cfg(feature = "enable")]
+    polled: std::time::Duration,
+}
+
+impl<F> Instrumented<F> {
+    #[allow(unused_variables)]
+    pub fn new(future: F, name: impl IntoScopeName) -> Self {
+        Self {
+            future,
+            #[cfg(feature = "enable")]
+            name: name.enabled(),
+            #[cfg(feature = "enable")]
+            polled: std::time::Duration::ZERO,
+        }
+    }
+}
+
+#[cfg(feature = "enable")]
+impl<F> Drop for Instrumented<F> {
+    fn drop(&mut self) {
+        // Dropped before completing, the polls are still a call
+        if let Some(name) = self.name.take().filter(|_| !self.polled.is_zero()) {
+            let _ = crate::measure::THREAD_PROFILER.try_with(|thread| {
+                let mut thread = thread.borrow_mut();
+                let depth = thread.push(name);
+                thread.complete(depth, self.polled);
+                let time = thread.clock.now();
+                thread.pop(time);
+            });
+        }
+    }
+}
+
+impl<F: std::future::Future> std::future::Future for Instrumented<F> {
+    type Output = F::Output;
+
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        // SAFETY: `future` is never moved out of `self`, the rest of fields are not pinned
+        let this = unsafe { self.get_unchecked_mut() };
+        #[cfg(feature = "enable")]
+        let Some(name) = this.name.clone() else {
+            // SAFETY: see above
+            return unsafe { std::pin::Pin::new_unchecked(&mut this.future) }.poll(cx);
+        };
+        // Every poll is opened as one that doesn't complete the future, the one that does becomes its call
+        #[cfg(feature = "enable")]
+        let (depth, clock) = crate::measure::THREAD_PROFILER
+            .with_borrow_mut(|thread| (thread.resume(name), thread.clock));
+        #[cfg(feature = "enable")]
+        let start = clock.now();
+        // Closes the poll even if it panics
+        #[cfg(feature = "enable")]
+        let _guard = Closer(clock);
+        // SAFETY: see above
+        let poll = unsafe { std::pin::Pin::new_unchecked(&mut this.future) }.poll(cx);
+        #[cfg(feature = "enable")]
+        match poll {
+            std::task::Poll::Ready(_) => {
+                this.name = None;
+                let polled = std::mem::take(&mut this.polled);
+                crate::measure::THREAD_PROFILER
+                    .with_borrow_mut(|thread| thread.complete(depth, polled));
+            }
+            std::task::Poll::Pending => this.polled += clock.now().saturating_duration_since(start),
+        }
+        poll
+    }
+}
+
+/// Closes the innermost scope of the thread when dropped.
+struct Closer(
+    /// Clock of the thread, so closing takes the time right away
+    #[cfg(feature = "enable")]
+    crate::clock::Source,
+);
+
+impl Closer {
+    #[cfg(feature = "enable")]
+    fn pop_at(&self, time: crate::clock::Instant) {
+        // Closed by nobody if the profiler of the thread was already destroyed
+        let _ = crate::measure::THREAD_PROFILER.try_with(|thread| thread.borrow_mut().pop(time));
+    }
+}
+
+impl Drop for Closer {
+    fn drop(&mut self) {
+        #[cfg(feature = "enable")]
+        {
+            // Do the measure as early as possible
+            let time = self.0.now();
+            self.pop_at(time)
+        }
     }
 }
 
 /// Explicit guard that profiles the lifetime of an object.  
 /// Stops when it's dropped or when `into_inner` is called.
-/// 
-/// It's `#[repr(transparent)]` and implements `deref`, so it will work as if it was the original object.
-/// 
-/// Similar to all other profiling methods in `profi`, when the `enable` feature is disabled this guard is optimized away.
-/// 
+///
+/// Lifetimes are not scopes: they're shown in a separate "Lifetimes" section, with the number of objects created,
+/// their average lifetime and the maximum number alive at the same time, see [`Report::lifetimes`](crate::Report::lifetimes).
+///
+/// It implements `deref`, so it will work as if it was the original object.
+/// The common traits of `T` are forwarded too, like [`Display`](std::fmt::Display), [`Iterator`], [`Future`](std::future::Future) and [`Read`](std::io::Read)/[`Write`](std::io::Write),
+/// so a `Guard` can be passed to generic code that needs them.
+///
+/// Similar to all other profiling methods in `profi`, when the `enable` feature is disabled this guard is optimized away:
+/// only then it's `#[repr(transparent)]`, with the same layout as `T`.
+///
 /// # Example
 /// ```
 /// use profi::Guard;
-/// 
+///
 /// // profiles while "names" is alive
 /// let mut names = Guard::new(vec![], "names");
 /// names.push("Alex West");
-/// 
+///
 /// // ...
-/// 
+///
 /// // stop profiling
 /// let names = names.into_inner();
+/// # #[cfg(feature = "enable")]
+/// assert_eq!(profi::report().lifetime("names").unwrap().created, 1);
 /// ```
-#[cfg_attr(feature = "enable", derive(Debug))]
-#[repr(transparent)]
-pub struct Guard<T>(T);
+#[cfg_attr(not(feature = "enable"), repr(transparent))]
+pub struct Guard<T>(T, crate::lifetimes::Lifetime);
 
 impl<T> Guard<T> {
+    #[allow(unused_variables)]
     pub fn new(value: T, name: impl Into<Str>) -> Self {
         #[cfg(feature = "enable")]
-        crate::measure::THREAD_PROFILER.with_borrow_mut(|thread| thread.push(name.into()));
-        Self(value)
+        let lifetime = crate::lifetimes::Lifetime::start(name.into());
+        #[cfg(not(feature = "enable"))]
+        let lifetime = crate::lifetimes::Lifetime::start();
+        Self(value, lifetime)
     }
 
     pub fn into_inner(self) -> T {
-        // Call "drop" manually
-        self.pop();
-        
-        // Copy the inner value, needed because we implement `Drop`
-        // SAFETY: std::mem::forget is called directly after this, so the old T is never dropped
-        let inner = unsafe { std::ptr::read(std::ptr::addr_of!(self.0)) };
-        std::mem::forget(self);
-        
-        inner
-    }
-    
-    fn pop(&self) {
-        #[cfg(feature = "enable")]
-        {
-            // Do the measure as early as possible
-            let time = minstant::Instant::now();
-            crate::measure::THREAD_PROFILER.with_borrow_mut(|thread| {
-                thread.pop(time);
-            })
-        }
+        // The lifetime ends when it's dropped at the end of the function
+        let Guard(value, _) = self;
+        value
     }
-}
 
-impl<T> Drop for Guard<T> {
-    fn drop(&mut self) {
-        self.pop()
+    /// Calls `f` with the wrapped value, returning its result.
+    ///
+    /// Useful to limit a mutable borrow to a closure, for example in a chain of calls.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::Guard;
+    ///
+    /// let mut names = Guard::new(Vec::new(), "names");
+    /// let len = names.with(|names| {
+    ///     names.push("Alex West");
+    ///     names.len()
+    /// });
+    /// assert_eq!(len, 1);
+    /// ```
+    pub fn with<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.0)
+    }
+
+    /// Transforms the wrapped value with `f`, without stopping the measure.
+    ///
+    /// The returned guard continues the same measure, so the lifetime of an object is profiled across builders and conversions.
+    /// Not called `map` so it doesn't hide [`Iterator::map`] when the wrapped value is an iterator.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::Guard;
+    ///
+    /// // profiles from the creation of the builder until the string is dropped
+    /// let name = Guard::new(String::new(), "name")
+    ///     .map_inner(|mut name| {
+    ///         name.push_str("Alex West");
+    ///         name
+    ///     })
+    ///     .map_inner(String::into_boxed_str);
+    /// assert_eq!(&**name, "Alex West");
+    /// ```
+    pub fn map_inner<U>(self, f: impl FnOnce(T) -> U) -> Guard<U> {
+        let Guard(value, lifetime) = self;
+        Guard(f(value), lifetime)
     }
 }
 
 impl<T> std::ops::Deref for Guard<T> {
     type Target = T;
-    
+
     fn deref(&self) -> &Self::Target {
         &self.0
     }
@@ -116,7 +729,136 @@ impl<T> AsMut<T> for Guard<T> {
     }
 }
 
-impl<T> From<T> for Guard<T> where T: Sized {
+impl<T: std::fmt::Debug> std::fmt::Debug for Guard<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for Guard<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Profiles an iterator while it's alive, not only while it's advanced.
+///
+/// # Example
+/// ```
+/// use profi::Guard;
+///
+/// let lines = Guard::new("a\nb\nc".lines(), "lines");
+/// assert_eq!(lines.map(str::len).sum::<usize>(), 3);
+/// ```
+impl<T: Iterator> Iterator for Guard<T> {
+    type Item = T::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T: DoubleEndedIterator> DoubleEndedIterator for Guard<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back()
+    }
+}
+
+impl<T: ExactSizeIterator> ExactSizeIterator for Guard<T> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Profiles a future from its creation until it's dropped, including the time it's not polled.
+///
+/// Use [`prof_future!`](crate::prof_future) to only measure the polls.
+impl<T: std::future::Future> std::future::Future for Guard<T> {
+    type Output = T::Output;
+
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        // SAFETY: the inner value is never moved out of a pinned `Guard`, which doesn't implement `Drop`, and `into_inner` needs it by value
+        unsafe { self.map_unchecked_mut(|guard| &mut guard.0) }.poll(cx)
+    }
+}
+
+/// # Example
+/// ```
+/// use std::io::Read;
+/// use profi::Guard;
+///
+/// let mut file = Guard::new(std::io::Cursor::new(b"hello"), "file");
+/// let mut contents = String::new();
+/// file.read_to_string(&mut contents).unwrap();
+/// assert_eq!(contents, "hello");
+/// ```
+impl<T: std::io::Read> std::io::Read for Guard<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [std::io::IoSliceMut<'_>]) -> std::io::Result<usize> {
+        self.0.read_vectored(bufs)
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> std::io::Result<usize> {
+        self.0.read_to_end(buf)
+    }
+
+    fn read_to_string(&mut self, buf: &mut String) -> std::io::Result<usize> {
+        self.0.read_to_string(buf)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        self.0.read_exact(buf)
+    }
+}
+
+impl<T: std::io::BufRead> std::io::BufRead for Guard<T> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.0.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.0.consume(amt)
+    }
+}
+
+impl<T: std::io::Write> std::io::Write for Guard<T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+        self.0.write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.0.write_all(buf)
+    }
+}
+
+impl<T: std::io::Seek> std::io::Seek for Guard<T> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+impl<T> From<T> for Guard<T>
+where
+    T: Sized,
+{
     fn from(value: T) -> Self {
         let name = std::any::type_name_of_val(&value);
         Guard::new(value, name)
@@ -124,30 +866,207 @@ impl<T> From<T> for Guard<T> where T: Sized {
 }
 
 #[allow(dead_code)]
-pub struct ProfiDrop<W: std::io::Write, F: Fn(&mut W)>(W, F);
+pub struct ProfiDrop<S: crate::ReportSink, F: Fn(&mut S)>(S, F, crate::PrintOptions);
 
-impl<W, F> ProfiDrop<W, F>
+impl<S, F> ProfiDrop<S, F>
 where
-    W: std::io::Write,
-    F: Fn(&mut W),
+    S: crate::ReportSink,
+    F: Fn(&mut S),
 {
-    pub fn new(to: W, ondrop: F) -> Self {
-        Self(to, ondrop)
+    pub fn new(to: S, ondrop: F, options: crate::PrintOptions) -> Self {
+        #[cfg(all(feature = "enable", feature = "signals"))]
+        crate::signals::print_on_interrupt(&options);
+        Self(to, ondrop, options)
+    }
+
+    /// Defers the report until [`finalize`](crate::finalize) or the exit of the process, used by `print_on_exit!(at_exit)`.
+    #[allow(unused_variables, unused_mut)]
+    pub fn at_exit(mut to: S, ondrop: F, options: crate::PrintOptions) -> AtExit
+    where
+        S: Send + 'static,
+        F: Send + 'static,
+    {
+        #[cfg(all(feature = "enable", feature = "signals"))]
+        crate::signals::print_on_interrupt(&options);
+        #[cfg(feature = "enable")]
+        crate::finalize::register();
+        AtExit(Some(Box::new(move || {
+            #[cfg(feature = "enable")]
+            {
+                #[cfg(feature = "signals")]
+                {
+                    crate::signals::stop_interrupt();
+                    crate::signals::stop_report_on_stop();
+                }
+                send_report(&mut to, &options);
+                ondrop(&mut to);
+            }
+        })))
     }
 }
 
 #[cfg(feature = "enable")]
-impl<W, F> std::ops::Drop for ProfiDrop<W, F>
+impl<S, F> std::ops::Drop for ProfiDrop<S, F>
 where
-    W: std::io::Write,
-    F: Fn(&mut W),
+    S: crate::ReportSink,
+    F: Fn(&mut S),
 {
     fn drop(&mut self) {
+        #[cfg(feature = "signals")]
+        {
+            crate::signals::stop_interrupt();
+            crate::signals::stop_report_on_stop();
+        }
         drop_threads();
         block_until_exited();
-        print_timings_to(&mut self.0).unwrap();
+        let over_budget = send_report(&mut self.0, &self.2);
         let s = &self.1;
-        s(&mut self.0)
+        s(&mut self.0);
+        if over_budget && self.2.fail_on_budget && !std::thread::panicking() {
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Sends the report of all the threads to `to`, returns `true` if a budget was exceeded.
+///
+/// Errors go to [`on_error`](crate::on_error), panicking while the program exits could abort it.
+#[cfg(feature = "enable")]
+fn send_report<S: crate::ReportSink>(to: &mut S, options: &crate::PrintOptions) -> bool {
+    if !crate::filter::is_profiling_enabled() {
+        return false;
+    }
+    let report = crate::measure::GLOBAL_PROFILER.report();
+    crate::Error::unbalanced(&report).for_each(crate::error::handle);
+    if let Err(e) = to.write_report(&report, options) {
+        crate::error::handle(e.into());
+    }
+    !report.budget_violations().is_empty()
+}
+
+/// Guard of `print_on_exit!(at_exit)`, which hands the report to [`finalize`](crate::finalize) when dropped.
+pub struct AtExit(Option<Box<dyn FnOnce() + Send>>);
+
+impl Drop for AtExit {
+    fn drop(&mut self) {
+        if let Some(print) = self.0.take() {
+            #[cfg(feature = "enable")]
+            crate::finalize::defer(print);
+            #[cfg(not(feature = "enable"))]
+            drop(print);
+        }
+    }
+}
+
+/// Prints the profiled timings when it's dropped, the object form of [`print_on_exit!`](crate::print_on_exit).
+///
+/// Useful when `main` is not yours, like in a test harness or in the entry point of a plugin,
+/// keep it alive until the end of the code to profile.
+///
+/// Like the macro, it profiles its own lifetime as a scope, named after the current thread,
+/// which in tests is the name of the test.
+/// When dropped, it waits for the other profiled threads to exit before printing,
+/// except the ones dropping their own printer at the same time:
+/// ```
+/// use profi::{ExitPrinter, PrintOptions};
+///
+/// let barrier = std::sync::Barrier::new(2);
+/// std::thread::scope(|s| {
+///   for _ in 0..2 {
+///     s.spawn(|| {
+///       let _profi = ExitPrinter::to(std::io::sink(), PrintOptions::new());
+///       barrier.wait();
+///     });
+///   }
+/// });
+/// ```
+///
+/// # Example
+/// ```
+/// use profi::{prof, ExitPrinter, PrintOptions};
+///
+/// // A test, where `main` belongs to the harness
+/// fn parse() {
+///   let _profi = ExitPrinter::new(PrintOptions::new().limit(10));
+///   prof!(parse);
+///   // ...
+/// }
+/// # parse();
+/// ```
+///
+/// Print to stderr or to any `std::io::Write` or [`ReportSink`](crate::ReportSink) instead:
+/// ```
+/// use profi::{ExitPrinter, PrintOptions};
+///
+/// {
+///   let _profi = ExitPrinter::stderr(PrintOptions::new());
+/// }
+/// let mut file = Vec::<u8>::new();
+/// {
+///   let _profi = ExitPrinter::to(&mut file, PrintOptions::new());
+/// }
+/// ```
+///
+/// Only one can be alive in each thread, like [`print_on_exit!`](crate::print_on_exit).
+pub struct ExitPrinter<S: crate::ReportSink = std::io::Stdout> {
+    /// Declared before `printer` so it's closed before printing
+    _scope: ScopeGuard,
+    _printer: ProfiDrop<S, fn(&mut S)>,
+}
+
+impl ExitPrinter {
+    /// Prints to stdout when dropped.
+    pub fn new(options: crate::PrintOptions) -> Self {
+        Self::to(std::io::stdout(), options)
+    }
+
+    /// Prints to stderr when dropped.
+    pub fn stderr(options: crate::PrintOptions) -> ExitPrinter<std::io::Stderr> {
+        ExitPrinter::to(std::io::stderr(), options)
+    }
+}
+
+impl<S: crate::ReportSink> ExitPrinter<S> {
+    /// Sends the report to `to` when dropped.
+    pub fn to(to: S, options: crate::PrintOptions) -> Self {
+        let printer = ProfiDrop::new(to, (|_| {}) as fn(&mut S), options);
+        let name = std::thread::current().name().unwrap_or("main").to_owned();
+        Self {
+            _scope: ScopeGuard::new(name),
+            _printer: printer,
+        }
+    }
+
+    /// Also writes the report to `path` if the OS stops the process before it's dropped, like a service or a daemon being stopped.
+    ///
+    /// Handles `SIGTERM` on Linux, which is what `systemctl stop` and `docker stop` send,
+    /// and closing the console, Ctrl+Break and shutting down on Windows.
+    /// The process stops right after writing the report, as if it wasn't handled.
+    /// If the application already had a handler for `SIGTERM` it's called instead, and the report is written when this is dropped.
+    ///
+    /// Prints a warning if it can't be handled, or on other platforms.
+    ///
+    /// # Example
+    /// ```
+    /// use profi::{ExitPrinter, PrintOptions};
+    ///
+    /// fn run_service() {
+    ///   let _profi = ExitPrinter::new(PrintOptions::new())
+    ///     .report_on_stop(std::env::temp_dir().join("profi-service.txt"));
+    ///   // ...
+    /// }
+    /// # run_service();
+    /// ```
+    #[cfg(feature = "signals")]
+    #[allow(unused_variables)]
+    pub fn report_on_stop(self, path: impl Into<std::path::PathBuf>) -> Self {
+        #[cfg(feature = "enable")]
+        if let Err(e) = crate::signals::report_on_stop(path.into(), &self._printer.2) {
+            eprintln!(
+                "[profi] Could not handle stopping, the report won't be written if stopped: {e}"
+            );
+        }
+        self
     }
 }
 
@@ -161,13 +1080,15 @@ pub fn dbg_thread() {
 fn drop_threads() {
     crate::measure::THREAD_PROFILER.with_borrow_mut(|t| {
         t.manual_drop(true);
+        // Printers in other threads must not wait for this one
+        t.detach();
 
         #[cfg(feature = "rayon")]
         {
             // Drop threads manually, as `rayon` never drops them
             let current = std::thread::current().id();
 
-            rayon::broadcast(|t| {
+            rayon::broadcast(|_| {
                 if std::thread::current().id() != current {
                     crate::measure::THREAD_PROFILER.with_borrow_mut(|t| t.manual_drop(false))
                 }
@@ -178,17 +1099,23 @@ fn drop_threads() {
 
 /// **Should not be used on its own, will be applied automatically with `print_on_exit!`.**
 ///
-/// Blocks until all threads are dropped.
+/// Blocks until all threads are dropped, except the ones waiting to print.
 ///
 /// Must be used on [`print_on_exit!`](crate::print_on_exit) because sometimes the threads will drop *after* the main one, corrupting the results.
 #[cfg(feature = "enable")]
 fn block_until_exited() {
     // Wait for all threads to finish
     #[cfg(feature = "enable")]
-    let mut threads = crate::measure::GLOBAL_PROFILER.threads.lock().unwrap();
+    let mut threads = crate::measure::GLOBAL_PROFILER
+        .threads
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
     #[cfg(feature = "enable")]
-    while *threads > 1 {
-        threads = crate::measure::GLOBAL_PROFILER.cvar.wait(threads).unwrap();
+    while *threads > 0 {
+        threads = crate::measure::GLOBAL_PROFILER
+            .cvar
+            .wait(threads)
+            .unwrap_or_else(|e| e.into_inner());
     }
 }
 
@@ -197,10 +1124,11 @@ fn block_until_exited() {
 /// If profiling the `main` function, you can use [`print_on_exit!`](crate::print_on_exit) instead.
 ///
 /// It's recommended to only use it when all threads have exited and have been joined correctly, or you'll risk corrupt data.
+/// Returns an error if the report can't be written, the ends of scopes that were never started go to [`on_error`](crate::on_error).
 #[inline(always)]
-pub fn print_timings() -> std::io::Result<()> {
+pub fn print_timings() -> Result<(), crate::Error> {
     #[cfg(feature = "enable")]
-    crate::measure::GLOBAL_PROFILER.print_timings(std::io::stdout().lock())?;
+    crate::measure::GLOBAL_PROFILER.print_timings(std::io::stdout().lock(), &Default::default())?;
     Ok(())
 }
 /// Prints the profiled timings to stderr.
@@ -208,10 +1136,11 @@ pub fn print_timings() -> std::io::Result<()> {
 /// If profiling the `main` function, you can use [`print_on_exit!`](crate::print_on_exit) instead.
 ///
 /// It's recommended to only use it when all threads have exited and have been joined correctly, or you'll risk corrupt data.
+/// Returns an error if the report can't be written, the ends of scopes that were never started go to [`on_error`](crate::on_error).
 #[inline(always)]
-pub fn eprint_timings() -> std::io::Result<()> {
+pub fn eprint_timings() -> Result<(), crate::Error> {
     #[cfg(feature = "enable")]
-    crate::measure::GLOBAL_PROFILER.print_timings(std::io::stderr())?;
+    crate::measure::GLOBAL_PROFILER.print_timings(std::io::stderr(), &Default::default())?;
     Ok(())
 }
 /// Prints the profiled timings to the provided [`std::io::Write`].
@@ -219,14 +1148,341 @@ pub fn eprint_timings() -> std::io::Result<()> {
 /// If profiling the `main` function, you can use [`print_on_exit!`](crate::print_on_exit) instead.
 ///
 /// It's recommended to only use it when all threads have exited and have been joined correctly, or you'll risk corrupt data.
+/// Returns an error if the report can't be written, the ends of scopes that were never started go to [`on_error`](crate::on_error).
 #[inline(always)]
 #[allow(unused)]
-pub fn print_timings_to(to: impl std::io::Write) -> std::io::Result<()> {
+pub fn print_timings_to(to: impl std::io::Write) -> Result<(), crate::Error> {
     #[cfg(feature = "enable")]
-    crate::measure::GLOBAL_PROFILER.print_timings(to)?;
+    crate::measure::GLOBAL_PROFILER.print_timings(to, &Default::default())?;
     Ok(())
 }
 
+/// Profiles a closure and returns its result.
+///
+/// Same as [`prof_ret!`](crate::prof_ret), useful when the name is not known at compile time or to wrap function pointers.
+///
+/// # Example
+/// ```
+/// fn expensive() -> u64 {
+///   (0..1000).sum()
+/// }
+///
+/// fn main() {
+///   let total = profi::profile_fn("expensive", || expensive());
+///   assert_eq!(total, 499500);
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(profi::report().scope("expensive").unwrap().calls, 1);
+/// }
+/// ```
+#[inline]
+pub fn profile_fn<R>(name: impl Into<Str>, f: impl FnOnce() -> R) -> R {
+    let _guard = ScopeGuard::new(name);
+    f()
+}
+
+/// Records `duration` as a call of the scope `name`, inside the innermost open scope, see [`record!`](crate::record).
+///
+/// The call ends when this function is called.
+pub fn record(name: impl Into<Str>, duration: std::time::Duration) {
+    record_many(name, &[duration]);
+}
+
+/// Records a call of the scope `name` that started at `start` and ended at `end`, inside the innermost open scope.
+///
+/// Useful when the time of an external event is known, like the timestamps of a GPU query converted to [`Instant`](std::time::Instant)s.
+/// It's placed in the scope tree where this function is called, even if it happened before the scope was opened.
+///
+/// # Example
+/// ```
+/// use std::time::{Duration, Instant};
+///
+/// fn main() {
+///   profi::prof!(frame);
+///   // Reported by the GPU once the pass finished
+///   let start = Instant::now();
+///   std::thread::sleep(Duration::from_millis(4));
+///   let end = Instant::now();
+///   profi::record_at("gpu::pass", start, end);
+///
+///   # #[cfg(feature = "enable")]
+///   assert_eq!(profi::report().scope("gpu::pass").unwrap().total_real, end - start);
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn record_at(name: impl Into<Str>, start: std::time::Instant, end: std::time::Instant) {
+    #[cfg(feature = "enable")]
+    {
+        let name = name.into();
+        if !crate::filter::is_enabled(&name) {
+            return;
+        }
+        // Same distance from now in the profiler's clock
+        let now = crate::clock::Instant::now();
+        let since_end = std::time::Instant::now().saturating_duration_since(end);
+        let duration = end.saturating_duration_since(start);
+        let end = now
+            .checked_sub(since_end)
+            .unwrap_or(crate::clock::Instant::ZERO);
+        let start = end
+            .checked_sub(duration)
+            .unwrap_or(crate::clock::Instant::ZERO);
+        crate::measure::THREAD_PROFILER
+            .with_borrow_mut(|thread| thread.record_calls(Name::new(name), [(start, end)]));
+    }
+}
+
+/// Records each of `durations` as a call of the scope `name`, inside the innermost open scope.
+///
+/// Useful to feed timings that were measured externally, like the ones returned by a database driver,
+/// which get the same statistics as any other scope.
+/// The calls are placed one after the other, ending when this function is called.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+///
+/// fn main() {
+///   // Returned by the driver
+///   let roundtrips = [Duration::from_millis(2), Duration::from_millis(5), Duration::from_millis(3)];
+///   profi::record_many("db roundtrip", &roundtrips);
+///
+///   let report = profi::report();
+///   # #[cfg(feature = "enable")]
+///   # {
+///   let scope = report.scope("db roundtrip").unwrap();
+///   assert_eq!(scope.calls, 3);
+///   assert_eq!(scope.max, Duration::from_millis(5));
+///   # }
+/// }
+/// ```
+#[allow(unused_variables)]
+pub fn record_many(name: impl Into<Str>, durations: &[std::time::Duration]) {
+    #[cfg(feature = "enable")]
+    {
+        let name = name.into();
+        if durations.is_empty() || !crate::filter::is_enabled(&name) {
+            return;
+        }
+        let name = Name::new(name);
+        crate::measure::THREAD_PROFILER
+            .with_borrow_mut(|thread| thread.record_many(name, durations));
+    }
+}
+
+/// Pauses measuring the scopes that are open on this thread, see [`pause!`](crate::pause).
+#[inline(always)]
+pub fn pause() {
+    #[cfg(feature = "enable")]
+    {
+        let time = crate::clock::Instant::now();
+        crate::measure::THREAD_PROFILER.with_borrow_mut(|thread| thread.pause(time));
+    }
+}
+
+/// Resumes measuring the scopes of this thread, see [`resume!`](crate::resume).
+#[inline(always)]
+pub fn resume() {
+    #[cfg(feature = "enable")]
+    {
+        let time = crate::clock::Instant::now();
+        crate::measure::THREAD_PROFILER.with_borrow_mut(|thread| thread.unpause(time));
+    }
+}
+
+/// Pauses measuring the open scopes of the thread while it's alive.
+///
+/// Same as calling [`pause!`](crate::pause) on creation and [`resume!`](crate::resume) when dropped.
+///
+/// # Example
+/// ```
+/// use profi::{prof, PauseGuard};
+///
+/// fn main() {
+///   prof!(prompt);
+///   // ...
+///   let paused = PauseGuard::new();
+///   std::thread::sleep(std::time::Duration::from_millis(10));
+///   drop(paused);
+/// }
+/// ```
+#[must_use = "measuring is resumed when the guard is dropped"]
+pub struct PauseGuard(());
+
+impl PauseGuard {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        pause();
+        Self(())
+    }
+}
+
+impl Drop for PauseGuard {
+    fn drop(&mut self) {
+        resume()
+    }
+}
+
+/// Records a milestone, see [`milestone!`](crate::milestone).
+#[inline(always)]
+#[allow(unused_variables)]
+pub fn milestone(name: impl Into<Str>) {
+    #[cfg(feature = "enable")]
+    {
+        let time = crate::clock::Instant::now();
+        crate::measure::GLOBAL_PROFILER.milestone(name.into(), time);
+    }
+}
+
+/// Marks the start of a new frame, see [`new_frame!`](crate::new_frame).
+#[inline(always)]
+pub fn new_frame() {
+    #[cfg(feature = "enable")]
+    {
+        let time = crate::clock::Instant::now();
+        crate::measure::GLOBAL_PROFILER.new_frame(time);
+    }
+}
+
+/// Adds `value` to the counter `name` of the innermost open scope, see [`prof_count!`](crate::prof_count).
+#[inline(always)]
+#[allow(unused_variables)]
+pub fn count(name: &'static str, value: u64) {
+    #[cfg(feature = "enable")]
+    crate::measure::THREAD_PROFILER
+        .with_borrow_mut(|thread| thread.count(crate::names::Id::new(name), value));
+}
+
+/// Lowercase aliases of [`Format`](crate::Format), used by `print_on_exit!(format = ...)`.
+#[allow(non_upper_case_globals)]
+pub mod formats {
+    use crate::Format;
+
+    pub const table: Format = Format::Table;
+    pub const folded: Format = Format::Folded;
+    pub const markdown: Format = Format::Markdown;
+    pub const html: Format = Format::Html;
+    pub const binary: Format = Format::Binary;
+}
+
+/// Lowercase aliases of [`Sort`](crate::Sort), used by `print_on_exit!(sort = ...)`.
+#[allow(non_upper_case_globals)]
+pub mod sorts {
+    use crate::Sort;
+
+    pub const tree: Sort = Sort::Tree;
+    pub const real_time: Sort = Sort::RealTime;
+    pub const cpu_time: Sort = Sort::CpuTime;
+    pub const average: Sort = Sort::Average;
+    pub const calls: Sort = Sort::Calls;
+}
+
+/// Lowercase aliases of [`Hierarchy`](crate::Hierarchy), used by `print_on_exit!(hierarchy = ...)`.
+#[allow(non_upper_case_globals)]
+pub mod hierarchies {
+    use crate::Hierarchy;
+
+    pub const merged: Hierarchy = Hierarchy::Merged;
+    pub const deep: Hierarchy = Hierarchy::Deep;
+}
+
+/// Lowercase aliases of [`TimeUnit`](crate::TimeUnit), used by `print_on_exit!(time_unit = ...)`.
+#[allow(non_upper_case_globals)]
+pub mod time_units {
+    use crate::TimeUnit;
+
+    pub const auto: TimeUnit = TimeUnit::Auto;
+    pub const s: TimeUnit = TimeUnit::Seconds;
+    pub const ms: TimeUnit = TimeUnit::Millis;
+    pub const us: TimeUnit = TimeUnit::Micros;
+    pub const ns: TimeUnit = TimeUnit::Nanos;
+}
+
+/// Lowercase constructors of [`Budget`](crate::Budget), used by [`assert_budget!`](crate::assert_budget).
+pub mod budgets {
+    use crate::Budget;
+
+    pub fn average(limit: std::time::Duration) -> Budget {
+        Budget::Average(limit)
+    }
+
+    pub fn call(limit: std::time::Duration) -> Budget {
+        Budget::Call(limit)
+    }
+
+    pub fn total(limit: std::time::Duration) -> Budget {
+        Budget::Total(limit)
+    }
+
+    /// Parses the duration literal given to [`assert_budget!`](crate::assert_budget), like `4ms` or `1.5s`,
+    /// evaluated in a constant so an invalid one fails to compile.
+    pub const fn limit(literal: &str) -> std::time::Duration {
+        const fn invalid() -> ! {
+            panic!("[profi] Invalid budget, expected a duration like `4ms`, `250us` or `1.5s`")
+        }
+
+        let bytes = literal.as_bytes();
+        let mut i = 0;
+        let mut whole: u128 = 0;
+        while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'_') {
+            if bytes[i] != b'_' {
+                whole = whole * 10 + (bytes[i] - b'0') as u128;
+            }
+            if whole > u64::MAX as u128 {
+                invalid();
+            }
+            i += 1;
+        }
+        if i == 0 {
+            invalid();
+        }
+        let mut fraction: u128 = 0;
+        let mut scale: u128 = 1;
+        if i < bytes.len() && bytes[i] == b'.' {
+            i += 1;
+            // Digits past the nanoseconds of an hour are ignored
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                if scale < 1_000_000_000_000_000 {
+                    fraction = fraction * 10 + (bytes[i] - b'0') as u128;
+                    scale *= 10;
+                }
+                i += 1;
+            }
+        }
+        let (_, unit) = bytes.split_at(i);
+        let per_unit: u128 = match unit {
+            b"ns" => 1,
+            b"us" | b"\xC2\xB5s" => 1_000,
+            b"ms" => 1_000_000,
+            b"" | b"s" => 1_000_000_000,
+            b"m" => 60_000_000_000,
+            b"h" => 3_600_000_000_000,
+            _ => invalid(),
+        };
+        let nanos = whole * per_unit + fraction * per_unit / scale;
+        if nanos == 0 || nanos / 1_000_000_000 > u64::MAX as u128 {
+            invalid();
+        }
+        std::time::Duration::new(
+            (nanos / 1_000_000_000) as u64,
+            (nanos % 1_000_000_000) as u32,
+        )
+    }
+}
+
+/// Returns `true` if `stream` is a terminal and the `NO_COLOR` environment variable is not set, used by `print_on_exit!`.
+pub fn is_color_terminal(stream: &impl std::io::IsTerminal) -> bool {
+    stream.is_terminal() && !std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+/// Removes the `::{{closure}}` suffixes added to functions by `async` and closures.
+#[inline(always)]
+pub fn strip_closures(mut name: &str) -> &str {
+    while let Some(stripped) = name.strip_suffix("::{{closure}}") {
+        name = stripped;
+    }
+    name
+}
+
 #[cfg(feature = "nightly")]
 #[inline(always)]
 pub const fn type_name_of(f: fn()) -> &'static str {
@@ -237,4 +1493,4 @@ pub const fn type_name_of(f: fn()) -> &'static str {
 #[inline(always)]
 pub fn type_name_of<T>(_: T) -> &'static str {
     std::any::type_name::<T>()
-}
\ No newline at end of file
+}
@@ -0,0 +1,3 @@
+fn main() {
+    profi::assert_budget!("physics", 4msec);
+}
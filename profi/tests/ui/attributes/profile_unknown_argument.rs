@@ -0,0 +1,6 @@
+#[profi::profile(nmae = "typo")]
+fn typo() {}
+
+fn main() {
+    typo();
+}
@@ -0,0 +1,4 @@
+fn main() {
+    profi::print_on_exit!();
+    profi::print_on_exit!(stderr);
+}
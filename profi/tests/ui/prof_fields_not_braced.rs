@@ -0,0 +1,3 @@
+fn main() {
+    profi::prof!("query", fields = (table, "users"));
+}
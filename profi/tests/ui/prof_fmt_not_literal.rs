@@ -0,0 +1,4 @@
+fn main() {
+    let format = "iteration {}";
+    profi::prof!(fmt = format, 1);
+}
@@ -0,0 +1,6 @@
+profi::library_namespace!("mylib");
+
+fn main() {
+    let name = String::from("decode");
+    profi::prof_lib!(name.as_str());
+}
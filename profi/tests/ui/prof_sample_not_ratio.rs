@@ -0,0 +1,3 @@
+fn main() {
+    profi::prof!("hot", sample = 0.01);
+}